@@ -2,7 +2,9 @@ use crate::ast::{Attribute, EnumConstant};
 use crate::compiler::Visitor;
 
 pub struct EnumGenerator {
+    name: String,
     declaration: codegen::Enum,
+    constants: Vec<String>,
 }
 
 impl EnumGenerator {
@@ -11,20 +13,59 @@ impl EnumGenerator {
         declaration
             .derive("Debug")
             .derive("Clone")
+            .derive("PartialEq")
+            .derive("Eq")
+            .derive("Hash")
             .derive("Serialize")
+            .derive("Deserialize")
             .vis("pub");
 
-        Self { declaration }
+        Self {
+            name: name.into(),
+            declaration,
+            constants: Vec::default(),
+        }
     }
 
     pub fn push_into(self, module: &mut codegen::Scope) {
+        let tests = self.serialization_tests();
         module.push_enum(self.declaration);
+        module.raw(tests);
+    }
+
+    /// Generates a test that guards JSON representation of every enum constant.
+    fn serialization_tests(&self) -> String {
+        let mut lines = vec![
+            "#[cfg(test)]".to_string(),
+            "mod tests {".into(),
+            "    use serde_json::to_string;".into(),
+            "".into(),
+            "    use super::*;".into(),
+            "".into(),
+            "    #[test]".into(),
+            "    fn it_json_serializes_valid_constants() {".into(),
+            "        // The JSON-serialized values must match the value of `constantName` in".into(),
+            format!("        // `schema/{}.json`.", self.name),
+            "        //".into(),
+            "        // Regression test for appinsights-rs#18.".into(),
+        ];
+        for constant in &self.constants {
+            lines.push(format!(
+                "        assert_eq!(to_string(&{}::{}).unwrap(), r#\"\"{}\"\"#);",
+                self.name, constant, constant
+            ));
+        }
+        lines.push("    }".into());
+        lines.push("}".into());
+
+        lines.join("\n")
     }
 }
 
 impl Visitor for EnumGenerator {
     fn visit_enum_constant(&mut self, constant: &EnumConstant) {
         self.declaration.new_variant(constant.name());
+        self.constants.push(constant.name().into());
 
         if constant.value().is_some() {
             panic!("enum value is not supported: {:#?}", constant)
@@ -7,4 +7,4 @@ mod types;
 pub use enums::EnumGenerator;
 pub use packages::PackageGenerator;
 pub use schemas::SchemaGenerator;
-pub use structs::{BuilderGenerator, StructGenerator, TelemetryDataTraitGenerator};
+pub use structs::{DefaultGenerator, StructGenerator};
@@ -1,71 +1,38 @@
-use crate::compiler::Module;
-
 pub struct PackageGenerator {
     modules: Vec<String>,
-    usages: Vec<String>,
 }
 
 impl PackageGenerator {
     pub fn new() -> Self {
         Self {
             modules: Vec::default(),
-            usages: Vec::default(),
         }
     }
 
-    pub fn visit_module(&mut self, module: &Module) {
-        self.modules.push(format!("mod {};", module.name()));
-        self.usages.push(format!("pub use {}::*;", module.name()));
+    pub fn visit_module(&mut self, name: &str) {
+        self.modules.push(name.into());
     }
 }
 
-impl ToString for PackageGenerator {
-    fn to_string(&self) -> String {
-        codegen::Scope::new()
-            .raw("// NOTE: This file was automatically generated.")
-            .raw("#![allow(unused_variables, dead_code, unused_imports)]")
-            .raw(&self.modules.join("\n"))
-            .raw(&self.usages.join("\n"))
-            .push_trait(telemetry_data_trait())
-            .to_string()
-    }
-}
+impl std::fmt::Display for PackageGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut modules = self.modules.clone();
+        modules.sort();
 
-fn telemetry_data_trait() -> codegen::Trait {
-    let mut telemetry_data = codegen::Trait::new("TelemetryData");
-    telemetry_data
-        .vis("pub")
-        .doc("Common interface implemented by telemetry data contacts.")
-        .new_fn("envelope_name")
-        .doc(&format!(
-            "Returns the name used when this is embedded within an [{name}](trait.{name}.html) container.",
-            name = "Envelope"
-        ))
-        .arg_ref_self()
-        .arg("key", "&str")
-        .ret("String")
-        .line("let mut name = self.base_type();")
-        .line("name.truncate(name.len() - 4);")
-        .line("")
-        .push_block({
-            let mut block = codegen::Block::new("if key.is_empty()");
-            block.line(r#"format!("Microsoft.ApplicationInsights.{}.{}", key, name)"#);
-            block
-        })
-        .push_block({
-            let mut block = codegen::Block::new("else");
-            block.line(r#"format!("Microsoft.ApplicationInsights.{}", name)"#);
-            block
-        });
+        let declarations: Vec<_> = modules.iter().map(|name| format!("mod {};", name)).collect();
+        let usages: Vec<_> = modules.iter().map(|name| format!("pub use {}::*;", name)).collect();
 
-    telemetry_data
-        .new_fn("base_type")
-        .doc(&format!(
-            "Returns the base type when placed within an [{name}](trait.{name}.html) container.",
-            name = "Data"
-        ))
-        .arg_ref_self()
-        .ret("String");
-
-    telemetry_data
+        let scope = codegen::Scope::new()
+            .raw(
+                "//! Data contracts of telemetry items in the format Application Insights ingestion endpoint accepts.\n\
+                 //! [`Envelope`](struct.Envelope.html) is a final representation of a telemetry item, which\n\
+                 //! [`processors`](../processor/index.html) can inspect and modify before it is sent.",
+            )
+            .raw("// NOTE: This file was automatically generated.")
+            .raw("#![allow(unused_imports)]")
+            .raw(declarations.join("\n"))
+            .raw(usages.join("\n"))
+            .to_string();
+        write!(f, "{}", scope)
+    }
 }
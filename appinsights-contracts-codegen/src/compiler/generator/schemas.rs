@@ -1,5 +1,5 @@
 use crate::ast::{Enum, Schema, Struct};
-use crate::compiler::generator::{BuilderGenerator, EnumGenerator, StructGenerator, TelemetryDataTraitGenerator};
+use crate::compiler::generator::{DefaultGenerator, EnumGenerator, StructGenerator};
 use crate::compiler::Visitor;
 
 pub struct SchemaGenerator {
@@ -16,30 +16,26 @@ impl SchemaGenerator {
 
 impl Visitor for SchemaGenerator {
     fn visit_schema(&mut self, schema: &Schema) {
-        self.body.raw("// NOTE: This file was automatically generated.");
-        self.body.import("serde", "Serialize");
         self.body.import("crate::contracts", "*");
+        self.body.import("serde", "Deserialize");
+        self.body.import("serde", "Serialize");
+        self.body.raw("// NOTE: This file was automatically generated.");
 
         self.visit_declarations(schema.declarations());
     }
 
     fn visit_struct(&mut self, declaration: &Struct) {
-        // generate struct declaration
         let mut struct_generator = StructGenerator::new(declaration.name());
         struct_generator.visit_struct(declaration);
-        struct_generator.push_into(&mut self.body);
-
-        // generate struct builder declaration
-        let mut builder_generator = BuilderGenerator::new(declaration.name());
-        builder_generator.visit_struct(declaration);
-        builder_generator.push_into(&mut self.body);
-
-        // assume that if struct name ends with Data and it is not "Data"
-        // so it required TelemetryData trait implemented for this type
-        if declaration.is_telemetry_data() {
-            let mut telemetry_data_generator = TelemetryDataTraitGenerator::new(declaration.name());
-            telemetry_data_generator.visit_struct(declaration);
-            telemetry_data_generator.push_into(&mut self.body);
+
+        // derive Default unless the schema sets default values of some fields
+        let mut default_generator = DefaultGenerator::new(declaration.name());
+        default_generator.visit_struct(declaration);
+
+        let derive_default = default_generator.is_derivable();
+        struct_generator.push_into(&mut self.body, derive_default);
+        if !derive_default {
+            default_generator.push_into(&mut self.body);
         }
     }
 
@@ -50,8 +46,8 @@ impl Visitor for SchemaGenerator {
     }
 }
 
-impl ToString for SchemaGenerator {
-    fn to_string(&self) -> String {
-        self.body.to_string()
+impl std::fmt::Display for SchemaGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.body.to_string())
     }
 }
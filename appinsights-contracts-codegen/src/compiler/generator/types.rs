@@ -27,23 +27,26 @@ impl From<Type> for codegen::Type {
 
 impl From<BasicType> for codegen::Type {
     fn from(type_: BasicType) -> codegen::Type {
-        let name = match type_ {
-            BasicType::Bool => "bool",
-            BasicType::UInt8 => "u8",
-            BasicType::UInt16 => "u16",
-            BasicType::UInt32 => "u32",
-            BasicType::UInt64 => "u64",
-            BasicType::Int8 => "i8",
-            BasicType::Int16 => "i16",
-            BasicType::Int32 => "i32",
-            BasicType::Int64 => "i64",
-            BasicType::Float => "f32",
-            BasicType::Double => "f64",
-            BasicType::String => "String",
-            BasicType::WString => "String",
-        };
+        codegen::Type::new(basic_type_name(&type_))
+    }
+}
 
-        codegen::Type::new(name)
+/// Returns a name of the Rust type that represents a basic schema type.
+pub fn basic_type_name(type_: &BasicType) -> &'static str {
+    match type_ {
+        BasicType::Bool => "bool",
+        BasicType::UInt8 => "u8",
+        BasicType::UInt16 => "u16",
+        BasicType::UInt32 => "u32",
+        BasicType::UInt64 => "u64",
+        BasicType::Int8 => "i8",
+        BasicType::Int16 => "i16",
+        BasicType::Int32 => "i32",
+        BasicType::Int64 => "i64",
+        BasicType::Float => "f32",
+        BasicType::Double => "f64",
+        BasicType::String => "String",
+        BasicType::WString => "String",
     }
 }
 
@@ -62,8 +65,10 @@ impl From<ComplexType> for codegen::Type {
             }
             ComplexType::Parameter { value } => codegen::Type::new(value.name()),
             ComplexType::Vector { element } => {
-                let type_: Type = *element;
-                type_.into()
+                let mut type_ = codegen::Type::new("Vec");
+                let element = *element;
+                type_.generic(element);
+                type_
             }
             ComplexType::Nullable { element } => {
                 let mut type_ = codegen::Type::new("Option");
@@ -13,10 +13,18 @@ use crate::compiler::generator::{PackageGenerator, SchemaGenerator};
 use crate::parser::Parser;
 use crate::Result;
 
+/// Schemas of types that don't map to plain structs well, so their contracts are written by hand, e.g.
+/// generic `Data<TDomain>` becomes an enum of all telemetry data types tagged with `baseType`.
+const HANDWRITTEN_SCHEMAS: [&str; 4] = ["base", "context_tag_keys", "data", "domain"];
+
+/// Hand-written modules in the output directory the package re-exports along with generated ones.
+const HANDWRITTEN_MODULES: [&str; 4] = ["base", "data", "json", "response"];
+
 pub fn compile_all(input_dir: PathBuf, output_dir: PathBuf) -> Result<()> {
     let mut modules: Vec<_> = fs::read_dir(&input_dir)?
         .filter_map(|entry| entry.ok().map(|entry| entry.path()))
         .map(|path| Module::try_from((path, output_dir.clone())).expect("unable to read module path"))
+        .filter(|module| !HANDWRITTEN_SCHEMAS.contains(&module.name()))
         .collect();
     modules.sort_by(|a, b| a.file_name().cmp(b.file_name()));
 
@@ -39,20 +47,23 @@ fn compile_files<'a>(modules: impl Iterator<Item = &'a Module>) -> Result<()> {
 }
 
 fn compile(module: &Module) -> Result<()> {
-    let parser = Parser::default();
+    let parser = Parser;
     let schema = parser.parse(module.source_path())?;
 
     let mut generator = SchemaGenerator::new();
     generator.visit_schema(&schema);
 
-    fs::write(&module.path(), generator.to_string())?;
+    fs::write(module.path(), generator.to_string())?;
     Ok(())
 }
 
 fn compile_package<'a>(modules: impl Iterator<Item = &'a Module>, path: &Path) -> Result<()> {
     let mut generator = PackageGenerator::new();
     for module in modules {
-        generator.visit_module(module);
+        generator.visit_module(module.name());
+    }
+    for name in HANDWRITTEN_MODULES.iter() {
+        generator.visit_module(name);
     }
 
     fs::write(path, generator.to_string())?;
@@ -0,0 +1,125 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{
+    aggregator::{Dimensions, Histogram, Series, DIMENSION_CAP_REACHED},
+    telemetry::DurationDistribution,
+};
+
+/// Prefix of dimensions the service uses internally, which are left out of distributions.
+const INTERNAL_DIMENSION: &str = "_MS.";
+
+/// Keeps distributions of standard duration metrics across aggregation intervals, so they can be read
+/// in-process at any time. The number of dimension combinations of a metric is capped the same way
+/// aggregates of an interval are.
+pub(crate) struct DurationDistributions {
+    max_series: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    series: BTreeMap<(String, Dimensions), Series>,
+    series_count: BTreeMap<String, usize>,
+}
+
+impl DurationDistributions {
+    /// Creates new distributions with a maximum number of dimension combinations per metric.
+    pub fn new(max_series: usize) -> Self {
+        Self {
+            max_series,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Adds a duration in milliseconds to a distribution of a metric with specified name and dimensions.
+    pub fn add(&self, name: &str, dimensions: &Dimensions, value: f64) {
+        let mut dimensions: Dimensions = dimensions
+            .iter()
+            .filter(|(key, _)| !key.starts_with(INTERNAL_DIMENSION))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = &mut *state;
+        if !state.series.contains_key(&(name.to_string(), dimensions.clone())) {
+            let count = state.series_count.entry(name.to_string()).or_default();
+            if *count < self.max_series {
+                *count += 1;
+            } else {
+                dimensions = Dimensions::from([(DIMENSION_CAP_REACHED.to_string(), "true".to_string())]);
+            }
+        }
+
+        let series = state.series.entry((name.to_string(), dimensions)).or_default();
+        series.stats.add_data(&[value]);
+        series.histogram.get_or_insert_with(Histogram::default).add(value);
+    }
+
+    /// Returns snapshots of all distributions.
+    pub fn snapshot(&self) -> Vec<DurationDistribution> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state
+            .series
+            .iter()
+            .map(|((name, dimensions), series)| {
+                DurationDistribution::new(
+                    name.clone(),
+                    dimensions.clone(),
+                    series.stats.clone(),
+                    series.histogram.clone().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn it_keeps_distributions_of_durations() {
+        let distributions = DurationDistributions::new(1);
+        let dimensions = |code: &str| {
+            Dimensions::from([
+                ("_MS.MetricId".to_string(), "requests/duration".to_string()),
+                ("request/resultCode".to_string(), code.to_string()),
+            ])
+        };
+        for value in 1..=100 {
+            distributions.add("Server response time", &dimensions("200"), value as f64);
+        }
+        distributions.add("Server response time", &dimensions("500"), 1000.0);
+
+        let snapshot = distributions.snapshot();
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].name(), "Server response time");
+        assert_eq!(
+            snapshot[0].dimensions(),
+            &BTreeMap::from([(DIMENSION_CAP_REACHED.to_string(), "true".to_string())])
+        );
+        assert_eq!(snapshot[0].count(), 1);
+
+        let distribution = &snapshot[1];
+        assert_eq!(
+            distribution.dimensions(),
+            &BTreeMap::from([("request/resultCode".to_string(), "200".to_string())])
+        );
+        assert_eq!(distribution.count(), 100);
+        assert_eq!(
+            (distribution.min(), distribution.max()),
+            (Duration::from_millis(1), Duration::from_millis(100))
+        );
+        assert_eq!(distribution.mean(), Duration::from_micros(50500));
+        let p90 = distribution.percentile(90.0).unwrap().as_secs_f64() * 1000.0;
+        assert!((p90 - 90.0).abs() <= 4.5, "unexpected p90 {}", p90);
+        let slow = distribution.count_over(Duration::from_millis(75));
+        assert!(slow.abs_diff(25) <= 5, "unexpected count {}", slow);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    time, TelemetryConfig,
+};
+
+/// Name of a measurement of a coalesced event telling how many times it was tracked within an interval.
+const COUNT_MEASUREMENT: &str = "count";
+
+/// Maximum number of distinct events coalesced within an interval. Events beyond it are submitted as is.
+const MAX_EVENTS: usize = 1000;
+
+/// Coalesces identical custom events within an aggregation interval into one event with a `count`
+/// measurement. Events are identical when everything but their time is the same. Events that have
+/// measurements are passed through, since their values cannot be added up meaningfully.
+pub(crate) struct EventAggregator {
+    interval: Duration,
+    window: Mutex<Window>,
+}
+
+struct Window {
+    started: Instant,
+    events: BTreeMap<String, (Envelope, usize)>,
+}
+
+impl EventAggregator {
+    /// Creates a new aggregator with an aggregation interval of specified config.
+    pub fn new(config: &TelemetryConfig) -> Self {
+        Self::with_interval(config.aggregation_interval())
+    }
+
+    fn with_interval(interval: Duration) -> Self {
+        Self {
+            interval,
+            window: Mutex::new(Window {
+                started: time::instant(),
+                events: BTreeMap::default(),
+            }),
+        }
+    }
+
+    /// Coalesces an item with identical events of the current interval if it is a custom event. Returns
+    /// items to submit right away: the item itself if it cannot be coalesced, and coalesced events of the
+    /// past interval if it has ended.
+    pub fn track(&self, envelope: Envelope) -> Vec<Envelope> {
+        let now = time::instant();
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut completed = if now.saturating_duration_since(window.started) >= self.interval {
+            window.take(now)
+        } else {
+            Vec::new()
+        };
+
+        match key(&envelope) {
+            Some(key) if window.events.contains_key(&key) || window.events.len() < MAX_EVENTS => {
+                window.events.entry(key).or_insert((envelope, 0)).1 += 1;
+            }
+            _ => completed.push(envelope),
+        }
+
+        completed
+    }
+
+    /// Returns coalesced events of the current interval and starts a new one.
+    pub fn take(&self) -> Vec<Envelope> {
+        let now = time::instant();
+        self.window.lock().unwrap_or_else(PoisonError::into_inner).take(now)
+    }
+}
+
+impl Window {
+    fn take(&mut self, now: Instant) -> Vec<Envelope> {
+        self.started = now;
+        std::mem::take(&mut self.events)
+            .into_values()
+            .map(|(mut envelope, count)| {
+                if let Some(Base::Data(Data::EventData(data))) = envelope.data.as_mut() {
+                    data.measurements
+                        .get_or_insert_with(Default::default)
+                        .insert(COUNT_MEASUREMENT.into(), count as f64);
+                }
+                envelope
+            })
+            .collect()
+    }
+}
+
+/// Returns a key identical events share, or `None` if an item is not an event that can be coalesced.
+fn key(envelope: &Envelope) -> Option<String> {
+    match &envelope.data {
+        Some(Base::Data(Data::EventData(data))) if data.measurements.iter().all(BTreeMap::is_empty) => {}
+        _ => return None,
+    }
+
+    let mut envelope = envelope.clone();
+    envelope.time.clear();
+    serde_json::to_string(&envelope).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_coalesces_identical_events_per_interval() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 0));
+        let aggregator = EventAggregator::with_interval(Duration::from_secs(60));
+
+        assert!(aggregator.track(event("login", None)).is_empty());
+        assert!(aggregator.track(event("login", None)).is_empty());
+        assert!(aggregator.track(event("logout", None)).is_empty());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 5, 0));
+        let completed = aggregator.track(event("login", None));
+
+        assert_eq!(
+            counts(&completed),
+            vec![("login".into(), Some(2.0)), ("logout".into(), Some(1.0))]
+        );
+        assert_eq!(counts(&aggregator.take()), vec![("login".into(), Some(1.0))]);
+        assert!(aggregator.take().is_empty());
+    }
+
+    #[test]
+    fn it_passes_through_events_with_measurements() {
+        let aggregator = EventAggregator::with_interval(Duration::from_secs(60));
+
+        let completed = aggregator.track(event("purchase", Some(42.0)));
+
+        assert_eq!(counts(&completed), vec![("purchase".into(), None)]);
+        assert!(aggregator.take().is_empty());
+    }
+
+    fn event(name: &str, amount: Option<f64>) -> Envelope {
+        let mut event = EventTelemetry::new(name);
+        event.properties_mut().insert("region".into(), "west".into());
+        if let Some(amount) = amount {
+            event.measurements_mut().insert("amount".into(), amount);
+        }
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        Envelope::from((context, event))
+    }
+
+    fn counts(envelopes: &[Envelope]) -> Vec<(String, Option<f64>)> {
+        envelopes
+            .iter()
+            .map(|envelope| match &envelope.data {
+                Some(Base::Data(Data::EventData(data))) => (
+                    data.name.clone(),
+                    data.measurements
+                        .as_ref()
+                        .and_then(|m| m.get(COUNT_MEASUREMENT))
+                        .copied(),
+                ),
+                _ => panic!("unexpected data"),
+            })
+            .collect()
+    }
+}
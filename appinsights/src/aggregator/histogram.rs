@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+/// Growth factor of bucket boundaries. Every bucket is this much wider than the previous one, so the
+/// relative error of a percentile estimate is within 5%.
+const GROWTH: f64 = 1.1;
+
+/// Approximate distribution of values stored in exponentially growing buckets. Positive and negative
+/// values are counted in separate sets of buckets by magnitude, zeros are counted separately.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct Histogram {
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+}
+
+impl Histogram {
+    /// Adds a value to the distribution.
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        if value > 0.0 {
+            *self.positive.entry(index(value)).or_default() += 1;
+        } else if value < 0.0 {
+            *self.negative.entry(index(-value)).or_default() += 1;
+        } else {
+            self.zeros += 1;
+        }
+        self.count += 1;
+    }
+
+    /// Returns an estimate of a value below which specified percent of values fall.
+    /// Returns `None` for an empty distribution.
+    pub fn percentile(&self, percent: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((percent.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as u64).max(1);
+
+        let negative = self
+            .negative
+            .iter()
+            .rev()
+            .map(|(index, count)| (-value(*index), *count));
+        let zeros = std::iter::once((0.0, self.zeros));
+        let positive = self.positive.iter().map(|(index, count)| (value(*index), *count));
+
+        let mut seen = 0;
+        negative
+            .chain(zeros)
+            .chain(positive)
+            .find(|(_, count)| {
+                seen += count;
+                seen >= rank
+            })
+            .map(|(value, _)| value)
+    }
+
+    /// Returns an estimate of a number of values greater than specified one. Values in the same bucket as
+    /// the threshold are not counted.
+    pub fn count_above(&self, threshold: f64) -> u64 {
+        if threshold > 0.0 {
+            let bucket = index(threshold);
+            self.positive.range(bucket + 1..).map(|(_, count)| count).sum()
+        } else if threshold == 0.0 {
+            self.positive.values().sum()
+        } else {
+            let bucket = index(-threshold);
+            let negative: u64 = self.negative.range(..bucket).map(|(_, count)| count).sum();
+            negative + self.zeros + self.positive.values().sum::<u64>()
+        }
+    }
+}
+
+/// Returns an index of a bucket for a positive value.
+fn index(value: f64) -> i32 {
+    value.log(GROWTH).ceil() as i32
+}
+
+/// Returns a representative value of a bucket, which is a midpoint between its boundaries.
+fn value(index: i32) -> f64 {
+    let upper = GROWTH.powi(index);
+    let lower = upper / GROWTH;
+    (lower + upper) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(50.0, 50.0 ; "median")]
+    #[test_case(95.0, 95.0 ; "95th percentile")]
+    #[test_case(99.0, 99.0 ; "99th percentile")]
+    #[test_case(100.0, 100.0 ; "maximum")]
+    #[test_case(0.0, 1.0 ; "minimum")]
+    fn it_estimates_percentiles(percent: f64, expected: f64) {
+        let mut histogram = Histogram::default();
+        for value in 1..=100 {
+            histogram.add(value as f64);
+        }
+
+        let actual = histogram.percentile(percent).unwrap();
+
+        assert!(
+            (actual - expected).abs() / expected <= 0.05,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn it_estimates_percentiles_of_non_positive_values() {
+        let mut histogram = Histogram::default();
+        for value in [-10.0, 0.0, 0.0, 5.0] {
+            histogram.add(value);
+        }
+
+        assert!((histogram.percentile(25.0).unwrap() + 10.0).abs() <= 0.5);
+        assert_eq!(histogram.percentile(50.0), Some(0.0));
+        assert_eq!(histogram.percentile(75.0), Some(0.0));
+    }
+
+    #[test_case(50.0, 50 ; "positive threshold")]
+    #[test_case(0.0, 100 ; "zero threshold")]
+    #[test_case(-1.0, 100 ; "negative threshold")]
+    fn it_counts_values_above_threshold(threshold: f64, expected: u64) {
+        let mut histogram = Histogram::default();
+        for value in 1..=100 {
+            histogram.add(value as f64);
+        }
+
+        let actual = histogram.count_above(threshold);
+
+        assert!(
+            actual.abs_diff(expected) <= 5,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn it_returns_nothing_for_empty_histogram() {
+        assert_eq!(Histogram::default().percentile(50.0), None);
+    }
+}
@@ -0,0 +1,242 @@
+mod distributions;
+mod events;
+mod histogram;
+pub(crate) mod standard;
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    telemetry::{AggregateMetricTelemetry, Stats, Telemetry},
+    time, TelemetryConfig,
+};
+pub(crate) use distributions::DurationDistributions;
+pub(crate) use events::EventAggregator;
+pub(crate) use histogram::Histogram;
+
+/// Name of a dimension of a series that aggregates values of all dimension combinations exceeding the cap.
+const DIMENSION_CAP_REACHED: &str = "dimensionCapReached";
+
+pub(crate) type Dimensions = BTreeMap<String, String>;
+
+/// Aggregates metric values locally and produces one aggregated metric telemetry item per metric name
+/// and dimension combination for every aggregation interval. Aggregates of an interval are produced when
+/// a value is tracked after the interval ends or when pending aggregates are requested explicitly.
+///
+/// The number of dimension combinations of a metric within an interval is capped. Values of all
+/// combinations exceeding the cap are aggregated into a single series marked with a
+/// `dimensionCapReached` dimension.
+///
+/// When percentiles are configured, an approximate distribution of values is tracked as well and every
+/// percentile is produced as a separate series named after the metric with a percentile suffix,
+/// e.g. `latency_p95`.
+pub(crate) struct MetricAggregator {
+    interval: Duration,
+    max_series: usize,
+    percentiles: Vec<f64>,
+    window: Mutex<Window>,
+}
+
+struct Window {
+    started: Instant,
+    series: BTreeMap<(String, Dimensions), Series>,
+    series_count: BTreeMap<String, usize>,
+}
+
+#[derive(Default)]
+struct Series {
+    stats: Stats,
+    histogram: Option<Histogram>,
+}
+
+impl MetricAggregator {
+    /// Creates a new aggregator with aggregation interval, a maximum number of dimension combinations per
+    /// metric and percentiles of specified config.
+    pub fn new(config: &TelemetryConfig) -> Self {
+        Self::with_settings(
+            config.aggregation_interval(),
+            config.max_metric_series(),
+            config.metric_percentiles().to_vec(),
+        )
+    }
+
+    fn with_settings(interval: Duration, max_series: usize, percentiles: Vec<f64>) -> Self {
+        Self {
+            interval,
+            max_series,
+            percentiles,
+            window: Mutex::new(Window {
+                started: time::instant(),
+                series: BTreeMap::default(),
+                series_count: BTreeMap::default(),
+            }),
+        }
+    }
+
+    /// Adds a value to the aggregate of a metric with specified name and dimensions. Returns aggregates of
+    /// the past interval if it has ended.
+    pub fn track(&self, name: String, dimensions: Dimensions, value: f64) -> Vec<AggregateMetricTelemetry> {
+        let now = time::instant();
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let completed = if now.saturating_duration_since(window.started) >= self.interval {
+            window.take(now, &self.percentiles)
+        } else {
+            Vec::new()
+        };
+
+        let mut key = (name, dimensions);
+        if !window.series.contains_key(&key) {
+            let count = window.series_count.entry(key.0.clone()).or_default();
+            if *count < self.max_series {
+                *count += 1;
+            } else {
+                let mut overflow = Dimensions::default();
+                overflow.insert(DIMENSION_CAP_REACHED.into(), "true".into());
+                key.1 = overflow;
+            }
+        }
+        let series = window.series.entry(key).or_default();
+        series.stats.add_data(&[value]);
+        if !self.percentiles.is_empty() {
+            series.histogram.get_or_insert_with(Histogram::default).add(value);
+        }
+
+        completed
+    }
+
+    /// Returns aggregates of the current interval and starts a new one.
+    pub fn take(&self) -> Vec<AggregateMetricTelemetry> {
+        let now = time::instant();
+        self.window
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take(now, &self.percentiles)
+    }
+}
+
+impl Window {
+    fn take(&mut self, now: Instant, percentiles: &[f64]) -> Vec<AggregateMetricTelemetry> {
+        self.started = now;
+        self.series_count.clear();
+
+        let mut aggregates = Vec::new();
+        for ((name, dimensions), series) in std::mem::take(&mut self.series) {
+            if let Some(histogram) = &series.histogram {
+                for percent in percentiles {
+                    if let Some(value) = histogram.percentile(*percent) {
+                        let value = value.clamp(series.stats.min, series.stats.max);
+                        let stats = Stats {
+                            value,
+                            min: value,
+                            max: value,
+                            count: 1,
+                            std_dev: 0.0,
+                        };
+                        aggregates.push(aggregate(format!("{}_p{}", name, percent), stats, dimensions.clone()));
+                    }
+                }
+            }
+            aggregates.push(aggregate(name, series.stats, dimensions));
+        }
+
+        aggregates
+    }
+}
+
+fn aggregate(name: String, stats: Stats, dimensions: Dimensions) -> AggregateMetricTelemetry {
+    let mut telemetry = AggregateMetricTelemetry::new(name);
+    *telemetry.stats_mut() = stats;
+    telemetry.properties_mut().extend(dimensions);
+    telemetry
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn it_aggregates_values_per_interval() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 0));
+        let aggregator = MetricAggregator::with_settings(Duration::from_secs(60), 10, Vec::new());
+
+        assert!(aggregator
+            .track("latency".into(), Dimensions::default(), 10.0)
+            .is_empty());
+        assert!(aggregator
+            .track("latency".into(), Dimensions::default(), 30.0)
+            .is_empty());
+        assert!(aggregator.track("queue".into(), Dimensions::default(), 5.0).is_empty());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 5, 0));
+        let completed = aggregator.track("latency".into(), Dimensions::default(), 100.0);
+
+        assert_eq!(completed.len(), 2);
+        assert_eq!(completed[0].stats().count, 2);
+        assert_eq!(completed[0].stats().value, 40.0);
+        assert_eq!(completed[0].stats().min, 10.0);
+        assert_eq!(completed[0].stats().max, 30.0);
+        assert_eq!(completed[1].stats().count, 1);
+
+        let pending = aggregator.take();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].stats().value, 100.0);
+        assert!(aggregator.take().is_empty());
+    }
+
+    #[test]
+    fn it_aggregates_values_per_dimension_combination() {
+        let aggregator = MetricAggregator::with_settings(Duration::from_secs(60), 2, Vec::new());
+
+        for endpoint in ["/orders", "/users", "/orders", "/items", "/health"] {
+            aggregator.track("latency".into(), dimensions(endpoint), 10.0);
+        }
+
+        let completed = aggregator.take();
+        let series: Vec<_> = completed
+            .iter()
+            .map(|telemetry| (BTreeMap::from(telemetry.properties().clone()), telemetry.stats().count))
+            .collect();
+
+        assert_eq!(
+            series,
+            vec![(overflow(), 2), (dimensions("/orders"), 2), (dimensions("/users"), 1),]
+        );
+    }
+
+    #[test]
+    fn it_produces_percentiles_as_separate_series() {
+        let aggregator = MetricAggregator::with_settings(Duration::from_secs(60), 10, vec![50.0, 99.0]);
+
+        for value in 1..=100 {
+            aggregator.track("latency".into(), dimensions("/orders"), value as f64);
+        }
+
+        let completed = aggregator.take();
+        assert_eq!(completed.len(), 3);
+
+        let p50 = completed[0].stats().value;
+        assert!((p50 - 50.0).abs() <= 2.5, "unexpected p50 {}", p50);
+        let p99 = completed[1].stats().value;
+        assert!((p99 - 99.0).abs() <= 5.0 && p99 <= 100.0, "unexpected p99 {}", p99);
+        assert_eq!(BTreeMap::from(completed[1].properties().clone()), dimensions("/orders"));
+        assert_eq!(completed[2].stats().count, 100);
+    }
+
+    fn dimensions(endpoint: &str) -> Dimensions {
+        let mut dimensions = Dimensions::default();
+        dimensions.insert("endpoint".into(), endpoint.into());
+        dimensions
+    }
+
+    fn overflow() -> Dimensions {
+        let mut dimensions = Dimensions::default();
+        dimensions.insert(DIMENSION_CAP_REACHED.into(), "true".into());
+        dimensions
+    }
+}
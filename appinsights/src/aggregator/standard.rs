@@ -0,0 +1,159 @@
+use crate::{
+    aggregator::Dimensions,
+    contracts::{Base, Data, Envelope},
+    time::Duration,
+};
+
+/// Name of a property that tells the service a telemetry item is already counted by a standard metric.
+const PROCESSED_BY_EXTRACTORS: &str = "_MS.ProcessedByMetricExtractors";
+
+const REQUEST_DURATION: &str = "Server response time";
+const DEPENDENCY_DURATION: &str = "Dependency duration";
+
+/// A value of a standard metric extracted from a request or a dependency call.
+#[derive(Debug, PartialEq)]
+pub(crate) struct StandardMetric {
+    pub(crate) name: String,
+    pub(crate) dimensions: Dimensions,
+    pub(crate) value: f64,
+}
+
+/// Extracts a duration of a request or a dependency call as a standard metric split by success, result
+/// code and, for dependencies, type and target. The item is marked as processed, so the service computes
+/// request and dependency charts from pre-aggregated metrics, which stay accurate even when raw items are
+/// sampled out afterwards.
+pub(crate) fn extract(envelope: &mut Envelope) -> Option<StandardMetric> {
+    let tags = envelope.tags.as_ref();
+    let mut dimensions = Dimensions::default();
+    dimensions.insert("_MS.IsAutocollected".into(), "True".into());
+    for (tag, dimension) in [
+        ("ai.cloud.role", "cloud/roleName"),
+        ("ai.cloud.roleInstance", "cloud/roleInstance"),
+    ] {
+        if let Some(value) = tags.and_then(|tags| tags.get(tag)) {
+            dimensions.insert(dimension.into(), value.clone());
+        }
+    }
+
+    let (name, duration, properties, extractor) = match envelope.data.as_mut()? {
+        Base::Data(Data::RequestData(data)) => {
+            dimensions.insert("_MS.MetricId".into(), "requests/duration".into());
+            dimensions.insert("Request.Success".into(), flag(data.success));
+            dimensions.insert("request/resultCode".into(), data.response_code.clone());
+            (REQUEST_DURATION, &data.duration, &mut data.properties, "Requests")
+        }
+        Base::Data(Data::RemoteDependencyData(data)) => {
+            dimensions.insert("_MS.MetricId".into(), "dependencies/duration".into());
+            dimensions.insert("Dependency.Success".into(), flag(data.success.unwrap_or(true)));
+            let values = [
+                ("Dependency.Type", &data.type_),
+                ("dependency/target", &data.target),
+                ("dependency/resultCode", &data.result_code),
+            ];
+            for (dimension, value) in values {
+                if let Some(value) = value {
+                    dimensions.insert(dimension.into(), value.clone());
+                }
+            }
+            (
+                DEPENDENCY_DURATION,
+                &data.duration,
+                &mut data.properties,
+                "Dependencies",
+            )
+        }
+        _ => return None,
+    };
+
+    let duration = duration.parse::<Duration>().ok()?;
+    properties.get_or_insert_with(Default::default).insert(
+        PROCESSED_BY_EXTRACTORS.into(),
+        format!("(Name:'{}', Ver:'1.1')", extractor),
+    );
+
+    Some(StandardMetric {
+        name: name.into(),
+        dimensions,
+        value: duration.as_secs_f64() * 1000.0,
+    })
+}
+
+fn flag(value: bool) -> String {
+    if value { "True" } else { "False" }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use http::Method;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties, RemoteDependencyTelemetry, RequestTelemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_extracts_request_duration() {
+        let request = RequestTelemetry::new(
+            Method::GET,
+            "https://example.com/orders".parse().unwrap(),
+            StdDuration::from_millis(250),
+            "500",
+        );
+        let mut envelope = Envelope::from((context(), request));
+
+        let metric = extract(&mut envelope).unwrap();
+
+        assert_eq!(metric.name, "Server response time");
+        assert_eq!(metric.value, 250.0);
+        assert_eq!(metric.dimensions["_MS.MetricId"], "requests/duration");
+        assert_eq!(metric.dimensions["Request.Success"], "False");
+        assert_eq!(metric.dimensions["request/resultCode"], "500");
+        assert_eq!(metric.dimensions["cloud/roleName"], "orders");
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => assert_eq!(
+                data.properties.unwrap()[PROCESSED_BY_EXTRACTORS],
+                "(Name:'Requests', Ver:'1.1')"
+            ),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_extracts_dependency_duration() {
+        let mut dependency = RemoteDependencyTelemetry::new(
+            "GET /users",
+            "Http",
+            StdDuration::from_micros(1500),
+            "example.com",
+            true,
+        );
+        dependency.set_result_code("200");
+        let mut envelope = Envelope::from((context(), dependency));
+
+        let metric = extract(&mut envelope).unwrap();
+
+        assert_eq!(metric.name, "Dependency duration");
+        assert_eq!(metric.value, 1.5);
+        assert_eq!(metric.dimensions["_MS.MetricId"], "dependencies/duration");
+        assert_eq!(metric.dimensions["Dependency.Type"], "Http");
+        assert_eq!(metric.dimensions["Dependency.Success"], "True");
+        assert_eq!(metric.dimensions["dependency/target"], "example.com");
+        assert_eq!(metric.dimensions["dependency/resultCode"], "200");
+    }
+
+    #[test]
+    fn it_does_not_extract_from_other_items() {
+        let mut envelope = Envelope::from((context(), EventTelemetry::new("event")));
+
+        assert_eq!(extract(&mut envelope), None);
+    }
+
+    fn context() -> TelemetryContext {
+        let mut tags = ContextTags::default();
+        tags.cloud_mut().set_role("orders".into());
+        TelemetryContext::new("instrumentation".into(), tags, Properties::default())
+    }
+}
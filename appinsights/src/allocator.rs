@@ -0,0 +1,201 @@
+//! Memory allocator statistics metrics.
+//!
+//! Resident memory of a long-running service tells little about why it grows. Statistics of a memory
+//! allocator tell apart memory held by live allocations from memory the allocator keeps for reuse or
+//! can't return to the system. [`AllocatorMetrics`](struct.AllocatorMetrics.html) samples
+//! [`AllocatorStats`](struct.AllocatorStats.html) and reports them as gauges every aggregation
+//! interval:
+//! * `allocator_allocated_bytes` held by live allocations,
+//! * `allocator_active_bytes` of pages with live allocations,
+//! * `allocator_resident_bytes` physically resident in memory,
+//! * `allocator_mapped_bytes` and `allocator_retained_bytes` mapped or kept without being returned to
+//!   the system,
+//! * `allocator_fragmentation_percent` of active or resident memory that is not allocated.
+//!
+//! Only gauges of statistics the allocator provides are reported. The metrics do not depend on a
+//! particular version of allocator crates. With `jemalloc` feature statistics are read from
+//! [`tikv-jemalloc-ctl`](https://docs.rs/tikv-jemalloc-ctl) keys, with `mimalloc` feature they are
+//! built from a process info of [`libmimalloc-sys`](https://docs.rs/libmimalloc-sys).
+//!
+//! ```rust, ignore
+//! use appinsights::{allocator::{AllocatorMetrics, AllocatorStats}, TelemetryClient};
+//! use std::{ffi::CString, sync::Arc};
+//!
+//! #[global_allocator]
+//! static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! AllocatorMetrics::new(|| {
+//!     // statistics are cached by jemalloc until an epoch is advanced
+//!     tikv_jemalloc_ctl::epoch::advance().ok()?;
+//!     AllocatorStats::jemalloc(|key| {
+//!         let key = CString::new(key).ok()?;
+//!         unsafe { tikv_jemalloc_ctl::raw::read::<usize>(key.as_bytes_with_nul()) }.ok()
+//!     })
+//! })
+//! .register(&client);
+//! # }
+//! ```
+use std::sync::Arc;
+
+use crate::TelemetryClient;
+
+/// Reads a value of a gauge from statistics.
+type Statistic = fn(&AllocatorStats) -> Option<f64>;
+
+/// Statistics of a memory allocator in bytes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Memory held by live allocations.
+    pub allocated: Option<u64>,
+
+    /// Memory in pages with live allocations.
+    pub active: Option<u64>,
+
+    /// Memory physically resident in memory.
+    pub resident: Option<u64>,
+
+    /// Memory mapped by the allocator.
+    pub mapped: Option<u64>,
+
+    /// Memory retained by the allocator without being returned to the system.
+    pub retained: Option<u64>,
+}
+
+impl AllocatorStats {
+    /// Reads statistics with a function that returns a value of a jemalloc `mallctl` key, like
+    /// `stats.allocated`. Returns `None` when allocated memory can't be read. An epoch must be advanced
+    /// before reading for values to be current.
+    #[cfg(feature = "jemalloc")]
+    pub fn jemalloc<F>(read: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        let read = |key| read(key).map(|value| value as u64);
+        Some(Self {
+            allocated: Some(read("stats.allocated")?),
+            active: read("stats.active"),
+            resident: read("stats.resident"),
+            mapped: read("stats.mapped"),
+            retained: read("stats.retained"),
+        })
+    }
+
+    /// Creates statistics from current resident and committed memory reported by `mi_process_info`
+    /// of mimalloc. Committed memory is reported as active.
+    #[cfg(feature = "mimalloc")]
+    pub fn mimalloc(current_rss: usize, current_commit: usize) -> Self {
+        Self {
+            resident: Some(current_rss as u64),
+            active: Some(current_commit as u64),
+            ..Self::default()
+        }
+    }
+
+    /// Returns a percentage of active, or if it is unknown resident, memory that is not held by live
+    /// allocations.
+    pub fn fragmentation(&self) -> Option<f64> {
+        let allocated = self.allocated?;
+        let total = self.active.or(self.resident).filter(|total| *total > 0)?;
+        Some(total.saturating_sub(allocated) as f64 / total as f64 * 100.0)
+    }
+}
+
+/// Reports statistics of a memory allocator as gauges.
+pub struct AllocatorMetrics {
+    sample: Arc<dyn Fn() -> Option<AllocatorStats> + Send + Sync>,
+}
+
+impl AllocatorMetrics {
+    /// Creates new metrics that read statistics with specified function.
+    pub fn new<F>(sample: F) -> Self
+    where
+        F: Fn() -> Option<AllocatorStats> + Send + Sync + 'static,
+    {
+        Self {
+            sample: Arc::new(sample),
+        }
+    }
+
+    /// Registers gauges of statistics the allocator currently provides with a client.
+    pub fn register(self, client: &Arc<TelemetryClient>) {
+        let stats = match (self.sample)() {
+            Some(stats) => stats,
+            None => {
+                log::warn!("Allocator statistics are not available");
+                return;
+            }
+        };
+
+        let gauges: [(&str, Statistic); 6] = [
+            ("allocator_allocated_bytes", |stats| {
+                stats.allocated.map(|value| value as f64)
+            }),
+            ("allocator_active_bytes", |stats| stats.active.map(|value| value as f64)),
+            ("allocator_resident_bytes", |stats| {
+                stats.resident.map(|value| value as f64)
+            }),
+            ("allocator_mapped_bytes", |stats| stats.mapped.map(|value| value as f64)),
+            ("allocator_retained_bytes", |stats| {
+                stats.retained.map(|value| value as f64)
+            }),
+            ("allocator_fragmentation_percent", AllocatorStats::fragmentation),
+        ];
+        for (name, value) in gauges {
+            if value(&stats).is_some() {
+                let sample = self.sample.clone();
+                client.register_gauge(name, move || sample().as_ref().and_then(value).unwrap_or_default());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Some(60), Some(80), Some(100), Some(25.0) ; "active")]
+    #[test_case(Some(60), None,     Some(100), Some(40.0) ; "resident")]
+    #[test_case(None,     Some(80), Some(100), None       ; "unknown allocated")]
+    fn it_computes_fragmentation(
+        allocated: Option<u64>,
+        active: Option<u64>,
+        resident: Option<u64>,
+        expected: Option<f64>,
+    ) {
+        let stats = AllocatorStats {
+            allocated,
+            active,
+            resident,
+            ..AllocatorStats::default()
+        };
+
+        assert_eq!(stats.fragmentation(), expected);
+    }
+
+    #[cfg(feature = "jemalloc")]
+    #[test]
+    fn it_reads_jemalloc_keys() {
+        let stats = AllocatorStats::jemalloc(|key| match key {
+            "stats.allocated" => Some(1),
+            "stats.active" => Some(2),
+            "stats.resident" => Some(3),
+            _ => None,
+        });
+
+        assert_eq!(
+            stats,
+            Some(AllocatorStats {
+                allocated: Some(1),
+                active: Some(2),
+                resident: Some(3),
+                mapped: None,
+                retained: None,
+            })
+        );
+    }
+}
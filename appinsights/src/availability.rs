@@ -0,0 +1,649 @@
+//! Module for running availability tests from within the application.
+//!
+//! [`AvailabilityRunner`](struct.AvailabilityRunner.html) periodically runs user-supplied checks,
+//! measures how long each check takes and submits the result as an
+//! [`AvailabilityTelemetry`](../telemetry/struct.AvailabilityTelemetry.html) item. It makes possible to
+//! monitor availability of services that are not reachable from the internet, e.g. in-cluster
+//! ones, without configuring web tests in Azure Portal.
+//!
+//! Every test run is an operation. A check receives an [`AvailabilityScope`](struct.AvailabilityScope.html)
+//! of the run, and the scope is also current while the check runs, so any telemetry a client submits
+//! from the check's task, like dependency calls made to a service under test by instrumented clients,
+//! is correlated to the availability result. It makes possible to find a root cause of a failed
+//! test from Azure Portal. Tasks a check spawns do not inherit the scope, they can correlate
+//! telemetry with [`AvailabilityScope::track`](struct.AvailabilityScope.html#method.track) explicitly.
+//!
+//! ```rust, no_run
+//! use appinsights::{
+//!     availability::{AvailabilityRunner, AvailabilityTest},
+//!     telemetry::{RemoteDependencyTelemetry, Telemetry},
+//!     time::Stopwatch,
+//!     TelemetryClient,
+//! };
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let runner = AvailabilityRunner::new(client)
+//!     .run_location("westeurope-cluster")
+//!     // succeeds when the service responds with a success status code
+//!     .test(AvailabilityTest::ping("orders", "http://orders.svc.cluster.local/health".parse().unwrap()))
+//!     // succeeds when the check returns Ok
+//!     .test(
+//!         AvailabilityTest::new("queue depth", |scope| async move {
+//!             let started = Stopwatch::start();
+//!             let depth = 42;
+//!             // submit a dependency call correlated with the test run
+//!             scope.track(
+//!                 RemoteDependencyTelemetry::new("LLEN jobs", "Redis", started.elapsed(), "redis", true)
+//!                     .with_timestamp(started.started_at()),
+//!             );
+//!
+//!             if depth < 100 {
+//!                 Ok(())
+//!             } else {
+//!                 Err(format!("queue is too deep: {}", depth))
+//!             }
+//!         })
+//!         .interval(Duration::from_secs(60)),
+//!     )
+//!     .start();
+//!
+//! // ... stop running tests
+//! runner.stop();
+//! # }
+//! ```
+//!
+//! A [`ProbeTracker`](struct.ProbeTracker.html) records results of checks the application runs itself,
+//! like Kubernetes liveness and readiness probe handlers. Probes run every few seconds, so successful
+//! results are sampled and submitted at most once per interval, while failures and recoveries are
+//! always submitted. Every submitted result carries numbers of successful and failed checks it stands
+//! for. It keeps probe health history queryable in Application Insights.
+//!
+//! ```rust, no_run
+//! use appinsights::{availability::ProbeTracker, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! let readiness = ProbeTracker::new(client, "readiness").run_location("orders-7d9f8");
+//!
+//! // in a handler of GET /ready
+//! let status = match readiness.check(async { Ok::<_, String>(()) }).await {
+//!     Ok(()) => 200,
+//!     Err(_) => 503,
+//! };
+//! # }
+//! ```
+use std::{
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "net")]
+use http::Uri;
+use log::debug;
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "net")]
+use crate::telemetry::{DependencyTarget, RemoteDependencyTelemetry};
+use crate::{
+    contracts::Envelope,
+    telemetry::{AvailabilityTelemetry, Telemetry},
+    time::{self, Stopwatch},
+    TelemetryClient, TelemetryContext,
+};
+
+tokio::task_local! {
+    static CURRENT: AvailabilityScope;
+}
+
+type Check = Arc<dyn Fn(AvailabilityScope) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+/// An operation of a single availability test run. Telemetry submitted within the scope is
+/// correlated to the availability result of the run.
+#[derive(Clone)]
+pub struct AvailabilityScope {
+    client: Arc<TelemetryClient>,
+    id: String,
+    name: String,
+}
+
+impl AvailabilityScope {
+    /// Returns the identifier of a test run. It is used as an operation id and a parent id of
+    /// all telemetry submitted within the scope.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the name of the test.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a scope of a test run the current task runs a check of, if there is any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Sets operation tags of a telemetry item to correlate it to the test run.
+    pub fn correlate<E: Telemetry + ?Sized>(&self, telemetry: &mut E) {
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.id.clone());
+        operation.set_parent_id(self.id.clone());
+        operation.set_name(self.name.clone());
+    }
+
+    /// Correlates a telemetry item to the test run and submits it with the telemetry client.
+    pub fn track<E>(&self, mut telemetry: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.correlate(&mut telemetry);
+        self.client.track(telemetry);
+    }
+}
+
+/// Describes an availability test: a name, a check to run and how often to run it.
+#[derive(Clone)]
+pub struct AvailabilityTest {
+    name: String,
+    interval: Duration,
+    timeout: Duration,
+    check: Check,
+}
+
+impl AvailabilityTest {
+    /// Creates a new availability test that runs a custom check. The test succeeds when the check
+    /// returns `Ok` and fails with the error as a diagnostic message otherwise. A check receives a scope
+    /// of the test run to correlate telemetry it submits.
+    /// The test runs every 5 minutes and times out after 30 seconds by default.
+    pub fn new<F, Fut, E>(name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn(AvailabilityScope) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        let check: Check = Arc::new(move |scope| {
+            let check = check(scope);
+            Box::pin(async move { check.await.map_err(|err| err.to_string()) })
+        });
+        Self {
+            name: name.into(),
+            interval: Duration::from_secs(300),
+            timeout: Duration::from_secs(30),
+            check,
+        }
+    }
+
+    /// Creates a new availability test that sends a `GET` request to specified URL. The test succeeds
+    /// when the server responds with a success status code. The request is submitted as a dependency
+    /// call correlated to the test run. It requires `net` feature.
+    #[cfg(feature = "net")]
+    pub fn ping(name: impl Into<String>, uri: Uri) -> Self {
+        let client = reqwest::Client::new();
+        let target = DependencyTarget::from_uri(&uri);
+        Self::new(name, move |scope| {
+            let started = Stopwatch::start();
+            let request = client.get(uri.to_string()).send();
+            let name = format!("GET {}", uri.path());
+            let target = target.clone();
+            async move {
+                let result = match request.await {
+                    Ok(response) if response.status().is_success() => Ok(response.status()),
+                    Ok(response) => Err(format!("server responded with {}", response.status())),
+                    Err(err) => Err(err.to_string()),
+                };
+
+                let mut dependency = RemoteDependencyTelemetry::new(
+                    name,
+                    target.dependency_type(),
+                    started.elapsed(),
+                    target.target(),
+                    result.is_ok(),
+                );
+                dependency.set_timestamp(started.started_at());
+                if let Ok(status) = &result {
+                    dependency.set_result_code(status.as_str());
+                }
+                scope.track(dependency);
+
+                result.map(|_| ())
+            }
+        })
+    }
+
+    /// Sets how often the test runs.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets maximum time a check is allowed to run before the test is considered failed.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the name of the test.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs the check once and submits the result with the telemetry client.
+    async fn run(&self, client: &Arc<TelemetryClient>, run_location: Option<&str>) {
+        let scope = AvailabilityScope {
+            client: client.clone(),
+            id: client.context().ids.new_random().as_hyphenated().to_string(),
+            name: self.name.clone(),
+        };
+
+        let started = Stopwatch::start();
+        let check = CURRENT.scope(scope.clone(), (self.check)(scope.clone()));
+        let result = match tokio::time::timeout(self.timeout, check).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("timed out after {:?}", self.timeout)),
+        };
+
+        let mut telemetry = AvailabilityTelemetry::new(self.name.clone(), started.elapsed(), result.is_ok());
+        telemetry.set_timestamp(started.started_at());
+        telemetry.set_id(scope.id.clone());
+        if let Some(run_location) = run_location {
+            telemetry.set_run_location(run_location);
+        }
+        if let Err(message) = result {
+            debug!("Availability test {} failed: {}", self.name, message);
+            telemetry.set_message(message);
+        }
+
+        scope.track(telemetry);
+    }
+}
+
+/// Periodically runs availability tests and submits results with the telemetry client.
+pub struct AvailabilityRunner {
+    client: Arc<TelemetryClient>,
+    run_location: Option<String>,
+    tests: Vec<AvailabilityTest>,
+}
+
+impl AvailabilityRunner {
+    /// Creates a new runner that submits test results with specified telemetry client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            run_location: None,
+            tests: Vec::new(),
+        }
+    }
+
+    /// Sets the name of the location where tests run, e.g. a cluster or a region name.
+    pub fn run_location(mut self, run_location: impl Into<String>) -> Self {
+        self.run_location = Some(run_location.into());
+        self
+    }
+
+    /// Adds a test to run.
+    pub fn test(mut self, test: AvailabilityTest) -> Self {
+        self.tests.push(test);
+        self
+    }
+
+    /// Spawns a new task for every test. Each test runs immediately and then with its own interval.
+    /// It must be called within Tokio runtime.
+    pub fn start(self) -> AvailabilityRunnerHandle {
+        let Self {
+            client,
+            run_location,
+            tests,
+        } = self;
+
+        let tasks = tests
+            .into_iter()
+            .map(|test| {
+                let client = client.clone();
+                let run_location = run_location.clone();
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(test.interval);
+                    loop {
+                        interval.tick().await;
+                        test.run(&client, run_location.as_deref()).await;
+                    }
+                })
+            })
+            .collect();
+
+        AvailabilityRunnerHandle { tasks }
+    }
+}
+
+/// A handle to running availability tests.
+pub struct AvailabilityRunnerHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl AvailabilityRunnerHandle {
+    /// Stops running all tests.
+    pub fn stop(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Submits sampled results of a probe the application runs, e.g. a Kubernetes liveness or readiness
+/// probe handler, as availability results.
+pub struct ProbeTracker {
+    client: Arc<TelemetryClient>,
+    name: String,
+    run_location: Option<String>,
+    interval: Duration,
+    state: Mutex<ProbeState>,
+}
+
+/// Results of a probe since the last submitted one.
+#[derive(Default)]
+struct ProbeState {
+    submitted: Option<(Instant, bool)>,
+    skipped: u64,
+}
+
+/// A measurement of a probe result with a number of checks it stands for.
+const PROBE_COUNT: &str = "probe_count";
+
+/// A measurement of a probe result with a number of successful checks it stands for.
+const PROBE_SUCCESS_COUNT: &str = "probe_success_count";
+
+/// A measurement of a probe result with a number of failed checks it stands for.
+const PROBE_FAILURE_COUNT: &str = "probe_failure_count";
+
+impl ProbeTracker {
+    /// Creates a new tracker of a probe with specified name. Successful results are submitted at most
+    /// once a minute by default.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>, name: impl Into<String>) -> Self {
+        Self {
+            client: client.into(),
+            name: name.into(),
+            run_location: None,
+            interval: Duration::from_secs(60),
+            state: Mutex::new(ProbeState::default()),
+        }
+    }
+
+    /// Sets the name of the location where the probe runs, e.g. a pod name.
+    pub fn run_location(mut self, run_location: impl Into<String>) -> Self {
+        self.run_location = Some(run_location.into());
+        self
+    }
+
+    /// Sets how often successful results are submitted.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Awaits a future that performs a probe check and submits the result if it is sampled.
+    pub async fn check<F, T, E>(&self, future: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let started = Stopwatch::start();
+        let result = future.await;
+        self.submit(started.elapsed(), result.as_ref().err());
+        result
+    }
+
+    /// Runs a function that performs a probe check synchronously and submits the result if it is sampled.
+    pub fn check_blocking<F, T, E>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: Display,
+    {
+        let started = Stopwatch::start();
+        let result = f();
+        self.submit(started.elapsed(), result.as_ref().err());
+        result
+    }
+
+    /// Submits a result when it is a failure, a change of state or the first success in the interval.
+    /// A number of results it stands for is submitted as `probe_count` measurement, split into
+    /// `probe_success_count` and `probe_failure_count`. Only successes are ever skipped, so all results
+    /// but the submitted one are successes.
+    fn submit(&self, duration: Duration, error: Option<&impl Display>) {
+        let success = error.is_none();
+        let now = time::instant();
+        let skipped = {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            let sampled = match state.submitted {
+                Some((at, last)) => !success || !last || now.duration_since(at) >= self.interval,
+                None => true,
+            };
+            if !sampled {
+                state.skipped += 1;
+                return;
+            }
+            state.submitted = Some((now, success));
+            std::mem::take(&mut state.skipped)
+        };
+
+        let (successes, failures) = if success { (skipped + 1, 0) } else { (skipped, 1) };
+        let mut telemetry = AvailabilityTelemetry::new(self.name.clone(), duration, success)
+            .with_measurement(PROBE_COUNT, (successes + failures) as f64)
+            .with_measurement(PROBE_SUCCESS_COUNT, successes as f64)
+            .with_measurement(PROBE_FAILURE_COUNT, failures as f64);
+        telemetry.set_id(self.client.context().ids.new_random().as_hyphenated().to_string());
+        if let Some(run_location) = &self.run_location {
+            telemetry.set_run_location(run_location.clone());
+        }
+        if let Some(error) = error {
+            debug!("Probe {} failed: {}", self.name, error);
+            telemetry.set_message(error.to_string());
+        }
+        self.client.track(telemetry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        client::tests::create_client,
+        contracts::{Base, Data},
+    };
+
+    #[tokio::test]
+    async fn it_submits_successful_result() {
+        let events = Arc::new(SegQueue::default());
+        let client = Arc::new(create_client(events.clone()));
+
+        let test = AvailabilityTest::new("check", |_| async { Ok::<_, String>(()) });
+        test.run(&client, Some("local")).await;
+
+        let envelope = events.pop().unwrap();
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::AvailabilityData(data)))
+                if data.name == "check"
+                    && data.success
+                    && !data.id.is_empty()
+                    && data.run_location == Some("local".into())
+                    && data.message.is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_submits_failed_result_with_message() {
+        let events = Arc::new(SegQueue::default());
+        let client = Arc::new(create_client(events.clone()));
+
+        let test = AvailabilityTest::new("check", |_| async { Err("connection refused") });
+        test.run(&client, None).await;
+
+        let envelope = events.pop().unwrap();
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::AvailabilityData(data)))
+                if !data.success && data.message == Some("connection refused".into()) && data.run_location.is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_test_when_check_times_out() {
+        let events = Arc::new(SegQueue::default());
+        let client = Arc::new(create_client(events.clone()));
+
+        let test = AvailabilityTest::new("check", |_| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok::<_, String>(())
+        })
+        .timeout(Duration::from_millis(10));
+        test.run(&client, None).await;
+
+        let envelope = events.pop().unwrap();
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::AvailabilityData(data)))
+                if !data.success && data.message == Some("timed out after 10ms".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_correlates_telemetry_submitted_by_check() {
+        let events = Arc::new(SegQueue::default());
+        let client = Arc::new(create_client(events.clone()));
+
+        let test = AvailabilityTest::new("check", |scope| async move {
+            scope.track(RemoteDependencyTelemetry::new(
+                "query",
+                "SQL",
+                Duration::default(),
+                "db",
+                true,
+            ));
+            Ok::<_, String>(())
+        });
+        test.run(&client, None).await;
+
+        let dependency = events.pop().unwrap();
+        let availability = events.pop().unwrap();
+
+        let id = match availability.data {
+            Some(Base::Data(Data::AvailabilityData(data))) => data.id,
+            data => panic!("unexpected data {:?}", data),
+        };
+        let tags = dependency.tags.unwrap();
+        assert_eq!(tags.get("ai.operation.id"), Some(&id));
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&id));
+        assert_eq!(tags.get("ai.operation.name"), Some(&"check".to_string()));
+        assert_eq!(availability.tags.unwrap().get("ai.operation.id"), Some(&id));
+    }
+
+    #[tokio::test]
+    async fn it_runs_tests_periodically() {
+        let events = Arc::new(SegQueue::default());
+        let runner = AvailabilityRunner::new(create_client(events.clone()))
+            .test(AvailabilityTest::new("check", |_| async { Ok::<_, String>(()) }).interval(Duration::from_millis(10)))
+            .start();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        runner.stop();
+
+        assert!(events.len() > 1);
+    }
+
+    #[tokio::test]
+    async fn it_samples_successful_probes() {
+        let events = Arc::new(SegQueue::default());
+        let probe = ProbeTracker::new(create_client(events.clone()), "readiness");
+
+        for result in [
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err("not ready"),
+            Err("not ready"),
+            Ok(()),
+            Ok(()),
+        ] {
+            let _ = probe.check(async { result }).await;
+        }
+
+        assert_eq!(
+            probe_results(&events, PROBE_COUNT),
+            vec![(true, 1.0), (false, 3.0), (false, 1.0), (true, 1.0)]
+        );
+    }
+
+    #[test_case(PROBE_SUCCESS_COUNT, [1.0, 2.0, 0.0, 1.0] ; "successes")]
+    #[test_case(PROBE_FAILURE_COUNT, [0.0, 1.0, 1.0, 0.0] ; "failures")]
+    #[tokio::test]
+    async fn it_counts_probe_results_separately(measurement: &str, expected: [f64; 4]) {
+        let events = Arc::new(SegQueue::default());
+        let probe = ProbeTracker::new(create_client(events.clone()), "readiness");
+
+        for result in [
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err("not ready"),
+            Err("not ready"),
+            Ok(()),
+            Ok(()),
+        ] {
+            let _ = probe.check(async { result }).await;
+        }
+
+        let counts: Vec<_> = probe_results(&events, measurement)
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        assert_eq!(counts, expected);
+    }
+
+    #[tokio::test]
+    async fn it_correlates_telemetry_tracked_within_check() {
+        let events = Arc::new(SegQueue::default());
+        let client = Arc::new(create_client(events.clone()));
+
+        let tracking = client.clone();
+        let test = AvailabilityTest::new("check", move |_| {
+            let client = tracking.clone();
+            async move {
+                client.track_remote_dependency("query", "SQL", "db", true);
+                Ok::<_, String>(())
+            }
+        });
+        test.run(&client, None).await;
+        client.track_remote_dependency("outside", "SQL", "db", true);
+
+        let ids: Vec<_> = std::iter::from_fn(|| events.pop())
+            .map(|envelope| envelope.tags.unwrap().get("ai.operation.id").cloned())
+            .collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids[0].is_some());
+        assert_eq!(ids[0], ids[1]);
+        assert_ne!(ids[2], ids[0]);
+    }
+
+    fn probe_results(events: &SegQueue<Envelope>, measurement: &str) -> Vec<(bool, f64)> {
+        std::iter::from_fn(|| events.pop())
+            .map(|envelope| match envelope.data {
+                Some(Base::Data(Data::AvailabilityData(data))) => {
+                    (data.success, data.measurements.unwrap()[measurement])
+                }
+                data => panic!("unexpected data {:?}", data),
+            })
+            .collect()
+    }
+}
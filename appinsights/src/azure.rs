@@ -0,0 +1,261 @@
+//! Dependency tracking for Azure SDK for Rust.
+//!
+//! [`AzureSdkPolicy`](struct.AzureSdkPolicy.html) is meant to be called from a pipeline policy of
+//! [`azure_core`](https://docs.rs/azure_core) crate. Every request an Azure SDK client sends to an Azure
+//! service is submitted as a [`RemoteDependencyTelemetry`](../telemetry/struct.RemoteDependencyTelemetry.html)
+//! item with a dependency type inferred from the service host, e.g. `Azure blob` or `Azure Key Vault`,
+//! and a trace context is injected into request headers, so services that support W3C Trace Context
+//! continue the same distributed operation.
+//!
+//! The policy does not depend on a particular version of `azure_core` crate.
+//!
+//! ```rust, ignore
+//! use std::sync::Arc;
+//!
+//! use appinsights::{azure::AzureSdkPolicy, TelemetryClient};
+//! use azure_core::{Context, Policy, PolicyResult, Request};
+//!
+//! #[derive(Debug)]
+//! struct TelemetryPolicy(Arc<AzureSdkPolicy>);
+//!
+//! #[async_trait::async_trait]
+//! impl Policy for TelemetryPolicy {
+//!     async fn send(&self, ctx: &Context, request: &mut Request, next: &[Arc<dyn Policy>]) -> PolicyResult {
+//!         let method = request.method().to_string();
+//!         let url = request.url().to_string();
+//!         let operation = self.0.start(&method, &url, None, &mut |key: &str, value: String| {
+//!             request.insert_header(key.to_owned(), value)
+//!         });
+//!
+//!         let result = next[0].send(ctx, request, &next[1..]).await;
+//!         match &result {
+//!             Ok(response) => operation.complete(u16::from(response.status())),
+//!             Err(err) => operation.fail(err),
+//!         }
+//!         result
+//!     }
+//! }
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! let policy = TelemetryPolicy(Arc::new(AzureSdkPolicy::new(client)));
+//! // add the policy to `per_call_policies` of Azure SDK client options
+//! ```
+use std::{fmt::Display, sync::Arc};
+
+use http::Uri;
+
+use crate::{
+    correlation::{self, Injector, TraceContext},
+    telemetry::{ConnectionTimings, DependencyTarget, RemoteDependencyTelemetry, Telemetry},
+    time::Stopwatch,
+    TelemetryClient,
+};
+
+/// Tracks requests of Azure SDK clients as dependency calls.
+pub struct AzureSdkPolicy {
+    client: Arc<TelemetryClient>,
+}
+
+impl AzureSdkPolicy {
+    /// Creates a new policy that submits Azure SDK requests with specified client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self { client: client.into() }
+    }
+
+    /// Starts tracking a request and injects its trace context into request headers. A request becomes
+    /// a child of specified parent operation or starts a new trace if there is no parent.
+    pub fn start(
+        &self,
+        method: &str,
+        url: &str,
+        parent: Option<&TraceContext>,
+        headers: &mut impl Injector,
+    ) -> AzureOperation {
+        let uri: Uri = url.parse().unwrap_or_default();
+        let context = TraceContext::start(parent, &self.client.context().ids);
+        correlation::inject(&context, headers);
+
+        AzureOperation {
+            client: self.client.clone(),
+            name: format!("{} {}", method.to_uppercase(), uri.path()),
+            data: without_query(&uri),
+            target: DependencyTarget::from_uri(&uri),
+            parent: parent.cloned(),
+            context,
+            started: Stopwatch::start(),
+            timings: None,
+        }
+    }
+}
+
+/// A request of Azure SDK client in flight. It is submitted when it completes or fails.
+pub struct AzureOperation {
+    client: Arc<TelemetryClient>,
+    name: String,
+    data: String,
+    target: DependencyTarget,
+    parent: Option<TraceContext>,
+    context: TraceContext,
+    started: Stopwatch,
+    timings: Option<ConnectionTimings>,
+}
+
+impl AzureOperation {
+    /// Returns a trace context injected into request headers.
+    pub fn context(&self) -> &TraceContext {
+        &self.context
+    }
+
+    /// Sets a breakdown of time the request spent connecting and waiting for a response to submit with it,
+    /// when a transport of Azure SDK client measures it.
+    pub fn set_connection_timings(&mut self, timings: ConnectionTimings) {
+        self.timings = Some(timings);
+    }
+
+    /// Submits a request a service responded to with specified status code. Requests with status
+    /// codes below 400 are successful.
+    pub fn complete(self, status: u16) {
+        self.submit(Some(status), None::<String>);
+    }
+
+    /// Submits a request that failed without a response, e.g. because of a connection error.
+    pub fn fail(self, error: impl Display) {
+        self.submit(None, Some(error));
+    }
+
+    fn submit<E: Display>(self, status: Option<u16>, error: Option<E>) {
+        let success = status.is_some_and(|status| status < 400);
+        let mut telemetry = RemoteDependencyTelemetry::new(
+            self.name,
+            self.target.dependency_type(),
+            self.started.elapsed(),
+            self.target.target(),
+            success,
+        );
+        telemetry.set_timestamp(self.started.started_at());
+        telemetry.set_id(self.context.span_id());
+        telemetry.set_data(self.data);
+        if let Some(status) = status {
+            telemetry.set_result_code(status.to_string());
+        }
+        if let Some(timings) = self.timings {
+            telemetry.set_connection_timings(timings);
+        }
+        if let Some(error) = error {
+            telemetry.properties_mut().insert("error".into(), error.to_string());
+        }
+
+        match &self.parent {
+            Some(parent) => parent.correlate(&mut telemetry),
+            None => telemetry
+                .tags_mut()
+                .operation_mut()
+                .set_id(self.context.trace_id().to_string()),
+        }
+
+        self.client.track(telemetry);
+    }
+}
+
+/// Returns a URL without a query string, which often contains SAS tokens.
+fn without_query(uri: &Uri) -> String {
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => format!("{}://{}{}", scheme, authority, uri.path()),
+        _ => uri.path().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use http::HeaderMap;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[test]
+    fn it_submits_completed_request_as_child_of_parent() {
+        let events = Arc::new(SegQueue::default());
+        let policy = AzureSdkPolicy::new(create_client(events.clone()));
+        let parent = TraceContext::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+
+        let mut headers = HeaderMap::new();
+        let mut operation = policy.start(
+            "get",
+            "https://account.blob.core.windows.net/container/blob.txt?sig=secret",
+            Some(&parent),
+            &mut headers,
+        );
+        let span_id = operation.context().span_id().to_string();
+        operation.set_connection_timings(ConnectionTimings {
+            dns: Some(std::time::Duration::from_millis(5)),
+            ..ConnectionTimings::default()
+        });
+        operation.complete(200);
+
+        assert_eq!(
+            headers["traceparent"],
+            format!("00-0af7651916cd43dd8448eb211c80319c-{}-01", span_id)
+        );
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        assert_eq!(
+            tags.get("ai.operation.id"),
+            Some(&"0af7651916cd43dd8448eb211c80319c".to_string())
+        );
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&"b7ad6b7169203331".to_string()));
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::RemoteDependencyData(data)))
+                if data.name == "GET /container/blob.txt"
+                    && data.id == Some(span_id.clone())
+                    && data.type_ == Some("Azure blob".into())
+                    && data.target == Some("account.blob.core.windows.net".into())
+                    && data.data == Some("https://account.blob.core.windows.net/container/blob.txt".into())
+                    && data.result_code == Some("200".into())
+                    && data.success == Some(true)
+                    && data.measurements.as_ref().unwrap().get("dns_ms") == Some(&5.0)
+        );
+    }
+
+    #[test]
+    fn it_submits_failed_request_in_new_trace() {
+        let events = Arc::new(SegQueue::default());
+        let policy = AzureSdkPolicy::new(create_client(events.clone()));
+
+        let mut headers = Vec::new();
+        let operation = policy.start(
+            "GET",
+            "https://secrets.vault.azure.net/secrets/db",
+            None,
+            &mut |key: &str, value: String| headers.push((key.to_string(), value)),
+        );
+        let trace_id = operation.context().trace_id().to_string();
+        operation.fail("connection reset");
+
+        assert_eq!(headers.len(), 3);
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        assert_eq!(tags.get("ai.operation.id"), Some(&trace_id));
+        assert_eq!(tags.get("ai.operation.parentId"), None);
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::RemoteDependencyData(data)))
+                if data.type_ == Some("Azure Key Vault".into())
+                    && data.result_code.is_none()
+                    && data.success == Some(false)
+        );
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
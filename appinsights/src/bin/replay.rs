@@ -0,0 +1,321 @@
+//! Re-submits telemetry items stored in newline-delimited JSON files, e.g. audit copies written by
+//! `appinsights::tee::Tee::ndjson`, to Application Insights.
+//!
+//! ```text
+//! appinsights-replay --connection-string "InstrumentationKey=...;IngestionEndpoint=https://..." \
+//!     [--batch-size 500] [--rate 1000] FILE...
+//! ```
+//!
+//! Items are submitted in batches with an instrumentation key from the connection string. A batch the
+//! server is unable to accept right now is retried after a delay the server asks for with `Retry-After`
+//! header, or after an exponentially growing delay otherwise. `--rate` limits the number of items
+//! submitted per second to stay within ingestion limits.
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+    process,
+    time::Duration,
+};
+
+use appinsights::{contracts::Transmission, TelemetryConfig};
+use chrono::{DateTime, Utc};
+use http::{header::RETRY_AFTER, HeaderMap, StatusCode};
+use serde_json::Value;
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+const MAX_ATTEMPTS: u32 = 10;
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+
+    match rt.block_on(replay(&options)) {
+        Ok(stats) => {
+            eprintln!("Submitted {} items, dropped {} items", stats.submitted, stats.dropped);
+            if stats.dropped > 0 {
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+const USAGE: &str = "Usage: appinsights-replay --connection-string <CONNECTION STRING> [--batch-size <ITEMS>] \
+                     [--rate <ITEMS PER SECOND>] <FILE>...";
+
+/// Command line options.
+#[derive(Debug, PartialEq)]
+struct Options {
+    i_key: String,
+    endpoint: String,
+    batch_size: usize,
+    rate: Option<f64>,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut connection_string = None;
+        let mut batch_size = DEFAULT_BATCH_SIZE;
+        let mut rate = None;
+        let mut files = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} requires a value", name));
+            match arg.as_str() {
+                "--connection-string" => connection_string = Some(value(&arg)?),
+                "--batch-size" => batch_size = value(&arg)?.parse().map_err(|_| "invalid batch size")?,
+                "--rate" => rate = Some(value(&arg)?.parse().map_err(|_| "invalid rate")?),
+                flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag).into()),
+                _ => files.push(arg),
+            }
+        }
+
+        let connection_string = connection_string.ok_or("--connection-string is required")?;
+        let config = TelemetryConfig::from_connection_string(&connection_string)?;
+        if batch_size == 0 {
+            return Err("batch size must be positive".into());
+        }
+        if rate.is_some_and(|rate: f64| rate <= 0.0) {
+            return Err("rate must be positive".into());
+        }
+        if files.is_empty() {
+            return Err("no files to replay".into());
+        }
+
+        Ok(Self {
+            i_key: config.i_key().into(),
+            endpoint: config.endpoint().into(),
+            batch_size,
+            rate,
+            files,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    submitted: usize,
+    dropped: usize,
+}
+
+async fn replay(options: &Options) -> Result<Stats> {
+    let client = reqwest::Client::new();
+    let mut stats = Stats::default();
+
+    for path in &options.files {
+        let reader = BufReader::new(File::open(path).map_err(|err| format!("{}: {}", path, err))?);
+        let mut batch = Vec::with_capacity(options.batch_size);
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Value>(&line) {
+                Ok(mut item) => {
+                    item["iKey"] = Value::String(options.i_key.clone());
+                    batch.push(item);
+                }
+                Err(err) => {
+                    eprintln!("{}:{}: skipping malformed item: {}", path, number + 1, err);
+                    stats.dropped += 1;
+                }
+            }
+
+            if batch.len() == options.batch_size {
+                submit(&client, options, std::mem::take(&mut batch), &mut stats).await?;
+            }
+        }
+
+        if !batch.is_empty() {
+            submit(&client, options, batch, &mut stats).await?;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Submits a batch of items, retrying those the server is unable to accept right now.
+async fn submit(client: &reqwest::Client, options: &Options, mut items: Vec<Value>, stats: &mut Stats) -> Result<()> {
+    let count = items.len();
+    let mut backoff = Duration::from_secs(1);
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = client
+            .post(&options.endpoint)
+            .body(serde_json::to_string(&items)?)
+            .send()
+            .await;
+
+        let delay = match response {
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = retry_after(response.headers());
+                let transmission = response.json::<Transmission>().await.ok();
+
+                match (status, transmission) {
+                    (StatusCode::OK, _) => {
+                        stats.submitted += items.len();
+                        items.clear();
+                    }
+                    (
+                        StatusCode::PARTIAL_CONTENT
+                        | StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::TOO_MANY_REQUESTS
+                        | StatusCode::INTERNAL_SERVER_ERROR,
+                        Some(transmission),
+                    ) => {
+                        let total = items.len();
+                        items = retain_retry_items(items, &transmission);
+                        stats.submitted += transmission.items_accepted;
+                        stats.dropped += total.saturating_sub(transmission.items_accepted + items.len());
+                    }
+                    (status, _) if is_retryable(status) => {}
+                    (status, _) => {
+                        eprintln!("Items rejected with status {}", status);
+                        stats.dropped += items.len();
+                        items.clear();
+                    }
+                }
+                retry_after
+            }
+            Err(err) => {
+                eprintln!("Unable to submit items: {}", err);
+                None
+            }
+        };
+
+        if items.is_empty() {
+            break;
+        }
+
+        if attempt == MAX_ATTEMPTS {
+            eprintln!("Giving up on {} items after {} attempts", items.len(), attempt);
+            stats.dropped += items.len();
+            break;
+        }
+
+        let delay = delay.unwrap_or(backoff);
+        eprintln!("Retrying {} items in {:?}", items.len(), delay);
+        tokio::time::sleep(delay).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    if let Some(rate) = options.rate {
+        tokio::time::sleep(Duration::from_secs_f64(count as f64 / rate)).await;
+    }
+
+    Ok(())
+}
+
+/// Determines whether items are worth submitting again after a server responded with status code.
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::PARTIAL_CONTENT
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::INTERNAL_SERVER_ERROR
+        || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// Keeps items rejected with a status code that allows to submit them again.
+fn retain_retry_items(items: Vec<Value>, transmission: &Transmission) -> Vec<Value> {
+    let mut retry = vec![false; items.len()];
+    for error in &transmission.errors {
+        if let (Some(retry), Ok(status)) = (retry.get_mut(error.index), StatusCode::from_u16(error.status_code)) {
+            *retry = is_retryable(status) && status != StatusCode::PARTIAL_CONTENT;
+        }
+    }
+
+    items
+        .into_iter()
+        .zip(retry)
+        .filter_map(|(item, retry)| if retry { Some(item) } else { None })
+        .collect()
+}
+
+/// Reads a delay from `Retry-After` header given either in seconds or as a date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_parses_options() {
+        let args = [
+            "--connection-string",
+            "InstrumentationKey=key;IngestionEndpoint=https://example.com/",
+            "--rate",
+            "100",
+            "telemetry.ndjson",
+        ];
+
+        let options = Options::parse(args.iter().map(ToString::to_string)).unwrap();
+
+        assert_eq!(
+            options,
+            Options {
+                i_key: "key".into(),
+                endpoint: "https://example.com/v2/track".into(),
+                batch_size: DEFAULT_BATCH_SIZE,
+                rate: Some(100.0),
+                files: vec!["telemetry.ndjson".into()],
+            }
+        );
+    }
+
+    #[test_case(&["telemetry.ndjson"]                                              ; "without connection string")]
+    #[test_case(&["--connection-string", "InstrumentationKey=key"]                 ; "without files")]
+    #[test_case(&["--connection-string", "Endpoint=x", "telemetry.ndjson"]         ; "without instrumentation key")]
+    #[test_case(&["--connection-string", "InstrumentationKey=key", "--rate", "0", "a"] ; "zero rate")]
+    fn it_rejects_invalid_options(args: &[&str]) {
+        assert!(Options::parse(args.iter().map(ToString::to_string)).is_err());
+    }
+
+    #[test]
+    fn it_retains_items_to_retry() {
+        let items = (0..4).map(|i| json!({ "name": i })).collect();
+        let transmission = serde_json::from_value(json!({
+            "itemsReceived": 4,
+            "itemsAccepted": 2,
+            "errors": [
+                { "index": 1, "statusCode": 400, "message": "Invalid" },
+                { "index": 3, "statusCode": 429, "message": "Throttled" },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(retain_retry_items(items, &transmission), vec![json!({ "name": 3 })]);
+    }
+}
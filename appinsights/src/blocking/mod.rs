@@ -29,15 +29,24 @@ use log::debug;
 use tokio::sync::mpsc;
 
 use crate::{
+    aggregator::{standard, Dimensions, MetricAggregator},
     channel::{InMemoryChannel, TelemetryChannel},
+    command,
     contracts::Envelope,
+    diagnostics::Diagnostics,
+    initializer::{self, TelemetryInitializer},
+    privacy::UserDataPolicy,
+    processor::{self, TelemetryProcessor},
     telemetry::{
-        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, RemoteDependencyTelemetry, RequestTelemetry,
-        SeverityLevel, Telemetry, TraceTelemetry,
+        AvailabilityTelemetry, EventTelemetry, FeatureResult, FeatureUsage, MetricTelemetry, RemoteDependencyTelemetry,
+        RequestTelemetry, SeverityLevel, Telemetry, TraceTelemetry,
     },
     TelemetryConfig, TelemetryContext,
 };
 
+#[cfg(feature = "test-util")]
+use crate::testing::Hooks;
+
 /// A blocking version of Application Insights telemetry client. It provides an interface to track telemetry items.
 pub struct TelemetryClient {
     inner: ChannelHandle,
@@ -51,7 +60,7 @@ impl TelemetryClient {
 
     /// Creates a new telemetry client configured with specified configuration.
     pub fn from_config(config: TelemetryConfig) -> Self {
-        Self::create(config, |config| InMemoryChannel::new(config))
+        Self::create(config, InMemoryChannel::new)
     }
 
     pub(crate) fn create<C, F>(config: TelemetryConfig, channel: F) -> Self
@@ -83,12 +92,45 @@ impl TelemetryClient {
         &mut self.inner.context
     }
 
+    /// Adds an initializer that will receive every telemetry item as soon as it is tracked. Initializers
+    /// run in order they were added before any processor.
+    pub fn add_initializer(&mut self, initializer: impl TelemetryInitializer + 'static) {
+        self.inner.initializers.push(Box::new(initializer));
+    }
+
+    /// Adds a processor that will receive every telemetry item before it is submitted. Processors run in
+    /// order they were added.
+    pub fn add_processor(&mut self, processor: impl TelemetryProcessor + 'static) {
+        self.inner.processors.push(Box::new(processor));
+    }
+
     /// Logs a user action with the specified name.
     pub fn track_event(&self, name: impl Into<String>) {
         let event = EventTelemetry::new(name);
         self.track(event)
     }
 
+    /// Logs a use of a product feature as a `FeatureUsage` event with a stable schema.
+    pub fn track_feature_usage(&self, feature: impl Into<String>) {
+        self.track(EventTelemetry::from(FeatureUsage::new(feature)))
+    }
+
+    /// Logs a start of a trial of a product feature variant as a `FeatureUsage` event.
+    pub fn track_feature_trial(&self, feature: impl Into<String>, variant: impl Into<String>) {
+        let usage = FeatureUsage::new(feature)
+            .with_variant(variant)
+            .with_result(FeatureResult::Trial);
+        self.track(EventTelemetry::from(usage))
+    }
+
+    /// Logs a conversion after a trial of a product feature variant as a `FeatureUsage` event.
+    pub fn track_feature_conversion(&self, feature: impl Into<String>, variant: impl Into<String>) {
+        let usage = FeatureUsage::new(feature)
+            .with_variant(variant)
+            .with_result(FeatureResult::Converted);
+        self.track(EventTelemetry::from(usage))
+    }
+
     /// Logs a trace message with a specified severity level.
     pub fn track_trace(&self, message: impl Into<String>, severity: SeverityLevel) {
         let event = TraceTelemetry::new(message, severity);
@@ -102,12 +144,45 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Aggregates a numeric value locally and logs a single aggregated metric per aggregation interval.
+    /// Aggregates are submitted when a value is tracked after the interval ends, and when the channel is
+    /// flushed or closed.
+    pub fn track_value(&self, name: impl Into<String>, value: f64) {
+        self.inner.track_value(name.into(), Dimensions::default(), value);
+    }
+
+    /// Aggregates a numeric value locally like [`track_value`](#method.track_value) does, but keeps a
+    /// separate aggregate for every combination of dimension values.
+    pub fn track_value_with_dimensions<K, V>(
+        &self,
+        name: impl Into<String>,
+        value: f64,
+        dimensions: impl IntoIterator<Item = (K, V)>,
+    ) where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let dimensions = dimensions.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        self.inner.track_value(name.into(), dimensions, value);
+    }
+
     /// Logs a HTTP request with the specified method, URL, duration and response code.
     pub fn track_request(&self, method: Method, uri: Uri, duration: Duration, response_code: impl Into<String>) {
         let event = RequestTelemetry::new(method, uri, duration, response_code);
         self.track(event)
     }
 
+    /// Logs an invocation of a command-line tool with the specified command name, sanitized arguments,
+    /// exit code and duration as a request that succeeds when the exit code is 0.
+    pub fn track_command<I, S>(&self, name: &str, args: I, exit_code: i32, duration: Duration)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let event = command::request(name, args, exit_code, duration);
+        self.track(event)
+    }
+
     /// Logs a dependency with the specified name, type, target, and success status.
     pub fn track_remote_dependency(
         &self,
@@ -116,7 +191,7 @@ impl TelemetryClient {
         target: impl Into<String>,
         success: bool,
     ) {
-        let event = RemoteDependencyTelemetry::new(name, dependency_type, Default::default(), target, success);
+        let event = RemoteDependencyTelemetry::new(name, dependency_type, Duration::default(), target, success);
         self.track(event)
     }
 
@@ -196,6 +271,15 @@ impl TelemetryClient {
 struct ChannelHandle {
     enabled: bool,
     context: TelemetryContext,
+    initializers: Vec<Box<dyn TelemetryInitializer>>,
+    processors: Vec<Box<dyn TelemetryProcessor>>,
+    quotas: Vec<Box<dyn TelemetryProcessor>>,
+    diagnostics: Diagnostics,
+    metrics: MetricAggregator,
+    user_data: UserDataPolicy,
+    standard_metrics: bool,
+    #[cfg(feature = "test-util")]
+    testing: Hooks,
     inner: InnerChannelHandle,
 }
 
@@ -206,6 +290,14 @@ impl ChannelHandle {
         F: FnOnce(&TelemetryConfig) -> C + Send + 'static,
     {
         let context = TelemetryContext::from_config(&config);
+        let metrics = MetricAggregator::new(&config);
+        let user_data = config.user_data().clone();
+        let standard_metrics = config.standard_metrics();
+        let processors = processor::from_config(&config);
+        let quotas = processor::quotas_from_config(&config);
+        let diagnostics = config.diagnostics().clone();
+        #[cfg(feature = "test-util")]
+        let testing = config.testing().clone();
 
         let (tx, mut rx) = mpsc::unbounded_channel::<(ClientCommand, OneshotResponse)>();
 
@@ -222,12 +314,12 @@ impl ChannelHandle {
 
                     while let Some((command, req_tx)) = rx.recv().await {
                         match command {
-                            ClientCommand::Envelope(envelop) => channel.send(envelop),
+                            ClientCommand::Envelope(envelop) => channel.send(*envelop),
                             ClientCommand::Flush => channel.flush(),
                             ClientCommand::Stop => channel.close().await,
                             ClientCommand::Terminate => channel.terminate().await,
                         }
-                        let _ = req_tx.send(());
+                        let _ = req_tx.try_send(());
                     }
                 };
                 rt.block_on(f);
@@ -243,6 +335,15 @@ impl ChannelHandle {
             inner,
             enabled: true,
             context,
+            initializers: Vec::new(),
+            processors,
+            quotas,
+            diagnostics,
+            metrics,
+            user_data,
+            standard_metrics,
+            #[cfg(feature = "test-util")]
+            testing,
         }
     }
 
@@ -260,8 +361,24 @@ impl ChannelHandle {
         (TelemetryContext, E): Into<Envelope>,
     {
         if self.is_enabled() {
-            let envelop = (self.context.clone(), event).into();
-            let command = ClientCommand::Envelope(envelop);
+            let mut event = event;
+            let mut context = self.context.clone();
+            initializer::initialize(&self.initializers, &mut event, &mut context);
+            #[cfg(feature = "test-util")]
+            self.testing.stamp(&mut event);
+
+            let mut envelop = (context, event).into();
+            if self.standard_metrics {
+                self.extract_standard_metric(&mut envelop);
+            }
+            if !processor::process(&self.processors, &mut envelop, &self.diagnostics)
+                || !processor::process(&self.quotas, &mut envelop, &self.diagnostics)
+            {
+                return;
+            }
+            self.user_data.apply(&mut envelop);
+
+            let command = ClientCommand::Envelope(Box::new(envelop));
 
             let (tx, mut rx) = mpsc::channel(1);
 
@@ -276,11 +393,36 @@ impl ChannelHandle {
         }
     }
 
+    fn track_value(&self, name: String, dimensions: Dimensions, value: f64) {
+        if self.is_enabled() {
+            for telemetry in self.metrics.track(name, dimensions, value) {
+                self.track(telemetry);
+            }
+        }
+    }
+
+    /// Aggregates a duration of a request or a dependency call into a standard metric.
+    fn extract_standard_metric(&self, envelope: &mut Envelope) {
+        if let Some(metric) = standard::extract(envelope) {
+            for telemetry in self.metrics.track(metric.name, metric.dimensions, metric.value) {
+                self.track(telemetry);
+            }
+        }
+    }
+
+    fn submit_aggregated_metrics(&self) {
+        for telemetry in self.metrics.take() {
+            self.track(telemetry);
+        }
+    }
+
     fn flush(&self) {
+        self.submit_aggregated_metrics();
         self.inner.flush();
     }
 
     fn close(mut self) {
+        self.submit_aggregated_metrics();
         self.inner.shutdown(ClientCommand::Stop)
     }
 }
@@ -326,7 +468,7 @@ fn send_command(sender: &ThreadSender, command: ClientCommand) {
 
 #[derive(Debug, Clone)]
 enum ClientCommand {
-    Envelope(Envelope),
+    Envelope(Box<Envelope>),
     Flush,
     Stop,
     Terminate,
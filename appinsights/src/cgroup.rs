@@ -0,0 +1,311 @@
+//! Resource metrics of a container the process runs in.
+//!
+//! Host-level numbers are misleading for a containerized service: a process limited to half of a CPU
+//! and 512 MiB of memory on a large node looks idle while it is throttled or close to being killed for
+//! running out of memory. [`Cgroup`](struct.Cgroup.html) reads limits and usage of the control group of
+//! the process, supporting both cgroup v1 and v2 hierarchies, and reports them as gauges sampled every
+//! aggregation interval:
+//! * `cgroup_memory_usage_bytes` and `cgroup_memory_limit_bytes`,
+//! * `cgroup_memory_utilization_percent` of the limit,
+//! * `cgroup_cpu_limit_cores`,
+//! * `cgroup_cpu_usage_cores` since the previous sample,
+//! * `cgroup_cpu_throttled_percent` of scheduler periods the group was throttled in since the previous
+//!   sample.
+//!
+//! Gauges of limits are not reported when no limit is set.
+//!
+//! ```rust, no_run
+//! use appinsights::{cgroup::Cgroup, TelemetryClient};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! if let Some(cgroup) = Cgroup::detect() {
+//!     cgroup.register(&client);
+//! }
+//! # }
+//! ```
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+use crate::TelemetryClient;
+
+/// Limits above this value mean there is no limit in cgroup v1.
+const UNLIMITED: u64 = 1 << 60;
+
+/// A control group hierarchy of the process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cgroup {
+    /// A legacy hierarchy with a directory per controller, e.g. `/sys/fs/cgroup/memory`.
+    V1(PathBuf),
+
+    /// A unified hierarchy with a directory of the group.
+    V2(PathBuf),
+}
+
+/// Limits and usage of a control group.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CgroupStats {
+    /// Current memory usage in bytes.
+    pub memory_usage: Option<u64>,
+
+    /// Memory limit in bytes.
+    pub memory_limit: Option<u64>,
+
+    /// A number of CPUs the group may use.
+    pub cpu_limit: Option<f64>,
+
+    /// Total CPU time used by the group in microseconds.
+    pub cpu_usage_us: Option<u64>,
+
+    /// A number of elapsed scheduler periods.
+    pub periods: Option<u64>,
+
+    /// A number of scheduler periods the group was throttled in.
+    pub throttled_periods: Option<u64>,
+}
+
+impl Cgroup {
+    /// Detects a control group hierarchy mounted at `/sys/fs/cgroup`.
+    pub fn detect() -> Option<Self> {
+        let root = Path::new("/sys/fs/cgroup");
+        if root.join("cgroup.controllers").exists() {
+            // in a container the group of the process is usually mounted at the root
+            let group = fs::read_to_string("/proc/self/cgroup")
+                .ok()
+                .and_then(|groups| {
+                    groups.lines().find_map(|line| {
+                        line.strip_prefix("0::")
+                            .map(|path| root.join(path.trim_start_matches('/')))
+                    })
+                })
+                .filter(|group| group.join("memory.current").exists() || group.join("cpu.stat").exists())
+                .unwrap_or_else(|| root.to_path_buf());
+            Some(Cgroup::V2(group))
+        } else if root.join("memory").is_dir() || root.join("cpu").is_dir() {
+            Some(Cgroup::V1(root.to_path_buf()))
+        } else {
+            None
+        }
+    }
+
+    /// Reads current limits and usage of the group.
+    pub fn stats(&self) -> CgroupStats {
+        match self {
+            Cgroup::V1(root) => {
+                let cpu = |file: &str| root.join("cpu").join(file);
+                let quota = read(&cpu("cpu.cfs_quota_us")).and_then(|quota| quota.parse::<i64>().ok());
+                let period = read_u64(&cpu("cpu.cfs_period_us"));
+                let stat = read(&cpu("cpu.stat")).unwrap_or_default();
+                CgroupStats {
+                    memory_usage: read_u64(&root.join("memory/memory.usage_in_bytes")),
+                    memory_limit: read_u64(&root.join("memory/memory.limit_in_bytes"))
+                        .filter(|limit| *limit < UNLIMITED),
+                    cpu_limit: match (quota, period) {
+                        (Some(quota), Some(period)) if quota > 0 && period > 0 => Some(quota as f64 / period as f64),
+                        _ => None,
+                    },
+                    cpu_usage_us: read_u64(&root.join("cpuacct/cpuacct.usage")).map(|usage| usage / 1000),
+                    periods: field(&stat, "nr_periods"),
+                    throttled_periods: field(&stat, "nr_throttled"),
+                }
+            }
+            Cgroup::V2(group) => {
+                let stat = read(&group.join("cpu.stat")).unwrap_or_default();
+                CgroupStats {
+                    memory_usage: read_u64(&group.join("memory.current")),
+                    memory_limit: read_u64(&group.join("memory.max")),
+                    cpu_limit: read(&group.join("cpu.max")).and_then(|max| {
+                        let mut parts = max.split_whitespace();
+                        let quota = parts.next()?.parse::<f64>().ok()?;
+                        let period = parts.next()?.parse::<f64>().ok().filter(|period| *period > 0.0)?;
+                        Some(quota / period)
+                    }),
+                    cpu_usage_us: field(&stat, "usage_usec"),
+                    periods: field(&stat, "nr_periods"),
+                    throttled_periods: field(&stat, "nr_throttled"),
+                }
+            }
+        }
+    }
+
+    /// Registers gauges of limits and usage of the group with a client.
+    pub fn register(self, client: &Arc<TelemetryClient>) {
+        let cgroup = Arc::new(self);
+        let stats = cgroup.stats();
+
+        if stats.memory_usage.is_some() {
+            let group = cgroup.clone();
+            client.register_gauge("cgroup_memory_usage_bytes", move || {
+                group.stats().memory_usage.unwrap_or_default() as f64
+            });
+        }
+        if stats.memory_limit.is_some() {
+            let group = cgroup.clone();
+            client.register_gauge("cgroup_memory_limit_bytes", move || {
+                group.stats().memory_limit.unwrap_or_default() as f64
+            });
+            let group = cgroup.clone();
+            client.register_gauge("cgroup_memory_utilization_percent", move || {
+                let stats = group.stats();
+                match (stats.memory_usage, stats.memory_limit) {
+                    (Some(usage), Some(limit)) if limit > 0 => usage as f64 / limit as f64 * 100.0,
+                    _ => 0.0,
+                }
+            });
+        }
+        if stats.cpu_limit.is_some() {
+            let group = cgroup.clone();
+            client.register_gauge("cgroup_cpu_limit_cores", move || {
+                group.stats().cpu_limit.unwrap_or_default()
+            });
+        }
+        if let Some(usage) = stats.cpu_usage_us {
+            let group = cgroup.clone();
+            let previous = Mutex::new((usage, Instant::now()));
+            client.register_gauge("cgroup_cpu_usage_cores", move || {
+                let usage = group.stats().cpu_usage_us.unwrap_or_default();
+                let now = Instant::now();
+                let mut previous = previous.lock().unwrap_or_else(PoisonError::into_inner);
+                let elapsed = now.duration_since(previous.1).as_micros() as f64;
+                let used = usage.saturating_sub(previous.0) as f64;
+                *previous = (usage, now);
+                if elapsed > 0.0 {
+                    used / elapsed
+                } else {
+                    0.0
+                }
+            });
+        }
+        if let (Some(periods), Some(throttled)) = (stats.periods, stats.throttled_periods) {
+            let group = cgroup;
+            let previous = Mutex::new((periods, throttled));
+            client.register_gauge("cgroup_cpu_throttled_percent", move || {
+                let stats = group.stats();
+                let current = (
+                    stats.periods.unwrap_or_default(),
+                    stats.throttled_periods.unwrap_or_default(),
+                );
+                let mut previous = previous.lock().unwrap_or_else(PoisonError::into_inner);
+                let (periods, throttled) = (
+                    current.0.saturating_sub(previous.0),
+                    current.1.saturating_sub(previous.1),
+                );
+                *previous = current;
+                throttled_percent(periods, throttled)
+            });
+        }
+    }
+}
+
+/// Returns a percentage of periods the group was throttled in.
+fn throttled_percent(periods: u64, throttled: u64) -> f64 {
+    if periods > 0 {
+        throttled as f64 / periods as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Reads a trimmed content of a control file.
+fn read(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|content| content.trim().to_string())
+}
+
+/// Reads a number from a control file, ignoring values like `max` that mean no limit.
+fn read_u64(path: &Path) -> Option<u64> {
+    read(path).and_then(|value| value.parse().ok())
+}
+
+/// Returns a value of a field of a flat keyed file like `cpu.stat`.
+fn field(content: &str, name: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        if parts.next()? == name {
+            parts.next()?.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn hierarchy(files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("appinsights-cgroup-{}", uuid::Uuid::new_v4()));
+        for (file, content) in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn it_reads_v1_hierarchy() {
+        let root = hierarchy(&[
+            ("memory/memory.usage_in_bytes", "104857600\n"),
+            ("memory/memory.limit_in_bytes", "536870912\n"),
+            ("cpu/cpu.cfs_quota_us", "50000\n"),
+            ("cpu/cpu.cfs_period_us", "100000\n"),
+            (
+                "cpu/cpu.stat",
+                "nr_periods 200\nnr_throttled 20\nthrottled_time 123456789\n",
+            ),
+            ("cpuacct/cpuacct.usage", "5000000000\n"),
+        ]);
+
+        let stats = Cgroup::V1(root.clone()).stats();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(
+            stats,
+            CgroupStats {
+                memory_usage: Some(104_857_600),
+                memory_limit: Some(536_870_912),
+                cpu_limit: Some(0.5),
+                cpu_usage_us: Some(5_000_000),
+                periods: Some(200),
+                throttled_periods: Some(20),
+            }
+        );
+    }
+
+    #[test_case("max\n",  "max 100000\n",    None,       None      ; "unlimited")]
+    #[test_case("1024\n", "200000 100000\n", Some(1024), Some(2.0) ; "limited")]
+    fn it_reads_v2_hierarchy(memory_max: &str, cpu_max: &str, memory_limit: Option<u64>, cpu_limit: Option<f64>) {
+        let root = hierarchy(&[
+            ("memory.current", "512\n"),
+            ("memory.max", memory_max),
+            ("cpu.max", cpu_max),
+            (
+                "cpu.stat",
+                "usage_usec 1500\nuser_usec 1000\nsystem_usec 500\nnr_periods 10\nnr_throttled 1\n",
+            ),
+        ]);
+
+        let stats = Cgroup::V2(root.clone()).stats();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(
+            stats,
+            CgroupStats {
+                memory_usage: Some(512),
+                memory_limit,
+                cpu_limit,
+                cpu_usage_us: Some(1500),
+                periods: Some(10),
+                throttled_periods: Some(1),
+            }
+        );
+    }
+}
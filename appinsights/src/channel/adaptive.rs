@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+/// Smoothed latency above which the endpoint is considered slow and batches are stretched.
+const SLOW_LATENCY: Duration = Duration::from_secs(1);
+
+/// Smoothed latency below which the endpoint is considered fast and batches shrink back.
+const FAST_LATENCY: Duration = Duration::from_millis(250);
+
+/// Weight of the latest submission in smoothed latency.
+const SMOOTHING: f64 = 0.3;
+
+/// Scales a batching interval and batch limits by a factor that follows ingestion latency. The factor
+/// doubles while submissions are slow or throttled and halves while they are fast again, so batches
+/// converge to a size the endpoint keeps up with without oscillating on a single slow response.
+pub struct Adaptive {
+    max_scale: usize,
+    scale: usize,
+    latency: Option<Duration>,
+}
+
+impl Adaptive {
+    /// Creates a new controller that stretches a base `interval` up to `max_interval`.
+    pub fn new(interval: Duration, max_interval: Duration) -> Self {
+        let max_scale = if interval.is_zero() {
+            1
+        } else {
+            (max_interval.as_secs_f64() / interval.as_secs_f64()).floor() as usize
+        };
+
+        Self {
+            max_scale: max_scale.max(1),
+            scale: 1,
+            latency: None,
+        }
+    }
+
+    /// Accounts a submission that took `latency` and returns a new scale factor.
+    pub fn observe(&mut self, latency: Duration, throttled: bool) -> usize {
+        let latency = match self.latency {
+            Some(smoothed) => smoothed.mul_f64(1.0 - SMOOTHING) + latency.mul_f64(SMOOTHING),
+            None => latency,
+        };
+        self.latency = Some(latency);
+
+        if throttled || latency >= SLOW_LATENCY {
+            self.scale = (self.scale * 2).min(self.max_scale);
+        } else if latency <= FAST_LATENCY {
+            self.scale = (self.scale / 2).max(1);
+        }
+        self.scale
+    }
+
+    /// Returns a factor the interval and batch limits are scaled by.
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    /// Returns a base interval scaled by the current factor.
+    pub fn interval(&self, interval: Duration) -> Duration {
+        interval * self.scale as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_stretches_batches_while_endpoint_is_slow() {
+        let mut adaptive = Adaptive::new(Duration::from_secs(2), Duration::from_secs(10));
+
+        let scales: Vec<_> = (0..4)
+            .map(|_| adaptive.observe(Duration::from_secs(3), false))
+            .collect();
+
+        assert_eq!(scales, vec![2, 4, 5, 5]);
+        assert_eq!(adaptive.interval(Duration::from_secs(2)), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn it_shrinks_batches_as_endpoint_recovers() {
+        let mut adaptive = Adaptive::new(Duration::from_secs(1), Duration::from_secs(8));
+        (0..3).for_each(|_| {
+            adaptive.observe(Duration::from_millis(100), true);
+        });
+        assert_eq!(adaptive.scale(), 8);
+
+        let scales: Vec<_> = (0..4)
+            .map(|_| adaptive.observe(Duration::from_millis(100), false))
+            .collect();
+
+        assert_eq!(scales, vec![4, 2, 1, 1]);
+    }
+
+    #[test_case(Duration::from_millis(500), false, 1 ; "moderate latency keeps scale")]
+    #[test_case(Duration::from_millis(100), true,  2 ; "throttled")]
+    #[test_case(Duration::from_secs(5),     false, 2 ; "slow")]
+    fn it_adapts_to_single_submission(latency: Duration, throttled: bool, expected: usize) {
+        let mut adaptive = Adaptive::new(Duration::from_secs(1), Duration::from_secs(4));
+
+        assert_eq!(adaptive.observe(latency, throttled), expected);
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    future::Future,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use tokio::sync::{oneshot, Notify};
+
+use crate::{contracts::Envelope, transport::IngestionResponse};
+
+/// Tracks telemetry items queued since the last batch was taken for submission and determines when a batch
+/// is large enough to be submitted without waiting for the oldest item to reach its maximum age. It also
+/// hands responses of every submitted batch over to callers waiting for a flush to complete.
+pub struct PendingBatch {
+    max_items: Option<usize>,
+    max_bytes: Option<usize>,
+    scale: AtomicUsize,
+    items: AtomicUsize,
+    bytes: AtomicUsize,
+    flush_requested: AtomicBool,
+    arrived: Notify,
+    flushes: AtomicU64,
+    waiting: Mutex<Waiting>,
+}
+
+/// Callers waiting for responses of batches, each along with a number of its flush.
+#[derive(Default)]
+struct Waiting {
+    flushes: Vec<(u64, oneshot::Sender<Vec<IngestionResponse>>)>,
+    closed: bool,
+}
+
+impl PendingBatch {
+    /// Creates a new batch that is full either when it has `max_items` or when items take `max_bytes` of
+    /// serialized JSON. No limit is applied if a value is not specified.
+    pub fn new(max_items: Option<usize>, max_bytes: Option<usize>) -> Self {
+        Self {
+            max_items,
+            max_bytes,
+            scale: AtomicUsize::new(1),
+            items: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            flush_requested: AtomicBool::new(false),
+            arrived: Notify::new(),
+            flushes: AtomicU64::new(0),
+            waiting: Mutex::default(),
+        }
+    }
+
+    /// Determines whether batches are limited by size, so sizes of items must be passed to
+    /// [`add`](#method.add).
+    pub fn measures_bytes(&self) -> bool {
+        self.max_bytes.is_some()
+    }
+
+    /// Accounts a new item of specified size in bytes of serialized JSON added to a queue and returns
+    /// `true` if the batch became full with it. It returns `true` only once per batch.
+    pub fn add(&self, size: Option<usize>) -> bool {
+        self.arrived.notify_one();
+
+        let scale = self.scale.load(Ordering::Relaxed);
+        let items = self.items.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut full = self.max_items.is_some_and(|max| items >= max.saturating_mul(scale));
+
+        if let Some(max) = self.max_bytes.map(|max| max.saturating_mul(scale)) {
+            if let Some(size) = size {
+                let bytes = self.bytes.fetch_add(size, Ordering::Relaxed) + size;
+                full |= bytes >= max;
+            }
+        }
+
+        full && !self.flush_requested.swap(true, Ordering::Relaxed)
+    }
+
+    /// Multiplies both limits by a factor, so batches grow while the ingestion endpoint is slow.
+    pub fn set_scale(&self, scale: usize) {
+        self.scale.store(scale.max(1), Ordering::Relaxed);
+    }
+
+    /// Starts a new batch when all queued items are taken for submission.
+    pub fn reset(&self) {
+        self.items.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.flush_requested.store(false, Ordering::Relaxed);
+    }
+
+    /// Waits until a new item is added to a queue.
+    pub async fn arrived(&self) {
+        self.arrived.notified().await
+    }
+
+    /// Accounts a flush a caller waits for and returns a future that resolves to responses of the batch
+    /// taken after it once the batch is submitted. It resolves to no responses if the batch is never
+    /// submitted because the submission routine stopped.
+    pub fn request_flush(&self) -> impl Future<Output = Vec<IngestionResponse>> + Send + 'static {
+        let (sender, receiver) = oneshot::channel();
+        let mut waiting = self.waiting();
+        let flush = self.flushes.fetch_add(1, Ordering::SeqCst) + 1;
+        if !waiting.closed {
+            waiting.flushes.push((flush, sender));
+        }
+        drop(waiting);
+
+        async move { receiver.await.unwrap_or_default() }
+    }
+
+    /// Returns a number of flushes requested so far. A batch taken afterwards contains all items queued
+    /// before them.
+    pub fn flushes(&self) -> u64 {
+        self.flushes.load(Ordering::SeqCst)
+    }
+
+    /// Hands responses of a batch taken after `flushes` flushes over to callers waiting for them. Callers
+    /// of later flushes keep waiting for a batch of their own.
+    pub fn delivered(&self, flushes: u64, responses: Vec<IngestionResponse>) {
+        let mut waiting = self.waiting();
+        let (done, pending) = waiting
+            .flushes
+            .drain(..)
+            .partition::<Vec<_>, _>(|(flush, _)| *flush <= flushes);
+        waiting.flushes = pending;
+        drop(waiting);
+
+        for (_, sender) in done {
+            // a caller may have stopped waiting already
+            let _ = sender.send(responses.clone());
+        }
+    }
+
+    /// Completes flushes callers wait for and will wait for once no batch is going to be submitted anymore,
+    /// i.e. when a submission routine stops.
+    pub fn close(&self) {
+        let mut waiting = self.waiting();
+        waiting.closed = true;
+        waiting.flushes.clear();
+    }
+
+    fn waiting(&self) -> MutexGuard<'_, Waiting> {
+        self.waiting.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns a size of an item in bytes of serialized JSON without storing it.
+pub fn serialized_size(item: &Envelope) -> Option<usize> {
+    let mut counter = ByteCounter(0);
+    serde_json::to_writer(&mut counter, item).ok().map(|_| counter.0)
+}
+
+/// Counts bytes written without storing them.
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+
+    #[test]
+    fn it_becomes_full_with_max_items_once() {
+        let batch = PendingBatch::new(Some(2), None);
+
+        assert!(!batch.add(None));
+        assert!(batch.add(None));
+        assert!(!batch.add(None));
+
+        batch.reset();
+
+        assert!(!batch.add(None));
+        assert!(batch.add(None));
+    }
+
+    #[test]
+    fn it_becomes_full_with_max_bytes() {
+        let size = serde_json::to_vec(&item()).unwrap().len();
+        let batch = PendingBatch::new(None, Some(size * 3 - 1));
+
+        assert!(batch.measures_bytes());
+        assert!(!batch.add(serialized_size(&item())));
+        assert!(!batch.add(serialized_size(&item())));
+        assert!(batch.add(serialized_size(&item())));
+    }
+
+    #[test]
+    fn it_scales_limits() {
+        let batch = PendingBatch::new(Some(2), None);
+        batch.set_scale(2);
+
+        assert!((0..3).all(|_| !batch.add(None)));
+        assert!(batch.add(None));
+    }
+
+    #[test]
+    fn it_never_becomes_full_without_limits() {
+        let batch = PendingBatch::new(None, None);
+
+        assert!((0..1000).all(|_| !batch.add(None)));
+    }
+
+    #[tokio::test]
+    async fn it_completes_flushes_when_closed() {
+        let batch = PendingBatch::new(None, None);
+        let waiting = tokio::spawn(batch.request_flush());
+
+        batch.close();
+
+        assert!(waiting.await.unwrap().is_empty());
+        assert!(batch.request_flush().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_hands_responses_of_own_batch_over_to_each_flush() {
+        let batch = PendingBatch::new(None, None);
+
+        let first = batch.request_flush();
+        batch.delivered(batch.flushes(), vec![response(StatusCode::OK)]);
+        let second = batch.request_flush();
+        let third = batch.request_flush();
+        batch.delivered(batch.flushes(), vec![response(StatusCode::PARTIAL_CONTENT)]);
+        batch.delivered(batch.flushes(), Vec::new());
+        let fourth = batch.request_flush();
+        batch.delivered(batch.flushes() - 1, vec![response(StatusCode::OK)]);
+        batch.close();
+
+        assert_eq!(first.await, vec![response(StatusCode::OK)]);
+        assert_eq!(second.await, vec![response(StatusCode::PARTIAL_CONTENT)]);
+        assert_eq!(third.await, vec![response(StatusCode::PARTIAL_CONTENT)]);
+        assert!(fourth.await.is_empty());
+    }
+
+    #[test]
+    fn it_counts_serialized_size_of_item() {
+        assert_eq!(
+            serialized_size(&item()),
+            Some(serde_json::to_vec(&item()).unwrap().len())
+        );
+    }
+
+    fn response(status: StatusCode) -> IngestionResponse {
+        IngestionResponse::new(status, &[], b"")
+    }
+
+    fn item() -> Envelope {
+        Envelope {
+            name: "event".into(),
+            ..Envelope::default()
+        }
+    }
+}
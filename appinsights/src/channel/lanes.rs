@@ -0,0 +1,332 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, PoisonError,
+};
+
+use crossbeam_queue::SegQueue;
+use log::warn;
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    diagnostics::{Diagnostic, Diagnostics, DropReason},
+};
+
+/// Importance of a telemetry item that determines an order items are transmitted and dropped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Traces are the first to be dropped.
+    Low = 0,
+
+    /// Events, metrics, requests, dependencies and page views.
+    Normal = 1,
+
+    /// Exceptions and availability results are the first to be transmitted.
+    High = 2,
+}
+
+impl Priority {
+    /// Returns a priority of a telemetry item based on its type.
+    pub fn of(envelope: &Envelope) -> Self {
+        match &envelope.data {
+            Some(Base::Data(Data::ExceptionData(_))) | Some(Base::Data(Data::AvailabilityData(_))) => Priority::High,
+            Some(Base::Data(Data::MessageData(_))) => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+/// Queues telemetry items in separate lanes per priority. When a queue reaches its capacity or its memory
+/// budget, an oldest item of the lowest priority is dropped to make room for a new one. Dropped items are
+/// reported to diagnostics.
+pub struct Lanes {
+    lanes: [SegQueue<Queued>; 3],
+    capacity: Option<usize>,
+    max_bytes: Option<usize>,
+    bytes: AtomicUsize,
+    // serializes pushes, so concurrent ones never exceed the limits between a check and an update
+    push: Mutex<()>,
+    diagnostics: Diagnostics,
+}
+
+/// A queued item with its estimated size.
+struct Queued {
+    item: Envelope,
+    bytes: usize,
+}
+
+impl Lanes {
+    /// Creates a new queue that holds at most `capacity` items if specified.
+    pub fn new(capacity: Option<usize>) -> Self {
+        Self {
+            lanes: Default::default(),
+            capacity,
+            max_bytes: None,
+            bytes: AtomicUsize::new(0),
+            push: Mutex::default(),
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Limits a size of queued items in bytes of serialized JSON if specified.
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Reports dropped items to specified diagnostics.
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Determines whether items are limited by size, so their sizes must be passed to
+    /// [`push`](#method.push).
+    pub fn measures_bytes(&self) -> bool {
+        self.max_bytes.is_some()
+    }
+
+    /// Adds a new item of specified size in bytes of serialized JSON to a lane of its priority. The size
+    /// is computed once by a caller, so it is not serialized again on the thread that tracks the item.
+    /// Returns `true` if the item was queued.
+    pub fn push(&self, item: Envelope, size: Option<usize>) -> bool {
+        let priority = Priority::of(&item);
+        let bytes = match self.max_bytes {
+            Some(max_bytes) => {
+                let bytes = size.unwrap_or_default();
+                if bytes > max_bytes {
+                    warn!(
+                        "Telemetry item exceeds queue memory budget. Dropping {:?} priority item",
+                        priority
+                    );
+                    self.report(&item, priority, DropReason::OverMemoryBudget);
+                    return false;
+                }
+                bytes
+            }
+            None => 0,
+        };
+
+        // report drops after the lock is released, so a hook is free to track items of its own
+        let (evicted, rejected) = self.enqueue(item, priority, bytes);
+        for item in &evicted {
+            warn!("Telemetry queue is full. Dropping lower priority item");
+            self.report(item, Priority::of(item), DropReason::Evicted);
+        }
+        match rejected {
+            Some(item) => {
+                warn!("Telemetry queue is full. Dropping {:?} priority item", priority);
+                self.report(&item, priority, DropReason::QueueFull);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Evicts lower priority items until a new one fits both limits and queues it. Returns evicted items and
+    /// the new one if it was rejected.
+    fn enqueue(&self, item: Envelope, priority: Priority, bytes: usize) -> (Vec<Envelope>, Option<Envelope>) {
+        let _push = self.push.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut evicted = Vec::new();
+        while self.is_full(bytes) {
+            match self.lanes[..=priority as usize].iter().find_map(SegQueue::pop) {
+                Some(queued) => {
+                    self.bytes.fetch_sub(queued.bytes, Ordering::Relaxed);
+                    evicted.push(queued.item);
+                }
+                None => return (evicted, Some(item)),
+            }
+        }
+
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.lanes[priority as usize].push(Queued { item, bytes });
+        (evicted, None)
+    }
+
+    fn report(&self, envelope: &Envelope, priority: Priority, reason: DropReason) {
+        self.diagnostics.report(Diagnostic::Dropped {
+            envelope,
+            priority,
+            reason,
+        });
+    }
+
+    /// Takes an oldest item of the highest priority.
+    pub fn pop(&self) -> Option<Envelope> {
+        let queued = self.lanes.iter().rev().find_map(SegQueue::pop)?;
+        self.bytes.fetch_sub(queued.bytes, Ordering::Relaxed);
+        Some(queued.item)
+    }
+
+    /// Returns a number of queued items.
+    pub fn len(&self) -> usize {
+        self.lanes.iter().map(SegQueue::len).sum()
+    }
+
+    /// Returns `true` if there are no queued items.
+    pub fn is_empty(&self) -> bool {
+        self.lanes.iter().all(SegQueue::is_empty)
+    }
+
+    /// Determines whether a new item of specified size doesn't fit either limit.
+    fn is_full(&self, bytes: usize) -> bool {
+        self.capacity.is_some_and(|capacity| self.len() >= capacity)
+            || self
+                .max_bytes
+                .is_some_and(|max_bytes| self.bytes.load(Ordering::Relaxed) + bytes > max_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Arc, thread, time::Duration};
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        channel::batch::serialized_size,
+        telemetry::{
+            AvailabilityTelemetry, ContextTags, EventTelemetry, ExceptionTelemetry, Properties, SeverityLevel,
+            TraceTelemetry,
+        },
+        TelemetryContext,
+    };
+
+    #[test_case(exception("error"),                                                                      Priority::High   ; "exceptions")]
+    #[test_case(Envelope::from((context(), AvailabilityTelemetry::new("ping", Duration::from_secs(1), true))), Priority::High   ; "availability results")]
+    #[test_case(Envelope::from((context(), EventTelemetry::new("event"))),                               Priority::Normal ; "events")]
+    #[test_case(Envelope::from((context(), TraceTelemetry::new("trace", SeverityLevel::Information))),   Priority::Low    ; "traces")]
+    fn it_determines_priority(envelope: Envelope, expected: Priority) {
+        assert_eq!(Priority::of(&envelope), expected);
+    }
+
+    #[test]
+    fn it_takes_high_priority_items_first() {
+        let lanes = Lanes::new(None);
+        push(&lanes, trace("trace"));
+        push(&lanes, event("event"));
+        push(&lanes, exception("exception"));
+
+        assert_eq!(names(&lanes), vec!["exception", "event", "trace"]);
+    }
+
+    #[test]
+    fn it_drops_low_priority_items_first_when_full() {
+        let lanes = Lanes::new(Some(2));
+        push(&lanes, trace("trace"));
+        push(&lanes, event("event 1"));
+        push(&lanes, event("event 2"));
+        push(&lanes, trace("dropped trace"));
+        push(&lanes, exception("exception"));
+
+        assert_eq!(lanes.len(), 2);
+        assert_eq!(names(&lanes), vec!["exception", "event 2"]);
+    }
+
+    #[test]
+    fn it_drops_low_priority_items_when_over_memory_budget() {
+        let size = serialized_size(&trace("trace 1"))
+            .max(serialized_size(&event("event")))
+            .unwrap();
+        let lanes = Lanes::new(Some(10)).with_max_bytes(Some(size * 2));
+        push(&lanes, trace("trace 1"));
+        push(&lanes, trace("trace 2"));
+        push(&lanes, exception(&"x".repeat(size * 2)));
+        push(&lanes, event("event"));
+
+        assert_eq!(lanes.len(), 2);
+        assert_eq!(names(&lanes), vec!["event", "trace 2"]);
+        assert_eq!(lanes.bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn it_never_exceeds_limits_when_pushed_concurrently() {
+        let size = serialized_size(&event("event")).unwrap();
+        let lanes = Arc::new(Lanes::new(Some(5)).with_max_bytes(Some(size * 3)));
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let lanes = lanes.clone();
+                thread::spawn(move || {
+                    for _ in 0..500 {
+                        push(&lanes, event("event"));
+                        assert!(lanes.len() <= 3);
+                        assert!(lanes.bytes.load(Ordering::Relaxed) <= size * 3);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(lanes.len(), 3);
+    }
+
+    #[test]
+    fn it_reports_dropped_items() {
+        let size = serialized_size(&event("event")).unwrap();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics = Diagnostics::new({
+            let reported = reported.clone();
+            move |diagnostic| {
+                if let Diagnostic::Dropped { priority, reason, .. } = diagnostic {
+                    reported.lock().unwrap().push((*priority, *reason));
+                }
+            }
+        });
+        let lanes = Lanes::new(Some(2))
+            .with_max_bytes(Some(size * 10))
+            .with_diagnostics(diagnostics);
+
+        assert!(push(&lanes, trace("trace")));
+        assert!(push(&lanes, exception("exception 1")));
+        assert!(push(&lanes, exception("exception 2")));
+        assert!(!push(&lanes, event("event")));
+        assert!(!push(&lanes, exception(&"x".repeat(size * 10))));
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                (Priority::Low, DropReason::Evicted),
+                (Priority::Normal, DropReason::QueueFull),
+                (Priority::High, DropReason::OverMemoryBudget),
+            ]
+        );
+    }
+
+    fn push(lanes: &Lanes, item: Envelope) -> bool {
+        let size = serialized_size(&item);
+        lanes.push(item, size)
+    }
+
+    fn names(lanes: &Lanes) -> Vec<String> {
+        std::iter::from_fn(|| lanes.pop())
+            .map(|envelope| match envelope.data {
+                Some(Base::Data(Data::MessageData(data))) => data.message,
+                Some(Base::Data(Data::EventData(data))) => data.name,
+                Some(Base::Data(Data::ExceptionData(data))) => data.exceptions[0].message.clone(),
+                data => panic!("unexpected data {:?}", data),
+            })
+            .collect()
+    }
+
+    fn trace(message: &str) -> Envelope {
+        Envelope::from((context(), TraceTelemetry::new(message, SeverityLevel::Information)))
+    }
+
+    fn event(name: &str) -> Envelope {
+        Envelope::from((context(), EventTelemetry::new(name)))
+    }
+
+    fn exception(message: &str) -> Envelope {
+        Envelope::from((
+            context(),
+            ExceptionTelemetry::new(&io::Error::other(message.to_string())),
+        ))
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default())
+    }
+}
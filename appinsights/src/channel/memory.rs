@@ -1,21 +1,29 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use crossbeam_queue::SegQueue;
 use futures_channel::mpsc::UnboundedSender;
 use log::{debug, trace, warn};
 use tokio::task::JoinHandle;
 
 use crate::{
-    channel::{command::Command, state::Worker, TelemetryChannel},
+    channel::{
+        adaptive::Adaptive,
+        batch::{serialized_size, PendingBatch},
+        command::Command,
+        lanes::Lanes,
+        state::Worker,
+        TelemetryChannel,
+    },
     contracts::Envelope,
     transmitter::Transmitter,
+    transport::IngestionResponse,
     TelemetryConfig,
 };
 
 /// A telemetry channel that stores events exclusively in memory.
 pub struct InMemoryChannel {
-    items: Arc<SegQueue<Envelope>>,
+    items: Arc<Lanes>,
+    pending: Arc<PendingBatch>,
     command_sender: Option<UnboundedSender<Command>>,
     join: Option<JoinHandle<()>>,
 }
@@ -23,20 +31,39 @@ pub struct InMemoryChannel {
 impl InMemoryChannel {
     /// Creates a new instance of in-memory channel and starts a submission routine.
     pub fn new(config: &TelemetryConfig) -> Self {
-        let items = Arc::new(SegQueue::new());
+        let items = Arc::new(
+            Lanes::new(config.max_pending_items())
+                .with_max_bytes(config.max_pending_bytes())
+                .with_diagnostics(config.diagnostics().clone()),
+        );
+        let pending = Arc::new(PendingBatch::new(config.max_batch_items(), config.max_batch_bytes()));
 
         let (command_sender, command_receiver) = futures_channel::mpsc::unbounded();
         let worker = Worker::new(
-            Transmitter::new(config.endpoint()),
+            Transmitter::new(config.endpoint())
+                .with_tee(config.tee().cloned())
+                .with_otlp_endpoint(config.otlp_endpoint())
+                .with_max_payload_bytes(config.max_payload_bytes())
+                .with_max_concurrent_requests(config.max_concurrent_requests())
+                .with_max_bytes_per_second(config.max_bytes_per_second())
+                .with_diagnostics(config.diagnostics().clone())
+                .with_transport(config.transport().cloned()),
             items.clone(),
+            pending.clone(),
             command_receiver,
             config.interval(),
-        );
+            config.retry_policy(),
+            config
+                .adaptive_batching()
+                .map(|max_interval| Adaptive::new(config.interval(), max_interval)),
+        )
+        .with_diagnostics(config.diagnostics().clone());
 
         let handle = tokio::spawn(worker.run());
 
         Self {
             items,
+            pending,
             command_sender: Some(command_sender),
             join: Some(handle),
         }
@@ -60,7 +87,17 @@ impl InMemoryChannel {
 impl TelemetryChannel for InMemoryChannel {
     fn send(&self, envelop: Envelope) {
         trace!("Sending telemetry to channel");
-        self.items.push(envelop);
+        // serialize an item at most once to account it in both the queue and the batch
+        let size = if self.items.measures_bytes() || self.pending.measures_bytes() {
+            serialized_size(&envelop)
+        } else {
+            None
+        };
+        // account only items that were actually queued
+        if self.items.push(envelop, size) && self.pending.add(size) {
+            debug!("Batch is full");
+            self.flush();
+        }
     }
 
     fn flush(&self) {
@@ -69,6 +106,12 @@ impl TelemetryChannel for InMemoryChannel {
         }
     }
 
+    async fn flush_and_wait(&self) -> Vec<IngestionResponse> {
+        let delivered = self.pending.request_flush();
+        self.flush();
+        delivered.await
+    }
+
     async fn close(&mut self) {
         self.shutdown(Command::Close).await
     }
@@ -1,5 +1,12 @@
+mod adaptive;
+
+mod batch;
+
 mod command;
 
+mod lanes;
+pub use lanes::Priority;
+
 mod memory;
 pub use memory::InMemoryChannel;
 
@@ -9,7 +16,7 @@ mod state;
 
 use async_trait::async_trait;
 
-use crate::contracts::Envelope;
+use crate::{contracts::Envelope, transport::IngestionResponse};
 
 /// An implementation of [TelemetryChannel](trait.TelemetryChannel.html) is responsible for queueing
 /// and periodically submitting telemetry events.
@@ -21,6 +28,13 @@ pub trait TelemetryChannel: Send + Sync {
     /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
     fn flush(&self);
 
+    /// Forces all pending telemetry items to be submitted and waits until they are, returning responses of
+    /// the ingestion endpoint. It should not wait once the channel is closed or terminated.
+    async fn flush_and_wait(&self) -> Vec<IngestionResponse> {
+        self.flush();
+        Vec::new()
+    }
+
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
@@ -1,20 +1,39 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
+
+use crate::retry::{Failure, RetryPolicy};
 
 /// Encapsulates retry logic for submit telemetry items operation.
-#[derive(Default, Debug)]
-pub struct Retry(Vec<Duration>);
+#[derive(Default)]
+pub struct Retry {
+    policy: Option<Arc<dyn RetryPolicy>>,
+    attempt: usize,
+    failure: Option<Failure>,
+}
 
 impl Retry {
-    pub fn exponential() -> Self {
-        let timeouts = vec![Duration::from_secs(16), Duration::from_secs(4), Duration::from_secs(2)];
-        Self(timeouts)
+    /// Retries a batch as long as a policy allows.
+    pub fn with_policy(policy: Arc<dyn RetryPolicy>) -> Self {
+        Self {
+            policy: Some(policy),
+            ..Self::default()
+        }
     }
 
+    /// Sends a batch only once.
     pub fn once() -> Self {
         Self::default()
     }
 
+    /// Records a reason the last attempt failed.
+    pub fn failed(&mut self, failure: Failure) {
+        self.failure = Some(failure);
+    }
+
+    /// Returns a delay before the next attempt or `None` when retries are exhausted.
     pub fn next(&mut self) -> Option<Duration> {
-        self.0.pop()
+        let failure = self.failure.take()?;
+        let delay = self.policy.as_ref()?.next_delay(self.attempt, &failure);
+        self.attempt += 1;
+        delay
     }
 }
@@ -1,18 +1,29 @@
-use std::{mem, sync::Arc, time::Duration};
+use std::{
+    mem,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crossbeam_queue::SegQueue;
+use chrono::{DateTime, Utc};
 use futures_channel::mpsc::UnboundedReceiver;
 use futures_util::{Future, Stream, StreamExt};
-use log::{debug, error, trace};
+use http::StatusCode;
+use log::{debug, error, info, trace, warn};
 use sm::{sm, Event};
 
 use crate::{
+    channel::adaptive::Adaptive,
+    channel::batch::PendingBatch,
     channel::command::Command,
+    channel::lanes::Lanes,
     channel::retry::Retry,
     channel::state::worker::{Variant::*, *},
     contracts::Envelope,
-    timeout,
+    diagnostics::{Diagnostic, Diagnostics},
+    retry::{Failure, RetryPolicy},
+    time, timeout,
     transmitter::{Response, Transmitter},
+    transport::ConnectivityEvent,
 };
 
 sm! {
@@ -59,26 +70,45 @@ sm! {
 
 pub struct Worker {
     transmitter: Transmitter,
-    items: Arc<SegQueue<Envelope>>,
+    items: Arc<Lanes>,
+    pending: Arc<PendingBatch>,
     command_receiver: UnboundedReceiver<Command>,
     interval: Duration,
+    retry_policy: Arc<dyn RetryPolicy>,
+    adaptive: Option<Adaptive>,
+    diagnostics: Diagnostics,
+    lost_at: Option<DateTime<Utc>>,
 }
 
 impl Worker {
     pub fn new(
         transmitter: Transmitter,
-        items: Arc<SegQueue<Envelope>>,
+        items: Arc<Lanes>,
+        pending: Arc<PendingBatch>,
         command_receiver: UnboundedReceiver<Command>,
         interval: Duration,
+        retry_policy: Arc<dyn RetryPolicy>,
+        adaptive: Option<Adaptive>,
     ) -> Self {
         Self {
             transmitter,
             items,
+            pending,
             command_receiver,
             interval,
+            retry_policy,
+            adaptive,
+            diagnostics: Diagnostics::default(),
+            lost_at: None,
         }
     }
 
+    /// Reports changes of connectivity to the ingestion endpoint to diagnostics of a client.
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
     pub async fn run(mut self) {
         let mut state = Machine::new(Receiving).as_enum();
 
@@ -87,11 +117,11 @@ impl Worker {
 
         loop {
             state = match state {
-                InitialReceiving(m) => self.handle_receiving(m, &mut items).await,
-                ReceivingByItemsSentAndContinue(m) => self.handle_receiving(m, &mut items).await,
-                ReceivingByRetryExhausted(m) => self.handle_receiving(m, &mut items).await,
-                SendingByTimeoutExpired(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
-                SendingByFlushRequested(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
+                InitialReceiving(m) => self.handle_receiving(m, &mut items, &mut retry).await,
+                ReceivingByItemsSentAndContinue(m) => self.handle_receiving(m, &mut items, &mut retry).await,
+                ReceivingByRetryExhausted(m) => self.handle_receiving(m, &mut items, &mut retry).await,
+                SendingByTimeoutExpired(m) => self.handle_sending(m, &mut items, &mut retry).await,
+                SendingByFlushRequested(m) => self.handle_sending(m, &mut items, &mut retry).await,
                 SendingByCloseRequested(m) => self.handle_sending_once_and_terminate(m, &mut items, &mut retry).await,
                 WaitingByRetryRequested(m) => self.handle_waiting(m, &mut retry).await,
                 StoppedByItemsSentAndStop(_) => break,
@@ -99,48 +129,61 @@ impl Worker {
                 StoppedByTerminateRequested(_) => break,
             }
         }
+
+        // items queued since the last batch are never submitted, so nobody should wait for them
+        self.pending.close();
     }
 
-    async fn handle_receiving<E: Event>(&mut self, m: Machine<Receiving, E>, items: &mut Vec<Envelope>) -> Variant {
+    async fn handle_receiving<E: Event>(
+        &mut self,
+        m: Machine<Receiving, E>,
+        items: &mut Vec<Envelope>,
+        retry: &mut Retry,
+    ) -> Variant {
         debug!("Receiving messages triggered by {:?}", m.trigger());
 
-        let timeout = timeout::sleep(self.interval);
+        // every new batch is retried from the first attempt
         items.clear();
+        *retry = Retry::with_policy(self.retry_policy.clone());
 
-        loop {
+        // the oldest item waits at most an interval, so there is nothing to wait for until the first one
+        if self.items.is_empty() {
             tokio::select! {
-                command = self.command_receiver.next() => {
-                    match command {
-                        Some(command) => {
-                            trace!("Command received: {}", command);
-                            match command {
-                                Command::Flush => return m.transition(FlushRequested).as_enum(),
-                                Command::Terminate => return m.transition(TerminateRequested).as_enum(),
-                                Command::Close => return m.transition(CloseRequested).as_enum(),
-                            }
-                        },
-                        None => {
-                            error!("commands channel closed");
-                            return m.transition(TerminateRequested).as_enum()
-                        },
-                    }
-                },
-                _ = timeout => {
-                    debug!("Timeout expired");
-                    return m.transition(TimeoutExpired).as_enum()
-                },
+                command = self.command_receiver.next() => return Self::handle_command(m, command),
+                _ = self.pending.arrived() => trace!("First item arrived"),
             }
         }
+
+        let interval = match &self.adaptive {
+            Some(adaptive) => adaptive.interval(self.interval),
+            None => self.interval,
+        };
+        let timeout = timeout::sleep(interval);
+
+        tokio::select! {
+            command = self.command_receiver.next() => Self::handle_command(m, command),
+            _ = timeout => {
+                debug!("Timeout expired");
+                m.transition(TimeoutExpired).as_enum()
+            },
+        }
     }
 
-    async fn handle_sending_with_retry<E: Event>(
-        &mut self,
-        m: Machine<Sending, E>,
-        items: &mut Vec<Envelope>,
-        retry: &mut Retry,
-    ) -> Variant {
-        *retry = Retry::exponential();
-        self.handle_sending(m, items).await
+    fn handle_command<E: Event>(m: Machine<Receiving, E>, command: Option<Command>) -> Variant {
+        match command {
+            Some(command) => {
+                trace!("Command received: {}", command);
+                match command {
+                    Command::Flush => m.transition(FlushRequested).as_enum(),
+                    Command::Terminate => m.transition(TerminateRequested).as_enum(),
+                    Command::Close => m.transition(CloseRequested).as_enum(),
+                }
+            }
+            None => {
+                error!("commands channel closed");
+                m.transition(TerminateRequested).as_enum()
+            }
+        }
     }
 
     async fn handle_sending_once_and_terminate<E: Event>(
@@ -151,12 +194,19 @@ impl Worker {
     ) -> Variant {
         *retry = Retry::once();
         let cloned = m.clone(); // clone here
-        self.handle_sending(m, items).await;
+        self.handle_sending(m, items, retry).await;
         cloned.transition(TerminateRequested).as_enum()
     }
 
-    async fn handle_sending<E: Event>(&mut self, m: Machine<Sending, E>, items: &mut Vec<Envelope>) -> Variant {
-        // read pending items from a channel
+    async fn handle_sending<E: Event>(
+        &mut self,
+        m: Machine<Sending, E>,
+        items: &mut Vec<Envelope>,
+        retry: &mut Retry,
+    ) -> Variant {
+        // read pending items from a channel, the most important ones first
+        let flushes = self.pending.flushes();
+        self.pending.reset();
         while let Some(item) = self.items.pop() {
             items.push(item);
         }
@@ -170,29 +220,105 @@ impl Worker {
         // submit items to the server if any
         if items.is_empty() {
             debug!("Nothing to send. Continue to wait");
+            self.pending.delivered(flushes, Vec::new());
             m.transition(ItemsSentAndContinue).as_enum()
         } else {
             // attempt to send items
-            match self.transmitter.send(mem::take(items)).await {
-                Ok(Response::Success) => m.transition(ItemsSentAndContinue).as_enum(),
-                Ok(Response::Retry(retry_items)) => {
+            let started = Instant::now();
+            let response = self.transmitter.send(mem::take(items)).await;
+            self.adapt(started.elapsed(), &response);
+            self.pending.delivered(flushes, self.transmitter.take_responses());
+
+            match response {
+                Ok(Response::Success) => {
+                    self.restored();
+                    m.transition(ItemsSentAndContinue).as_enum()
+                }
+                Ok(Response::Retry(failure, retry_items)) => {
                     *items = retry_items;
+                    self.lost(&failure, items.len());
+                    retry.failed(failure);
                     m.transition(RetryRequested).as_enum()
                 }
-                Ok(Response::Throttled(_retry_after, retry_items)) => {
+                Ok(Response::Throttled(retry_after, retry_items)) => {
                     *items = retry_items;
-                    // TODO implement throttling instead
+                    let failure = Failure::Throttled(retry_after);
+                    self.lost(&failure, items.len());
+                    retry.failed(failure);
                     m.transition(RetryRequested).as_enum()
                 }
-                Ok(Response::NoRetry) => m.transition(ItemsSentAndContinue).as_enum(),
+                Ok(Response::NoRetry) => {
+                    self.restored();
+                    m.transition(ItemsSentAndContinue).as_enum()
+                }
                 Err(err) => {
                     debug!("Error occurred during sending telemetry items: {}", err);
+                    let failure = Failure::Error(err.to_string());
+                    self.lost(&failure, items.len());
+                    retry.failed(failure);
                     m.transition(RetryRequested).as_enum()
                 }
             }
         }
     }
 
+    /// Reports lost connectivity when a submission with `retrying` items fails after successful ones.
+    fn lost(&mut self, failure: &Failure, retrying: usize) {
+        if self.lost_at.is_some() {
+            return;
+        }
+
+        let timestamp = time::now();
+        self.lost_at = Some(timestamp);
+        let queue_depth = self.items.len() + retrying;
+        warn!(
+            "Telemetry submission failed with {} items queued: {:?}",
+            queue_depth, failure
+        );
+        self.report(ConnectivityEvent::Lost {
+            timestamp,
+            failure: failure.clone(),
+            queue_depth,
+        });
+    }
+
+    /// Reports restored connectivity when a submission succeeds after failed ones.
+    fn restored(&mut self) {
+        if let Some(lost_at) = self.lost_at.take() {
+            let queue_depth = self.items.len();
+            info!("Telemetry submission recovered after failing since {}", lost_at);
+            self.report(ConnectivityEvent::Restored {
+                timestamp: time::now(),
+                lost_at,
+                queue_depth,
+            });
+        }
+    }
+
+    fn report(&self, event: ConnectivityEvent) {
+        self.diagnostics.report(Diagnostic::Connectivity(&event));
+    }
+
+    /// Stretches or shrinks next batches by the latency of a submission and whether the endpoint throttled it.
+    fn adapt<T>(&mut self, latency: Duration, response: &Result<Response, T>) {
+        if let Some(adaptive) = &mut self.adaptive {
+            let throttled = matches!(
+                response,
+                Ok(Response::Throttled(..))
+                    | Ok(Response::Retry(
+                        Failure::Status(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE),
+                        _
+                    ))
+            );
+            let previous = adaptive.scale();
+            let scale = adaptive.observe(latency, throttled);
+            if scale != previous {
+                debug!("Scaling batches by {} after submission took {:?}", scale, latency);
+            }
+            self.pending.set_scale(scale);
+        }
+    }
+
     async fn handle_waiting<E: Event>(&mut self, m: Machine<Waiting, E>, retry: &mut Retry) -> Variant {
         if let Some(timeout) = retry.next() {
             debug!(
@@ -236,14 +362,19 @@ struct SkipFlush<'a, St: ?Sized> {
     stream: &'a mut St,
 }
 
+/// Skips flushes requested while waiting to retry. Every attempt takes all queued items and completes
+/// flushes requested before it, so such flushes are completed by the next attempt.
 impl<St: ?Sized + Stream<Item = Command> + Unpin> Future for SkipFlush<'_, St> {
     type Output = Option<St::Item>;
 
     fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        match self.stream.poll_next_unpin(cx) {
-            std::task::Poll::Ready(Some(Command::Flush)) => std::task::Poll::Pending,
-            std::task::Poll::Ready(command) => std::task::Poll::Ready(command),
-            std::task::Poll::Pending => std::task::Poll::Pending,
+        loop {
+            // poll again after a skipped flush, so the task is woken by the next command
+            match self.stream.poll_next_unpin(cx) {
+                std::task::Poll::Ready(Some(Command::Flush)) => continue,
+                std::task::Poll::Ready(command) => return std::task::Poll::Ready(command),
+                std::task::Poll::Pending => return std::task::Poll::Pending,
+            }
         }
     }
 }
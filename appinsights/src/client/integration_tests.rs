@@ -21,7 +21,14 @@ use tokio::sync::{
     oneshot,
 };
 
-use crate::{timeout, TelemetryClient, TelemetryConfig};
+use crate::{
+    channel::{InMemoryChannel, TelemetryChannel},
+    diagnostics::Diagnostic,
+    retry::Failure,
+    timeout,
+    transport::ConnectivityEvent,
+    TelemetryClient, TelemetryConfig,
+};
 
 lazy_static! {
     /// A global lock since most tests need to run in serial.
@@ -369,6 +376,186 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_returns_ingestion_responses_of_flushed_items() {
+        let mut server = server()
+            .response(
+                StatusCode::PARTIAL_CONTENT,
+                json!(
+                {
+                    "itemsAccepted": 1,
+                    "itemsReceived": 2,
+                    "errors": [
+                        {
+                            "index": 1,
+                            "statusCode": StatusCode::BAD_REQUEST.as_u16(),
+                            "message": "Field 'name' on type 'EventData' is too long"
+                        }
+                    ],
+                }),
+                None,
+            )
+            .create();
+
+        let client = create_client(server.url());
+        client.track_event("--event 0--");
+        client.track_event("--event 1--");
+
+        // NOTE no timeout expired
+        let responses = client.flush_channel_and_wait().await;
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!((responses[0].items_received(), responses[0].items_accepted()), (2, 1));
+        assert_eq!(responses[0].errors().len(), 1);
+        assert_eq!(responses[0].errors()[0].status_code(), 400);
+        assert_eq!(responses[0].errors()[0].message(), "Field 'name' on type 'EventData' is too long");
+        assert!(client.flush_channel_and_wait().await.is_empty());
+
+        // terminate server
+        server.wait_for_requests(1).await;
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_reports_lost_and_restored_connectivity() {
+        let mut server = server()
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .status(StatusCode::OK)
+            .create();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .on_diagnostic({
+                let events = events.clone();
+                move |diagnostic| {
+                    if let Diagnostic::Connectivity(event) = diagnostic {
+                        events.lock().push((*event).clone())
+                    }
+                }
+            })
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+
+        // "wait" until interval expired and retry logic handled
+        timeout::expire();
+        timeout::expire();
+        assert_eq!(server.wait_for_requests(2).await.len(), 2);
+        for _ in 0..100 {
+            if events.lock().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let events = events.lock().clone();
+        assert_eq!(events.len(), 2);
+        let lost_at = match &events[0] {
+            ConnectivityEvent::Lost {
+                timestamp,
+                failure,
+                queue_depth,
+            } => {
+                assert_eq!(failure, &Failure::Status(StatusCode::INTERNAL_SERVER_ERROR));
+                assert_eq!(*queue_depth, 1);
+                *timestamp
+            }
+            event => panic!("unexpected event {:?}", event),
+        };
+        assert_matches!(
+            &events[1],
+            ConnectivityEvent::Restored { lost_at: restored, queue_depth: 0, .. } if *restored == lost_at
+        );
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_completes_flush_requested_while_retrying() {
+        let mut server = server()
+            .response(StatusCode::SERVICE_UNAVAILABLE, json!({}), None)
+            .status(StatusCode::OK)
+            .create();
+
+        let client = Arc::new(create_client(server.url()));
+        client.track_event("--event 0--");
+        client.flush_channel_and_wait().await;
+
+        // flush while the channel waits to retry
+        client.track_event("--event 1--");
+        let flushing = tokio::spawn({
+            let client = client.clone();
+            async move { client.flush_channel_and_wait().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!flushing.is_finished());
+
+        // "wait" until retry logic handled
+        timeout::expire();
+        let responses = tokio::time::timeout(Duration::from_secs(1), flushing).await.unwrap().unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].status(), StatusCode::OK);
+
+        // terminate server
+        assert_eq!(server.wait_for_requests(2).await.len(), 2);
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_completes_flush_when_retries_exhausted() {
+        let mut server = server()
+            .response(StatusCode::SERVICE_UNAVAILABLE, json!({}), None)
+            .status(StatusCode::OK)
+            .create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .retry_policy(|_: usize, _: &Failure| None)
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event 0--");
+        let flushed = tokio::time::timeout(Duration::from_secs(1), client.flush_channel_and_wait()).await;
+        assert_matches!(flushed, Ok(_));
+
+        client.track_event("--event 1--");
+        let responses = tokio::time::timeout(Duration::from_secs(1), client.flush_channel_and_wait()).await.unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].status(), StatusCode::OK);
+
+        // terminate server
+        assert_eq!(server.wait_for_requests(2).await.len(), 2);
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_completes_flush_after_channel_closed() {
+        let server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .build();
+        let mut channel = InMemoryChannel::new(&config);
+        channel.close().await;
+
+        let flushed = tokio::time::timeout(Duration::from_secs(1), channel.flush_and_wait()).await;
+        assert_matches!(flushed, Ok(responses) if responses.is_empty());
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 // TODO Check case when all retries exhausted. Pending items should not be lost
 
 fn create_client(endpoint: &str) -> TelemetryClient {
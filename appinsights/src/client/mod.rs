@@ -1,24 +1,68 @@
-use std::time::Duration;
+mod scoped;
+pub use scoped::ScopedClient;
+
+mod tracked;
+pub use tracked::TrackedItem;
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
 
 use http::{Method, Uri};
 
 use crate::{
+    aggregator::{standard, Dimensions, DurationDistributions, EventAggregator, MetricAggregator},
+    availability::AvailabilityScope,
     channel::{InMemoryChannel, TelemetryChannel},
+    command,
     context::TelemetryContext,
     contracts::Envelope,
+    correlation::AppIdProvider,
+    diagnostics::Diagnostics,
+    initializer::{self, TelemetryInitializer},
+    privacy::UserDataPolicy,
+    processor::{self, TelemetryProcessor},
     telemetry::{
-        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, RemoteDependencyTelemetry, RequestTelemetry,
-        SeverityLevel, Telemetry, TraceTelemetry,
+        AvailabilityTelemetry, DurationDistribution, EventTelemetry, FeatureResult, FeatureUsage, MetricTelemetry,
+        PageViewTelemetry, RemoteDependencyTelemetry, RequestTelemetry, SeverityLevel, Telemetry, TraceTelemetry,
     },
-    TelemetryConfig,
+    time::Stopwatch,
+    transport::IngestionResponse,
+    uuid::IdStrategy,
+    ConfigError, ProbeError, TelemetryConfig,
 };
 
-/// Application Insights telemetry client provides an interface to track telemetry items.
+#[cfg(feature = "test-util")]
+use crate::testing::Hooks;
+
+/// A callback that samples a current value of a gauge.
+type Gauge = Box<dyn Fn() -> f64 + Send + Sync>;
 
+/// Application Insights telemetry client provides an interface to track telemetry items.
 pub struct TelemetryClient {
     enabled: bool,
     context: TelemetryContext,
     channel: Box<dyn TelemetryChannel>,
+    app_id: AppIdProvider,
+    initializers: Vec<Box<dyn TelemetryInitializer>>,
+    processors: Vec<Box<dyn TelemetryProcessor>>,
+    quotas: Vec<Box<dyn TelemetryProcessor>>,
+    diagnostics: Diagnostics,
+    metrics: MetricAggregator,
+    durations: DurationDistributions,
+    events: Option<EventAggregator>,
+    user_data: UserDataPolicy,
+    standard_metrics: bool,
+    gauges: Mutex<Vec<(String, Gauge)>>,
+    gauges_started: AtomicBool,
+    gauge_interval: Duration,
+    #[cfg(feature = "test-util")]
+    testing: Hooks,
 }
 
 unsafe impl Send for TelemetryClient {}
@@ -35,12 +79,62 @@ impl TelemetryClient {
         Self::create(&config, InMemoryChannel::new(&config))
     }
 
+    /// Creates a new telemetry client configured with specified configuration after checking it is valid.
+    /// Unlike [`from_config`](#method.from_config), it fails fast on a malformed instrumentation key or
+    /// endpoint instead of queueing telemetry that will never be accepted. See
+    /// [`TelemetryConfig::validate`](struct.TelemetryConfig.html#method.validate) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{TelemetryClient, TelemetryConfig};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = TelemetryConfig::from_connection_string("InstrumentationKey=<instrumentation key>")?;
+    /// let client = TelemetryClient::try_from_config(config)?;
+    ///
+    /// // make sure the service accepts telemetry of the key before serving traffic
+    /// client.probe().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_from_config(config: TelemetryConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self::from_config(config))
+    }
+
     /// Creates a new telemetry client with custom telemetry channel.
     pub(crate) fn create<C: TelemetryChannel + 'static>(config: &TelemetryConfig, channel: C) -> Self {
+        Self::create_with_context(config, TelemetryContext::from_config(config), channel)
+    }
+
+    /// Creates a new telemetry client with custom telemetry context and channel. Ids of the context are
+    /// generated the way the configuration tells.
+    fn create_with_context<C: TelemetryChannel + 'static>(
+        config: &TelemetryConfig,
+        mut context: TelemetryContext,
+        channel: C,
+    ) -> Self {
+        context.ids = IdStrategy::new(config);
         Self {
             enabled: true,
-            context: TelemetryContext::from_config(config),
+            context,
             channel: Box::new(channel),
+            app_id: AppIdProvider::new(config),
+            initializers: initializer::from_config(config),
+            processors: processor::from_config(config),
+            quotas: processor::quotas_from_config(config),
+            diagnostics: config.diagnostics().clone(),
+            metrics: MetricAggregator::new(config),
+            durations: DurationDistributions::new(config.max_metric_series()),
+            events: config.aggregate_events().then(|| EventAggregator::new(config)),
+            user_data: config.user_data().clone(),
+            standard_metrics: config.standard_metrics(),
+            gauges: Mutex::default(),
+            gauges_started: AtomicBool::new(false),
+            gauge_interval: config.aggregation_interval(),
+            #[cfg(feature = "test-util")]
+            testing: config.testing().clone(),
         }
     }
 
@@ -105,6 +199,180 @@ impl TelemetryClient {
         &mut self.context
     }
 
+    /// Adds an initializer that will receive every telemetry item as soon as it is tracked. Initializers
+    /// run in order they were added before any processor. See [`initializer`](initializer/index.html) for
+    /// details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{telemetry::Telemetry, TelemetryClient, TelemetryContext};
+    /// # struct Tenant;
+    /// # impl appinsights::initializer::TelemetryInitializer for Tenant {
+    /// #     fn initialize(&self, _: &mut dyn Telemetry, _: &mut TelemetryContext) {}
+    /// # }
+    /// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.add_initializer(Tenant);
+    /// ```
+    pub fn add_initializer(&mut self, initializer: impl TelemetryInitializer + 'static) {
+        self.initializers.push(Box::new(initializer));
+    }
+
+    /// Adds a processor that will receive every telemetry item before it is submitted. Processors run in
+    /// order they were added. See [`processor`](processor/index.html) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// use appinsights::processor::TraceRateLimiter;
+    /// use std::time::Duration;
+    ///
+    /// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.add_processor(TraceRateLimiter::new(Duration::from_secs(60)));
+    /// ```
+    pub fn add_processor(&mut self, processor: impl TelemetryProcessor + 'static) {
+        self.processors.push(Box::new(processor));
+    }
+
+    /// Determines whether telemetry of an operation with specified id will be submitted, so application
+    /// code can skip expensive enrichment, e.g. serializing large payloads into properties, when it would
+    /// be discarded anyway. Processors that [sample](processor/struct.Sampler.html) items make the
+    /// decision, and a disabled client discards everything. Items a sampler always keeps, like
+    /// exceptions, are submitted regardless. Pass a user or a session id instead when a sampler samples
+    /// by one of them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{processor::Sampler, telemetry::{EventTelemetry, Telemetry}, TelemetryClient};
+    /// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.add_processor(Sampler::new(10.0));
+    ///
+    /// let context = client.context().child();
+    /// let operation_id = context.tags().operation().id().unwrap_or_default().to_string();
+    /// if client.is_sampled_in(&operation_id) {
+    ///     let payload = format!("{:?}", vec![0u8; 4096]);
+    ///     client.with_context(context).track(EventTelemetry::new("payload received").with_property("payload", payload));
+    /// }
+    /// ```
+    pub fn is_sampled_in(&self, operation_id: &str) -> bool {
+        self.is_enabled()
+            && self
+                .processors
+                .iter()
+                .all(|processor| processor.is_sampled_in(operation_id))
+    }
+
+    /// Returns an application id of this component prefixed with `cid-v1:`. Application id is looked
+    /// up by instrumentation key once and cached afterwards. Returns `None` when the lookup fails.
+    /// See [`correlation`](correlation/index.html) to learn how to use it to correlate components.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{correlation, TelemetryClient};
+    /// # async fn run() {
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    ///
+    /// if let Some(app_id) = client.app_id().await {
+    ///     println!("{}: {}", correlation::REQUEST_CONTEXT_HEADER, correlation::request_context(&app_id));
+    /// }
+    /// # }
+    /// ```
+    pub async fn app_id(&self) -> Option<String> {
+        self.app_id.app_id().await
+    }
+
+    /// Verifies the ingestion service knows the instrumentation key of this client by looking up its
+    /// application id, so a startup can fail instead of silently losing all telemetry. A retrieved
+    /// application id is cached and reused for correlation afterwards.
+    pub async fn probe(&self) -> Result<(), ProbeError> {
+        self.app_id.probe().await.map(|_| ())
+    }
+
+    /// Returns an application id if it is already known. Otherwise starts looking it up in the background
+    /// when called on a Tokio runtime, so the id becomes available to later calls without waiting for
+    /// the profile endpoint.
+    pub(crate) fn known_app_id(self: &Arc<Self>) -> Option<String> {
+        let app_id = self.app_id.cached();
+        if app_id.is_none() && self.app_id.start_lookup() {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let client = self.clone();
+                runtime.spawn(async move { client.app_id().await });
+            }
+        }
+        app_id
+    }
+
+    /// Registers a callback that is sampled every
+    /// [`aggregation interval`](struct.TelemetryConfig.html#method.aggregation_interval) and submitted as a
+    /// metric with specified name, e.g. a depth of a queue or a size of a pool. Sampling runs in the
+    /// background on a Tokio runtime the first gauge is registered on, and it stops when the client is
+    /// dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+    /// let queue = Arc::new(Mutex::new(Vec::<String>::new()));
+    ///
+    /// let depth = queue.clone();
+    /// client.register_gauge("queue_depth", move || depth.lock().unwrap().len() as f64);
+    /// # }
+    /// ```
+    pub fn register_gauge<F>(self: &Arc<Self>, name: impl Into<String>, gauge: F)
+    where
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        self.gauges
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((name.into(), Box::new(gauge)));
+
+        if self.gauges_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let client = Arc::downgrade(self);
+                let period = self.gauge_interval.max(Duration::from_millis(1));
+                runtime.spawn(async move {
+                    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                    loop {
+                        ticks.tick().await;
+                        match client.upgrade() {
+                            Some(client) => client.sample_gauges(),
+                            None => break,
+                        }
+                    }
+                });
+            }
+            Err(_) => {
+                log::warn!("Gauges are not sampled without a Tokio runtime");
+                self.gauges_started.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Submits current values of all registered gauges.
+    fn sample_gauges(&self) {
+        let values: Vec<_> = self
+            .gauges
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(name, gauge)| (name.clone(), gauge()))
+            .collect();
+        for (name, value) in values {
+            self.track_metric(name, value);
+        }
+    }
+
     /// Logs a user action with the specified name.
     ///
     /// # Examples
@@ -119,6 +387,36 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs a use of a product feature as a `FeatureUsage` event with a stable schema. See
+    /// [`FeatureUsage`](telemetry/struct.FeatureUsage.html) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_feature_usage("export");
+    /// ```
+    pub fn track_feature_usage(&self, feature: impl Into<String>) {
+        self.track(EventTelemetry::from(FeatureUsage::new(feature)))
+    }
+
+    /// Logs a start of a trial of a product feature variant as a `FeatureUsage` event.
+    pub fn track_feature_trial(&self, feature: impl Into<String>, variant: impl Into<String>) {
+        let usage = FeatureUsage::new(feature)
+            .with_variant(variant)
+            .with_result(FeatureResult::Trial);
+        self.track(EventTelemetry::from(usage))
+    }
+
+    /// Logs a conversion after a trial of a product feature variant as a `FeatureUsage` event.
+    pub fn track_feature_conversion(&self, feature: impl Into<String>, variant: impl Into<String>) {
+        let usage = FeatureUsage::new(feature)
+            .with_variant(variant)
+            .with_result(FeatureResult::Converted);
+        self.track(EventTelemetry::from(usage))
+    }
+
     /// Logs a trace message with a specified severity level.
     ///
     /// # Examples
@@ -150,6 +448,61 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Aggregates a numeric value locally and logs a single aggregated metric with count, sum, min, max and
+    /// standard deviation of all values tracked with the same name within the
+    /// [`aggregation interval`](struct.TelemetryConfig.html#method.aggregation_interval). It is a cheaper
+    /// alternative to [`track_metric`](#method.track_metric) for frequently measured values.
+    ///
+    /// Aggregates are submitted when a value is tracked after the interval ends, and when the channel is
+    /// flushed or closed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// for latency in [113.0, 250.0, 316.0] {
+    ///     client.track_value("gateway_latency_ms", latency);
+    /// }
+    /// ```
+    pub fn track_value(&self, name: impl Into<String>, value: f64) {
+        if self.is_enabled() {
+            for telemetry in self.metrics.track(name.into(), Dimensions::default(), value) {
+                self.track(telemetry);
+            }
+        }
+    }
+
+    /// Aggregates a numeric value locally like [`track_value`](#method.track_value) does, but keeps a
+    /// separate aggregate for every combination of dimension values. Dimensions are submitted as custom
+    /// properties of the aggregated metric, which makes possible to split a metric by endpoint, tenant, etc.
+    /// The number of combinations per metric is capped with
+    /// [`max_metric_series`](struct.TelemetryConfig.html#method.max_metric_series).
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_value_with_dimensions("request_latency_ms", 113.0, [("endpoint", "/orders"), ("method", "GET")]);
+    /// ```
+    pub fn track_value_with_dimensions<K, V>(
+        &self,
+        name: impl Into<String>,
+        value: f64,
+        dimensions: impl IntoIterator<Item = (K, V)>,
+    ) where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        if self.is_enabled() {
+            let dimensions = dimensions.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+            for telemetry in self.metrics.track(name.into(), dimensions, value) {
+                self.track(telemetry);
+            }
+        }
+    }
+
     /// Logs a HTTP request with the specified method, URL, duration and response code.
     ///
     /// # Examples
@@ -168,6 +521,28 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs an invocation of a command-line tool with the specified command name, sanitized arguments,
+    /// exit code and duration as a request that succeeds when the exit code is 0. See
+    /// [`command`](command/index.html) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use std::time::Duration;
+    ///
+    /// client.track_command("deploy", ["--env", "staging"], 0, Duration::from_secs(42));
+    /// ```
+    pub fn track_command<I, S>(&self, name: &str, args: I, exit_code: i32, duration: Duration)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let event = command::request(name, args, exit_code, duration);
+        self.track(event)
+    }
+
     /// Logs a dependency with the specified name, type, target, and success status.
     ///
     /// # Examples
@@ -189,10 +564,89 @@ impl TelemetryClient {
         target: impl Into<String>,
         success: bool,
     ) {
-        let event = RemoteDependencyTelemetry::new(name, dependency_type, Default::default(), target, success);
+        let event = RemoteDependencyTelemetry::new(name, dependency_type, Duration::default(), target, success);
         self.track(event)
     }
 
+    /// Runs a closure and logs a page view with the specified name and URL and a duration of the closure.
+    /// The closure receives a handle that tracks nested telemetry under the operation of the page view
+    /// with the page view as a parent, as Application Insights JavaScript SDK does between
+    /// `startTrackPage` and `stopTrackPage`. A page view is not logged when the closure panics.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let html = client.measure_page_view("checkout", "https://example.com/checkout".parse().unwrap(), |page| {
+    ///     page.track_event("cart loaded");
+    ///     "<html>...</html>".to_string()
+    /// });
+    /// ```
+    pub fn measure_page_view<F, T>(&self, name: impl Into<String>, uri: Uri, f: F) -> T
+    where
+        F: FnOnce(ScopedClient<'_>) -> T,
+    {
+        let (telemetry, page, nested) = self.start_page_view(name, uri);
+        let started = Stopwatch::start();
+        let result = f(self.with_context(nested));
+        self.track_in(&page, telemetry.with_duration(started.elapsed()));
+        result
+    }
+
+    /// Works like [`measure_page_view`](#method.measure_page_view), but awaits a future returned by the
+    /// closure and logs a page view when it completes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # async fn run(client: TelemetryClient) {
+    /// let uri = "https://example.com/checkout".parse().unwrap();
+    /// let html = client
+    ///     .measure_page_view_async("checkout", uri, |page| async move {
+    ///         page.track_event("cart loaded");
+    ///         "<html>...</html>".to_string()
+    ///     })
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn measure_page_view_async<'a, F, Fut, T>(&'a self, name: impl Into<String>, uri: Uri, f: F) -> T
+    where
+        F: FnOnce(ScopedClient<'a>) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let (telemetry, page, nested) = self.start_page_view(name, uri);
+        let started = Stopwatch::start();
+        let result = f(self.with_context(nested)).await;
+        self.track_in(&page, telemetry.with_duration(started.elapsed()));
+        result
+    }
+
+    /// Creates a page view and contexts it and its nested telemetry are tracked under. Both share an
+    /// operation id that is generated when the client context does not have one yet.
+    fn start_page_view(
+        &self,
+        name: impl Into<String>,
+        uri: Uri,
+    ) -> (PageViewTelemetry, TelemetryContext, TelemetryContext) {
+        let mut page = self.context.clone();
+        if page.tags().operation().id().is_none() {
+            page.tags_mut()
+                .operation_mut()
+                .set_id(self.context.ids.new_id().simple().to_string());
+        }
+
+        let id = self.context.ids.new_id();
+        let mut nested = page.clone();
+        nested
+            .tags_mut()
+            .operation_mut()
+            .set_parent_id(id.as_hyphenated().to_string());
+
+        (PageViewTelemetry::new(name, uri).with_id(id), page, nested)
+    }
+
     /// Logs an availability test result with the specified test name, duration, and success status.
     ///
     /// # Examples
@@ -228,16 +682,146 @@ impl TelemetryClient {
     /// client.track(telemetry);
     /// ```
     pub fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.track_in(&self.context, event)
+    }
+
+    /// Submits a specific telemetry event and returns identifiers of the submitted item, so they can be
+    /// logged, returned to a caller as a support id or used to link subsequent telemetry to the item.
+    /// An operation id is generated when the client context does not have one yet. It returns `None`
+    /// when the client is disabled and nothing is tracked. Identifiers are returned even when the item
+    /// is dropped by a processor or sampling afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{ExceptionTelemetry, SeverityLevel};
+    ///
+    /// let error = "not a number".parse::<i32>().unwrap_err();
+    /// let telemetry = ExceptionTelemetry::new(&error).with_severity_level(SeverityLevel::Error);
+    ///
+    /// if let Some(tracked) = client.track_with_id(telemetry) {
+    ///     println!("Something went wrong. Support id: {}", tracked);
+    /// }
+    /// ```
+    pub fn track_with_id<E>(&self, event: E) -> Option<TrackedItem>
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.track_in_with_id(&self.context, event)
+    }
+
+    /// Returns a lightweight handle that tracks telemetry items under specified context instead of the
+    /// client's one, e.g. a context of a single request or a tenant. Initializers, processors and the
+    /// channel of the client are shared by all handles.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let mut context = client.context().child();
+    /// context.properties_mut().insert("tenant".into(), "contoso".into());
+    ///
+    /// let scoped = client.with_context(context);
+    /// scoped.track_event("order placed");
+    /// ```
+    pub fn with_context(&self, context: TelemetryContext) -> ScopedClient<'_> {
+        ScopedClient::new(self, context)
+    }
+
+    /// Submits a telemetry item under specified context.
+    pub(crate) fn track_in<E>(&self, context: &TelemetryContext, event: E)
     where
         E: Telemetry,
         (TelemetryContext, E): Into<Envelope>,
     {
         if self.is_enabled() {
-            let envelop = (self.context.clone(), event).into();
-            self.channel.send(envelop);
+            self.submit(self.envelope_in(context.clone(), event));
         }
     }
 
+    /// Submits a telemetry item under specified context and returns its identifiers. An operation id is
+    /// generated when the context does not have one yet.
+    pub(crate) fn track_in_with_id<E>(&self, context: &TelemetryContext, event: E) -> Option<TrackedItem>
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        if self.is_enabled() {
+            let mut context = context.clone();
+            let operation_id = match context.tags().operation().id() {
+                Some(id) => id.to_string(),
+                None => {
+                    let id = self.context.ids.new_id().simple().to_string();
+                    context.tags_mut().operation_mut().set_id(id.clone());
+                    id
+                }
+            };
+
+            let envelope = self.envelope_in(context, event);
+            let tracked = TrackedItem::from_envelope(operation_id, &envelope);
+            self.submit(envelope);
+            Some(tracked)
+        } else {
+            None
+        }
+    }
+
+    /// Stamps baggage, runs initializers, applies a clock for tests and converts a telemetry item into an envelope.
+    fn envelope_in<E>(&self, mut context: TelemetryContext, mut event: E) -> Envelope
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        let baggage = std::mem::take(&mut context.baggage);
+        baggage.stamp(&mut context.properties);
+        if let Some(scope) = AvailabilityScope::current() {
+            if event.tags().operation().id().is_none() {
+                scope.correlate(&mut event);
+            }
+        }
+        initializer::initialize(&self.initializers, &mut event, &mut context);
+        #[cfg(feature = "test-util")]
+        self.testing.stamp(&mut event);
+
+        (context, event).into()
+    }
+
+    /// Extracts standard metrics from an envelope, runs processors and quotas and sends it to the channel.
+    pub(crate) fn submit(&self, mut envelop: Envelope) {
+        if self.standard_metrics {
+            self.extract_standard_metric(&mut envelop);
+        }
+        if processor::process(&self.processors, &mut envelop, &self.diagnostics)
+            && processor::process(&self.quotas, &mut envelop, &self.diagnostics)
+        {
+            self.user_data.apply(&mut envelop);
+            match &self.events {
+                Some(events) => events
+                    .track(envelop)
+                    .into_iter()
+                    .for_each(|item| self.channel.send(item)),
+                None => self.channel.send(envelop),
+            }
+        }
+    }
+
+    /// Returns distributions of durations of requests and dependency calls aggregated locally for
+    /// standard metrics since the client was created, one per metric and combination of dimensions. It
+    /// is empty unless [`standard_metrics`](struct.TelemetryConfigBuilder.html#method.standard_metrics)
+    /// are enabled. See [`DurationDistribution`](telemetry/struct.DurationDistribution.html) for an example
+    /// of computing an SLO burn rate.
+    pub fn duration_distributions(&self) -> Vec<DurationDistribution> {
+        self.durations.snapshot()
+    }
+
     /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
     ///
     /// # Examples
@@ -263,9 +847,39 @@ impl TelemetryClient {
     /// }
     /// ```
     pub fn flush_channel(&self) {
+        self.submit_aggregated_metrics();
         self.channel.flush();
     }
 
+    /// Forces all pending telemetry items to be submitted and waits until the ingestion endpoint responds,
+    /// returning its [`responses`](transport/struct.IngestionResponse.html) that tell how many items it
+    /// accepted and why it rejected others. Items the endpoint asked to send again are retried in the
+    /// background as usual, and their later responses are not waited for.
+    ///
+    /// Pending items are submitted right away, unless the channel waits to retry a batch that failed to
+    /// be sent: then they are submitted along with it once a delay of a
+    /// [`retry policy`](retry/trait.RetryPolicy.html) passes, e.g. up to 16 seconds by default or longer
+    /// when the endpoint throttles. It returns no responses if the channel is closed before items are
+    /// submitted. Wrap it into `tokio::time::timeout` to bound the wait.
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # async fn run() {
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_event("started");
+    ///
+    /// for response in client.flush_channel_and_wait().await {
+    ///     if response.items_accepted() < response.items_received() {
+    ///         eprintln!("rejected items: {:?}", response.errors());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn flush_channel_and_wait(&self) -> Vec<IngestionResponse> {
+        self.submit_aggregated_metrics();
+        self.channel.flush_and_wait().await
+    }
+
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
@@ -290,6 +904,7 @@ impl TelemetryClient {
     /// // client.track_event("app is stopped".to_string());
     /// ```
     pub async fn close_channel(mut self) {
+        self.submit_aggregated_metrics();
         self.channel.close().await;
     }
 
@@ -321,21 +936,38 @@ impl TelemetryClient {
     pub async fn terminate(mut self) {
         self.channel.terminate().await;
     }
+
+    /// Aggregates a duration of a request or a dependency call into a standard metric.
+    fn extract_standard_metric(&self, envelope: &mut Envelope) {
+        if let Some(metric) = standard::extract(envelope) {
+            self.durations.add(&metric.name, &metric.dimensions, metric.value);
+            for telemetry in self.metrics.track(metric.name, metric.dimensions, metric.value) {
+                self.track(telemetry);
+            }
+        }
+    }
+
+    /// Submits metric values and events aggregated so far.
+    fn submit_aggregated_metrics(&self) {
+        for telemetry in self.metrics.take() {
+            self.track(telemetry);
+        }
+        for envelope in self.events.iter().flat_map(EventAggregator::take) {
+            self.channel.send(envelope);
+        }
+    }
 }
 
 impl From<(TelemetryConfig, TelemetryContext)> for TelemetryClient {
     fn from((config, context): (TelemetryConfig, TelemetryContext)) -> Self {
-        Self {
-            enabled: true,
-            context,
-            channel: Box::new(InMemoryChannel::new(&config)),
-        }
+        let channel = InMemoryChannel::new(&config);
+        Self::create_with_context(&config, context, channel)
     }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use std::sync::Arc;
+    use std::{collections::BTreeMap, sync::Arc};
 
     use async_trait::async_trait;
     use chrono::{DateTime, Utc};
@@ -343,7 +975,13 @@ pub(crate) mod tests {
     use matches::assert_matches;
 
     use super::*;
-    use crate::telemetry::{ContextTags, Properties};
+    use crate::{
+        contracts::{Base, Data, RequestData},
+        diagnostics::Diagnostic,
+        processor::{Quota, Sampler, SchemaValidator},
+        telemetry::{ContextTags, Properties},
+        uuid::{self, Uuid},
+    };
 
     #[tokio::test]
     async fn it_enabled_by_default() {
@@ -381,6 +1019,416 @@ pub(crate) mod tests {
         assert!(events.is_empty())
     }
 
+    #[tokio::test]
+    async fn it_drops_telemetry_filtered_out_by_processor() {
+        let events = Arc::new(SegQueue::default());
+        let mut client = create_client(events.clone());
+        client.add_processor(DropAll);
+
+        client.track(TestTelemetry {});
+
+        assert!(events.is_empty())
+    }
+
+    #[tokio::test]
+    async fn it_reports_diagnostics_of_processors_to_configured_hook() {
+        let events = Arc::new(SegQueue::default());
+        let reported = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .on_diagnostic({
+                let reported = reported.clone();
+                move |diagnostic| {
+                    if let Diagnostic::SchemaViolation { envelope, .. } = diagnostic {
+                        reported.push(envelope.name.clone());
+                    }
+                }
+            })
+            .build();
+        let mut client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        client.add_processor(SchemaValidator::new());
+
+        client.track_event("e".repeat(513));
+
+        assert_eq!(reported.pop().as_deref(), Some("Microsoft.ApplicationInsights.Event"));
+        assert_eq!(events.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_applies_daily_quota_after_processors() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .daily_quota(Quota::Items(1))
+            .build();
+        let mut client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        client.add_processor(Sampler::new(0.0));
+
+        for _ in 0..10 {
+            client.track_event("sampled out");
+        }
+        client.track_metric("latency", 1.0);
+
+        assert_eq!(events.len(), 1);
+        assert_matches!(
+            events.pop().and_then(|envelope| envelope.data),
+            Some(Base::Data(Data::MetricData(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn it_submits_aggregated_values_on_flush() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client.track_value("latency", 10.0);
+        client.track_value("latency", 20.0);
+        assert!(events.is_empty());
+
+        client.flush_channel();
+
+        assert_eq!(events.len(), 1)
+    }
+
+    #[tokio::test]
+    async fn it_submits_aggregated_values_per_dimensions() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client.track_value_with_dimensions("latency", 10.0, [("endpoint", "/orders")]);
+        client.track_value_with_dimensions("latency", 20.0, [("endpoint", "/users")]);
+        client.flush_channel();
+
+        assert_eq!(events.len(), 2)
+    }
+
+    #[tokio::test]
+    async fn it_drops_user_data_restricted_by_policy() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .user_data(UserDataPolicy::no_user_data())
+            .build();
+        let mut client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        client.context_mut().tags_mut().user_mut().set_id("user".into());
+        client.context_mut().tags_mut().session_mut().set_id("session".into());
+
+        client.track_event("event");
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert!(!tags.contains_key("ai.user.id"));
+        assert!(!tags.contains_key("ai.session.id"));
+    }
+
+    #[tokio::test]
+    async fn it_extracts_standard_metrics_before_processors() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .standard_metrics(true)
+            .build();
+        let mut client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        client.add_processor(DropRequests);
+
+        client.track_request(
+            Method::GET,
+            "https://example.com/".parse().unwrap(),
+            Duration::from_millis(10),
+            "200",
+        );
+        client.track_request(
+            Method::GET,
+            "https://example.com/".parse().unwrap(),
+            Duration::from_millis(30),
+            "200",
+        );
+        client.flush_channel();
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::MetricData(data)))
+                if data.metrics[0].name == "Server response time" && data.metrics[0].count == Some(2) && data.metrics[0].value == 40.0
+        );
+        assert!(events.is_empty());
+
+        let distributions = client.duration_distributions();
+        assert_eq!(distributions.len(), 1);
+        assert_eq!(distributions[0].count(), 2);
+        assert_eq!(distributions[0].max(), Duration::from_millis(30));
+    }
+
+    struct DropRequests;
+
+    impl TelemetryProcessor for DropRequests {
+        fn process(&self, envelope: &mut Envelope) -> bool {
+            !matches!(envelope.data, Some(Base::Data(Data::RequestData(_))))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_initializes_telemetry_before_processors() {
+        let events = Arc::new(SegQueue::default());
+        let mut client = create_client(events.clone());
+        client.add_initializer(Tenant);
+        client.add_processor(DropOtherTenants);
+
+        client.track_event("event");
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(
+            envelope.tags.unwrap().get("ai.user.accountId").map(String::as_str),
+            Some("contoso")
+        );
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::EventData(data))) if data.properties.as_ref().unwrap()["tenant"] == "contoso"
+        );
+    }
+
+    struct Tenant;
+
+    impl TelemetryInitializer for Tenant {
+        fn initialize(&self, telemetry: &mut dyn Telemetry, context: &mut TelemetryContext) {
+            telemetry.properties_mut().insert("tenant".into(), "contoso".into());
+            context.tags_mut().user_mut().set_account_id("contoso".into());
+        }
+    }
+
+    struct DropOtherTenants;
+
+    impl TelemetryProcessor for DropOtherTenants {
+        fn process(&self, envelope: &mut Envelope) -> bool {
+            envelope
+                .tags
+                .as_ref()
+                .and_then(|tags| tags.get("ai.user.accountId"))
+                .is_some()
+        }
+    }
+
+    struct DropAll;
+
+    impl TelemetryProcessor for DropAll {
+        fn process(&self, _: &mut Envelope) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn it_submits_aggregated_events_on_flush() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .aggregate_events(true)
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        for _ in 0..3 {
+            client.track_event("heartbeat");
+        }
+        assert!(events.is_empty());
+
+        client.flush_channel();
+
+        assert_eq!(events.len(), 1);
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::EventData(data))) if data.measurements.as_ref().and_then(|m| m.get("count")) == Some(&3.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn it_samples_registered_gauges_periodically() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .aggregation_interval(Duration::from_millis(10))
+            .build();
+        let client = Arc::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+
+        client.register_gauge("queue_depth", || 42.0);
+        for _ in 0..100 {
+            if events.len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(events.len() >= 2);
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::MetricData(data))) if data.metrics[0].name == "queue_depth" && data.metrics[0].value == 42.0
+        );
+    }
+
+    #[tokio::test]
+    async fn it_tracks_telemetry_under_scoped_context() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let mut context = client.context().child();
+        context.properties_mut().insert("tenant".into(), "contoso".into());
+
+        client.with_context(context).track_event("scoped");
+        client.track_event("unscoped");
+
+        let properties = |envelope: Envelope| match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => data.properties.unwrap_or_default(),
+            _ => panic!("unexpected data"),
+        };
+        assert_eq!(
+            properties(events.pop().unwrap()).get("tenant"),
+            Some(&"contoso".to_string())
+        );
+        assert_eq!(properties(events.pop().unwrap()).get("tenant"), None);
+    }
+
+    #[tokio::test]
+    async fn it_attaches_baggage_as_properties() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let mut context = client.context().child();
+        context.baggage_mut().insert("experiment".into(), "beta".into());
+        context.baggage_mut().insert("tenant".into(), "fabrikam".into());
+        context.properties_mut().insert("tenant".into(), "contoso".into());
+
+        client.with_context(context).track_event("scoped");
+
+        let properties = match events.pop().unwrap().data {
+            Some(Base::Data(Data::EventData(data))) => data.properties.unwrap_or_default(),
+            _ => panic!("unexpected data"),
+        };
+        assert_eq!(properties.get("experiment"), Some(&"beta".to_string()));
+        assert_eq!(properties.get("tenant"), Some(&"contoso".to_string()));
+    }
+
+    #[tokio::test]
+    async fn it_returns_ids_of_tracked_items() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let tracked = client.track_with_id(EventTelemetry::new("event")).unwrap();
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap_or_default();
+        assert_eq!(
+            tags.get("ai.operation.id").map(String::as_str),
+            Some(tracked.operation_id())
+        );
+        assert_eq!(tracked.id(), None);
+
+        let context = client.context().child();
+        let operation_id = context.tags().operation().id().unwrap().to_string();
+        let request = RequestTelemetry::new(
+            Method::GET,
+            "https://example.com".parse().unwrap(),
+            Duration::default(),
+            "200",
+        );
+        let tracked = client.with_context(context).track_with_id(request).unwrap();
+        assert_eq!(tracked.to_string(), operation_id);
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RequestData(data))) if Some(data.id.as_str()) == tracked.id()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_measures_page_views_with_nested_telemetry() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let uri: Uri = "https://example.com/checkout".parse().unwrap();
+
+        let items = client.measure_page_view("checkout", uri.clone(), |page| {
+            page.track_event("cart loaded");
+            3
+        });
+        let total = client
+            .measure_page_view_async("checkout", uri, |page| async move {
+                page.track_event("cart loaded");
+                items * 2
+            })
+            .await;
+        assert_eq!(total, 6);
+
+        for _ in 0..2 {
+            let nested = events.pop().unwrap();
+            let page_view = events.pop().unwrap();
+            let (nested_tags, page_tags) = (nested.tags.unwrap(), page_view.tags.unwrap());
+            assert_eq!(nested_tags.get("ai.operation.id"), page_tags.get("ai.operation.id"));
+            assert_matches!(
+                page_view.data,
+                Some(Base::Data(Data::PageViewData(data)))
+                    if Some(&data.id) == nested_tags.get("ai.operation.parentId") && data.duration.is_some()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_tells_whether_operations_are_sampled_in() {
+        let events = Arc::new(SegQueue::default());
+        let mut client = create_client(events.clone());
+        assert!(client.is_sampled_in("operation"));
+
+        client.add_processor(Sampler::new(50.0));
+        let (kept, dropped): (Vec<_>, Vec<_>) = (0..100)
+            .map(|_| client.context().ids.new_id().simple().to_string())
+            .partition(|id| client.is_sampled_in(id));
+        assert!(!kept.is_empty() && !dropped.is_empty());
+
+        let mut context = client.context().clone();
+        context.tags_mut().operation_mut().set_id(dropped[0].clone());
+        client.with_context(context).track_event("dropped");
+        assert!(events.is_empty());
+
+        client.enabled(false);
+        assert!(!client.is_sampled_in(&kept[0]));
+    }
+
+    #[tokio::test]
+    async fn it_generates_ids_with_strategy_of_each_client() {
+        uuid::reset();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .time_ordered_ids(true)
+            .build();
+        let ordered = TelemetryClient::create(&config, TestChannel::new(Arc::default()));
+        let random = create_client(Arc::default());
+
+        let version = |client: &TelemetryClient| {
+            let context = client.context().child();
+            let operation = context.tags().operation();
+            Uuid::parse_str(operation.id().unwrap()).unwrap().get_version_num()
+        };
+        assert_eq!((version(&ordered), version(&random)), (7, 4));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn it_applies_testing_hooks_to_configured_client_only() {
+        use chrono::TimeZone;
+
+        let now = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .clock(move || now)
+            .id_generator(crate::testing::SequentialIds::default())
+            .build();
+        let hooked_events = Arc::new(SegQueue::default());
+        let hooked = TelemetryClient::create(&config, TestChannel::new(hooked_events.clone()));
+        let other_events = Arc::new(SegQueue::default());
+        let other = create_client(other_events.clone());
+
+        for client in [&hooked, &other].iter() {
+            client.track_request(Method::GET, "/".parse().unwrap(), Duration::from_secs(1), "200");
+        }
+
+        let hooked = hooked_events.pop().unwrap();
+        assert_eq!(hooked.time, "2020-01-01T00:00:00.000Z");
+        assert_matches!(hooked.data, Some(Base::Data(Data::RequestData(data))) if data.id == "0000000000000001");
+        let other = other_events.pop().unwrap();
+        assert_ne!(other.time, "2020-01-01T00:00:00.000Z");
+        assert_matches!(other.data, Some(Base::Data(Data::RequestData(data))) if data.id != "0000000000000001");
+    }
+
     #[tokio::test]
     async fn it_creates_client_with_default_tags() {
         let client = TelemetryClient::new("instrumentation".into());
@@ -396,11 +1444,22 @@ pub(crate) mod tests {
         assert!(client.is_enabled())
     }
 
-    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+    /// Creates a client with default configuration that submits telemetry items to a queue.
+    pub(crate) fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
         let config = TelemetryConfig::new("instrumentation".into());
         TelemetryClient::create(&config, TestChannel::new(events))
     }
 
+    /// Takes all telemetry items out of a queue and returns tags and data of requests among them.
+    pub(crate) fn take_requests(events: &SegQueue<Envelope>) -> Vec<(BTreeMap<String, String>, RequestData)> {
+        std::iter::from_fn(|| events.pop())
+            .filter_map(|envelope| match envelope.data {
+                Some(Base::Data(Data::RequestData(data))) => Some((envelope.tags.unwrap_or_default(), data)),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub(crate) struct TestTelemetry {}
 
     impl Telemetry for TestTelemetry {
@@ -408,6 +1467,10 @@ pub(crate) mod tests {
             unimplemented!()
         }
 
+        fn set_timestamp(&mut self, _: DateTime<Utc>) {
+            unimplemented!()
+        }
+
         fn properties(&self) -> &Properties {
             unimplemented!()
         }
@@ -425,9 +1488,6 @@ pub(crate) mod tests {
         }
     }
 
-    #[derive(Clone)]
-    pub(crate) struct TestData;
-
     impl From<(TelemetryContext, TestTelemetry)> for Envelope {
         fn from((_, _): (TelemetryContext, TestTelemetry)) -> Self {
             Envelope::default()
@@ -450,13 +1510,9 @@ pub(crate) mod tests {
             self.events.push(envelop);
         }
 
-        fn flush(&self) {
-            unimplemented!()
-        }
+        fn flush(&self) {}
 
-        async fn close(&mut self) {
-            unimplemented!()
-        }
+        async fn close(&mut self) {}
 
         async fn terminate(&mut self) {}
     }
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use http::{Method, Uri};
+
+use crate::{
+    context::TelemetryContext,
+    contracts::Envelope,
+    telemetry::{
+        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, RemoteDependencyTelemetry, RequestTelemetry,
+        SeverityLevel, Telemetry, TraceTelemetry,
+    },
+    TelemetryClient, TrackedItem,
+};
+
+/// A handle of a [`TelemetryClient`](struct.TelemetryClient.html) that tracks telemetry items under its
+/// own context, e.g. a context of a single request or a tenant. It is created with
+/// [`TelemetryClient::with_context`](struct.TelemetryClient.html#method.with_context) and is cheap
+/// enough to create one per request.
+pub struct ScopedClient<'a> {
+    client: &'a TelemetryClient,
+    context: TelemetryContext,
+}
+
+impl<'a> ScopedClient<'a> {
+    pub(crate) fn new(client: &'a TelemetryClient, context: TelemetryContext) -> Self {
+        Self { client, context }
+    }
+
+    /// Returns an immutable reference to the context items are tracked under.
+    pub fn context(&self) -> &TelemetryContext {
+        &self.context
+    }
+
+    /// Returns a mutable reference to the context items are tracked under.
+    pub fn context_mut(&mut self) -> &mut TelemetryContext {
+        &mut self.context
+    }
+
+    /// Returns a handle that tracks items under a child context of this one. See
+    /// [`TelemetryContext::child`](struct.TelemetryContext.html#method.child) for details.
+    pub fn child(&self) -> ScopedClient<'a> {
+        Self::new(self.client, self.context.child())
+    }
+
+    /// Logs a user action with the specified name.
+    pub fn track_event(&self, name: impl Into<String>) {
+        self.track(EventTelemetry::new(name))
+    }
+
+    /// Logs a trace message with a specified severity level.
+    pub fn track_trace(&self, message: impl Into<String>, severity: SeverityLevel) {
+        self.track(TraceTelemetry::new(message, severity))
+    }
+
+    /// Logs a numeric value that is not specified with a specific event.
+    pub fn track_metric(&self, name: impl Into<String>, value: f64) {
+        self.track(MetricTelemetry::new(name, value))
+    }
+
+    /// Logs a HTTP request with the specified method, URL, duration and response code.
+    pub fn track_request(&self, method: Method, uri: Uri, duration: Duration, response_code: impl Into<String>) {
+        self.track(RequestTelemetry::new(method, uri, duration, response_code))
+    }
+
+    /// Logs a dependency with the specified name, type, target, and success status.
+    pub fn track_remote_dependency(
+        &self,
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        target: impl Into<String>,
+        success: bool,
+    ) {
+        let event = RemoteDependencyTelemetry::new(name, dependency_type, Duration::default(), target, success);
+        self.track(event)
+    }
+
+    /// Logs an availability test result with the specified test name, duration, and success status.
+    pub fn track_availability(&self, name: impl Into<String>, duration: Duration, success: bool) {
+        self.track(AvailabilityTelemetry::new(name, duration, success))
+    }
+
+    /// Submits a specific telemetry event under the context of this handle.
+    pub fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.client.track_in(&self.context, event)
+    }
+
+    /// Submits a specific telemetry event under the context of this handle and returns identifiers of
+    /// the submitted item. See [`TelemetryClient::track_with_id`](struct.TelemetryClient.html#method.track_with_id)
+    /// for details.
+    pub fn track_with_id<E>(&self, event: E) -> Option<TrackedItem>
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.client.track_in_with_id(&self.context, event)
+    }
+}
@@ -0,0 +1,45 @@
+use std::fmt;
+
+use crate::contracts::{Base, Data, Envelope};
+
+/// Identifiers of a submitted telemetry item returned by
+/// [`TelemetryClient::track_with_id`](struct.TelemetryClient.html#method.track_with_id). An operation id
+/// finds all items of the same operation in Azure Portal, so it is suitable to be returned to callers
+/// as a support id. It displays as an operation id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackedItem {
+    operation_id: String,
+    id: Option<String>,
+}
+
+impl TrackedItem {
+    /// Collects identifiers of an envelope that is about to be submitted.
+    pub(crate) fn from_envelope(operation_id: String, envelope: &Envelope) -> Self {
+        let id = match &envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => Some(data.id.clone()),
+            Some(Base::Data(Data::RemoteDependencyData(data))) => data.id.clone(),
+            Some(Base::Data(Data::PageViewData(data))) => Some(data.id.clone()),
+            Some(Base::Data(Data::PageViewPerfData(data))) => Some(data.id.clone()),
+            Some(Base::Data(Data::AvailabilityData(data))) => Some(data.id.clone()),
+            _ => None,
+        };
+        Self { operation_id, id }
+    }
+
+    /// Returns an id of an operation the item belongs to.
+    pub fn operation_id(&self) -> &str {
+        &self.operation_id
+    }
+
+    /// Returns an id of the item itself. Only requests, dependencies, page views and availability results
+    /// carry one. Use it as a parent id of telemetry items of child operations.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+impl fmt::Display for TrackedItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.operation_id)
+    }
+}
@@ -0,0 +1,164 @@
+//! Usage and failure telemetry of command-line tools.
+//!
+//! An invocation of a command is submitted as a [`RequestTelemetry`](../telemetry/struct.RequestTelemetry.html)
+//! item named after the command, with an exit code as a response code, so usage and failure rates of
+//! internal tools can be analyzed the same way requests of a service are. Arguments are submitted as
+//! `command.args` property as they are passed, so they should be sanitized from secrets and personal data
+//! first.
+//!
+//! [`TelemetryClient::track_command`](../struct.TelemetryClient.html#method.track_command) submits an
+//! invocation measured by the caller, while [`main`](fn.main.html) wraps the whole program: it times it,
+//! submits it with an exit code the program ends with and waits until telemetry is submitted before the
+//! process exits.
+//!
+//! ```rust, no_run
+//! use appinsights::{command, TelemetryClient};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), String> {
+//!     let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!     command::main(client, "deploy", ["--env", "staging"], async {
+//!         // run the command
+//!         Ok(())
+//!     })
+//!     .await
+//! }
+//! ```
+use std::{fmt::Debug, future::Future, time::Duration};
+
+use http::Method;
+
+use crate::{
+    telemetry::{self, RequestTelemetry, Telemetry},
+    time::Stopwatch,
+    TelemetryClient,
+};
+
+/// A property of a request arguments of a command are submitted in.
+pub const ARGS_PROPERTY: &str = "command.args";
+
+/// An outcome of a command that tells an exit code it ends with.
+pub trait CommandStatus {
+    /// Returns an exit code, where 0 means success.
+    fn exit_code(&self) -> i32;
+
+    /// Returns a description of an error the command failed with, if there is any.
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
+
+impl CommandStatus for () {
+    fn exit_code(&self) -> i32 {
+        0
+    }
+}
+
+impl CommandStatus for i32 {
+    fn exit_code(&self) -> i32 {
+        *self
+    }
+}
+
+/// A command that returns an error exits with code 1, as `main` returning it does.
+impl<T, E: Debug> CommandStatus for Result<T, E> {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Ok(_) => 0,
+            Err(_) => 1,
+        }
+    }
+
+    fn error(&self) -> Option<String> {
+        self.as_ref().err().map(|err| format!("{:?}", err))
+    }
+}
+
+/// Runs a program as an invocation of a command with specified name and sanitized arguments, submits it
+/// as a request that succeeds when the program exits with code 0, and closes the channel of the client,
+/// waiting until all telemetry is submitted. Returns an outcome of the program, so it can be returned
+/// from `main`.
+pub async fn main<I, S, F, T>(client: TelemetryClient, name: &str, args: I, program: F) -> T
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+    F: Future<Output = T>,
+    T: CommandStatus,
+{
+    let started = Stopwatch::start();
+    let status = program.await;
+
+    let mut telemetry = request(name, args, status.exit_code(), started.elapsed());
+    telemetry.set_timestamp(started.started_at());
+    if let Some(error) = status.error() {
+        telemetry.properties_mut().insert("error".into(), error);
+    }
+    client.track(telemetry);
+    client.close_channel().await;
+
+    status
+}
+
+/// Creates a request of an invocation of a command.
+pub(crate) fn request<I, S>(name: &str, args: I, exit_code: i32, duration: Duration) -> RequestTelemetry
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let uri = telemetry::path_of(name);
+    let mut telemetry = RequestTelemetry::new(Method::GET, uri, duration, exit_code.to_string());
+    telemetry.set_name(name);
+    telemetry.set_success(exit_code == 0);
+
+    let args: Vec<_> = args.into_iter().map(|arg| arg.as_ref().to_string()).collect();
+    if !args.is_empty() {
+        telemetry.properties_mut().insert(ARGS_PROPERTY.into(), args.join(" "));
+    }
+    telemetry
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::client::tests::{create_client, take_requests};
+
+    #[test_case(0, true  ; "success")]
+    #[test_case(2, false ; "failure")]
+    fn it_creates_request_of_command(exit_code: i32, success: bool) {
+        let telemetry = request("deploy", ["--env", "staging"], exit_code, Duration::from_secs(3));
+
+        assert_eq!(telemetry.is_success(), success);
+        assert_eq!(telemetry.properties()[ARGS_PROPERTY], "--env staging");
+        assert_eq!(telemetry.tags().operation().name(), Some("deploy"));
+    }
+
+    #[test_case(Ok(()),                 0 ; "ok")]
+    #[test_case(Err("failed".into()),   1 ; "error")]
+    fn it_tells_exit_code_of_result(status: Result<(), String>, exit_code: i32) {
+        assert_eq!(status.exit_code(), exit_code);
+    }
+
+    #[tokio::test]
+    async fn it_submits_program_as_command() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let status = main(client, "db migrate", Vec::<String>::new(), async {
+            Err::<(), _>("locked")
+        })
+        .await;
+        assert!(status.is_err());
+
+        let (_, request) = take_requests(&events).remove(0);
+        assert_eq!(request.name.as_deref(), Some("db migrate"));
+        assert_eq!(request.url.as_deref(), Some("/db%20migrate"));
+        assert_eq!(request.response_code, "1");
+        assert!(!request.success);
+        assert_eq!(request.properties.unwrap()["error"], "\"locked\"");
+    }
+}
@@ -1,5 +1,22 @@
 //! Module for telemetry client configuration.
-use std::time::Duration;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+#[cfg(feature = "test-util")]
+use crate::testing::{Clock, Hooks, IdGenerator};
+use crate::{
+    diagnostics::{Diagnostic, Diagnostics},
+    privacy::UserDataPolicy,
+    processor::Quota,
+    retry::{ExponentialBackoff, RetryPolicy, SharedRetryPolicy},
+    tee::Tee,
+    transmitter::MAX_PAYLOAD_BYTES,
+    transport::{self, IngestionTransport, SharedTransport},
+};
 
 /// Configuration data used to initialize a new [`TelemetryClient`](../struct.TelemetryClient.html) with.
 ///
@@ -28,8 +45,87 @@ pub struct TelemetryConfig {
     /// Endpoint URL where data will be sent.
     endpoint: String,
 
-    /// Maximum time to wait until send a batch of telemetry.
+    /// Maximum time the oldest pending item waits until a batch of telemetry is sent.
     interval: Duration,
+
+    /// Number of pending items that triggers sending a batch of telemetry before the interval expires.
+    max_batch_items: Option<usize>,
+
+    /// Size of pending items in bytes that triggers sending a batch of telemetry before the interval expires.
+    max_batch_bytes: Option<usize>,
+
+    /// Maximum interval batches are stretched to while ingestion is slow or throttled.
+    adaptive_batching: Option<Duration>,
+
+    /// Maximum size of a single request body in bytes batches are split by.
+    max_payload_bytes: usize,
+
+    /// Maximum size of a single telemetry item in bytes items are trimmed to.
+    max_item_bytes: Option<usize>,
+
+    /// Telemetry submitted per day after which non-critical items are dropped.
+    daily_quota: Option<Quota>,
+
+    /// Maximum number of requests a batch of telemetry is sent in at once.
+    max_concurrent_requests: usize,
+
+    /// Maximum number of bytes per second all requests send together.
+    max_bytes_per_second: Option<usize>,
+
+    /// Maximum number of items waiting to be sent before low priority items are dropped.
+    max_pending_items: Option<usize>,
+
+    /// Maximum size in bytes of items waiting to be sent before low priority items are dropped.
+    max_pending_bytes: Option<usize>,
+
+    /// Time interval metric values tracked with `track_value` are aggregated over.
+    aggregation_interval: Duration,
+
+    /// Maximum number of dimension combinations aggregated separately per metric within an interval.
+    max_metric_series: usize,
+
+    /// Percentiles of aggregated metric values to submit as separate metrics.
+    metric_percentiles: Vec<f64>,
+
+    /// Policy of submitting user ids, a session id and a client IP address.
+    user_data: UserDataPolicy,
+
+    /// Sink that receives a copy of every transmitted batch of telemetry.
+    tee: Option<Tee>,
+
+    /// Whether durations of requests and dependency calls are pre-aggregated into standard metrics.
+    standard_metrics: bool,
+
+    /// Whether identical custom events are coalesced into one event per aggregation interval.
+    aggregate_events: bool,
+
+    /// Whether traces and exceptions are stamped with thread and task they were tracked from.
+    thread_metadata: bool,
+
+    /// Whether telemetry carries properties with metadata of an Azure virtual machine the application runs on.
+    azure_vm_metadata: bool,
+
+    /// Marker of an integration prepended to `ai.internal.sdkVersion` tag.
+    sdk_version_prefix: Option<String>,
+
+    /// Base URL of an OTLP/HTTP receiver telemetry is exported to instead of the track endpoint.
+    otlp_endpoint: Option<String>,
+
+    /// Whether ids of operations, requests and page views are time-ordered UUIDv7.
+    time_ordered_ids: bool,
+
+    /// Policy that decides when a batch of telemetry is sent again after a failed attempt.
+    retry_policy: Option<SharedRetryPolicy>,
+
+    /// Hook that receives diagnostics of the SDK itself.
+    diagnostics: Diagnostics,
+
+    /// Transport that delivers requests instead of the built-in HTTP client.
+    transport: Option<SharedTransport>,
+
+    /// A clock and an id generator that replace the system ones in tests.
+    #[cfg(feature = "test-util")]
+    testing: Hooks,
 }
 
 impl TelemetryConfig {
@@ -38,9 +134,52 @@ impl TelemetryConfig {
         TelemetryConfig::builder().i_key(i_key).build()
     }
 
+    /// Creates a new telemetry configuration with an instrumentation key and an ingestion endpoint taken
+    /// from a connection string of Application Insights resource, and default values.
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::from_connection_string(
+    ///     "InstrumentationKey=<instrumentation key>;IngestionEndpoint=https://westeurope-1.in.applicationinsights.azure.com/",
+    /// )?;
+    /// assert_eq!(config.endpoint(), "https://westeurope-1.in.applicationinsights.azure.com/v2/track");
+    /// # Ok::<(), appinsights::ConnectionStringError>(())
+    /// ```
+    pub fn from_connection_string(connection_string: &str) -> Result<Self, ConnectionStringError> {
+        Ok(TelemetryConfig::builder().connection_string(connection_string)?.build())
+    }
+
     /// Creates a new telemetry configuration builder with default parameters.
     pub fn builder() -> DefaultTelemetryConfigBuilder {
-        DefaultTelemetryConfigBuilder::default()
+        DefaultTelemetryConfigBuilder
+    }
+
+    /// Checks that an instrumentation key is a GUID and an endpoint is an absolute URL, so a typo fails
+    /// at startup instead of every batch of telemetry being rejected by the ingestion endpoint later.
+    ///
+    /// ```rust
+    /// # use appinsights::{ConfigError, TelemetryConfig};
+    /// let config = TelemetryConfig::new("00000000-0000-0000-0000-00000000000".to_string());
+    /// assert!(matches!(config.validate(), Err(ConfigError::InvalidInstrumentationKey(_))));
+    /// ```
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let is_guid = self.i_key.len() == 36
+            && self.i_key.char_indices().all(|(i, c)| match i {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => c.is_ascii_hexdigit(),
+            });
+        if !is_guid {
+            return Err(ConfigError::InvalidInstrumentationKey(self.i_key.clone()));
+        }
+
+        let is_url = self.endpoint.parse::<http::Uri>().is_ok_and(|uri| {
+            matches!(uri.scheme_str(), Some("http" | "https" | "otlp" | "otlps")) && uri.authority().is_some()
+        }) || (cfg!(unix) && transport::of_endpoint(&self.endpoint).is_some());
+        if !is_url {
+            return Err(ConfigError::InvalidEndpoint(self.endpoint.clone()));
+        }
+
+        Ok(())
     }
 
     /// Returns an instrumentation key for the client.
@@ -53,10 +192,148 @@ impl TelemetryConfig {
         &self.endpoint
     }
 
-    /// Returns maximum time to wait until send a batch of telemetry.
+    /// Returns maximum time the oldest pending item waits until a batch of telemetry is sent.
     pub fn interval(&self) -> Duration {
         self.interval
     }
+
+    /// Returns a number of pending items that triggers sending a batch of telemetry before the interval
+    /// expires.
+    pub fn max_batch_items(&self) -> Option<usize> {
+        self.max_batch_items
+    }
+
+    /// Returns a size of pending items in bytes that triggers sending a batch of telemetry before the
+    /// interval expires.
+    pub fn max_batch_bytes(&self) -> Option<usize> {
+        self.max_batch_bytes
+    }
+
+    /// Returns a maximum interval batches are stretched to while ingestion is slow or throttled, if
+    /// adaptive batching is enabled.
+    pub fn adaptive_batching(&self) -> Option<Duration> {
+        self.adaptive_batching
+    }
+
+    /// Returns a maximum size of a single request body in bytes batches are split by.
+    pub fn max_payload_bytes(&self) -> usize {
+        self.max_payload_bytes
+    }
+
+    /// Returns a maximum size of a single telemetry item in bytes items are trimmed to.
+    pub fn max_item_bytes(&self) -> Option<usize> {
+        self.max_item_bytes
+    }
+
+    /// Returns telemetry submitted per day after which non-critical items are dropped.
+    pub fn daily_quota(&self) -> Option<Quota> {
+        self.daily_quota
+    }
+
+    /// Returns a maximum number of requests a batch of telemetry is sent in at once.
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.max_concurrent_requests
+    }
+
+    /// Returns a maximum number of bytes per second all requests send together.
+    pub fn max_bytes_per_second(&self) -> Option<usize> {
+        self.max_bytes_per_second
+    }
+
+    /// Returns a maximum number of items waiting to be sent before low priority items are dropped.
+    pub fn max_pending_items(&self) -> Option<usize> {
+        self.max_pending_items
+    }
+
+    /// Returns a maximum size in bytes of items waiting to be sent before low priority items are dropped.
+    pub fn max_pending_bytes(&self) -> Option<usize> {
+        self.max_pending_bytes
+    }
+
+    /// Returns time interval metric values are aggregated over.
+    pub fn aggregation_interval(&self) -> Duration {
+        self.aggregation_interval
+    }
+
+    /// Returns maximum number of dimension combinations aggregated separately per metric within an interval.
+    pub fn max_metric_series(&self) -> usize {
+        self.max_metric_series
+    }
+
+    /// Returns percentiles of aggregated metric values to submit as separate metrics.
+    pub fn metric_percentiles(&self) -> &[f64] {
+        &self.metric_percentiles
+    }
+
+    /// Returns a policy of submitting user ids, a session id and a client IP address.
+    pub fn user_data(&self) -> &UserDataPolicy {
+        &self.user_data
+    }
+
+    /// Returns a sink that receives a copy of every transmitted batch of telemetry.
+    pub fn tee(&self) -> Option<&Tee> {
+        self.tee.as_ref()
+    }
+
+    /// Returns whether durations of requests and dependency calls are pre-aggregated into standard metrics.
+    pub fn standard_metrics(&self) -> bool {
+        self.standard_metrics
+    }
+
+    /// Returns whether identical custom events are coalesced into one event per aggregation interval.
+    pub fn aggregate_events(&self) -> bool {
+        self.aggregate_events
+    }
+
+    /// Returns whether traces and exceptions are stamped with thread and task they were tracked from.
+    pub fn thread_metadata(&self) -> bool {
+        self.thread_metadata
+    }
+
+    /// Returns whether telemetry carries properties with metadata of an Azure virtual machine the application
+    /// runs on.
+    pub fn azure_vm_metadata(&self) -> bool {
+        self.azure_vm_metadata
+    }
+
+    /// Returns a marker of an integration prepended to `ai.internal.sdkVersion` tag.
+    pub fn sdk_version_prefix(&self) -> Option<&str> {
+        self.sdk_version_prefix.as_deref()
+    }
+
+    /// Returns a base URL of an OTLP/HTTP receiver telemetry is exported to instead of the track endpoint.
+    pub fn otlp_endpoint(&self) -> Option<&str> {
+        self.otlp_endpoint.as_deref()
+    }
+
+    /// Returns whether ids of operations, requests and page views are time-ordered UUIDv7.
+    pub fn time_ordered_ids(&self) -> bool {
+        self.time_ordered_ids
+    }
+
+    /// Returns a policy that decides when a batch of telemetry is sent again after a failed attempt.
+    pub(crate) fn retry_policy(&self) -> Arc<dyn RetryPolicy> {
+        match &self.retry_policy {
+            Some(policy) => policy.0.clone(),
+            None => Arc::new(ExponentialBackoff::default()),
+        }
+    }
+
+    /// Returns a hook that receives diagnostics of the SDK itself.
+    pub(crate) fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Returns a transport that delivers requests instead of the built-in HTTP client.
+    pub(crate) fn transport(&self) -> Option<&SharedTransport> {
+        self.transport.as_ref()
+    }
+
+    /// Returns a clock and an id generator that replace the system ones in tests.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn testing(&self) -> &Hooks {
+        &self.testing
+    }
 }
 
 /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with required
@@ -74,15 +351,161 @@ impl DefaultTelemetryConfigBuilder {
             i_key: i_key.into(),
             endpoint: "https://dc.services.visualstudio.com/v2/track".into(),
             interval: Duration::from_secs(2),
+            max_batch_items: None,
+            max_batch_bytes: None,
+            adaptive_batching: None,
+            max_payload_bytes: MAX_PAYLOAD_BYTES,
+            max_item_bytes: None,
+            daily_quota: None,
+            max_concurrent_requests: 1,
+            max_bytes_per_second: None,
+            max_pending_items: None,
+            max_pending_bytes: None,
+            aggregation_interval: Duration::from_secs(60),
+            max_metric_series: 1000,
+            metric_percentiles: Vec::new(),
+            user_data: UserDataPolicy::default(),
+            tee: None,
+            standard_metrics: false,
+            aggregate_events: false,
+            thread_metadata: false,
+            azure_vm_metadata: false,
+            sdk_version_prefix: None,
+            otlp_endpoint: None,
+            time_ordered_ids: false,
+            retry_policy: None,
+            diagnostics: Diagnostics::default(),
+            transport: None,
+            #[cfg(feature = "test-util")]
+            testing: Hooks::default(),
+        }
+    }
+
+    /// Initializes a builder with an instrumentation key and an ingestion endpoint taken from a connection
+    /// string, e.g. `InstrumentationKey=...;IngestionEndpoint=https://...`. An endpoint is derived from
+    /// `EndpointSuffix` when the connection string has no `IngestionEndpoint`, and the global endpoint is
+    /// used when it has neither.
+    pub fn connection_string(self, connection_string: &str) -> Result<TelemetryConfigBuilder, ConnectionStringError> {
+        let mut i_key = None;
+        let mut ingestion_endpoint = None;
+        let mut endpoint_suffix = None;
+        for pair in connection_string.split(';').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or(ConnectionStringError::Malformed)?;
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "instrumentationkey" if !value.is_empty() => i_key = Some(value),
+                "ingestionendpoint" => ingestion_endpoint = Some(value.trim_end_matches('/').to_string()),
+                "endpointsuffix" => endpoint_suffix = Some(format!("https://dc.{}", value.trim_matches('.'))),
+                _ => {}
+            }
+        }
+
+        let i_key = i_key.ok_or(ConnectionStringError::MissingInstrumentationKey)?;
+        let builder = self.i_key(i_key);
+        Ok(match ingestion_endpoint.or(endpoint_suffix) {
+            Some(endpoint) => builder.endpoint(format!("{}/v2/track", endpoint)),
+            None => builder,
+        })
+    }
+}
+
+/// An error returned when a connection string is not valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionStringError {
+    /// A connection string is not a list of `key=value` pairs separated with `;`.
+    Malformed,
+
+    /// A connection string has no instrumentation key.
+    MissingInstrumentationKey,
+}
+
+impl Display for ConnectionStringError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionStringError::Malformed => write!(f, "connection string is malformed"),
+            ConnectionStringError::MissingInstrumentationKey => {
+                write!(f, "connection string has no InstrumentationKey")
+            }
+        }
+    }
+}
+
+impl Error for ConnectionStringError {}
+
+/// An error returned when a configuration is not valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// An instrumentation key is not a GUID.
+    InvalidInstrumentationKey(String),
+
+    /// An endpoint is not an absolute HTTP, HTTPS or OTLP URL.
+    InvalidEndpoint(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidInstrumentationKey(i_key) => write!(f, "instrumentation key {:?} is not a GUID", i_key),
+            ConfigError::InvalidEndpoint(endpoint) => write!(f, "endpoint {:?} is not an absolute URL", endpoint),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// An error returned when a startup probe of the ingestion service fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeError {
+    /// The service does not know the instrumentation key, so all telemetry would be rejected.
+    UnknownInstrumentationKey,
+
+    /// The service cannot be reached or responded with an unexpected status.
+    Unavailable(String),
+}
+
+impl Display for ProbeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::UnknownInstrumentationKey => write!(f, "instrumentation key is unknown to the service"),
+            ProbeError::Unavailable(reason) => write!(f, "service is unavailable: {}", reason),
         }
     }
 }
 
+impl Error for ProbeError {}
+
 /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with custom settings.
 pub struct TelemetryConfigBuilder {
     i_key: String,
     endpoint: String,
     interval: Duration,
+    max_batch_items: Option<usize>,
+    max_batch_bytes: Option<usize>,
+    adaptive_batching: Option<Duration>,
+    max_payload_bytes: usize,
+    max_item_bytes: Option<usize>,
+    daily_quota: Option<Quota>,
+    max_concurrent_requests: usize,
+    max_bytes_per_second: Option<usize>,
+    max_pending_items: Option<usize>,
+    max_pending_bytes: Option<usize>,
+    aggregation_interval: Duration,
+    max_metric_series: usize,
+    metric_percentiles: Vec<f64>,
+    user_data: UserDataPolicy,
+    tee: Option<Tee>,
+    standard_metrics: bool,
+    aggregate_events: bool,
+    thread_metadata: bool,
+    azure_vm_metadata: bool,
+    sdk_version_prefix: Option<String>,
+    otlp_endpoint: Option<String>,
+    time_ordered_ids: bool,
+    retry_policy: Option<SharedRetryPolicy>,
+    diagnostics: Diagnostics,
+    transport: Option<SharedTransport>,
+    #[cfg(feature = "test-util")]
+    testing: Hooks,
 }
 
 impl TelemetryConfigBuilder {
@@ -95,7 +518,13 @@ impl TelemetryConfigBuilder {
         self
     }
 
-    /// Initializes a builder with an endpoint URL where data will be sent.
+    /// Initializes a builder with an endpoint URL where data will be sent. Besides the track endpoint of
+    /// Azure Monitor, it can point to a local forwarder or an OpenTelemetry collector that centralizes
+    /// egress of a cluster: an `otlp://host:port` or `otlps://host:port` URL exports telemetry to an
+    /// OTLP/HTTP receiver over HTTP or HTTPS respectively as [`otlp_endpoint`](#method.otlp_endpoint)
+    /// does, while any other URL is treated as a track endpoint. OTLP over gRPC is not supported. On Unix,
+    /// a `unix:///path/to/socket` URL sends batches to a local sidecar over a unix domain socket with
+    /// [`UnixSocketTransport`](transport/struct.UnixSocketTransport.html).
     pub fn endpoint<E>(mut self, endpoint: E) -> Self
     where
         E: Into<String>,
@@ -104,26 +533,379 @@ impl TelemetryConfigBuilder {
         self
     }
 
-    /// Initializes a builder with a maximum time to wait until send a batch of telemetry.
+    /// Initializes a builder with a maximum time the oldest pending item waits until a batch of telemetry
+    /// is sent. The timer starts when the first item is queued, so an idle client doesn't wake up until
+    /// there is something to send.
     pub fn interval(mut self, interval: Duration) -> Self {
         self.interval = interval;
         self
     }
 
+    /// Initializes a builder with a number of pending items that triggers sending a batch of telemetry
+    /// right away instead of waiting for the [`interval`](#method.interval) to expire. A batch is sent
+    /// whenever either limit is reached first. There is no limit by default.
+    pub fn max_batch_items(mut self, max_batch_items: usize) -> Self {
+        self.max_batch_items = Some(max_batch_items);
+        self
+    }
+
+    /// Initializes a builder with a size of pending items in bytes of serialized JSON that triggers
+    /// sending a batch of telemetry right away instead of waiting for the [`interval`](#method.interval)
+    /// to expire. Every item is serialized once more to measure its size. There is no limit by default.
+    pub fn max_batch_bytes(mut self, max_batch_bytes: usize) -> Self {
+        self.max_batch_bytes = Some(max_batch_bytes);
+        self
+    }
+
+    /// Initializes a builder with a maximum interval batches are stretched to while the ingestion endpoint
+    /// is slow. The channel measures how long every batch takes to submit: when responses get slow or the
+    /// endpoint throttles, it sends larger batches less often, up to `max_interval` and the batch limits
+    /// scaled by the same factor, and it returns to the configured [`interval`](#method.interval) and
+    /// limits as responses get fast again. Batches are not adapted by default.
+    pub fn adaptive_batching(mut self, max_interval: Duration) -> Self {
+        self.adaptive_batching = Some(max_interval);
+        self
+    }
+
+    /// Initializes a builder with a maximum size of a single request body in bytes of serialized JSON. A
+    /// batch that is larger is split into several requests, and an item that is larger on its own is
+    /// dropped, so the service doesn't reject a whole batch because of its size. It defaults to 64 MB the
+    /// track endpoint accepts.
+    pub fn max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = max_payload_bytes;
+        self
+    }
+
+    /// Initializes a builder with a maximum size of a single telemetry item in bytes of serialized JSON.
+    /// A larger item is trimmed by dropping its largest custom properties first and then truncating
+    /// exception stacks, and a warning tells what was removed. An item that still doesn't fit is
+    /// dropped. See [`SizeGuard`](processor/struct.SizeGuard.html) for custom trimming strategies.
+    pub fn max_item_bytes(mut self, max_item_bytes: usize) -> Self {
+        self.max_item_bytes = Some(max_item_bytes);
+        self
+    }
+
+    /// Initializes a builder with a daily quota of telemetry items or their estimated size. Once it is
+    /// exceeded, a single `Quota Exceeded` event is submitted and non-critical telemetry is dropped until
+    /// the next day by UTC, while exceptions, failed requests and error traces are still submitted. The
+    /// quota is applied after all processors, so items they drop don't use it up. See
+    /// [`DailyQuota`](processor/struct.DailyQuota.html) for details.
+    pub fn daily_quota(mut self, quota: Quota) -> Self {
+        self.daily_quota = Some(quota);
+        self
+    }
+
+    /// Initializes a builder with a maximum number of requests a batch of telemetry is sent in at once.
+    /// It defaults to 1, so batches are sent one request after another in order they were tracked. A
+    /// larger number lets a high-throughput service upload a batch in parallel: the batch is split into
+    /// that many requests, and items of the batch may reach the service in any order.
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    /// Initializes a builder with a maximum number of bytes per second all requests to the track endpoint
+    /// send together. Requests are delayed to keep the average rate within the cap regardless of how many
+    /// of them run at once. It is not limited by default.
+    pub fn max_bytes_per_second(mut self, max_bytes_per_second: usize) -> Self {
+        self.max_bytes_per_second = Some(max_bytes_per_second);
+        self
+    }
+
+    /// Initializes a builder with a maximum number of items waiting to be sent, e.g. while the server is
+    /// unavailable. Every item has a priority based on its type: exceptions and availability results are
+    /// high, traces are low and everything else is in between. Items are transmitted in order of
+    /// priority, and when the limit is reached an oldest item of the lowest priority is dropped to make
+    /// room for a new one of the same or higher priority. The number is not limited by default.
+    pub fn max_pending_items(mut self, max_pending_items: usize) -> Self {
+        self.max_pending_items = Some(max_pending_items);
+        self
+    }
+
+    /// Initializes a builder with a maximum size in bytes of items waiting to be sent, estimated as their
+    /// serialized JSON, so a few large items like exceptions with long stacks or verbose traces can't
+    /// exhaust memory while the number of items is within [`max_pending_items`](#method.max_pending_items).
+    /// Items are dropped in the same order of priority when the budget is reached, and an item larger
+    /// than the whole budget is dropped right away. Every item is serialized once more to measure its
+    /// size. The size is not limited by default.
+    pub fn max_pending_bytes(mut self, max_pending_bytes: usize) -> Self {
+        self.max_pending_bytes = Some(max_pending_bytes);
+        self
+    }
+
+    /// Initializes a builder with a time interval metric values tracked with
+    /// [`track_value`](struct.TelemetryClient.html#method.track_value) are aggregated over.
+    pub fn aggregation_interval(mut self, aggregation_interval: Duration) -> Self {
+        self.aggregation_interval = aggregation_interval;
+        self
+    }
+
+    /// Initializes a builder with a maximum number of dimension combinations aggregated separately per
+    /// metric within an interval. Values of all combinations exceeding the cap are aggregated into a single
+    /// series with a `dimensionCapReached` dimension.
+    pub fn max_metric_series(mut self, max_metric_series: usize) -> Self {
+        self.max_metric_series = max_metric_series;
+        self
+    }
+
+    /// Initializes a builder with percentiles of metric values tracked with
+    /// [`track_value`](struct.TelemetryClient.html#method.track_value) to submit as separate metrics for
+    /// every aggregation interval, e.g. `[50.0, 95.0, 99.0]`. A percentile metric is named after the
+    /// aggregated metric with a suffix, like `request_latency_ms_p95`. Percentiles are estimated with
+    /// relative error within 5%. No percentiles are submitted by default.
+    pub fn metric_percentiles(mut self, metric_percentiles: impl IntoIterator<Item = f64>) -> Self {
+        self.metric_percentiles = metric_percentiles.into_iter().collect();
+        self
+    }
+
+    /// Initializes a builder with a [`policy`](privacy/struct.UserDataPolicy.html) of submitting user ids,
+    /// a session id and a client IP address, e.g.
+    /// [`UserDataPolicy::no_user_data`](privacy/struct.UserDataPolicy.html#method.no_user_data) to never
+    /// submit them. All user data is submitted as is by default.
+    pub fn user_data(mut self, user_data: UserDataPolicy) -> Self {
+        self.user_data = user_data;
+        self
+    }
+
+    /// Initializes a builder with a [`tee`](tee/struct.Tee.html) that receives a copy of every batch of
+    /// telemetry right before it is transmitted, e.g. to retain an audit copy of submitted telemetry.
+    pub fn tee(mut self, tee: Tee) -> Self {
+        self.tee = Some(tee);
+        self
+    }
+
+    /// Initializes a builder with an indication whether durations of requests and dependency calls are
+    /// pre-aggregated into standard metrics over
+    /// [`aggregation_interval`](#method.aggregation_interval) before processors run. Request and
+    /// dependency charts of the portal are computed from these metrics, so they stay accurate even when
+    /// raw items are sampled or filtered out by processors. Standard metrics are disabled by default.
+    pub fn standard_metrics(mut self, standard_metrics: bool) -> Self {
+        self.standard_metrics = standard_metrics;
+        self
+    }
+
+    /// Initializes a builder with an indication whether identical custom events are coalesced within
+    /// [`aggregation_interval`](#method.aggregation_interval). Events of the same name, properties and
+    /// tags are submitted once per interval with a `count` measurement of how many times they were
+    /// tracked, which reduces ingestion volume of counters implemented as events. Events that have
+    /// measurements are never coalesced. It is disabled by default.
+    pub fn aggregate_events(mut self, aggregate_events: bool) -> Self {
+        self.aggregate_events = aggregate_events;
+        self
+    }
+
+    /// Initializes a builder with an indication whether traces and exceptions are stamped with a name and
+    /// an id of a thread and an id of a Tokio task they were tracked from. See
+    /// [`ThreadMetadata`](processor/struct.ThreadMetadata.html) for details. It is disabled by default.
+    pub fn thread_metadata(mut self, thread_metadata: bool) -> Self {
+        self.thread_metadata = thread_metadata;
+        self
+    }
+
+    /// Initializes a builder with an indication whether telemetry carries `azInst_*` properties with an id,
+    /// a size, a region and a scale set of an Azure virtual machine the application runs on, looked up in the
+    /// background from Azure Instance Metadata Service. See [`AzureVmMetadata`](initializer/struct.AzureVmMetadata.html)
+    /// for details. It is disabled by default and requires `net` feature.
+    pub fn azure_vm_metadata(mut self, azure_vm_metadata: bool) -> Self {
+        self.azure_vm_metadata = azure_vm_metadata;
+        self
+    }
+
+    /// Initializes a builder with a marker of an integration, e.g. a framework wrapping this crate, that
+    /// is prepended as is to `ai.internal.sdkVersion` tag. The tag is `rust:<crate version>` by default,
+    /// so a prefix `myframework_` makes it `myframework_rust:<crate version>`, which lets ingestion-side
+    /// analytics attribute telemetry to the integration that produced it.
+    pub fn sdk_version_prefix(mut self, sdk_version_prefix: impl Into<String>) -> Self {
+        self.sdk_version_prefix = Some(sdk_version_prefix.into());
+        self
+    }
+
+    /// Initializes a builder with a base URL of an OTLP/HTTP receiver, e.g. an OpenTelemetry collector
+    /// at `http://localhost:4318` or Azure Monitor OTLP ingestion, telemetry is exported to instead of the
+    /// classic [`endpoint`](#method.endpoint). Requests and dependency calls are exported as spans to
+    /// `v1/traces`, metrics as gauges and summaries to `v1/metrics`, and everything else as log records
+    /// to `v1/logs`, all in OTLP JSON encoding. Telemetry is sent to the track endpoint by default.
+    pub fn otlp_endpoint(mut self, otlp_endpoint: impl Into<String>) -> Self {
+        self.otlp_endpoint = Some(otlp_endpoint.into());
+        self
+    }
+
+    /// Initializes a builder with an indication whether ids of operations, requests and page views are
+    /// generated as time-ordered UUIDv7 instead of random UUIDv4, so they sort roughly by creation time in
+    /// the portal and Log Analytics. It applies to ids a client created with this configuration generates,
+    /// including ids of contexts derived from its context and trace ids of requests it starts, while other
+    /// clients in the process keep their own setting. Span ids stay random. It is disabled by default.
+    pub fn time_ordered_ids(mut self, time_ordered_ids: bool) -> Self {
+        self.time_ordered_ids = time_ordered_ids;
+        self
+    }
+
+    /// Initializes a builder with a [`retry policy`](retry/trait.RetryPolicy.html) that decides whether
+    /// and when a batch of telemetry is sent again after a failed attempt, e.g. a closure. It is
+    /// [`ExponentialBackoff`](retry/struct.ExponentialBackoff.html) with default settings by default.
+    pub fn retry_policy(mut self, retry_policy: impl RetryPolicy + 'static) -> Self {
+        self.retry_policy = Some(SharedRetryPolicy(Arc::new(retry_policy)));
+        self
+    }
+
+    /// Initializes a builder with a hook that receives every [`diagnostic`](diagnostics/enum.Diagnostic.html)
+    /// of the SDK itself, e.g. a telemetry item that breaks the item schema, is dropped because it fails to
+    /// serialize or is rejected by the ingestion endpoint, or a channel that lost connectivity to the endpoint.
+    /// Diagnostics are only logged by default.
+    ///
+    /// ```rust
+    /// # use appinsights::{diagnostics::Diagnostic, TelemetryConfig};
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .on_diagnostic(|diagnostic| match diagnostic {
+    ///         Diagnostic::Response(response) => {
+    ///             for error in response.errors() {
+    ///                 eprintln!("{} rejected: {}", error.name(), error.message());
+    ///             }
+    ///         }
+    ///         diagnostic => eprintln!("{:?}", diagnostic),
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn on_diagnostic<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Diagnostic<'_>) + Send + Sync + 'static,
+    {
+        self.diagnostics = Diagnostics::new(hook);
+        self
+    }
+
+    /// Initializes a builder with a [`transport`](transport/trait.IngestionTransport.html) that delivers
+    /// requests with telemetry to the endpoint, e.g. through an HTTP client of the application or a test
+    /// double. It is a built-in HTTP client by default.
+    pub fn transport(mut self, transport: impl IngestionTransport + 'static) -> Self {
+        self.transport = Some(SharedTransport(Arc::new(transport)));
+        self
+    }
+
+    /// Initializes a builder with a [`clock`](testing/trait.Clock.html) that replaces timestamps of telemetry
+    /// items a client tracks, e.g. a closure returning a fixed time. See
+    /// [`testing`](testing/index.html) for details. It is available with `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.testing.set_clock(clock);
+        self
+    }
+
+    /// Initializes a builder with an [`id generator`](testing/trait.IdGenerator.html) that replaces random
+    /// ids of operations, requests, spans and other telemetry items a client generates, e.g.
+    /// [`SequentialIds`](testing/struct.SequentialIds.html). See
+    /// [`testing`](testing/index.html) for details. It is available with `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn id_generator(mut self, id_generator: impl IdGenerator + 'static) -> Self {
+        self.testing.set_ids(id_generator);
+        self
+    }
+
     /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with custom settings.
     pub fn build(self) -> TelemetryConfig {
+        let endpoint = &self.endpoint;
+        let otlp_endpoint = self.otlp_endpoint.or_else(|| otlp_endpoint_of(endpoint));
         TelemetryConfig {
             i_key: self.i_key,
             endpoint: self.endpoint,
             interval: self.interval,
+            max_batch_items: self.max_batch_items,
+            max_batch_bytes: self.max_batch_bytes,
+            adaptive_batching: self.adaptive_batching,
+            max_payload_bytes: self.max_payload_bytes,
+            max_item_bytes: self.max_item_bytes,
+            daily_quota: self.daily_quota,
+            max_concurrent_requests: self.max_concurrent_requests,
+            max_bytes_per_second: self.max_bytes_per_second,
+            max_pending_items: self.max_pending_items,
+            max_pending_bytes: self.max_pending_bytes,
+            aggregation_interval: self.aggregation_interval,
+            max_metric_series: self.max_metric_series,
+            metric_percentiles: self.metric_percentiles,
+            user_data: self.user_data,
+            tee: self.tee,
+            standard_metrics: self.standard_metrics,
+            aggregate_events: self.aggregate_events,
+            thread_metadata: self.thread_metadata,
+            azure_vm_metadata: self.azure_vm_metadata,
+            sdk_version_prefix: self.sdk_version_prefix,
+            otlp_endpoint,
+            time_ordered_ids: self.time_ordered_ids,
+            retry_policy: self.retry_policy,
+            diagnostics: self.diagnostics,
+            transport: self.transport,
+            #[cfg(feature = "test-util")]
+            testing: self.testing,
         }
     }
 }
 
+/// Returns a base URL of an OTLP/HTTP receiver an endpoint with `otlp` or `otlps` scheme points to.
+fn otlp_endpoint_of(endpoint: &str) -> Option<String> {
+    if let Some(address) = endpoint.strip_prefix("otlp://") {
+        Some(format!("http://{}", address))
+    } else {
+        endpoint
+            .strip_prefix("otlps://")
+            .map(|address| format!("https://{}", address))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use test_case::test_case;
+
     use super::*;
 
+    #[test_case("InstrumentationKey=key", "https://dc.services.visualstudio.com/v2/track" ; "default endpoint")]
+    #[test_case(
+        "InstrumentationKey=key;IngestionEndpoint=https://westeurope-1.in.applicationinsights.azure.com/",
+        "https://westeurope-1.in.applicationinsights.azure.com/v2/track" ;
+        "ingestion endpoint"
+    )]
+    #[test_case(
+        "instrumentationkey = key ; EndpointSuffix=applicationinsights.azure.cn;",
+        "https://dc.applicationinsights.azure.cn/v2/track" ;
+        "endpoint suffix"
+    )]
+    fn it_creates_config_from_connection_string(connection_string: &str, endpoint: &str) {
+        let config = TelemetryConfig::from_connection_string(connection_string).unwrap();
+
+        assert_eq!(config.i_key(), "key");
+        assert_eq!(config.endpoint(), endpoint);
+    }
+
+    #[test_case("key",                                  ConnectionStringError::Malformed                 ; "not a list")]
+    #[test_case("IngestionEndpoint=https://example.com", ConnectionStringError::MissingInstrumentationKey ; "no key")]
+    #[test_case("InstrumentationKey=",                   ConnectionStringError::MissingInstrumentationKey ; "empty key")]
+    fn it_rejects_invalid_connection_string(connection_string: &str, expected: ConnectionStringError) {
+        assert_eq!(
+            TelemetryConfig::from_connection_string(connection_string),
+            Err(expected)
+        );
+    }
+
+    #[test_case("0f6b39e2-2a7c-4c1e-9b1d-8f4c2e6a7d31", "https://dc.services.visualstudio.com/v2/track", Ok(())                                                         ; "valid")]
+    #[test_case("0f6b39e2-2a7c-4c1e-9b1d-8f4c2e6a7d3",  "https://dc.services.visualstudio.com/v2/track", Err(ConfigError::InvalidInstrumentationKey("0f6b39e2-2a7c-4c1e-9b1d-8f4c2e6a7d3".into())) ; "short key")]
+    #[test_case("0f6b39e2_2a7c_4c1e_9b1d_8f4c2e6a7d31", "https://dc.services.visualstudio.com/v2/track", Err(ConfigError::InvalidInstrumentationKey("0f6b39e2_2a7c_4c1e_9b1d_8f4c2e6a7d31".into())) ; "no hyphens")]
+    #[test_case("0f6b39e2-2a7c-4c1e-9b1d-8f4c2e6a7d31", "unix:///var/run/appinsights.sock",             Ok(())                                                         ; "unix socket")]
+    #[test_case("0f6b39e2-2a7c-4c1e-9b1d-8f4c2e6a7d31", "dc.services.visualstudio.com/v2/track",         Err(ConfigError::InvalidEndpoint("dc.services.visualstudio.com/v2/track".into()))                  ; "relative endpoint")]
+    fn it_validates_config(i_key: &str, endpoint: &str, expected: Result<(), ConfigError>) {
+        let config = TelemetryConfig::builder().i_key(i_key).endpoint(endpoint).build();
+
+        assert_eq!(config.validate(), expected);
+    }
+
+    #[test_case("otlp://localhost:4318",              Some("http://localhost:4318")              ; "otlp scheme")]
+    #[test_case("otlps://collector.internal/otlp",     Some("https://collector.internal/otlp")    ; "otlps scheme")]
+    #[test_case("http://localhost:8080/v2/track",      None                                       ; "track endpoint")]
+    fn it_selects_transport_by_endpoint_scheme(endpoint: &str, expected: Option<&str>) {
+        let config = TelemetryConfig::builder().i_key("key").endpoint(endpoint).build();
+
+        assert_eq!(config.otlp_endpoint(), expected);
+    }
+
     #[test]
     fn it_creates_config_with_default_values() {
         let config = TelemetryConfig::new("instrumentation key".into());
@@ -132,7 +914,34 @@ mod tests {
             TelemetryConfig {
                 i_key: "instrumentation key".into(),
                 endpoint: "https://dc.services.visualstudio.com/v2/track".into(),
-                interval: Duration::from_secs(2)
+                interval: Duration::from_secs(2),
+                max_batch_items: None,
+                max_batch_bytes: None,
+                adaptive_batching: None,
+                max_payload_bytes: 64 * 1024 * 1024,
+                max_item_bytes: None,
+                daily_quota: None,
+                max_concurrent_requests: 1,
+                max_bytes_per_second: None,
+                max_pending_items: None,
+                max_pending_bytes: None,
+                aggregation_interval: Duration::from_secs(60),
+                max_metric_series: 1000,
+                metric_percentiles: Vec::new(),
+                user_data: UserDataPolicy::default(),
+                tee: None,
+                standard_metrics: false,
+                aggregate_events: false,
+                thread_metadata: false,
+                azure_vm_metadata: false,
+                sdk_version_prefix: None,
+                otlp_endpoint: None,
+                time_ordered_ids: false,
+                retry_policy: None,
+                diagnostics: Diagnostics::default(),
+                transport: None,
+                #[cfg(feature = "test-util")]
+                testing: Hooks::default(),
             },
             config
         )
@@ -144,13 +953,61 @@ mod tests {
             .i_key("instrumentation key")
             .endpoint("https://google.com")
             .interval(Duration::from_micros(100))
+            .max_batch_items(100)
+            .max_batch_bytes(1024)
+            .adaptive_batching(Duration::from_secs(30))
+            .max_payload_bytes(4096)
+            .max_item_bytes(1024)
+            .daily_quota(Quota::Items(100_000))
+            .max_concurrent_requests(4)
+            .max_bytes_per_second(1024 * 1024)
+            .max_pending_items(10000)
+            .max_pending_bytes(1024 * 1024)
+            .aggregation_interval(Duration::from_secs(10))
+            .max_metric_series(10)
+            .metric_percentiles([50.0, 99.0])
+            .user_data(UserDataPolicy::no_user_data())
+            .standard_metrics(true)
+            .aggregate_events(true)
+            .thread_metadata(true)
+            .azure_vm_metadata(true)
+            .sdk_version_prefix("wrapper_")
+            .otlp_endpoint("http://localhost:4318")
+            .time_ordered_ids(true)
             .build();
 
         assert_eq!(
             TelemetryConfig {
                 i_key: "instrumentation key".into(),
                 endpoint: "https://google.com".into(),
-                interval: Duration::from_micros(100)
+                interval: Duration::from_micros(100),
+                max_batch_items: Some(100),
+                max_batch_bytes: Some(1024),
+                adaptive_batching: Some(Duration::from_secs(30)),
+                max_payload_bytes: 4096,
+                max_item_bytes: Some(1024),
+                daily_quota: Some(Quota::Items(100_000)),
+                max_concurrent_requests: 4,
+                max_bytes_per_second: Some(1024 * 1024),
+                max_pending_items: Some(10000),
+                max_pending_bytes: Some(1024 * 1024),
+                aggregation_interval: Duration::from_secs(10),
+                max_metric_series: 10,
+                metric_percentiles: vec![50.0, 99.0],
+                user_data: UserDataPolicy::no_user_data(),
+                tee: None,
+                standard_metrics: true,
+                aggregate_events: true,
+                thread_metadata: true,
+                azure_vm_metadata: true,
+                sdk_version_prefix: Some("wrapper_".into()),
+                otlp_endpoint: Some("http://localhost:4318".into()),
+                time_ordered_ids: true,
+                retry_policy: None,
+                diagnostics: Diagnostics::default(),
+                transport: None,
+                #[cfg(feature = "test-util")]
+                testing: Hooks::default(),
             },
             config
         );
@@ -1,8 +1,13 @@
 use crate::{
+    correlation::Baggage,
     telemetry::{ContextTags, Properties},
+    uuid::IdStrategy,
     TelemetryConfig,
 };
 
+/// Name of a standard property a tenant identifier of a context is attached to telemetry items with.
+pub const TENANT_PROPERTY: &str = "tenant.id";
+
 /// Encapsulates contextual data common to all telemetry submitted through a telemetry client.
 /// # Examples
 /// ```rust
@@ -30,6 +35,12 @@ pub struct TelemetryContext {
 
     // A collection of common properties to attach to telemetry event.
     pub(crate) properties: Properties,
+
+    // Values propagated with the operation and attached to telemetry event as properties.
+    pub(crate) baggage: Baggage,
+
+    // A strategy ids of operations and requests tracked with this context are generated with.
+    pub(crate) ids: IdStrategy,
 }
 
 impl TelemetryContext {
@@ -37,7 +48,11 @@ impl TelemetryContext {
     pub fn from_config(config: &TelemetryConfig) -> Self {
         let i_key = config.i_key().into();
 
-        let sdk_version = format!("rust:{}", env!("CARGO_PKG_VERSION"));
+        let sdk_version = format!(
+            "{}rust:{}",
+            config.sdk_version_prefix().unwrap_or_default(),
+            env!("CARGO_PKG_VERSION")
+        );
         let os_version = if cfg!(target_os = "linux") {
             "linux"
         } else if cfg!(target_os = "windows") {
@@ -58,7 +73,9 @@ impl TelemetryContext {
         }
 
         let properties = Properties::default();
-        Self::new(i_key, tags, properties)
+        let mut context = Self::new(i_key, tags, properties);
+        context.ids = IdStrategy::new(config);
+        context
     }
 
     /// Creates a new instance of telemetry context.
@@ -67,6 +84,8 @@ impl TelemetryContext {
             i_key,
             tags,
             properties,
+            baggage: Baggage::default(),
+            ids: IdStrategy::default(),
         }
     }
 
@@ -89,6 +108,80 @@ impl TelemetryContext {
     pub fn tags(&self) -> &ContextTags {
         &self.tags
     }
+
+    /// Returns mutable reference to values propagated with the operation. Every telemetry item tracked
+    /// with this context carries them as properties, unless the item has properties with the same names.
+    /// Write them to outgoing requests with
+    /// [`correlation::inject_baggage`](correlation/fn.inject_baggage.html), so called components
+    /// attach them to their telemetry as well.
+    ///
+    /// ```rust
+    /// # use appinsights::{correlation, TelemetryContext};
+    /// # use appinsights::telemetry::{ContextTags, Properties};
+    /// let mut context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+    /// context.baggage_mut().insert("experiment".into(), "new-checkout".into());
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// correlation::inject_baggage(context.baggage(), &mut headers);
+    /// assert_eq!(headers["baggage"], "experiment=new-checkout");
+    /// ```
+    pub fn baggage_mut(&mut self) -> &mut Baggage {
+        &mut self.baggage
+    }
+
+    /// Returns immutable reference to values propagated with the operation.
+    pub fn baggage(&self) -> &Baggage {
+        &self.baggage
+    }
+
+    /// Sets an identifier of a tenant or a customer all telemetry tracked with this context belongs to. It
+    /// is attached to every item as a [`TENANT_PROPERTY`](constant.TENANT_PROPERTY.html) property, so
+    /// items can be filtered by tenant or routed to tenant-specific instrumentation keys with
+    /// [`TenantRouter`](processor/struct.TenantRouter.html).
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryContext;
+    /// # use appinsights::telemetry::{ContextTags, Properties};
+    /// let mut context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+    /// context.set_tenant("contoso");
+    ///
+    /// assert_eq!(context.tenant(), Some("contoso"));
+    /// assert_eq!(context.properties().get("tenant.id"), Some(&"contoso".to_string()));
+    /// ```
+    pub fn set_tenant(&mut self, tenant: impl Into<String>) {
+        self.properties.insert(TENANT_PROPERTY.into(), tenant.into());
+    }
+
+    /// Returns an identifier of a tenant telemetry tracked with this context belongs to.
+    pub fn tenant(&self) -> Option<&str> {
+        self.properties.get(TENANT_PROPERTY).map(String::as_str)
+    }
+
+    /// Creates a context of a child operation that inherits all tags and properties of this one. Items
+    /// tracked with the child share an operation id with this context, which is generated if it is not
+    /// set yet, and refer to a new parent id. Track a request or a dependency telemetry with that id
+    /// under this context to link the child operation to its parent.
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryContext;
+    /// # use appinsights::telemetry::{ContextTags, Properties};
+    /// let mut context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+    /// context.properties_mut().insert("tenant".into(), "contoso".into());
+    ///
+    /// let child = context.child();
+    /// assert_eq!(child.properties().get("tenant"), Some(&"contoso".to_string()));
+    /// assert!(child.tags().operation().id().is_some());
+    /// assert!(child.tags().operation().parent_id().is_some());
+    /// ```
+    pub fn child(&self) -> Self {
+        let mut child = self.clone();
+        let mut operation = child.tags.operation_mut();
+        if self.tags.operation().id().is_none() {
+            operation.set_id(self.ids.new_id().simple().to_string());
+        }
+        operation.set_parent_id(self.ids.new_request_id());
+        child
+    }
 }
 
 #[cfg(test)]
@@ -120,4 +213,39 @@ mod tests {
         assert_matches!(&context.tags().cloud().role_instance(), Some(_));
         assert!(context.properties().is_empty());
     }
+
+    #[test]
+    fn it_derives_child_context_in_same_operation() {
+        let config = TelemetryConfig::new("instrumentation".into());
+        let mut context = TelemetryContext::from_config(&config);
+        context.tags_mut().operation_mut().set_id("operation".into());
+        context.tags_mut().operation_mut().set_parent_id("parent".into());
+        context.properties_mut().insert("Resource Group".into(), "my-rg".into());
+
+        let child = context.child();
+
+        assert_eq!(child.tags().operation().id(), Some("operation"));
+        assert_matches!(child.tags().operation().parent_id(), Some(id) if id != "parent" && id.len() == 16);
+        assert_eq!(
+            child.tags().cloud().role_instance(),
+            context.tags().cloud().role_instance()
+        );
+        assert_eq!(child.properties().get("Resource Group"), Some(&"my-rg".to_string()));
+        assert_eq!(context.tags().operation().parent_id(), Some("parent"));
+    }
+
+    #[test]
+    fn it_prepends_sdk_version_with_prefix() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .sdk_version_prefix("myframework_")
+            .build();
+
+        let context = TelemetryContext::from_config(&config);
+
+        assert_eq!(
+            context.tags().internal().sdk_version(),
+            Some(format!("myframework_rust:{}", env!("CARGO_PKG_VERSION")).as_str())
+        );
+    }
 }
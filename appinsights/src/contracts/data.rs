@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Data struct to contain both B and C sections.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 #[serde(tag = "baseType", content = "baseData")]
 pub enum Data {
     AvailabilityData(AvailabilityData),
@@ -13,6 +14,7 @@ pub enum Data {
     MessageData(MessageData),
     MetricData(MetricData),
     PageViewData(PageViewData),
+    PageViewPerfData(PageViewPerfData),
     RemoteDependencyData(RemoteDependencyData),
     RequestData(RequestData),
 }
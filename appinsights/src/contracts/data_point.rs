@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Metric data single measurement.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct DataPoint {
     pub ns: Option<String>,
@@ -20,14 +21,14 @@ pub struct DataPoint {
 impl Default for DataPoint {
     fn default() -> Self {
         Self {
-            ns: None,
+            ns: Option::default(),
             name: String::default(),
             kind: Some(DataPointType::Measurement),
             value: f64::default(),
-            count: None,
-            min: None,
-            max: None,
-            std_dev: None,
+            count: Option::default(),
+            min: Option::default(),
+            max: Option::default(),
+            std_dev: Option::default(),
         }
     }
 }
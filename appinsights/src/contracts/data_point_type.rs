@@ -1,11 +1,28 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Type of the metric data measurement.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DataPointType {
     Measurement,
     Aggregation,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::to_string;
+
+    use super::*;
+
+    #[test]
+    fn it_json_serializes_valid_constants() {
+        // The JSON-serialized values must match the value of `constantName` in
+        // `schema/DataPointType.json`.
+        //
+        // Regression test for appinsights-rs#18.
+        assert_eq!(to_string(&DataPointType::Measurement).unwrap(), r#""Measurement""#);
+        assert_eq!(to_string(&DataPointType::Aggregation).unwrap(), r#""Aggregation""#);
+    }
+}
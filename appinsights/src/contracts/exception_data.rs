@@ -1,14 +1,15 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// An instance of Exception represents a handled or unhandled exception that occurred during execution of the monitored application.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct ExceptionData {
     pub ver: i32,
-    pub exceptions: ExceptionDetails,
+    pub exceptions: Vec<ExceptionDetails>,
     pub severity_level: Option<SeverityLevel>,
     pub problem_id: Option<String>,
     pub properties: Option<std::collections::BTreeMap<String, String>>,
@@ -19,7 +20,7 @@ impl Default for ExceptionData {
     fn default() -> Self {
         Self {
             ver: 2,
-            exceptions: ExceptionDetails::default(),
+            exceptions: Vec::default(),
             severity_level: Option::default(),
             problem_id: Option::default(),
             properties: Option::default(),
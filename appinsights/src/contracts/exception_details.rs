@@ -1,19 +1,20 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Exception details of the exception in a chain.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct ExceptionDetails {
-    id: Option<i32>,
-    outer_id: Option<i32>,
-    type_name: String,
-    message: String,
-    has_full_stack: Option<bool>,
-    stack: Option<String>,
-    parsed_stack: Option<StackFrame>,
+    pub id: Option<i32>,
+    pub outer_id: Option<i32>,
+    pub type_name: String,
+    pub message: String,
+    pub has_full_stack: Option<bool>,
+    pub stack: Option<String>,
+    pub parsed_stack: Option<Vec<StackFrame>>,
 }
 
 impl Default for ExceptionDetails {
@@ -0,0 +1,95 @@
+use serde_json::{Deserializer, Result};
+
+use crate::contracts::Envelope;
+
+impl Envelope {
+    /// Serializes a telemetry item into JSON of the wire format the ingestion endpoint accepts.
+    ///
+    /// ```rust
+    /// # use appinsights::contracts::Envelope;
+    /// let envelope = Envelope { name: "Microsoft.ApplicationInsights.Event".into(), ..Envelope::default() };
+    ///
+    /// let json = envelope.to_json()?;
+    /// assert_eq!(Envelope::from_json(&json)?, envelope);
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Deserializes a telemetry item from JSON of the wire format. Missing fields take default values.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Serializes a batch of telemetry items into a JSON array, the body of a request to the track
+    /// endpoint.
+    pub fn batch_to_json(envelopes: &[Envelope]) -> Result<String> {
+        serde_json::to_string(envelopes)
+    }
+
+    /// Serializes a batch of telemetry items into newline-delimited JSON with one item per line, the
+    /// format of [`Tee::ndjson`](../tee/struct.Tee.html#method.ndjson) files.
+    pub fn batch_to_ndjson(envelopes: &[Envelope]) -> Result<String> {
+        let mut lines = String::new();
+        for envelope in envelopes {
+            lines.push_str(&envelope.to_json()?);
+            lines.push('\n');
+        }
+        Ok(lines)
+    }
+
+    /// Deserializes a batch of telemetry items from either a JSON array or newline-delimited JSON.
+    ///
+    /// ```rust
+    /// # use appinsights::contracts::Envelope;
+    /// let envelopes = vec![Envelope::default(), Envelope::default()];
+    ///
+    /// assert_eq!(Envelope::batch_from_json(&Envelope::batch_to_json(&envelopes)?)?, envelopes);
+    /// assert_eq!(Envelope::batch_from_json(&Envelope::batch_to_ndjson(&envelopes)?)?, envelopes);
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    pub fn batch_from_json(json: &str) -> Result<Vec<Self>> {
+        if json.trim_start().starts_with('[') {
+            serde_json::from_str(json)
+        } else {
+            Deserializer::from_str(json).into_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_round_trips_telemetry_item() {
+        let mut event = EventTelemetry::new("order placed");
+        event.properties_mut().insert("tenant".into(), "contoso".into());
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let envelope = Envelope::from((context, event));
+
+        assert_eq!(Envelope::from_json(&envelope.to_json().unwrap()).unwrap(), envelope);
+    }
+
+    #[test_case("[{\"name\":\"first\"},{\"name\":\"second\"}]", 2 ; "array")]
+    #[test_case("{\"name\":\"first\"}\n{\"name\":\"second\"}\n",  2 ; "ndjson")]
+    #[test_case("  \n",                                            0 ; "empty")]
+    fn it_parses_batch(json: &str, expected: usize) {
+        let envelopes = Envelope::batch_from_json(json).unwrap();
+
+        assert_eq!(envelopes.len(), expected);
+        assert!(envelopes.iter().all(|envelope| envelope.ver == Some(1)));
+    }
+
+    #[test]
+    fn it_rejects_malformed_batch() {
+        assert!(Envelope::batch_from_json("{\"name\":\"first\"}\n{\"name\":").is_err());
+    }
+}
@@ -1,3 +1,7 @@
+//! Data contracts of telemetry items in the format Application Insights ingestion endpoint accepts.
+//! [`Envelope`](struct.Envelope.html) is a final representation of a telemetry item, which
+//! [`processors`](../processor/index.html) can inspect and modify before it is sent.
+
 // NOTE: This file was automatically generated.
 
 #![allow(unused_imports)]
@@ -11,9 +15,11 @@ mod envelope;
 mod event_data;
 mod exception_data;
 mod exception_details;
+mod json;
 mod message_data;
 mod metric_data;
 mod page_view_data;
+mod page_view_perf_data;
 mod remote_dependency_data;
 mod request_data;
 mod response;
@@ -29,9 +35,11 @@ pub use envelope::*;
 pub use event_data::*;
 pub use exception_data::*;
 pub use exception_details::*;
+pub use json::*;
 pub use message_data::*;
 pub use metric_data::*;
 pub use page_view_data::*;
+pub use page_view_perf_data::*;
 pub use remote_dependency_data::*;
 pub use request_data::*;
 pub use response::*;
@@ -0,0 +1,44 @@
+use crate::contracts::*;
+use serde::{Deserialize, Serialize};
+
+// NOTE: This file was automatically generated.
+
+/// An instance of PageViewPerf represents: a page view with no performance data, a page view with performance data, or just the performance data of an earlier page request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "camelCase")]
+pub struct PageViewPerfData {
+    pub ver: i32,
+    pub name: String,
+    pub url: Option<String>,
+    pub duration: Option<String>,
+    pub referrer_uri: Option<String>,
+    pub id: String,
+    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub measurements: Option<std::collections::BTreeMap<String, f64>>,
+    pub perf_total: Option<String>,
+    pub network_connect: Option<String>,
+    pub sent_request: Option<String>,
+    pub received_response: Option<String>,
+    pub dom_processing: Option<String>,
+}
+
+impl Default for PageViewPerfData {
+    fn default() -> Self {
+        Self {
+            ver: 2,
+            name: String::default(),
+            url: Option::default(),
+            duration: Option::default(),
+            referrer_uri: Option::default(),
+            id: String::default(),
+            properties: Option::default(),
+            measurements: Option::default(),
+            perf_total: Option::default(),
+            network_connect: Option::default(),
+            sent_request: Option::default(),
+            received_response: Option::default(),
+            dom_processing: Option::default(),
+        }
+    }
+}
@@ -1,27 +1,16 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Stack frame information.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct StackFrame {
-    level: i32,
-    method: String,
-    assembly: Option<String>,
-    file_name: Option<String>,
-    line: Option<i32>,
-}
-
-impl Default for StackFrame {
-    fn default() -> Self {
-        Self {
-            level: i32::default(),
-            method: String::default(),
-            assembly: Option::default(),
-            file_name: Option::default(),
-            line: Option::default(),
-        }
-    }
+    pub level: i32,
+    pub method: String,
+    pub assembly: Option<String>,
+    pub file_name: Option<String>,
+    pub line: Option<i32>,
 }
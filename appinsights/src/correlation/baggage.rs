@@ -0,0 +1,206 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    correlation::{Extractor, Injector},
+    telemetry::Properties,
+};
+
+/// Name of W3C Baggage header that carries application-defined values of an operation.
+pub const BAGGAGE_HEADER: &str = "baggage";
+
+/// Name of legacy header that carries application-defined values of an operation.
+pub const CORRELATION_CONTEXT_HEADER: &str = "Correlation-Context";
+
+/// Application-defined values propagated with a distributed operation, e.g. a tenant id or experiment
+/// flags. Components exchange them with W3C `baggage` header and legacy `Correlation-Context` one, and
+/// every telemetry item of the operation carries them as properties.
+///
+/// ```rust
+/// # use appinsights::correlation::Baggage;
+/// let mut baggage = Baggage::parse("tenant=contoso, experiment=new%20checkout;ttl=60");
+/// assert_eq!(baggage["experiment"], "new checkout");
+///
+/// baggage.insert("region".into(), "westeurope".into());
+/// assert_eq!(baggage.header(), "experiment=new%20checkout,region=westeurope,tenant=contoso");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baggage(BTreeMap<String, String>);
+
+impl Baggage {
+    /// Parses a `baggage` or a `Correlation-Context` header value. Malformed members and metadata of
+    /// members are ignored.
+    pub fn parse(header: &str) -> Self {
+        let members = header
+            .split(',')
+            .filter_map(|member| {
+                let (key, value) = member.split(';').next()?.split_once('=')?;
+                let key = key.trim();
+                if key.is_empty() || !key.bytes().all(is_token) {
+                    return None;
+                }
+                Some((key.to_string(), decode(value.trim())))
+            })
+            .collect();
+        Self(members)
+    }
+
+    /// Returns a `baggage` header value.
+    pub fn header(&self) -> String {
+        let mut header = String::new();
+        for (key, value) in &self.0 {
+            if !header.is_empty() {
+                header.push(',');
+            }
+            header.push_str(key);
+            header.push('=');
+            encode(value, &mut header);
+        }
+        header
+    }
+
+    /// Adds values to properties of a telemetry item unless the item already has properties with the same
+    /// names.
+    pub(crate) fn stamp(&self, properties: &mut Properties) {
+        for (key, value) in &self.0 {
+            properties.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+impl Deref for Baggage {
+    type Target = BTreeMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Baggage {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Writes `baggage` and `Correlation-Context` headers of specified values to a carrier. Nothing is written
+/// when there are no values.
+///
+/// ```rust
+/// # use appinsights::correlation::{self, Baggage};
+/// let baggage = Baggage::parse("tenant=contoso");
+///
+/// let mut headers = http::HeaderMap::new();
+/// correlation::inject_baggage(&baggage, &mut headers);
+///
+/// assert_eq!(headers["baggage"], "tenant=contoso");
+/// assert_eq!(headers["correlation-context"], "tenant=contoso");
+/// ```
+pub fn inject_baggage(baggage: &Baggage, carrier: &mut impl Injector) {
+    if baggage.is_empty() {
+        return;
+    }
+    let header = baggage.header();
+    carrier.set(BAGGAGE_HEADER, header.clone());
+    carrier.set(CORRELATION_CONTEXT_HEADER, header);
+}
+
+/// Reads values from a carrier. Values of a `baggage` header take precedence over ones of a legacy
+/// `Correlation-Context` header with the same names.
+pub fn extract_baggage(carrier: &impl Extractor) -> Baggage {
+    let mut baggage = carrier
+        .get(CORRELATION_CONTEXT_HEADER)
+        .map(Baggage::parse)
+        .unwrap_or_default();
+    if let Some(header) = carrier.get(BAGGAGE_HEADER) {
+        baggage.extend(Baggage::parse(header).0);
+    }
+    baggage
+}
+
+/// Returns `true` if a byte is allowed in a token, i.e. a name of a member.
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Percent-encodes a value, keeping characters allowed in a value of a member as is.
+fn encode(value: &str, out: &mut String) {
+    for b in value.bytes() {
+        if b.is_ascii_graphic() && !b"\",;\\%".contains(&b) {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+}
+
+/// Decodes a percent-encoded value.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = match bytes[i] {
+            b'%' => value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderMap;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("tenant=contoso",                  &[("tenant", "contoso")]                ; "single")]
+    #[test_case(" a = 1 , b=2;ttl=60",             &[("a", "1"), ("b", "2")]               ; "whitespace and metadata")]
+    #[test_case("flag=on%2Coff,city=K%C3%B6ln",    &[("city", "Köln"), ("flag", "on,off")] ; "percent encoded")]
+    #[test_case("malformed,=empty,bad key=1,ok=1", &[("ok", "1")]                          ; "malformed")]
+    fn it_parses_header(header: &str, expected: &[(&str, &str)]) {
+        let baggage = Baggage::parse(header);
+
+        let members: Vec<_> = baggage
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(members, expected);
+    }
+
+    #[test]
+    fn it_round_trips_encoded_values() {
+        let mut baggage = Baggage::default();
+        baggage.insert("experiment".into(), "new checkout, 50%; \"beta\"".into());
+
+        let header = baggage.header();
+
+        assert_eq!(header, "experiment=new%20checkout%2C%2050%25%3B%20%22beta%22");
+        assert_eq!(Baggage::parse(&header), baggage);
+    }
+
+    #[test]
+    fn it_extracts_baggage_before_correlation_context() {
+        let mut headers = HeaderMap::new();
+        headers.insert("correlation-context", "tenant=fabrikam,legacy=1".parse().unwrap());
+        headers.insert("baggage", "tenant=contoso".parse().unwrap());
+
+        let baggage = extract_baggage(&headers);
+
+        assert_eq!(baggage["tenant"], "contoso");
+        assert_eq!(baggage["legacy"], "1");
+    }
+}
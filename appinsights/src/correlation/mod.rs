@@ -0,0 +1,361 @@
+//! Module for cross-component correlation.
+//!
+//! Application Map draws an edge between two separately instrumented components when a dependency
+//! call of one component and a request of another one refer to each other by _application id_.
+//! Components exchange their application ids with `Request-Context` HTTP header:
+//! * a caller sends its application id in a request header, so a callee can set
+//!   [`source`](../telemetry/struct.RequestTelemetry.html#method.set_source) of a request telemetry;
+//! * a callee returns its application id in a response header, so a caller can attach it to the
+//!   [`target`](../telemetry/struct.RemoteDependencyTelemetry.html#method.set_target_app_id) of a
+//!   dependency telemetry.
+//!
+//! An application id of the component is looked up by instrumentation key with
+//! [`TelemetryClient::app_id`](../struct.TelemetryClient.html#method.app_id).
+//!
+//! ```rust, no_run
+//! use appinsights::{correlation, telemetry::RemoteDependencyTelemetry, TelemetryClient};
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let mut request = reqwest::Client::new().get("https://api.example.com/orders");
+//! if let Some(app_id) = client.app_id().await {
+//!     request = request.header(correlation::REQUEST_CONTEXT_HEADER, correlation::request_context(&app_id));
+//! }
+//! let response = request.send().await.unwrap();
+//!
+//! let mut dependency = RemoteDependencyTelemetry::new(
+//!     "GET /orders",
+//!     "Http",
+//!     Duration::from_millis(42),
+//!     "api.example.com",
+//!     response.status().is_success(),
+//! );
+//! let header = response.headers().get(correlation::REQUEST_CONTEXT_HEADER);
+//! if let Some(app_id) = header.and_then(|value| value.to_str().ok()).and_then(correlation::app_id_from_request_context) {
+//!     dependency.set_target_app_id(app_id);
+//! }
+//! client.track(dependency);
+//! # }
+//! ```
+//!
+//! Telemetry items of a single distributed operation are correlated by [`TraceContext`](struct.TraceContext.html)
+//! which components exchange with W3C `traceparent` and `tracestate` headers and legacy `Request-Id`
+//! header. A caller [`inject`](fn.inject.html)s a context into an outgoing request and a callee
+//! [`extract`](fn.extract.html)s it from an incoming one. Entries other tracing vendors added to
+//! `tracestate` are passed on, with an `az` entry of the component in front.
+//!
+//! Contexts are written to and read from any [`Injector`](trait.Injector.html) and
+//! [`Extractor`](trait.Extractor.html) carrier. `http::HeaderMap`, `HashMap<String, String>` and
+//! `BTreeMap<String, String>` are carriers, so are closures that write headers. gRPC metadata of
+//! [`tonic`](https://docs.rs/tonic) converts to and from a `HeaderMap`:
+//!
+//! ```rust, ignore
+//! use appinsights::correlation::{self, TraceContext};
+//! use tonic::metadata::MetadataMap;
+//!
+//! # fn run(context: TraceContext, mut request: tonic::Request<()>) {
+//! // outgoing call
+//! let mut headers = std::mem::take(request.metadata_mut()).into_headers();
+//! correlation::inject(&context, &mut headers);
+//! *request.metadata_mut() = MetadataMap::from_headers(headers);
+//!
+//! // incoming call
+//! let parent = correlation::extract(&request.metadata().clone().into_headers());
+//! # }
+//! ```
+//!
+//! Application-defined values of an operation, like a tenant id or experiment flags, travel along in
+//! W3C `baggage` and legacy `Correlation-Context` headers as [`Baggage`](struct.Baggage.html). Values set
+//! with [`TelemetryContext::baggage_mut`](../struct.TelemetryContext.html#method.baggage_mut) are
+//! attached as properties to all telemetry tracked with the context, and are written to outgoing
+//! requests with [`inject_baggage`](fn.inject_baggage.html).
+mod baggage;
+mod trace_context;
+
+pub use baggage::{extract_baggage, inject_baggage, Baggage, BAGGAGE_HEADER, CORRELATION_CONTEXT_HEADER};
+pub use trace_context::{
+    extract, inject, Extractor, Injector, TraceContext, TraceState, AZ_VENDOR_KEY, REQUEST_ID_HEADER,
+    TRACEPARENT_HEADER, TRACESTATE_HEADER,
+};
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "net")]
+use http::StatusCode;
+use http::Uri;
+use log::debug;
+#[cfg(feature = "net")]
+use reqwest::Client;
+use tokio::sync::OnceCell;
+
+use crate::{ProbeError, TelemetryConfig};
+
+/// Name of HTTP header components use to exchange their application ids.
+pub const REQUEST_CONTEXT_HEADER: &str = "Request-Context";
+
+/// Prefix of an application id value Application Insights uses to correlate components.
+pub const APP_ID_PREFIX: &str = "cid-v1:";
+
+/// Returns a `Request-Context` header value that contains specified application id.
+///
+/// ```rust
+/// # use appinsights::correlation;
+/// assert_eq!(correlation::request_context("cid-v1:1234"), "appId=cid-v1:1234");
+/// ```
+pub fn request_context(app_id: &str) -> String {
+    format!("appId={}", app_id)
+}
+
+/// Extracts an application id from a `Request-Context` header value.
+/// Returns `None` if the header value does not contain an application id.
+///
+/// ```rust
+/// # use appinsights::correlation;
+/// let app_id = correlation::app_id_from_request_context("appId=cid-v1:1234, roleName=frontend");
+/// assert_eq!(app_id, Some("cid-v1:1234"));
+/// ```
+pub fn app_id_from_request_context(value: &str) -> Option<&str> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("appId"))
+        .map(|(_, value)| value.trim())
+        .filter(|value| value.starts_with(APP_ID_PREFIX) && value.len() > APP_ID_PREFIX.len())
+}
+
+/// Minimum interval between background lookups of an application id.
+const LOOKUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Looks up an application id of the component by instrumentation key. A successfully retrieved
+/// application id is cached, so the profile endpoint is called at most once; failed lookups are
+/// retried next time.
+pub(crate) struct AppIdProvider {
+    url: Option<String>,
+    #[cfg(feature = "net")]
+    client: Client,
+    app_id: OnceCell<String>,
+    last_lookup: Mutex<Option<Instant>>,
+}
+
+impl AppIdProvider {
+    /// Creates a new application id provider for the instrumentation key and endpoint of specified config.
+    pub fn new(config: &TelemetryConfig) -> Self {
+        Self {
+            url: profile_url(config),
+            #[cfg(feature = "net")]
+            client: Client::new(),
+            app_id: OnceCell::new(),
+            last_lookup: Mutex::default(),
+        }
+    }
+
+    /// Returns an application id if it has already been retrieved.
+    pub fn cached(&self) -> Option<String> {
+        self.app_id.get().cloned()
+    }
+
+    /// Determines whether a background lookup should start now. Lookups start at most once a minute, so
+    /// an unavailable profile endpoint is not called for every request served.
+    pub fn start_lookup(&self) -> bool {
+        let mut last_lookup = self.last_lookup.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.url.is_none() || last_lookup.is_some_and(|last| last.elapsed() < LOOKUP_INTERVAL) {
+            return false;
+        }
+        *last_lookup = Some(Instant::now());
+        true
+    }
+
+    /// Returns an application id prefixed with `cid-v1:` or `None` if the lookup failed.
+    pub async fn app_id(&self) -> Option<String> {
+        match self.probe().await {
+            Ok(app_id) => Some(app_id),
+            Err(err) => {
+                debug!("Unable to retrieve application id: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Returns an application id prefixed with `cid-v1:` or an error that tells apart an instrumentation
+    /// key unknown to the profile endpoint from an unavailable endpoint.
+    pub async fn probe(&self) -> Result<String, ProbeError> {
+        let url = self
+            .url
+            .as_ref()
+            .ok_or_else(|| ProbeError::Unavailable("endpoint is not a valid URL".into()))?;
+
+        self.app_id.get_or_try_init(|| self.fetch(url)).await.cloned()
+    }
+
+    #[cfg(not(feature = "net"))]
+    async fn fetch(&self, _url: &str) -> Result<String, ProbeError> {
+        Err(ProbeError::Unavailable(
+            "built-in HTTP client requires `net` feature".into(),
+        ))
+    }
+
+    #[cfg(feature = "net")]
+    async fn fetch(&self, url: &str) -> Result<String, ProbeError> {
+        let unavailable = |err: reqwest::Error| ProbeError::Unavailable(err.to_string());
+        let response = self.client.get(url).send().await.map_err(unavailable)?;
+        match response.status() {
+            StatusCode::OK => {}
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => return Err(ProbeError::UnknownInstrumentationKey),
+            status => {
+                return Err(ProbeError::Unavailable(format!(
+                    "profile endpoint responded with {}",
+                    status
+                )))
+            }
+        }
+
+        let app_id = response.text().await.map_err(unavailable)?;
+        let app_id = app_id.trim();
+        if app_id.is_empty() {
+            return Err(ProbeError::Unavailable(
+                "profile endpoint responded with empty application id".into(),
+            ));
+        }
+
+        debug!("Retrieved application id {}", app_id);
+        Ok(format!("{}{}", APP_ID_PREFIX, app_id))
+    }
+}
+
+/// Builds a profile endpoint URL for the same host telemetry is sent to, e.g.
+/// `https://dc.services.visualstudio.com/api/profiles/<instrumentation key>/appId`.
+fn profile_url(config: &TelemetryConfig) -> Option<String> {
+    let endpoint: Uri = config.endpoint().parse().ok()?;
+    Some(format!(
+        "{}://{}/api/profiles/{}/appId",
+        endpoint.scheme_str()?,
+        endpoint.authority()?,
+        config.i_key()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use http::Request;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Server,
+    };
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("appId=cid-v1:1234",                    Some("cid-v1:1234") ; "app id only")]
+    #[test_case("roleName=frontend, appId = cid-v1:1234", Some("cid-v1:1234") ; "app id with other values")]
+    #[test_case("appid=cid-v1:1234",                    Some("cid-v1:1234") ; "case insensitive key")]
+    #[test_case("appId=1234",                           None                ; "without prefix")]
+    #[test_case("appId=cid-v1:",                        None                ; "empty app id")]
+    #[test_case("roleName=frontend",                    None                ; "no app id")]
+    fn it_extracts_app_id_from_request_context(value: &str, expected: Option<&str>) {
+        assert_eq!(app_id_from_request_context(value), expected);
+    }
+
+    #[test]
+    fn it_builds_profile_url_from_endpoint() {
+        let config = TelemetryConfig::new("instrumentation".into());
+
+        assert_eq!(
+            profile_url(&config),
+            Some("https://dc.services.visualstudio.com/api/profiles/instrumentation/appId".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_retrieves_and_caches_app_id() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = create_server(StatusCode::OK, requests.clone());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(format!("{}/v2/track", url))
+            .build();
+        let provider = AppIdProvider::new(&config);
+
+        assert_eq!(provider.app_id().await, Some("cid-v1:1234".into()));
+        assert_eq!(provider.app_id().await, Some("cid-v1:1234".into()));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn it_retries_failed_app_id_lookup() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = create_server(StatusCode::NOT_FOUND, requests.clone());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(format!("{}/v2/track", url))
+            .build();
+        let provider = AppIdProvider::new(&config);
+
+        assert_eq!(provider.app_id().await, None);
+        assert_eq!(provider.app_id().await, None);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test_case(StatusCode::OK,                    Ok("cid-v1:1234".into())                         ; "known key")]
+    #[test_case(StatusCode::NOT_FOUND,             Err(ProbeError::UnknownInstrumentationKey)       ; "unknown key")]
+    #[test_case(StatusCode::INTERNAL_SERVER_ERROR, Err(ProbeError::Unavailable(
+        "profile endpoint responded with 500 Internal Server Error".into()))                         ; "unavailable")]
+    #[tokio::test]
+    async fn it_probes_instrumentation_key(status_code: StatusCode, expected: Result<String, ProbeError>) {
+        let url = create_server(status_code, Arc::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(format!("{}/v2/track", url))
+            .build();
+
+        assert_eq!(AppIdProvider::new(&config).probe().await, expected);
+    }
+
+    #[tokio::test]
+    async fn it_starts_background_lookups_once_a_minute() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = create_server(StatusCode::OK, requests);
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(format!("{}/v2/track", url))
+            .build();
+        let provider = AppIdProvider::new(&config);
+
+        assert_eq!(provider.cached(), None);
+        assert!(provider.start_lookup());
+        assert!(!provider.start_lookup());
+
+        provider.app_id().await;
+        assert_eq!(provider.cached(), Some("cid-v1:1234".into()));
+    }
+
+    fn create_server(status_code: StatusCode, requests: Arc<AtomicUsize>) -> String {
+        let make_service = make_service_fn(move |_| {
+            let requests = requests.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    assert_eq!(request.uri().path(), "/api/profiles/instrumentation/appId");
+                    async move { hyper::Response::builder().status(status_code).body(Body::from("1234")) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+
+        tokio::spawn(server);
+
+        url
+    }
+}
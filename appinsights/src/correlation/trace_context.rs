@@ -0,0 +1,541 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use http::{header::HeaderName, HeaderMap, HeaderValue};
+
+use crate::{telemetry::Telemetry, uuid::IdStrategy};
+
+/// Name of W3C Trace Context header that carries a trace id and a parent span id.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Name of W3C Trace Context header that carries vendor-specific trace information.
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// A key of the `tracestate` entry Application Insights components add to outgoing requests.
+pub const AZ_VENDOR_KEY: &str = "az";
+
+/// A maximum number of `tracestate` entries.
+const MAX_ENTRIES: usize = 32;
+
+/// Name of legacy Application Insights header that carries a hierarchical request id.
+pub const REQUEST_ID_HEADER: &str = "Request-Id";
+
+/// Identifies a position of an operation in a distributed trace. A trace id becomes an operation id and
+/// a span id becomes an id of a request or a dependency telemetry item.
+///
+/// ```rust
+/// # use appinsights::correlation::TraceContext;
+/// let context = TraceContext::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+/// assert_eq!(context.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+/// assert_eq!(context.span_id(), "b7ad6b7169203331");
+///
+/// let child = context.child();
+/// assert_eq!(child.trace_id(), context.trace_id());
+/// assert_ne!(child.span_id(), context.span_id());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: String,
+    span_id: String,
+    sampled: bool,
+    trace_state: TraceState,
+}
+
+/// Vendor-specific trace information of W3C `tracestate` header. Entries other vendors added are passed
+/// on unchanged, so tracing chains of many vendors are not broken by a component in the middle. An
+/// updated entry moves to the front, as the specification requires.
+///
+/// ```rust
+/// # use appinsights::correlation::TraceState;
+/// let mut state = TraceState::parse("rojo=00f067aa0ba902b7, congo=t61rcWkgMzE");
+/// state.insert("az", "b7ad6b7169203331");
+///
+/// assert_eq!(state.get("rojo"), Some("00f067aa0ba902b7"));
+/// assert_eq!(state.header(), "az=b7ad6b7169203331,rojo=00f067aa0ba902b7,congo=t61rcWkgMzE");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceState(Vec<(String, String)>);
+
+impl TraceState {
+    /// Parses a `tracestate` header value. Malformed entries, duplicates of earlier keys and entries
+    /// above the limit of 32 are dropped.
+    pub fn parse(header: &str) -> Self {
+        let mut state = Self::default();
+        for entry in header.split(',') {
+            let entry = entry.trim();
+            if let Some((key, value)) = entry.split_once('=') {
+                if is_key(key) && is_value(value) && state.get(key).is_none() && state.0.len() < MAX_ENTRIES {
+                    state.0.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+        state
+    }
+
+    /// Returns a value of an entry with specified key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, value)| value.as_str())
+    }
+
+    /// Adds or updates an entry and moves it to the front. Entries with malformed keys or values are
+    /// ignored. The last entry is dropped when the limit of 32 entries is exceeded.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let (key, value) = (key.into(), value.into());
+        if !is_key(&key) || !is_value(&value) {
+            return;
+        }
+        self.remove(&key);
+        self.0.insert(0, (key, value));
+        self.0.truncate(MAX_ENTRIES);
+    }
+
+    /// Removes an entry with specified key.
+    pub fn remove(&mut self, key: &str) {
+        self.0.retain(|(k, _)| k != key);
+    }
+
+    /// Returns `true` if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns a `tracestate` header value.
+    pub fn header(&self) -> String {
+        let entries: Vec<_> = self.0.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        entries.join(",")
+    }
+}
+
+impl TraceContext {
+    /// Creates a new trace context that starts a new trace.
+    pub fn new() -> Self {
+        Self::generate(&IdStrategy::default())
+    }
+
+    /// Creates a context of an operation that continues a trace of a parent or starts a new trace if
+    /// there is no parent, with ids generated by a strategy of a client.
+    pub(crate) fn start(parent: Option<&Self>, ids: &IdStrategy) -> Self {
+        match parent {
+            Some(parent) => parent.child_with(ids),
+            None => Self::generate(ids),
+        }
+    }
+
+    /// Creates a new trace context that starts a new trace with ids generated by a strategy of a client.
+    fn generate(ids: &IdStrategy) -> Self {
+        Self {
+            trace_id: ids.new_id().simple().to_string(),
+            span_id: ids.new_span_id(),
+            sampled: true,
+            trace_state: TraceState::default(),
+        }
+    }
+
+    /// Parses a `traceparent` header value. Returns `None` if the value is malformed.
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        if trace_id.bytes().all(|b| b == b'0') || span_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_lowercase(),
+            span_id: span_id.to_lowercase(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+            trace_state: TraceState::default(),
+        })
+    }
+
+    /// Parses a legacy `Request-Id` header value, e.g. `|4bf92f3577b34da6a3ce929d0e0e4736.00f067aa0ba902b7.`
+    /// Returns `None` if the value does not contain a W3C compatible trace id.
+    pub fn parse_request_id(request_id: &str) -> Option<Self> {
+        let request_id = request_id.trim().trim_start_matches('|');
+        let (trace_id, rest) = request_id.split_once('.').unwrap_or((request_id, ""));
+        if !is_hex(trace_id, 32) {
+            return None;
+        }
+
+        let parent = rest.split('.').next().filter(|span_id| is_hex(span_id, 16));
+        Some(Self {
+            trace_id: trace_id.to_lowercase(),
+            span_id: parent.map_or_else(|| IdStrategy::default().new_span_id(), str::to_lowercase),
+            sampled: true,
+            trace_state: TraceState::default(),
+        })
+    }
+
+    /// Returns a trace id, which is an operation id in Application Insights terms.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Returns a span id, which is an id of a request or a dependency in Application Insights terms.
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// Returns `true` if a caller recorded this trace.
+    pub fn sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Returns vendor-specific trace information received from a caller.
+    pub fn trace_state(&self) -> &TraceState {
+        &self.trace_state
+    }
+
+    /// Returns mutable reference to vendor-specific trace information.
+    pub fn trace_state_mut(&mut self) -> &mut TraceState {
+        &mut self.trace_state
+    }
+
+    /// Works like [`trace_state_mut`](#method.trace_state_mut), but replaces the whole trace state and
+    /// returns the context to construct it inline.
+    pub fn with_trace_state(mut self, trace_state: TraceState) -> Self {
+        self.trace_state = trace_state;
+        self
+    }
+
+    /// Creates a context of a child operation in the same trace. The child carries trace state of
+    /// this context.
+    pub fn child(&self) -> Self {
+        self.child_with(&IdStrategy::default())
+    }
+
+    /// Creates a context of a child operation with a span id generated by a strategy of a client.
+    fn child_with(&self, ids: &IdStrategy) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            span_id: ids.new_span_id(),
+            sampled: self.sampled,
+            trace_state: self.trace_state.clone(),
+        }
+    }
+
+    /// Returns a `tracestate` header value with an `az` entry that refers to the span id of this context
+    /// in front of entries received from a caller.
+    pub fn tracestate(&self) -> String {
+        let mut state = self.trace_state.clone();
+        state.insert(AZ_VENDOR_KEY, self.span_id.clone());
+        state.header()
+    }
+
+    /// Returns a `traceparent` header value.
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.sampled as u8)
+    }
+
+    /// Returns a legacy `Request-Id` header value.
+    pub fn request_id(&self) -> String {
+        format!("|{}.{}.", self.trace_id, self.span_id)
+    }
+
+    /// Sets an operation id and a parent id of a telemetry item, so it becomes a child of this context.
+    pub fn correlate<E: Telemetry>(&self, telemetry: &mut E) {
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.trace_id.clone());
+        operation.set_parent_id(self.span_id.clone());
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.traceparent())
+    }
+}
+
+/// A carrier trace context headers are written to, e.g. headers of an outgoing request or message.
+pub trait Injector {
+    /// Sets a value of a header.
+    fn set(&mut self, key: &str, value: String);
+}
+
+/// A carrier trace context headers are read from, e.g. headers of an incoming request or message.
+pub trait Extractor {
+    /// Returns a value of a header if present.
+    fn get(&self, key: &str) -> Option<&str>;
+}
+
+impl<F: FnMut(&str, String)> Injector for F {
+    fn set(&mut self, key: &str, value: String) {
+        self(key, value)
+    }
+}
+
+impl Injector for HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (HeaderName::from_bytes(key.as_bytes()), HeaderValue::from_str(&value)) {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl Extractor for HeaderMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        HeaderMap::get(self, key).and_then(|value| value.to_str().ok())
+    }
+}
+
+/// Writes headers with lowercase keys, as HTTP/2 and gRPC metadata require.
+impl Injector for HashMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_ascii_lowercase(), value);
+    }
+}
+
+/// Reads headers with keys in any case, e.g. message headers collected from a queue client.
+impl Extractor for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        HashMap::get(self, key)
+            .or_else(|| find(self.iter(), key))
+            .map(String::as_str)
+    }
+}
+
+/// Writes headers with lowercase keys, as HTTP/2 and gRPC metadata require.
+impl Injector for BTreeMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_ascii_lowercase(), value);
+    }
+}
+
+/// Reads headers with keys in any case.
+impl Extractor for BTreeMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        BTreeMap::get(self, key)
+            .or_else(|| find(self.iter(), key))
+            .map(String::as_str)
+    }
+}
+
+/// Finds a value of a key compared case-insensitively.
+fn find<'a>(mut entries: impl Iterator<Item = (&'a String, &'a String)>, key: &str) -> Option<&'a String> {
+    entries
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value)
+}
+
+/// Writes `traceparent`, `tracestate` and `Request-Id` headers of specified trace context to a carrier.
+///
+/// ```rust
+/// # use appinsights::correlation::{self, TraceContext};
+/// let context = TraceContext::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+///
+/// let mut headers = http::HeaderMap::new();
+/// correlation::inject(&context, &mut headers);
+///
+/// assert_eq!(headers["traceparent"], "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01");
+/// assert_eq!(headers["tracestate"], "az=b7ad6b7169203331");
+/// assert_eq!(headers["request-id"], "|0af7651916cd43dd8448eb211c80319c.b7ad6b7169203331.");
+/// ```
+pub fn inject(context: &TraceContext, carrier: &mut impl Injector) {
+    carrier.set(TRACEPARENT_HEADER, context.traceparent());
+    carrier.set(TRACESTATE_HEADER, context.tracestate());
+    carrier.set(REQUEST_ID_HEADER, context.request_id());
+}
+
+/// Reads a trace context from a carrier. A `traceparent` header takes precedence over a legacy
+/// `Request-Id` one and carries a trace state of a `tracestate` header along. Returns `None` if a
+/// carrier contains neither of them.
+pub fn extract(carrier: &impl Extractor) -> Option<TraceContext> {
+    match carrier.get(TRACEPARENT_HEADER).and_then(TraceContext::parse) {
+        Some(context) => {
+            let trace_state = carrier
+                .get(TRACESTATE_HEADER)
+                .map(TraceState::parse)
+                .unwrap_or_default();
+            Some(context.with_trace_state(trace_state))
+        }
+        None => carrier.get(REQUEST_ID_HEADER).and_then(TraceContext::parse_request_id),
+    }
+}
+
+/// Returns `true` if a `tracestate` key is valid, i.e. `vendor` or `tenant@vendor`.
+fn is_key(key: &str) -> bool {
+    let valid = |part: &str, len: usize| {
+        !part.is_empty()
+            && part.len() <= len
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_-*/".contains(&b))
+    };
+    match key.split_once('@') {
+        Some((tenant, vendor)) => valid(tenant, 241) && valid(vendor, 14),
+        None => valid(key, 256) && key.as_bytes()[0].is_ascii_lowercase(),
+    }
+}
+
+/// Returns `true` if a `tracestate` value is valid.
+fn is_value(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 256
+        && !value.ends_with(' ')
+        && value
+            .bytes()
+            .all(|b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'=')
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01", true  ; "sampled")]
+    #[test_case("00-0AF7651916CD43DD8448EB211C80319C-B7AD6B7169203331-00", false ; "not sampled uppercase")]
+    #[test_case("01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra", true ; "future version")]
+    fn it_parses_traceparent(traceparent: &str, sampled: bool) {
+        let context = TraceContext::parse(traceparent).unwrap();
+
+        assert_eq!(context.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(context.span_id(), "b7ad6b7169203331");
+        assert_eq!(context.sampled(), sampled);
+    }
+
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331"       ; "missing flags")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-xx" ; "extra part")]
+    #[test_case("ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"    ; "invalid version")]
+    #[test_case("00-00000000000000000000000000000000-b7ad6b7169203331-01"    ; "zero trace id")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01"    ; "zero span id")]
+    #[test_case("00-0af7651916cd43dd8448eb211c8031-b7ad6b7169203331-01"      ; "short trace id")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319z-b7ad6b7169203331-01"    ; "not hex")]
+    fn it_rejects_malformed_traceparent(traceparent: &str) {
+        assert_eq!(TraceContext::parse(traceparent), None);
+    }
+
+    #[test_case("|0af7651916cd43dd8448eb211c80319c.b7ad6b7169203331.", Some("b7ad6b7169203331") ; "with span id")]
+    #[test_case("|0af7651916cd43dd8448eb211c80319c.1.",                None                      ; "hierarchical")]
+    #[test_case("0af7651916cd43dd8448eb211c80319c",                    None                      ; "trace id only")]
+    fn it_parses_request_id(request_id: &str, span_id: Option<&str>) {
+        let context = TraceContext::parse_request_id(request_id).unwrap();
+
+        assert_eq!(context.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        if let Some(span_id) = span_id {
+            assert_eq!(context.span_id(), span_id);
+        }
+    }
+
+    #[test]
+    fn it_extracts_traceparent_before_request_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "request-id",
+            "|4bf92f3577b34da6a3ce929d0e0e4736.00f067aa0ba902b7.".parse().unwrap(),
+        );
+        assert_eq!(
+            extract(&headers).unwrap().trace_id(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            extract(&headers).unwrap().trace_id(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+    }
+
+    #[test_case("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE", "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE" ; "valid")]
+    #[test_case("az=old, rojo=1, az=duplicate",             "az=old,rojo=1"                          ; "duplicate")]
+    #[test_case("Upper=1,tenant@vendor=2,=3,bad,x=a=b",     "tenant@vendor=2"                        ; "malformed")]
+    fn it_parses_tracestate(header: &str, expected: &str) {
+        assert_eq!(TraceState::parse(header).header(), expected);
+    }
+
+    #[test]
+    fn it_limits_tracestate_entries() {
+        let header: Vec<_> = (0..40).map(|i| format!("v{}=1", i)).collect();
+        let mut state = TraceState::parse(&header.join(","));
+        assert_eq!(state.get("v31"), Some("1"));
+        assert_eq!(state.get("v32"), None);
+
+        state.insert("az", "span");
+
+        assert_eq!(state.header().split(',').count(), 32);
+        assert!(state.header().starts_with("az=span,v0=1,"));
+        assert_eq!(state.get("v31"), None);
+    }
+
+    #[test]
+    fn it_passes_tracestate_to_children() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert(
+            "tracestate",
+            "az=b7ad6b7169203331,rojo=00f067aa0ba902b7".parse().unwrap(),
+        );
+
+        let child = extract(&headers).unwrap().child();
+        let mut outgoing = HeaderMap::new();
+        inject(&child, &mut outgoing);
+
+        assert_eq!(
+            outgoing["tracestate"],
+            format!("az={},rojo=00f067aa0ba902b7", child.span_id())
+        );
+    }
+
+    #[test]
+    fn it_round_trips_context_through_maps() {
+        let context = TraceContext::new();
+
+        let mut headers = HashMap::new();
+        inject(&context, &mut headers);
+        assert_eq!(headers["request-id"], context.request_id());
+        let extracted = extract(&headers).unwrap();
+        assert_eq!(extracted.traceparent(), context.traceparent());
+        assert_eq!(extracted.trace_state().get(AZ_VENDOR_KEY), Some(context.span_id()));
+
+        let headers: BTreeMap<_, _> = vec![("Traceparent".to_string(), context.traceparent())]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            extract(&headers).map(|extracted| extracted.span_id().to_string()),
+            Some(context.span_id().into())
+        );
+    }
+
+    #[test]
+    fn it_returns_nothing_when_headers_missing() {
+        assert_eq!(extract(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn it_round_trips_new_context() {
+        let context = TraceContext::new();
+
+        assert_eq!(TraceContext::parse(&context.traceparent()), Some(context));
+    }
+}
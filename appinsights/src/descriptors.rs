@@ -0,0 +1,233 @@
+//! File descriptor and socket count metrics on Linux.
+//!
+//! Leaking file descriptors or connections shows up long before a service fails with "too many open
+//! files" or can't connect to anything because all ephemeral ports are in use.
+//! [`Descriptors`](struct.Descriptors.html) reads `/proc` and reports gauges sampled every aggregation
+//! interval:
+//! * `process_open_fds` and `process_max_fds`, a soft limit of open files,
+//! * `process_sockets` among open file descriptors,
+//! * `tcp_connections` of the network namespace, not counting listening sockets,
+//! * `tcp_ephemeral_ports` used by connections with a local port in the ephemeral port range,
+//! * `tcp_ephemeral_ports_percent` of the range in use.
+//!
+//! ```rust, no_run
+//! use appinsights::{descriptors::Descriptors, TelemetryClient};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! Descriptors::new().register(&client);
+//! # }
+//! ```
+use std::{
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::TelemetryClient;
+
+/// A state of listening TCP sockets in `/proc/net/tcp`.
+const LISTEN: &str = "0A";
+
+/// Reads counts of file descriptors and sockets from `/proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptors {
+    proc: PathBuf,
+}
+
+/// Counts of file descriptors and sockets.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DescriptorStats {
+    /// A number of file descriptors open by the process.
+    pub open: Option<u64>,
+
+    /// A soft limit of open file descriptors.
+    pub limit: Option<u64>,
+
+    /// A number of open file descriptors that are sockets.
+    pub sockets: Option<u64>,
+
+    /// A number of TCP connections in the network namespace, not counting listening sockets.
+    pub tcp_connections: Option<u64>,
+
+    /// A number of TCP connections with a local port in the ephemeral port range.
+    pub ephemeral_ports: Option<u64>,
+
+    /// A size of the ephemeral port range.
+    pub ephemeral_range: Option<u64>,
+}
+
+impl Descriptors {
+    /// Creates a new collector reading `/proc`.
+    pub fn new() -> Self {
+        Self::at("/proc")
+    }
+
+    /// Creates a new collector reading a `proc` file system mounted at specified path.
+    pub fn at(proc: impl Into<PathBuf>) -> Self {
+        Self { proc: proc.into() }
+    }
+
+    /// Reads current counts of file descriptors and sockets.
+    pub fn stats(&self) -> DescriptorStats {
+        let descriptors = fs::read_dir(self.proc.join("self/fd")).ok().map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| fs::read_link(entry.path()).is_ok_and(|target| is_socket(&target)))
+                .collect::<Vec<_>>()
+        });
+        let range = fs::read_to_string(self.proc.join("sys/net/ipv4/ip_local_port_range"))
+            .ok()
+            .and_then(|range| port_range(&range));
+        let connections = ["net/tcp", "net/tcp6"]
+            .iter()
+            .filter_map(|table| fs::read_to_string(self.proc.join(table)).ok())
+            .flat_map(|table| connections(&table))
+            .collect::<Vec<_>>();
+        let tables = self.proc.join("net/tcp").exists() || self.proc.join("net/tcp6").exists();
+
+        DescriptorStats {
+            open: descriptors.as_ref().map(|descriptors| descriptors.len() as u64),
+            limit: fs::read_to_string(self.proc.join("self/limits"))
+                .ok()
+                .and_then(|limits| open_files_limit(&limits)),
+            sockets: descriptors.map(|descriptors| descriptors.into_iter().filter(|socket| *socket).count() as u64),
+            tcp_connections: Some(connections.len() as u64).filter(|_| tables),
+            ephemeral_ports: range
+                .as_ref()
+                .filter(|_| tables)
+                .map(|range| connections.iter().filter(|port| range.contains(port)).count() as u64),
+            ephemeral_range: range.map(|range| u64::from(range.end() - range.start()) + 1),
+        }
+    }
+
+    /// Registers gauges of counts `/proc` currently provides with a client.
+    pub fn register(self, client: &Arc<TelemetryClient>) {
+        let collector = Arc::new(self);
+        let stats = collector.stats();
+
+        let gauges: [(&str, Count); 6] = [
+            ("process_open_fds", |stats| stats.open.map(|value| value as f64)),
+            ("process_max_fds", |stats| stats.limit.map(|value| value as f64)),
+            ("process_sockets", |stats| stats.sockets.map(|value| value as f64)),
+            ("tcp_connections", |stats| {
+                stats.tcp_connections.map(|value| value as f64)
+            }),
+            ("tcp_ephemeral_ports", |stats| {
+                stats.ephemeral_ports.map(|value| value as f64)
+            }),
+            ("tcp_ephemeral_ports_percent", |stats| {
+                let range = stats.ephemeral_range.filter(|range| *range > 0)?;
+                Some(stats.ephemeral_ports? as f64 / range as f64 * 100.0)
+            }),
+        ];
+        for (name, value) in gauges {
+            if value(&stats).is_some() {
+                let collector = collector.clone();
+                client.register_gauge(name, move || value(&collector.stats()).unwrap_or_default());
+            }
+        }
+    }
+}
+
+impl Default for Descriptors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a value of a gauge from counts.
+type Count = fn(&DescriptorStats) -> Option<f64>;
+
+/// Returns true if a target of a file descriptor link is a socket, i.e. `socket:[12345]`.
+fn is_socket(target: &Path) -> bool {
+    target.to_str().is_some_and(|target| target.starts_with("socket:"))
+}
+
+/// Returns a soft limit of open files from `/proc/self/limits`.
+fn open_files_limit(limits: &str) -> Option<u64> {
+    limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))
+        .and_then(|limit| limit.split_whitespace().next()?.parse().ok())
+}
+
+/// Parses a range of ephemeral ports from `ip_local_port_range`, e.g. `32768 60999`.
+fn port_range(range: &str) -> Option<RangeInclusive<u16>> {
+    let mut ports = range.split_whitespace().map(|port| port.parse::<u16>());
+    match (ports.next()?.ok()?, ports.next()?.ok()?) {
+        (start, end) if start <= end => Some(start..=end),
+        _ => None,
+    }
+}
+
+/// Returns local ports of TCP sockets in a `/proc/net/tcp` table, not counting listening sockets.
+fn connections(table: &str) -> Vec<u16> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace().skip(1);
+            let local = columns.next()?;
+            let state = columns.nth(1)?;
+            let port = local.rsplit(':').next()?;
+            u16::from_str_radix(port, 16).ok().filter(|_| state != LISTEN)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 10001 1
+   1: 0100007F:8CA0 0100007F:1F90 01 00000000:00000000 00:00000000 00000000  1000        0 10002 1
+   2: 0100007F:0050 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 10003 1
+";
+
+    const LIMITS: &str = "Limit                     Soft Limit           Hard Limit           Units
+Max processes             63404                63404                processes
+Max open files            1024                 524288               files
+";
+
+    #[test]
+    fn it_reads_counts_from_proc() {
+        let root = std::env::temp_dir().join(format!("appinsights-proc-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("self/fd")).unwrap();
+        fs::create_dir_all(root.join("net")).unwrap();
+        fs::create_dir_all(root.join("sys/net/ipv4")).unwrap();
+        std::os::unix::fs::symlink("socket:[10002]", root.join("self/fd/3")).unwrap();
+        std::os::unix::fs::symlink("/var/log/app.log", root.join("self/fd/4")).unwrap();
+        fs::write(root.join("self/limits"), LIMITS).unwrap();
+        fs::write(root.join("net/tcp"), TCP).unwrap();
+        fs::write(root.join("sys/net/ipv4/ip_local_port_range"), "32768\t60999\n").unwrap();
+
+        let stats = Descriptors::at(&root).stats();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(
+            stats,
+            DescriptorStats {
+                open: Some(2),
+                limit: Some(1024),
+                sockets: Some(1),
+                tcp_connections: Some(2),
+                ephemeral_ports: Some(1),
+                ephemeral_range: Some(28232),
+            }
+        );
+    }
+
+    #[test]
+    fn it_skips_missing_tables() {
+        let root = std::env::temp_dir().join(format!("appinsights-proc-{}", uuid::Uuid::new_v4()));
+
+        let stats = Descriptors::at(&root).stats();
+
+        assert_eq!(stats, DescriptorStats::default());
+    }
+}
@@ -0,0 +1,159 @@
+//! Diagnostics of the SDK itself.
+//!
+//! Problems the SDK runs into while it processes and submits telemetry, e.g. a telemetry item that breaks
+//! the item schema, are logged and reported as a [`Diagnostic`](enum.Diagnostic.html) to a single hook
+//! configured with [`on_diagnostic`](../struct.TelemetryConfigBuilder.html#method.on_diagnostic), so an
+//! application can surface them in one place, e.g. fail tests or count them as metrics of its own.
+//!
+//! ```rust, no_run
+//! # use appinsights::{diagnostics::Diagnostic, processor::SchemaValidator, TelemetryClient, TelemetryConfig};
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .on_diagnostic(|diagnostic| match diagnostic {
+//!         Diagnostic::SchemaViolation { envelope, violations } => {
+//!             panic!("{} is invalid: {:?}", envelope.name, violations)
+//!         }
+//!         diagnostic => eprintln!("{:?}", diagnostic),
+//!     })
+//!     .build();
+//!
+//! let mut client = TelemetryClient::from_config(config);
+//! client.add_processor(SchemaValidator::new());
+//! ```
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+pub use crate::channel::Priority;
+use crate::{
+    contracts::Envelope,
+    processor::{Mismatch, Violation},
+    transport::{ConnectivityEvent, IngestionResponse},
+};
+
+/// A problem the SDK ran into while it processed or submitted telemetry.
+#[derive(Debug)]
+pub enum Diagnostic<'a> {
+    /// A telemetry item breaks constraints of the item schema a
+    /// [`SchemaValidator`](../processor/struct.SchemaValidator.html) checks.
+    SchemaViolation {
+        /// An invalid telemetry item.
+        envelope: &'a Envelope,
+
+        /// All violations found in the item.
+        violations: &'a [Violation],
+    },
+
+    /// A custom event doesn't match its schema registered in an
+    /// [`EventRegistry`](../processor/struct.EventRegistry.html).
+    SchemaMismatch {
+        /// A mismatching event.
+        envelope: &'a Envelope,
+
+        /// All mismatches found in the event.
+        mismatches: &'a [Mismatch],
+    },
+
+    /// A telemetry item exceeds a size limit of a [`SizeGuard`](../processor/struct.SizeGuard.html), so it
+    /// was trimmed, or dropped when trimming didn't make it fit. An item a channel can't fit into a payload
+    /// of the maximum size on its own is dropped without trimming.
+    Oversized {
+        /// A telemetry item after trimming.
+        envelope: &'a Envelope,
+
+        /// A size of serialized JSON of the item before trimming in bytes.
+        size: usize,
+
+        /// A size limit in bytes.
+        max_bytes: usize,
+
+        /// Parts of the item that were removed, e.g. `property payload` or `exception stacks`.
+        trimmed: &'a [String],
+
+        /// Whether the item was dropped.
+        dropped: bool,
+    },
+
+    /// A telemetry item fails to serialize, so it was dropped and the rest of its batch was sent regardless.
+    SerializationFailed {
+        /// A dropped telemetry item.
+        envelope: &'a Envelope,
+
+        /// An error the item failed to serialize with.
+        error: &'a serde_json::Error,
+    },
+
+    /// The ingestion endpoint responded to a submission, telling numbers of items it received and accepted
+    /// and reasons it rejected items for, e.g. an invalid instrumentation key or a field that is too long.
+    Response(&'a IngestionResponse),
+
+    /// A channel lost or restored connectivity to the ingestion endpoint.
+    Connectivity(&'a ConnectivityEvent),
+
+    /// A channel dropped a telemetry item, because its queue reached its capacity or its memory budget.
+    Dropped {
+        /// A dropped telemetry item.
+        envelope: &'a Envelope,
+
+        /// A priority of the item.
+        priority: Priority,
+
+        /// A reason the item was dropped for.
+        reason: DropReason,
+    },
+}
+
+/// A reason a channel dropped a telemetry item from its queue for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// The item alone exceeds the memory budget of the queue.
+    OverMemoryBudget,
+
+    /// The queue is full, so the item was evicted to make room for a new one of the same or higher priority.
+    Evicted,
+
+    /// The queue is full of items of higher priority, so the new item was rejected.
+    QueueFull,
+}
+
+type Hook = dyn Fn(&Diagnostic<'_>) + Send + Sync;
+
+/// A hook that receives diagnostics of a client. A client passes it to
+/// [`processors`](../processor/trait.TelemetryProcessor.html#method.process_with_diagnostics), so custom
+/// ones can report to it as well.
+#[derive(Clone, Default)]
+pub struct Diagnostics(Option<Arc<Hook>>);
+
+impl Diagnostics {
+    /// Creates diagnostics that are reported to specified hook.
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&Diagnostic<'_>) + Send + Sync + 'static,
+    {
+        Self(Some(Arc::new(hook)))
+    }
+
+    /// Reports a diagnostic to the hook, if there is one.
+    pub fn report(&self, diagnostic: Diagnostic<'_>) {
+        if let Some(hook) = &self.0 {
+            hook(&diagnostic);
+        }
+    }
+}
+
+impl Debug for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Diagnostics").field(&self.0.is_some()).finish()
+    }
+}
+
+impl PartialEq for Diagnostics {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(hook), Some(other)) => Arc::ptr_eq(hook, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
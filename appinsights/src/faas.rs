@@ -0,0 +1,226 @@
+//! Instrumentation of serverless functions hosted outside Azure, e.g. on AWS Lambda or Google Cloud Run.
+//!
+//! [`FaasInstrumentation`](struct.FaasInstrumentation.html) submits every invocation as a
+//! [`RequestTelemetry`](../telemetry/struct.RequestTelemetry.html) item named after the function and
+//! tells cold starts apart: the first invocation of an instance is marked with `ColdStart` property set
+//! to `True` and carries `initDurationMs` measurement, i.e. time between creating the instrumentation
+//! and the invocation, so it should be created as early as possible in `main`. Later invocations are
+//! marked with `ColdStart` set to `False`. A request id of the invocation passed in
+//! `Lambda-Runtime-Aws-Request-Id` header is submitted as `InvocationId` property, and a trace context in
+//! headers is continued.
+//!
+//! [`apply_environment`](fn.apply_environment.html) reads metadata of the function the platform exposes
+//! in environment variables into a telemetry context.
+//!
+//! ```rust, no_run
+//! use appinsights::{faas::{self, FaasInstrumentation}, TelemetryClient};
+//! use http::HeaderMap;
+//!
+//! # async fn run(headers: HeaderMap) -> Result<(), String> {
+//! let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+//! faas::apply_environment(client.context_mut());
+//! let faas = FaasInstrumentation::new(client);
+//!
+//! // for every invocation
+//! faas.invoke("resize-image", &headers, |scope| async move {
+//!     // correlate telemetry of the invocation with `scope`
+//!     Ok::<_, String>(())
+//! })
+//! .await
+//! # }
+//! ```
+use std::{
+    env,
+    fmt::Display,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use http::{HeaderMap, Method, StatusCode};
+
+use crate::{server::RequestScope, telemetry, time::Stopwatch, TelemetryClient, TelemetryContext};
+
+/// A header AWS Lambda runtime API passes a request id of an invocation in.
+pub const REQUEST_ID_HEADER: &str = "lambda-runtime-aws-request-id";
+
+/// A property of a request an invocation id is submitted in.
+pub const INVOCATION_ID_PROPERTY: &str = "InvocationId";
+
+/// A property of a request that tells whether the invocation started a new instance.
+pub const COLD_START_PROPERTY: &str = "ColdStart";
+
+/// A measurement of a cold start request with time the instance took to initialize in milliseconds.
+pub const INIT_DURATION_MEASUREMENT: &str = "initDurationMs";
+
+/// Environment variables with a name of a function, the first one set wins.
+const ROLE_VARIABLES: [&str; 2] = ["AWS_LAMBDA_FUNCTION_NAME", "K_SERVICE"];
+
+/// Environment variables with a version of a function, the first one set wins.
+const VERSION_VARIABLES: [&str; 2] = ["AWS_LAMBDA_FUNCTION_VERSION", "K_REVISION"];
+
+/// Environment variables of a platform and context properties they are read into.
+const ENVIRONMENT_PROPERTIES: [(&str, &str); 2] = [
+    ("AWS_REGION", "cloudRegion"),
+    ("AWS_LAMBDA_INITIALIZATION_TYPE", "initializationType"),
+];
+
+/// Tracks invocations of a serverless function as requests and detects cold starts.
+pub struct FaasInstrumentation {
+    client: Arc<TelemetryClient>,
+    initialized: Stopwatch,
+    cold: AtomicBool,
+}
+
+impl FaasInstrumentation {
+    /// Creates a new instrumentation that submits invocations with specified client. Initialization of
+    /// an instance is measured from this moment until the first invocation.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            initialized: Stopwatch::start(),
+            cold: AtomicBool::new(true),
+        }
+    }
+
+    /// Starts tracking an invocation of a function with specified headers, e.g. of an event or of a
+    /// runtime API response. The invocation is submitted when the scope is finished with a status code.
+    pub fn start(&self, function: &str, headers: &HeaderMap) -> RequestScope {
+        let uri = telemetry::path_of(function);
+        let scope = RequestScope::start(self.client.clone(), &Method::POST, &uri, headers);
+        scope.set_name(function);
+        if let Some(request_id) = headers.get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()) {
+            scope.insert_property(INVOCATION_ID_PROPERTY, request_id);
+        }
+
+        let cold = self.cold.swap(false, Ordering::SeqCst);
+        scope.insert_property(COLD_START_PROPERTY, if cold { "True" } else { "False" });
+        if cold {
+            let init_duration = self.initialized.elapsed().as_secs_f64() * 1000.0;
+            scope.insert_measurement(INIT_DURATION_MEASUREMENT, init_duration);
+        }
+        scope
+    }
+
+    /// Runs an invocation of a function and submits it as a request that succeeds when a future returned
+    /// by the handler resolves to `Ok`. The handler receives a scope of the invocation to correlate
+    /// telemetry items submitted while it runs.
+    pub async fn invoke<F, Fut, T, E>(&self, function: &str, headers: &HeaderMap, handle: F) -> Result<T, E>
+    where
+        F: FnOnce(RequestScope) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let scope = self.start(function, headers);
+        let result = handle(scope.clone()).await;
+        match &result {
+            Ok(_) => scope.finish(StatusCode::OK),
+            Err(err) => {
+                scope.insert_property("error", err.to_string());
+                scope.finish(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+        result
+    }
+}
+
+/// Reads metadata of a function from environment variables of AWS Lambda or Knative-based platforms,
+/// e.g. Google Cloud Run, into a telemetry context: the name of the function becomes a cloud role, its
+/// version an application version and a log stream of a Lambda instance a cloud role instance, while a
+/// region and an initialization type are added as properties.
+pub fn apply_environment(context: &mut TelemetryContext) {
+    apply(context, |name| env::var(name).ok());
+}
+
+fn apply(context: &mut TelemetryContext, var: impl Fn(&str) -> Option<String>) {
+    if let Some(role) = ROLE_VARIABLES.iter().find_map(|name| var(name)) {
+        context.tags_mut().cloud_mut().set_role(role);
+    }
+    if let Some(version) = VERSION_VARIABLES.iter().find_map(|name| var(name)) {
+        context.tags_mut().application_mut().set_version(version);
+    }
+    if let Some(instance) = var("AWS_LAMBDA_LOG_STREAM_NAME") {
+        context.tags_mut().cloud_mut().set_role_instance(instance);
+    }
+    for (name, property) in ENVIRONMENT_PROPERTIES {
+        if let Some(value) = var(name) {
+            context.properties_mut().insert(property.into(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::{create_client, take_requests},
+        contracts::RequestData,
+        telemetry::{ContextTags, Properties},
+    };
+
+    #[test]
+    fn it_applies_environment_of_lambda() {
+        let vars = HashMap::from([
+            ("AWS_LAMBDA_FUNCTION_NAME", "resize-image"),
+            ("AWS_LAMBDA_FUNCTION_VERSION", "7"),
+            ("AWS_LAMBDA_LOG_STREAM_NAME", "2024/01/02/[7]abc"),
+            ("AWS_REGION", "eu-west-1"),
+            ("K_SERVICE", "ignored"),
+        ]);
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        apply(&mut context, |name| vars.get(name).map(|value| value.to_string()));
+
+        assert_eq!(context.tags().cloud().role(), Some("resize-image"));
+        assert_eq!(context.tags().cloud().role_instance(), Some("2024/01/02/[7]abc"));
+        assert_eq!(context.tags().application().version(), Some("7"));
+        assert_eq!(context.properties().get("cloudRegion"), Some(&"eu-west-1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn it_marks_cold_start_invocation() {
+        let events = Arc::new(SegQueue::default());
+        let faas = FaasInstrumentation::new(create_client(events.clone()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            REQUEST_ID_HEADER,
+            "8476a536-e9f4-11e8-9739-2dfe598c3fcd".parse().unwrap(),
+        );
+        for _ in 0..2 {
+            faas.invoke("resize-image", &headers, |_| async { Ok::<_, String>(()) })
+                .await
+                .unwrap();
+        }
+
+        let requests: Vec<_> = take_requests(&events).into_iter().map(|(_, data)| data).collect();
+        assert_eq!(requests.len(), 2);
+        let cold = |request: &RequestData| request.properties.as_ref().unwrap()[COLD_START_PROPERTY].clone();
+        assert_eq!(
+            (cold(&requests[0]).as_str(), cold(&requests[1]).as_str()),
+            ("True", "False")
+        );
+        assert!(requests[0]
+            .measurements
+            .as_ref()
+            .unwrap()
+            .contains_key(INIT_DURATION_MEASUREMENT));
+        assert!(requests[1]
+            .measurements
+            .as_ref()
+            .and_then(|measurements| measurements.get(INIT_DURATION_MEASUREMENT))
+            .is_none());
+        assert_eq!(
+            requests[0].properties.as_ref().unwrap()[INVOCATION_ID_PROPERTY],
+            "8476a536-e9f4-11e8-9739-2dfe598c3fcd"
+        );
+        assert_eq!(requests[0].name.as_deref(), Some("resize-image"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Instrumentation of Azure Functions custom handlers.
+//!
+//! A custom handler is a web server the Functions host forwards every invocation to as an HTTP request.
+//! [`FunctionsInstrumentation`](struct.FunctionsInstrumentation.html) submits each invocation as a
+//! [`RequestTelemetry`](../telemetry/struct.RequestTelemetry.html) item named after the function, with
+//! an invocation id the host assigned as `InvocationId` property. A trace context the host supplies in
+//! request headers is continued, so an invocation is correlated to the operation that triggered it.
+//!
+//! [`apply_environment`](fn.apply_environment.html) reads metadata of the function app the host exposes
+//! in environment variables into a telemetry context, so telemetry of all instances is grouped under
+//! the name of the app.
+//!
+//! ```rust, no_run
+//! use appinsights::{functions::{self, FunctionsInstrumentation}, TelemetryClient};
+//! use http::HeaderMap;
+//!
+//! # async fn handle(headers: HeaderMap) -> Result<(), String> {
+//! let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+//! functions::apply_environment(client.context_mut());
+//! let functions = FunctionsInstrumentation::new(client);
+//!
+//! // in a handler of `POST /ProcessOrder`
+//! functions
+//!     .invoke("ProcessOrder", &headers, |scope| async move {
+//!         // correlate telemetry of the invocation with `scope`
+//!         Ok::<_, String>(())
+//!     })
+//!     .await
+//! # }
+//! ```
+use std::{env, fmt::Display, future::Future, sync::Arc};
+
+use http::{HeaderMap, Method, StatusCode};
+
+use crate::{server::RequestScope, telemetry, TelemetryClient, TelemetryContext};
+
+/// A header the Functions host passes an invocation id in.
+pub const INVOCATION_ID_HEADER: &str = "x-azure-functions-invocationid";
+
+/// A property of a request an invocation id is submitted in.
+pub const INVOCATION_ID_PROPERTY: &str = "InvocationId";
+
+/// Environment variables of the Functions host and context properties they are read into.
+const ENVIRONMENT_PROPERTIES: [(&str, &str); 2] = [
+    ("FUNCTIONS_EXTENSION_VERSION", "functionsExtensionVersion"),
+    ("FUNCTIONS_WORKER_RUNTIME", "functionsWorkerRuntime"),
+];
+
+/// Tracks invocations of functions served by a custom handler as requests.
+pub struct FunctionsInstrumentation {
+    client: Arc<TelemetryClient>,
+}
+
+impl FunctionsInstrumentation {
+    /// Creates a new instrumentation that submits invocations with specified client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self { client: client.into() }
+    }
+
+    /// Starts tracking an invocation of a function the host forwarded with specified request headers.
+    /// The invocation is submitted when the scope is finished with a status code of the response.
+    pub fn start(&self, function: &str, headers: &HeaderMap) -> RequestScope {
+        let uri = telemetry::path_of(function);
+        let scope = RequestScope::start(self.client.clone(), &Method::POST, &uri, headers);
+        scope.set_name(function);
+        if let Some(invocation_id) = headers.get(INVOCATION_ID_HEADER).and_then(|value| value.to_str().ok()) {
+            scope.insert_property(INVOCATION_ID_PROPERTY, invocation_id);
+        }
+        scope
+    }
+
+    /// Runs an invocation of a function and submits it as a request that succeeds when a future returned
+    /// by the handler resolves to `Ok`. The handler receives a scope of the invocation to correlate
+    /// telemetry items submitted while it runs.
+    pub async fn invoke<F, Fut, T, E>(&self, function: &str, headers: &HeaderMap, handle: F) -> Result<T, E>
+    where
+        F: FnOnce(RequestScope) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let scope = self.start(function, headers);
+        let result = handle(scope.clone()).await;
+        match &result {
+            Ok(_) => scope.finish(StatusCode::OK),
+            Err(err) => {
+                scope.insert_property("error", err.to_string());
+                scope.finish(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+        result
+    }
+}
+
+/// Reads metadata of a function app from environment variables the Functions host sets into a telemetry
+/// context: the name of the app becomes a cloud role and the id of the instance a cloud role instance,
+/// while versions of the host and a worker runtime are added as properties.
+pub fn apply_environment(context: &mut TelemetryContext) {
+    apply(context, |name| env::var(name).ok());
+}
+
+fn apply(context: &mut TelemetryContext, var: impl Fn(&str) -> Option<String>) {
+    if let Some(site) = var("WEBSITE_SITE_NAME") {
+        context.tags_mut().cloud_mut().set_role(site);
+    }
+    if let Some(instance) = var("WEBSITE_INSTANCE_ID") {
+        context.tags_mut().cloud_mut().set_role_instance(instance);
+    }
+    for (name, property) in ENVIRONMENT_PROPERTIES {
+        if let Some(value) = var(name) {
+            context.properties_mut().insert(property.into(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::{create_client, take_requests},
+        telemetry::{ContextTags, Properties},
+    };
+
+    #[test]
+    fn it_applies_environment_of_functions_host() {
+        let vars = HashMap::from([
+            ("WEBSITE_SITE_NAME", "orders-app"),
+            ("WEBSITE_INSTANCE_ID", "instance-1"),
+            ("FUNCTIONS_EXTENSION_VERSION", "~4"),
+            ("FUNCTIONS_WORKER_RUNTIME", "custom"),
+        ]);
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        apply(&mut context, |name| vars.get(name).map(|value| value.to_string()));
+
+        assert_eq!(context.tags().cloud().role(), Some("orders-app"));
+        assert_eq!(context.tags().cloud().role_instance(), Some("instance-1"));
+        assert_eq!(
+            context.properties().get("functionsExtensionVersion"),
+            Some(&"~4".to_string())
+        );
+        assert_eq!(
+            context.properties().get("functionsWorkerRuntime"),
+            Some(&"custom".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_submits_invocation_as_request() {
+        let events = Arc::new(SegQueue::default());
+        let functions = FunctionsInstrumentation::new(create_client(events.clone()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            INVOCATION_ID_HEADER,
+            "b3a6e3c0-0d5f-4b8e-9c33-2a1f5d8e7c11".parse().unwrap(),
+        );
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+
+        let result = functions
+            .invoke("ProcessOrder", &headers, |_| async { Err::<(), _>("order not found") })
+            .await;
+        assert!(result.is_err());
+
+        let (tags, request) = take_requests(&events).remove(0);
+        assert_eq!(tags["ai.operation.id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(tags["ai.operation.parentId"], "00f067aa0ba902b7");
+        assert_eq!(request.name.as_deref(), Some("ProcessOrder"));
+        assert_eq!(request.url.as_deref(), Some("/ProcessOrder"));
+        assert!(!request.success);
+        assert_eq!(
+            request.properties.unwrap()[INVOCATION_ID_PROPERTY],
+            "b3a6e3c0-0d5f-4b8e-9c33-2a1f5d8e7c11"
+        );
+    }
+}
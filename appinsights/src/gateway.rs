@@ -0,0 +1,241 @@
+//! Forwarding of telemetry produced by constrained devices.
+//!
+//! Devices that can't run the SDK or reach the ingestion endpoint, e.g. a fleet of microcontrollers
+//! publishing over MQTT, can serialize envelopes in the Application Insights format themselves and hand
+//! them to an edge service. A [`Gateway`](struct.Gateway.html) in that service parses received payloads,
+//! validates every item against the item schema, enriches it with context tags identifying the device
+//! and forwards it through the channel of a telemetry client, so processors, sampling and retries of
+//! the client apply to device telemetry as well.
+//!
+//! A payload is a single envelope, a JSON array of envelopes or newline-delimited envelopes, the same
+//! formats the ingestion endpoint accepts.
+//!
+//! ```rust, no_run
+//! use appinsights::{gateway::Gateway, telemetry::ContextTags, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! let gateway = Gateway::new(client);
+//!
+//! // a message received on topic "devices/thermostat-42/telemetry"
+//! let payload = br#"{"name":"Microsoft.ApplicationInsights.Event","time":"2024-01-02T03:04:05.000Z",
+//!     "data":{"baseType":"EventData","baseData":{"ver":2,"name":"door opened"}}}"#;
+//!
+//! let mut tags = ContextTags::default();
+//! tags.device_mut().set_id("thermostat-42".into());
+//!
+//! let report = gateway.forward(payload, &tags);
+//! for (index, rejection) in report.rejected() {
+//!     eprintln!("item {} is rejected: {}", index, rejection);
+//! }
+//! # }
+//! ```
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use crate::{
+    contracts::Envelope,
+    processor::{self, Violation},
+    telemetry::ContextTags,
+    TelemetryClient,
+};
+
+/// Validates, enriches and forwards pre-serialized envelopes.
+pub struct Gateway {
+    client: Arc<TelemetryClient>,
+    i_keys: HashSet<String>,
+}
+
+impl Gateway {
+    /// Creates a new gateway that forwards items with specified client. Items are submitted with the
+    /// instrumentation key of the client regardless of one they carry.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            i_keys: HashSet::new(),
+        }
+    }
+
+    /// Allows items to keep an instrumentation key they carry, e.g. when devices of different tenants
+    /// report to different Application Insights resources. Items carrying other keys are still
+    /// submitted with the key of the client.
+    pub fn allow_i_key(mut self, i_key: impl Into<String>) -> Self {
+        self.i_keys.insert(i_key.into());
+        self
+    }
+
+    /// Parses a payload, validates and forwards every item in it. Tags override tags of the same name an
+    /// item carries, as a gateway knows better which device sent a payload than the payload itself.
+    pub fn forward(&self, payload: &[u8], tags: &ContextTags) -> ForwardReport {
+        let mut report = ForwardReport::default();
+        for (index, item) in parse(payload).into_iter().enumerate() {
+            match item.and_then(|envelope| self.prepare(envelope, tags)) {
+                Ok(envelope) => {
+                    self.client.submit(envelope);
+                    report.forwarded += 1;
+                }
+                Err(rejection) => report.rejected.push((index, rejection)),
+            }
+        }
+        report
+    }
+
+    fn prepare(&self, mut envelope: Envelope, tags: &ContextTags) -> Result<Envelope, Rejection> {
+        let violations = processor::validate(&envelope);
+        if !violations.is_empty() {
+            return Err(Rejection::Invalid(violations));
+        }
+
+        if !envelope.i_key.as_ref().is_some_and(|i_key| self.i_keys.contains(i_key)) {
+            envelope.i_key = Some(self.client.context().i_key.clone());
+        }
+        envelope
+            .tags
+            .get_or_insert_with(BTreeMap::default)
+            .extend(BTreeMap::from(tags.clone()));
+        Ok(envelope)
+    }
+}
+
+/// Parses a JSON array of envelopes, or one envelope per line.
+fn parse(payload: &[u8]) -> Vec<Result<Envelope, Rejection>> {
+    let malformed = |err: serde_json::Error| Rejection::Malformed(err.to_string());
+    let trimmed = payload.trim_ascii_start();
+    if trimmed.starts_with(b"[") {
+        return match serde_json::from_slice::<Vec<serde_json::Value>>(trimmed) {
+            Ok(items) => items
+                .into_iter()
+                .map(|item| serde_json::from_value(item).map_err(malformed))
+                .collect(),
+            Err(err) => vec![Err(malformed(err))],
+        };
+    }
+
+    let items = serde_json::Deserializer::from_slice(payload).into_iter::<serde_json::Value>();
+    let mut parsed = Vec::new();
+    for item in items {
+        match item {
+            Ok(item) => parsed.push(serde_json::from_value(item).map_err(malformed)),
+            Err(err) => {
+                // the rest of a payload can't be parsed after malformed JSON
+                parsed.push(Err(malformed(err)));
+                break;
+            }
+        }
+    }
+    parsed
+}
+
+/// Results of forwarding a payload.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ForwardReport {
+    forwarded: usize,
+    rejected: Vec<(usize, Rejection)>,
+}
+
+impl ForwardReport {
+    /// Returns a number of items forwarded through the channel.
+    pub fn forwarded(&self) -> usize {
+        self.forwarded
+    }
+
+    /// Returns positions of rejected items in a payload together with reasons they were rejected.
+    pub fn rejected(&self) -> &[(usize, Rejection)] {
+        &self.rejected
+    }
+
+    /// Returns `true` if every item in a payload was forwarded.
+    pub fn is_complete(&self) -> bool {
+        self.rejected.is_empty()
+    }
+}
+
+/// A reason an item received by a gateway is not forwarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// An item is not a valid JSON envelope.
+    Malformed(String),
+
+    /// An item breaks constraints of the item schema.
+    Invalid(Vec<Violation>),
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Malformed(err) => write!(f, "malformed envelope: {}", err),
+            Rejection::Invalid(violations) => {
+                let violations: Vec<_> = violations.iter().map(Violation::to_string).collect();
+                write!(f, "invalid envelope: {}", violations.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for Rejection {}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data},
+        TelemetryConfig,
+    };
+
+    const EVENT: &str = r#"{"name":"Microsoft.ApplicationInsights.Event","time":"2024-01-02T03:04:05.000Z","iKey":"device","tags":{"ai.device.id":"spoofed","ai.device.model":"T1000"},"data":{"baseType":"EventData","baseData":{"ver":2,"name":"door opened"}}}"#;
+    const NO_TIME: &str = r#"{"name":"Microsoft.ApplicationInsights.Event","data":{"baseType":"EventData","baseData":{"ver":2,"name":"door closed"}}}"#;
+
+    #[tokio::test]
+    async fn it_forwards_valid_items_with_device_tags() {
+        let events = Arc::new(SegQueue::default());
+        let gateway = Gateway::new(create_client(events.clone()));
+        let mut tags = ContextTags::default();
+        tags.device_mut().set_id("thermostat-42".into());
+
+        let payload = format!("{}\n{}\n", EVENT, NO_TIME);
+        let report = gateway.forward(payload.as_bytes(), &tags);
+
+        assert_eq!(report.forwarded(), 1);
+        assert_matches!(report.rejected(), [(1, Rejection::Invalid(_))]);
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.i_key, Some("instrumentation".into()));
+        let tags = envelope.tags.unwrap();
+        assert_eq!(tags["ai.device.id"], "thermostat-42");
+        assert_eq!(tags["ai.device.model"], "T1000");
+        assert_matches!(envelope.data, Some(Base::Data(Data::EventData(data))) if data.name == "door opened");
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_keeps_allowed_instrumentation_keys_in_batches() {
+        let events = Arc::new(SegQueue::default());
+        let gateway = Gateway::new(create_client(events.clone())).allow_i_key("device");
+
+        let payload = format!("[{}, {{\"name\": 42}}]", EVENT);
+        let report = gateway.forward(payload.as_bytes(), &ContextTags::default());
+
+        assert_eq!(report.forwarded(), 1);
+        assert_matches!(report.rejected(), [(1, Rejection::Malformed(_))]);
+        assert_eq!(events.pop().unwrap().i_key, Some("device".into()));
+    }
+
+    #[test]
+    fn it_rejects_malformed_payload() {
+        assert_matches!(parse(b"{\"name\":").as_slice(), [Err(Rejection::Malformed(_))]);
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
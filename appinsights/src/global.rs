@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
+
+use crate::{
+    contracts::Envelope,
+    telemetry::{SeverityLevel, Telemetry},
+    ConnectionStringError, TelemetryClient, TelemetryConfig, TelemetryContext,
+};
+
+static GLOBAL: OnceLock<TelemetryClient> = OnceLock::new();
+
+static REGISTRY: Registry = Registry {
+    clients: RwLock::new(BTreeMap::new()),
+};
+
+/// Initializes a global telemetry client with a connection string, so libraries and deeply nested code
+/// can submit telemetry with free functions like [`track_event`](fn.track_event.html) without passing a
+/// client around. The global client can be initialized only once per process. It requires a running
+/// Tokio runtime like any [`TelemetryClient`](struct.TelemetryClient.html).
+///
+/// ```rust, no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), appinsights::InitError> {
+/// appinsights::init("InstrumentationKey=<instrumentation key>")?;
+///
+/// // anywhere in the application
+/// appinsights::track_event("application started");
+///
+/// // submit pending telemetry before exit
+/// appinsights::flush();
+/// # Ok(())
+/// # }
+/// ```
+pub fn init(connection_string: &str) -> Result<&'static TelemetryClient, InitError> {
+    let config = TelemetryConfig::from_connection_string(connection_string).map_err(InitError::ConnectionString)?;
+    init_with(TelemetryClient::from_config(config))
+}
+
+/// Installs a configured telemetry client as the global client. The global client can be initialized only
+/// once per process.
+pub fn init_with(client: TelemetryClient) -> Result<&'static TelemetryClient, InitError> {
+    let mut client = Some(client);
+    let global = GLOBAL.get_or_init(|| client.take().expect("client"));
+    if client.is_some() {
+        Err(InitError::AlreadyInitialized)
+    } else {
+        Ok(global)
+    }
+}
+
+/// Returns the global telemetry client or `None` if it was not initialized yet.
+pub fn global() -> Option<&'static TelemetryClient> {
+    GLOBAL.get()
+}
+
+/// Submits a telemetry item with the global client. It does nothing if the client was not initialized.
+pub fn track<E>(event: E)
+where
+    E: Telemetry,
+    (TelemetryContext, E): Into<Envelope>,
+{
+    if let Some(client) = global() {
+        client.track(event);
+    }
+}
+
+/// Submits a custom event with the global client. It does nothing if the client was not initialized.
+pub fn track_event(name: impl Into<String>) {
+    if let Some(client) = global() {
+        client.track_event(name);
+    }
+}
+
+/// Submits a trace message with the global client. It does nothing if the client was not initialized.
+pub fn track_trace(message: impl Into<String>, severity: SeverityLevel) {
+    if let Some(client) = global() {
+        client.track_trace(message, severity);
+    }
+}
+
+/// Submits a metric value with the global client. It does nothing if the client was not initialized.
+pub fn track_metric(name: impl Into<String>, value: f64) {
+    if let Some(client) = global() {
+        client.track_metric(name, value);
+    }
+}
+
+/// Forces all pending telemetry items of the global client to be submitted. The current task will not be
+/// blocked. It does nothing if the client was not initialized.
+pub fn flush() {
+    if let Some(client) = global() {
+        client.flush_channel();
+    }
+}
+
+/// Returns a registry of named telemetry clients of the process.
+///
+/// Components of a plugin-style application can submit telemetry to different Application Insights
+/// resources, or with different configs, under one process. Every component registers its own client
+/// by name, and each client has its own channel, so telemetry of components doesn't mix.
+///
+/// ```rust, no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), appinsights::InitError> {
+/// appinsights::registry().register_connection_string("billing", "InstrumentationKey=<instrumentation key>")?;
+///
+/// // anywhere in the billing component
+/// if let Some(client) = appinsights::registry().get("billing") {
+///     client.track_event("invoice issued");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn registry() -> &'static Registry {
+    &REGISTRY
+}
+
+/// Named telemetry clients of the process.
+pub struct Registry {
+    clients: RwLock<BTreeMap<String, Arc<TelemetryClient>>>,
+}
+
+impl Registry {
+    /// Registers a client with a name. It fails if a client with the same name is already registered.
+    pub fn register(
+        &self,
+        name: impl Into<String>,
+        client: impl Into<Arc<TelemetryClient>>,
+    ) -> Result<Arc<TelemetryClient>, InitError> {
+        let mut clients = self.clients.write().unwrap_or_else(PoisonError::into_inner);
+        let name = name.into();
+        if clients.contains_key(&name) {
+            return Err(InitError::AlreadyInitialized);
+        }
+        let client = client.into();
+        clients.insert(name, client.clone());
+        Ok(client)
+    }
+
+    /// Creates a client with a connection string and registers it with a name. It fails if a client with
+    /// the same name is already registered.
+    pub fn register_connection_string(
+        &self,
+        name: impl Into<String>,
+        connection_string: &str,
+    ) -> Result<Arc<TelemetryClient>, InitError> {
+        let config = TelemetryConfig::from_connection_string(connection_string).map_err(InitError::ConnectionString)?;
+        self.register(name, TelemetryClient::from_config(config))
+    }
+
+    /// Returns a client registered with a name.
+    pub fn get(&self, name: &str) -> Option<Arc<TelemetryClient>> {
+        self.clients
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+    }
+
+    /// Removes a client registered with a name and returns it, e.g. to close its channel when a
+    /// component is unloaded.
+    pub fn remove(&self, name: &str) -> Option<Arc<TelemetryClient>> {
+        self.clients
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(name)
+    }
+
+    /// Returns names of all registered clients.
+    pub fn names(&self) -> Vec<String> {
+        self.clients
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Forces pending telemetry items of all registered clients to be submitted. The current task will not
+    /// be blocked.
+    pub fn flush(&self) {
+        for client in self.clients.read().unwrap_or_else(PoisonError::into_inner).values() {
+            client.flush_channel();
+        }
+    }
+}
+
+/// An error returned when the global telemetry client cannot be initialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitError {
+    /// A connection string is not valid.
+    ConnectionString(ConnectionStringError),
+
+    /// The global client was already initialized, or a client with the same name is already registered.
+    AlreadyInitialized,
+}
+
+impl Display for InitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::ConnectionString(err) => write!(f, "unable to initialize global client: {}", err),
+            InitError::AlreadyInitialized => write!(f, "global client is already initialized"),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InitError::ConnectionString(err) => Some(err),
+            InitError::AlreadyInitialized => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data},
+    };
+
+    #[test]
+    fn it_rejects_invalid_connection_string() {
+        assert_eq!(
+            init("IngestionEndpoint=https://example.com").err(),
+            Some(InitError::ConnectionString(
+                ConnectionStringError::MissingInstrumentationKey
+            ))
+        );
+    }
+
+    #[test]
+    fn it_keeps_clients_by_name() {
+        let first = Arc::new(SegQueue::default());
+        let second = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+
+        assert!(registry()
+            .register(
+                "first",
+                TelemetryClient::create(&config, TestChannel::new(first.clone()))
+            )
+            .is_ok());
+        assert!(registry()
+            .register(
+                "second",
+                TelemetryClient::create(&config, TestChannel::new(second.clone()))
+            )
+            .is_ok());
+        assert_eq!(
+            registry()
+                .register(
+                    "first",
+                    TelemetryClient::create(&config, TestChannel::new(first.clone()))
+                )
+                .err(),
+            Some(InitError::AlreadyInitialized)
+        );
+        registry().get("second").unwrap().track_event("second");
+
+        assert!(first.is_empty());
+        assert_matches!(second.pop().unwrap().data, Some(Base::Data(Data::EventData(data))) if data.name == "second");
+        assert!(registry().remove("first").is_some());
+        assert!(registry().get("first").is_none());
+        assert!(registry().names().contains(&"second".to_string()));
+    }
+
+    // the only test that initializes the global client as it can be initialized once per process
+    #[test]
+    fn it_submits_telemetry_with_global_client() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+
+        assert!(init_with(TelemetryClient::create(&config, TestChannel::new(events.clone()))).is_ok());
+        track_event("event");
+
+        assert_matches!(events.pop().unwrap().data, Some(Base::Data(Data::EventData(data))) if data.name == "event");
+        assert_eq!(
+            init_with(TelemetryClient::create(&config, TestChannel::new(events))).err(),
+            Some(InitError::AlreadyInitialized)
+        );
+    }
+}
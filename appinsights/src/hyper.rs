@@ -0,0 +1,35 @@
+//! Request tracking for plain [`hyper`](https://docs.rs/hyper) servers.
+//!
+//! [`TelemetryService`](struct.TelemetryService.html) wraps any `tower` service that handles
+//! `http::Request` and responds with `http::Response`. For every request it extracts a trace context
+//! and a caller's application id from request headers, measures the inner service, and submits
+//! [`RequestTelemetry`](../telemetry/struct.RequestTelemetry.html) which is considered successful
+//! depending on a response status code. The [`RequestScope`](struct.RequestScope.html) of a request is
+//! added to request extensions, so handlers can access it.
+//!
+//! It is the same service framework integrations are built on, so it works with any server built on
+//! top of `tower` services. Since hyper 1.x declares its own `Service` trait, wrap the service with
+//! `TowerToHyperService` adapter from [`hyper-util`](https://docs.rs/hyper-util) crate.
+//!
+//! ```rust, ignore
+//! use std::{convert::Infallible, sync::Arc};
+//!
+//! use appinsights::{hyper::{RequestScope, TelemetryService}, TelemetryClient};
+//! use hyper::{body::Incoming, server::conn::http1, Request, Response};
+//! use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+//!
+//! async fn hello(request: Request<Incoming>) -> Result<Response<String>, Infallible> {
+//!     let scope = request.extensions().get::<RequestScope>().unwrap();
+//!     scope.insert_property("user-agent", format!("{:?}", request.headers().get("user-agent")));
+//!     Ok(Response::new("hello".to_string()))
+//! }
+//!
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+//! loop {
+//!     let (stream, _) = listener.accept().await?;
+//!     let service = TelemetryService::new(client.clone(), tower::service_fn(hello));
+//!     tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), TowerToHyperService::new(service)));
+//! }
+//! ```
+pub use crate::server::{RequestScope, TelemetryService};
@@ -0,0 +1,248 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::{initializer::TelemetryInitializer, telemetry::Telemetry, TelemetryContext};
+
+/// An endpoint of Azure Instance Metadata Service that describes a virtual machine.
+const IMDS_URL: &str = "http://169.254.169.254/metadata/instance/compute?api-version=2021-02-01";
+
+/// Time to wait for Azure Instance Metadata Service, which responds within milliseconds on Azure and
+/// is unreachable anywhere else.
+const IMDS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Adds properties with metadata of an Azure virtual machine or a scale set instance the application runs
+/// on to every telemetry item: an id, a size, a region and a name of a scale set as `azInst_vmId`,
+/// `azInst_vmSize`, `azInst_location` and `azInst_vmScaleSetName` properties, as well as a name, a
+/// resource group and a subscription of the virtual machine.
+///
+/// Metadata is retrieved from Azure Instance Metadata Service once, in the background, when the first
+/// item is tracked, and cached afterwards. Tracking never waits for the lookup: items tracked before it
+/// completes are not enriched. Outside Azure the lookup times out shortly and nothing is added.
+///
+/// It is added automatically when [`azure_vm_metadata`](../struct.TelemetryConfigBuilder.html#method.azure_vm_metadata)
+/// is set in the configuration.
+///
+/// ```rust, no_run
+/// # use appinsights::{initializer::AzureVmMetadata, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_initializer(AzureVmMetadata::new());
+/// ```
+pub struct AzureVmMetadata {
+    url: String,
+    client: Client,
+    started: AtomicBool,
+    metadata: Arc<OnceLock<Vec<(&'static str, String)>>>,
+}
+
+/// Compute metadata of a virtual machine as Azure Instance Metadata Service describes it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Compute {
+    vm_id: String,
+    vm_size: String,
+    location: String,
+    vm_scale_set_name: String,
+    name: String,
+    resource_group_name: String,
+    subscription_id: String,
+}
+
+impl Compute {
+    /// Returns properties items are stamped with, leaving out values that are empty, e.g. a name of a
+    /// scale set of a standalone virtual machine.
+    fn into_properties(self) -> Vec<(&'static str, String)> {
+        let properties = vec![
+            ("azInst_vmId", self.vm_id),
+            ("azInst_vmSize", self.vm_size),
+            ("azInst_location", self.location),
+            ("azInst_vmScaleSetName", self.vm_scale_set_name),
+            ("azInst_name", self.name),
+            ("azInst_resourceGroupName", self.resource_group_name),
+            ("azInst_subscriptionId", self.subscription_id),
+        ];
+        properties.into_iter().filter(|(_, value)| !value.is_empty()).collect()
+    }
+}
+
+impl AzureVmMetadata {
+    /// Creates a new initializer that looks up metadata of the virtual machine.
+    pub fn new() -> Self {
+        Self::with_endpoint(IMDS_URL, IMDS_TIMEOUT)
+    }
+
+    /// Creates a new initializer that looks up metadata at specified URL.
+    fn with_endpoint(url: impl Into<String>, timeout: Duration) -> Self {
+        // metadata service is link-local, so it must never be reached through a proxy
+        let client = Client::builder()
+            .no_proxy()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            url: url.into(),
+            client,
+            started: AtomicBool::new(false),
+            metadata: Arc::default(),
+        }
+    }
+
+    /// Starts the lookup in the background on a Tokio runtime it is called on, or on a dedicated thread
+    /// otherwise.
+    fn start_lookup(&self) {
+        let lookup = lookup(self.client.clone(), self.url.clone(), self.metadata.clone());
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(lookup);
+            return;
+        }
+
+        let spawned = thread::Builder::new().name("appinsights-imds".into()).spawn(move || {
+            match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime.block_on(lookup),
+                Err(err) => debug!("Unable to look up Azure VM metadata: {}", err),
+            }
+        });
+        if let Err(err) = spawned {
+            debug!("Unable to look up Azure VM metadata: {}", err);
+        }
+    }
+}
+
+impl Default for AzureVmMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryInitializer for AzureVmMetadata {
+    fn initialize(&self, _telemetry: &mut dyn Telemetry, context: &mut TelemetryContext) {
+        if !self.started.swap(true, Ordering::SeqCst) {
+            self.start_lookup();
+        }
+
+        if let Some(metadata) = self.metadata.get() {
+            for (key, value) in metadata {
+                context.properties_mut().insert((*key).into(), value.clone());
+            }
+        }
+    }
+}
+
+/// Retrieves metadata and caches it. A failed lookup caches no metadata, so items are not enriched.
+async fn lookup(client: Client, url: String, metadata: Arc<OnceLock<Vec<(&'static str, String)>>>) {
+    let response = client.get(&url).header("Metadata", "true").send().await;
+    let compute = match response.and_then(|response| response.error_for_status()) {
+        Ok(response) => response.json::<Compute>().await,
+        Err(err) => Err(err),
+    };
+    match compute {
+        Ok(compute) => {
+            debug!("Retrieved Azure VM metadata {:?}", compute);
+            metadata.get_or_init(|| compute.into_properties());
+        }
+        Err(err) => debug!("Unable to look up Azure VM metadata: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use http::Request;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Server,
+    };
+
+    use super::*;
+    use crate::telemetry::{ContextTags, EventTelemetry, Properties};
+
+    const COMPUTE: &str = r#"{
+        "location": "westeurope",
+        "name": "web_0",
+        "resourceGroupName": "orders",
+        "subscriptionId": "8d10da13-8125-4ba9-a717-bf7490507b3d",
+        "vmId": "02aab8a4-74ef-476e-8182-f6d2ba4166a6",
+        "vmScaleSetName": "web",
+        "vmSize": "Standard_D2s_v3",
+        "zone": "1"
+    }"#;
+
+    #[tokio::test]
+    async fn it_stamps_context_with_cached_vm_metadata() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = create_server(Duration::default(), requests.clone());
+        let initializer = AzureVmMetadata::with_endpoint(url, Duration::from_secs(1));
+
+        for _ in 0..100 {
+            if initializer.metadata.get().is_some() {
+                break;
+            }
+            initialize(&initializer);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let context = initialize(&initializer);
+
+        assert_eq!(
+            context.properties()["azInst_vmId"],
+            "02aab8a4-74ef-476e-8182-f6d2ba4166a6"
+        );
+        assert_eq!(context.properties()["azInst_vmSize"], "Standard_D2s_v3");
+        assert_eq!(context.properties()["azInst_location"], "westeurope");
+        assert_eq!(context.properties()["azInst_vmScaleSetName"], "web");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_wait_for_vm_metadata() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = create_server(Duration::from_secs(5), requests.clone());
+        let initializer = AzureVmMetadata::with_endpoint(url, Duration::from_millis(100));
+
+        assert!(initialize(&initializer).properties().is_empty());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert!(initialize(&initializer).properties().is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    fn initialize(initializer: &AzureVmMetadata) -> TelemetryContext {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        initializer.initialize(&mut EventTelemetry::new("event"), &mut context);
+        context
+    }
+
+    fn create_server(delay: Duration, requests: Arc<AtomicUsize>) -> String {
+        let make_service = make_service_fn(move |_| {
+            let requests = requests.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    assert_eq!(request.headers()["Metadata"], "true");
+                    async move {
+                        tokio::time::sleep(delay).await;
+                        hyper::Response::builder().body(Body::from(COMPUTE))
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/metadata/instance/compute", server.local_addr());
+
+        tokio::spawn(server);
+
+        url
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{initializer::TelemetryInitializer, telemetry::Telemetry, TelemetryContext};
+
+/// Stamps every telemetry item with information about a build of the application: a version as
+/// `ai.application.ver` tag, and a commit hash, a build profile and a compiler version as `build.commit`,
+/// `build.profile` and `build.rustc` properties, so regressions can be attributed to a build.
+///
+/// The crate can't see how the application was built, so build information is usually collected with
+/// [`build_info!`](../macro.build_info.html) macro at the call site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildInfo {
+    version: String,
+    commit: Option<String>,
+    profile: Option<String>,
+    rustc: Option<String>,
+}
+
+impl BuildInfo {
+    /// Creates build information with an application version.
+    pub fn new(version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            commit: None,
+            profile: None,
+            rustc: None,
+        }
+    }
+
+    /// Returns an application version.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns a commit hash the application was built from.
+    pub fn commit(&self) -> Option<&str> {
+        self.commit.as_deref()
+    }
+
+    /// Returns a build profile, e.g. `debug` or `release`.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Returns a version of the compiler the application was built with.
+    pub fn rustc(&self) -> Option<&str> {
+        self.rustc.as_deref()
+    }
+
+    /// Sets a commit hash the application was built from. Nothing is set when it is `None`.
+    pub fn with_commit(mut self, commit: Option<impl Into<String>>) -> Self {
+        self.commit = commit.map(Into::into);
+        self
+    }
+
+    /// Sets a build profile, e.g. `debug` or `release`.
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Sets a version of the compiler the application was built with. Nothing is set when it is `None`.
+    pub fn with_rustc(mut self, rustc: Option<impl Into<String>>) -> Self {
+        self.rustc = rustc.map(Into::into);
+        self
+    }
+}
+
+impl TelemetryInitializer for BuildInfo {
+    fn initialize(&self, _telemetry: &mut dyn Telemetry, context: &mut TelemetryContext) {
+        context.tags_mut().application_mut().set_version(self.version.clone());
+
+        let values = [
+            ("build.commit", &self.commit),
+            ("build.profile", &self.profile),
+            ("build.rustc", &self.rustc),
+        ];
+        for (key, value) in values {
+            if let Some(value) = value {
+                context.properties_mut().insert(key.into(), value.clone());
+            }
+        }
+    }
+}
+
+/// Collects [`BuildInfo`](initializer/struct.BuildInfo.html) of the crate the macro is called from:
+/// a version from `CARGO_PKG_VERSION`, a build profile from `debug_assertions`, and a commit hash and a
+/// compiler version from `GIT_COMMIT` and `RUSTC_VERSION` environment variables if they are set at
+/// compile time, e.g. by a build script:
+///
+/// ```rust, ignore
+/// // build.rs
+/// fn main() {
+///     let commit = std::process::Command::new("git").args(["rev-parse", "HEAD"]).output().unwrap();
+///     println!("cargo:rustc-env=GIT_COMMIT={}", String::from_utf8_lossy(&commit.stdout).trim());
+/// }
+/// ```
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_initializer(appinsights::build_info!());
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::initializer::BuildInfo::new(env!("CARGO_PKG_VERSION"))
+            .with_commit(option_env!("GIT_COMMIT"))
+            .with_profile(if cfg!(debug_assertions) { "debug" } else { "release" })
+            .with_rustc(option_env!("RUSTC_VERSION"))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{ContextTags, EventTelemetry, Properties};
+
+    #[test]
+    fn it_collects_build_info_at_call_site() {
+        let info = build_info!();
+
+        assert_eq!(info.version(), env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            info.profile(),
+            Some(if cfg!(debug_assertions) { "debug" } else { "release" })
+        );
+    }
+
+    #[test]
+    fn it_stamps_context_with_build_info() {
+        let info = BuildInfo::new("1.2.3")
+            .with_commit(Some("abc123"))
+            .with_profile("release")
+            .with_rustc(None::<String>);
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        info.initialize(&mut EventTelemetry::new("event"), &mut context);
+
+        assert_eq!(context.tags().application().version(), Some("1.2.3"));
+        assert_eq!(context.properties()["build.commit"], "abc123");
+        assert_eq!(context.properties()["build.profile"], "release");
+        assert!(!context.properties().contains_key("build.rustc"));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    initializer::TelemetryInitializer,
+    telemetry::{ExceptionTelemetry, Telemetry},
+    TelemetryContext,
+};
+
+type Fingerprint = dyn Fn(&ExceptionTelemetry) -> String + Send + Sync;
+
+/// Overrides a key exceptions are grouped by in Failures. Application Insights groups exceptions by their
+/// type and stack by default, while some errors are better told apart by an error code or a module they
+/// originate from. A hook computes a [`problem id`](../telemetry/struct.ExceptionTelemetry.html#method.set_problem_id)
+/// of every exception that does not have one yet. An empty key keeps the default grouping.
+///
+/// ```rust, no_run
+/// # use appinsights::{initializer::ExceptionFingerprint, telemetry::Telemetry, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_initializer(ExceptionFingerprint::new(|exception| match exception.properties().get("error_code") {
+///     Some(code) => format!("{}: {}", exception.type_name(), code),
+///     None => String::new(),
+/// }));
+/// ```
+pub struct ExceptionFingerprint {
+    fingerprint: Box<Fingerprint>,
+}
+
+impl ExceptionFingerprint {
+    /// Creates a new initializer that groups exceptions by a key a hook returns.
+    pub fn new<F>(fingerprint: F) -> Self
+    where
+        F: Fn(&ExceptionTelemetry) -> String + Send + Sync + 'static,
+    {
+        Self {
+            fingerprint: Box::new(fingerprint),
+        }
+    }
+}
+
+impl TelemetryInitializer for ExceptionFingerprint {
+    fn initialize(&self, telemetry: &mut dyn Telemetry, _context: &mut TelemetryContext) {
+        if let Some(exception) = telemetry.as_exception_mut() {
+            if exception.problem_id().is_none() {
+                let problem_id = (self.fingerprint)(exception);
+                if !problem_id.is_empty() {
+                    exception.set_problem_id(problem_id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data, Envelope},
+        telemetry::{ContextTags, EventTelemetry, Properties},
+    };
+
+    #[test_case(None,             Some("ParseError: E42") ; "computed")]
+    #[test_case(Some("explicit"), Some("explicit")        ; "explicit kept")]
+    fn it_overrides_exception_grouping(problem_id: Option<&str>, expected: Option<&str>) {
+        let initializer = ExceptionFingerprint::new(|exception| match exception.properties().get("error_code") {
+            Some(code) => format!("{}: {}", exception.type_name(), code),
+            None => String::new(),
+        });
+        let mut exception =
+            ExceptionTelemetry::from_message("ParseError", "unexpected token").with_property("error_code", "E42");
+        if let Some(problem_id) = problem_id {
+            exception.set_problem_id(problem_id);
+        }
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        initializer.initialize(&mut exception, &mut context);
+
+        match Envelope::from((context, exception)).data {
+            Some(Base::Data(Data::ExceptionData(data))) => assert_eq!(data.problem_id.as_deref(), expected),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_keeps_default_grouping_for_empty_key() {
+        let initializer = ExceptionFingerprint::new(|_| String::new());
+        let mut exception = ExceptionTelemetry::from_message("ParseError", "unexpected token");
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        initializer.initialize(&mut exception, &mut context);
+        initializer.initialize(&mut EventTelemetry::new("event"), &mut context);
+
+        assert_eq!(exception.problem_id(), None);
+    }
+}
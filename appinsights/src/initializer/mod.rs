@@ -0,0 +1,60 @@
+//! Module for telemetry initializers.
+//!
+//! A [`TelemetryInitializer`](trait.TelemetryInitializer.html) receives every telemetry item as soon as it
+//! is tracked, before it is converted to the [`Envelope`](../contracts/struct.Envelope.html). Unlike a
+//! [`processor`](../processor/index.html), an initializer works with a typed telemetry item and a copy of
+//! client context the item is going to be submitted with, so it can enrich all items uniformly, e.g. with
+//! request-scoped data, using the same API instrumentation code uses. An initializer cannot drop an item.
+//!
+//! ```rust, no_run
+//! use appinsights::{initializer::TelemetryInitializer, telemetry::Telemetry, TelemetryClient, TelemetryContext};
+//!
+//! struct Tenant;
+//!
+//! impl TelemetryInitializer for Tenant {
+//!     fn initialize(&self, telemetry: &mut dyn Telemetry, _context: &mut TelemetryContext) {
+//!         telemetry.properties_mut().insert("tenant".into(), "contoso".into());
+//!     }
+//! }
+//!
+//! let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+//! client.add_initializer(Tenant);
+//! ```
+#[cfg(feature = "net")]
+mod azure_vm;
+mod build_info;
+mod fingerprint;
+
+#[cfg(feature = "net")]
+pub use azure_vm::AzureVmMetadata;
+pub use build_info::BuildInfo;
+pub use fingerprint::ExceptionFingerprint;
+
+use crate::{telemetry::Telemetry, TelemetryConfig, TelemetryContext};
+
+/// Enriches telemetry items at the time they are tracked.
+pub trait TelemetryInitializer: Send + Sync {
+    /// Initializes a telemetry item and a copy of client context the item is going to be submitted with.
+    fn initialize(&self, telemetry: &mut dyn Telemetry, context: &mut TelemetryContext);
+}
+
+/// Runs all initializers in order they were added.
+pub(crate) fn initialize(
+    initializers: &[Box<dyn TelemetryInitializer>],
+    telemetry: &mut dyn Telemetry,
+    context: &mut TelemetryContext,
+) {
+    for initializer in initializers {
+        initializer.initialize(telemetry, context);
+    }
+}
+
+/// Returns initializers a client starts with according to the configuration.
+pub(crate) fn from_config(config: &TelemetryConfig) -> Vec<Box<dyn TelemetryInitializer>> {
+    #[cfg(feature = "net")]
+    if config.azure_vm_metadata() {
+        return vec![Box::new(AzureVmMetadata::new())];
+    }
+    let _ = config;
+    Vec::new()
+}
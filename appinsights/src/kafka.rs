@@ -0,0 +1,194 @@
+//! Instrumentation of Kafka producers and consumers.
+//!
+//! [`KafkaInstrumentation`](struct.KafkaInstrumentation.html) correlates a stream processing pipeline
+//! across services:
+//! * [`produce`](struct.KafkaInstrumentation.html#method.produce) submits a message delivery as a
+//!   [`RemoteDependencyTelemetry`](../telemetry/struct.RemoteDependencyTelemetry.html) item with
+//!   `Queue Message (Kafka)` type and a topic as a target, and passes trace context headers to be
+//!   attached to the message;
+//! * [`consume`](struct.KafkaInstrumentation.html#method.consume) extracts a trace context from message
+//!   headers and submits processing of the message as a
+//!   [`RequestTelemetry`](../telemetry/struct.RequestTelemetry.html) item that is a child of the
+//!   producer's dependency call.
+//!
+//! The instrumentation does not depend on a particular version of [`rdkafka`](https://docs.rs/rdkafka)
+//! crate.
+//!
+//! ```rust, ignore
+//! use appinsights::{kafka::KafkaInstrumentation, TelemetryClient};
+//! use rdkafka::{
+//!     message::{Header, Headers, OwnedHeaders},
+//!     producer::FutureRecord,
+//!     Message,
+//! };
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! let kafka = KafkaInstrumentation::new(client, "broker:9092");
+//!
+//! // producer
+//! kafka
+//!     .produce("orders", None, |headers| {
+//!         let headers = headers.iter().fold(OwnedHeaders::new(), |all, (key, value)| {
+//!             all.insert(Header { key, value: Some(value) })
+//!         });
+//!         let record = FutureRecord::to("orders").key("42").payload("order").headers(headers);
+//!         async move { producer.send(record, Duration::from_secs(0)).await.map_err(|(err, _)| err) }
+//!     })
+//!     .await?;
+//!
+//! // consumer
+//! let message = consumer.recv().await?;
+//! let mut headers = http::HeaderMap::new();
+//! for header in message.headers().into_iter().flat_map(|headers| headers.iter()) {
+//!     if let (Ok(key), Some(Ok(value))) = (header.key.parse(), header.value.map(|value| value.try_into())) {
+//!         headers.insert::<http::header::HeaderName>(key, value);
+//!     }
+//! }
+//! kafka.consume(message.topic(), &headers, |context| async move { process(message, context).await }).await?;
+//! ```
+use std::{fmt::Display, future::Future, sync::Arc};
+
+use crate::{
+    correlation::{Extractor, TraceContext},
+    messaging, TelemetryClient,
+};
+
+/// Dependency type of Kafka messages.
+pub const DEPENDENCY_TYPE: &str = "Queue Message (Kafka)";
+
+/// Tracks Kafka messages as dependency calls when they are produced and as requests when they are consumed.
+pub struct KafkaInstrumentation {
+    client: Arc<TelemetryClient>,
+    broker: String,
+}
+
+impl KafkaInstrumentation {
+    /// Creates a new instrumentation for a Kafka cluster with specified comma-separated list of
+    /// bootstrap servers.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>, bootstrap_servers: &str) -> Self {
+        let broker = bootstrap_servers
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        Self {
+            client: client.into(),
+            broker,
+        }
+    }
+
+    /// Produces a message to a topic and submits it as a dependency call. A function receives trace
+    /// context headers to attach to the message and returns a future that delivers the message.
+    /// A message becomes a child of specified parent operation or starts a new trace if there is no parent.
+    pub async fn produce<F, Fut, T, E>(&self, topic: &str, parent: Option<&TraceContext>, produce: F) -> Result<T, E>
+    where
+        F: FnOnce(Vec<(String, String)>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let name = format!("Produce {}", topic);
+        messaging::produce(&self.client, DEPENDENCY_TYPE, name, topic.into(), parent, produce).await
+    }
+
+    /// Processes a message consumed from a topic and submits it as a request. A trace context is extracted
+    /// from message headers, so processing of the message continues the trace of its producer. A function
+    /// receives a trace context of the processing operation to correlate telemetry items submitted while
+    /// processing or to produce messages further down the pipeline.
+    pub async fn consume<F, Fut, T, E>(&self, topic: &str, headers: &impl Extractor, process: F) -> Result<T, E>
+    where
+        F: FnOnce(TraceContext) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let name = format!("Process {}", topic);
+        let uri = format!("kafka://{}/{}", self.broker, topic);
+        messaging::consume(&self.client, name, uri, headers, process).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use http::HeaderMap;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        correlation::Injector,
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_correlates_consumer_to_producer() {
+        let events = Arc::new(SegQueue::default());
+        let kafka = KafkaInstrumentation::new(create_client(events.clone()), "Broker:9092, other:9092");
+
+        let mut message = HeaderMap::new();
+        kafka
+            .produce("orders", None, |headers| {
+                for (key, value) in headers {
+                    message.set(&key, value);
+                }
+                async { Ok::<_, String>(()) }
+            })
+            .await
+            .unwrap();
+
+        let producer = events.pop().unwrap();
+        let (dependency_id, operation_id) = match producer.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "Produce orders");
+                assert_eq!(data.type_, Some("Queue Message (Kafka)".into()));
+                assert_eq!(data.target, Some("orders".into()));
+                assert_eq!(data.success, Some(true));
+                (data.id.unwrap(), producer.tags.unwrap()["ai.operation.id"].clone())
+            }
+            data => panic!("unexpected data {:?}", data),
+        };
+
+        let result = kafka
+            .consume("orders", &message, |context| async move {
+                Err::<(), _>(format!("unable to process in {}", context.trace_id()))
+            })
+            .await;
+        assert!(result.is_err());
+
+        let consumer = events.pop().unwrap();
+        let tags = consumer.tags.unwrap();
+        assert_eq!(tags.get("ai.operation.id"), Some(&operation_id));
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&dependency_id));
+        assert_matches!(
+            consumer.data,
+            Some(Base::Data(Data::RequestData(data)))
+                if data.name == Some("Process orders".into())
+                    && data.url == Some("kafka://broker:9092/orders".into())
+                    && !data.success
+                    && data.properties.as_ref().unwrap().get("error") == Some(&format!("unable to process in {}", operation_id))
+        );
+    }
+
+    #[tokio::test]
+    async fn it_starts_new_trace_for_message_without_context() {
+        let events = Arc::new(SegQueue::default());
+        let kafka = KafkaInstrumentation::new(create_client(events.clone()), "broker:9092");
+
+        kafka
+            .consume("orders", &HeaderMap::new(), |_| async { Ok::<_, String>(()) })
+            .await
+            .unwrap();
+
+        let consumer = events.pop().unwrap();
+        let tags = consumer.tags.unwrap();
+        assert!(tags.contains_key("ai.operation.id"));
+        assert!(!tags.contains_key("ai.operation.parentId"));
+        assert_matches!(consumer.data, Some(Base::Data(Data::RequestData(data))) if data.success);
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
@@ -9,6 +9,7 @@
 //! The following Application Insights telemetry items are supported:
 //! * [Availability telemetry](telemetry/struct.AvailabilityTelemetry.html)
 //! * [Event telemetry](telemetry/struct.EventTelemetry.html)
+//! * [Exception telemetry](telemetry/struct.ExceptionTelemetry.html)
 //! * [Page view telemetry](telemetry/struct.PageViewTelemetry.html)
 //! * [Remote dependency telemetry](telemetry/struct.RemoteDependencyTelemetry.html)
 //! * [Request telemetry](telemetry/struct.RequestTelemetry.html)
@@ -29,8 +30,8 @@
 //! ## Examples
 //!
 //! 1. Create an new instance of [`TelemetryClient`](struct.TelemetryClient.html) with an
-//! Instrumentation Key and default settings. To get more control over client behavior please visit
-//! [`TelemetryConfig`](struct.TelemetryConfig.html).
+//!    Instrumentation Key and default settings. To get more control over client behavior please visit
+//!    [`TelemetryConfig`](struct.TelemetryConfig.html).
 //! 2. Send an event telemetry to the Application Insights service.
 //!
 //! ```rust
@@ -80,6 +81,7 @@
 //! * [track_event](struct.TelemetryClient.html#method.track_event) to log user action with the event name.
 //! * [track_trace](struct.TelemetryClient.html#method.track_trace) to log a trace message with severity level.
 //! * [track_metric](struct.TelemetryClient.html#method.track_metric) to log a numeric value that is not specified with a specific event.
+//! * [track_value](struct.TelemetryClient.html#method.track_value) to aggregate numeric values locally and log them as a single metric per interval.
 //! * [track_request](struct.TelemetryClient.html#method.track_request) to log a HTTP request with the specified method, URL, duration and response code.
 //! * [track_remote_dependency](struct.TelemetryClient.html#method.track_remote_dependency) to log a dependency with the specified name, type, target, and success status.
 //! * [track_availability](struct.TelemetryClient.html#method.track_availability) to log an availability test result with the specified test name, duration, and success status.
@@ -160,37 +162,98 @@
 //! worker stores it in memory, so when application crashes the data will be lost. Luckily SDK
 //! provides several convenient methods to deal with this issue.
 //! * [`flush_channel`](struct.TelemetryClient.html#method.flush_channel) will trigger telemetry submission
-//! as soon as possible. It returns immediately and telemetry is no guaranteed to be sent.
+//!   as soon as possible. It returns immediately and telemetry is no guaranteed to be sent.
 //! * [`close_channel`](struct.TelemetryClient.html#method.close_channel) will cause the channel to
-//! stop accepting any new telemetry items, submit all pending ones, block current task and
-//! wait until data will be sent at most once. If telemetry submission fails, it will not retry.
-//! This method consumes the value of client so it makes impossible to use a client with close channel.
+//!   stop accepting any new telemetry items, submit all pending ones, block current task and
+//!   wait until data will be sent at most once. If telemetry submission fails, it will not retry.
+//!   This method consumes the value of client so it makes impossible to use a client with close channel.
 //! * [`terminate`](struct.TelemetryClient.html#method.terminate) will trigger termination of submission flow, all pending items discarded and
-//! current task will be blocked until all resources freed.
+//!   current task will be blocked until all resources freed.
 #![deny(unused_extern_crates)]
 #![deny(missing_docs)]
 
+mod aggregator;
+#[cfg(any(feature = "jemalloc", feature = "mimalloc"))]
+pub mod allocator;
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod availability;
+#[cfg(feature = "azure")]
+pub mod azure;
+
 #[cfg(feature = "blocking")]
 pub mod blocking;
+pub mod cgroup;
+pub mod command;
 
 mod channel;
 
 mod client;
-pub use client::TelemetryClient;
+pub use client::{ScopedClient, TelemetryClient, TrackedItem};
 
 mod config;
 #[doc(inline)]
-pub use config::TelemetryConfig;
+pub use config::{ConfigError, ConnectionStringError, ProbeError, TelemetryConfig};
 
 mod context;
-pub use context::TelemetryContext;
+pub use context::{TelemetryContext, TENANT_PROPERTY};
+
+#[allow(missing_docs)]
+pub mod contracts;
+pub mod correlation;
+#[cfg(target_os = "linux")]
+pub mod descriptors;
+pub mod diagnostics;
+#[cfg(feature = "faas")]
+pub mod faas;
+#[cfg(feature = "functions")]
+pub mod functions;
+
+pub mod gateway;
+
+mod global;
+pub use global::{
+    flush, global, init, init_with, registry, track, track_event, track_metric, track_trace, InitError, Registry,
+};
 
-mod contracts;
+#[cfg(feature = "hyper")]
+pub mod hyper;
+pub mod initializer;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod lifecycle;
+pub mod log;
+#[cfg(any(feature = "amqp", feature = "kafka"))]
+mod messaging;
+#[cfg(feature = "mongodb")]
+pub mod mongodb;
+mod otlp;
+pub mod privacy;
+pub mod processor;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod retry;
+#[cfg(feature = "rocket")]
+pub mod rocket;
+pub mod server;
+#[cfg(feature = "slog")]
+pub mod slog;
+pub mod task;
+pub mod tee;
 pub mod telemetry;
-mod time;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod time;
 mod timeout;
+#[cfg(feature = "tracing")]
+pub mod tracing;
 mod transmitter;
+pub mod transport;
 mod uuid;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+#[cfg(feature = "warp")]
+pub mod warp;
 
 use std::error::Error;
 
@@ -0,0 +1,242 @@
+//! Process start, exit and uptime telemetry.
+//!
+//! [`Lifecycle::start`](struct.Lifecycle.html#method.start) submits a `ProcessStarted` event and
+//! registers a `process_uptime_s` gauge sampled every aggregation interval. A marker file keeps track of
+//! runs of the process: it is written when the process starts and marked as exited when the returned
+//! [`LifecycleGuard`](struct.LifecycleGuard.html) is dropped. A start that finds a previous run not
+//! marked as exited reports it as an unclean exit and counts a restart, so crash loops stand out in the
+//! portal as a growing `restart_count` measurement.
+//!
+//! ```rust, no_run
+//! use appinsights::{lifecycle::Lifecycle, TelemetryClient};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! let _guard = Lifecycle::new("/var/run/my-app.telemetry").start(&client);
+//!
+//! // run the application until it exits
+//! # }
+//! ```
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::Instant,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::warn;
+
+use crate::{telemetry::EventTelemetry, telemetry::Telemetry, time, TelemetryClient};
+
+/// Tracks starts and exits of the process with a marker file.
+#[derive(Debug, Clone)]
+pub struct Lifecycle {
+    marker: PathBuf,
+}
+
+/// How the previous run of the process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviousExit {
+    /// There is no record of a previous run.
+    Unknown,
+
+    /// The previous run exited cleanly.
+    Clean,
+
+    /// The previous run ended without marking its exit, e.g. it crashed or was killed.
+    Unclean,
+}
+
+impl PreviousExit {
+    fn as_str(self) -> &'static str {
+        match self {
+            PreviousExit::Unknown => "unknown",
+            PreviousExit::Clean => "clean",
+            PreviousExit::Unclean => "unclean",
+        }
+    }
+}
+
+/// A record of a run of the process kept in a marker file.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Run {
+    pid: Option<u32>,
+    started: Option<String>,
+    exited: bool,
+    restarts: u64,
+}
+
+impl Run {
+    fn parse(content: &str) -> Self {
+        let fields: BTreeMap<_, _> = content
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        Self {
+            pid: fields.get("pid").and_then(|pid| pid.parse().ok()),
+            started: fields.get("started").map(|started| started.to_string()),
+            exited: fields.get("exited") == Some(&"true"),
+            restarts: fields
+                .get("restarts")
+                .and_then(|count| count.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn write(&self, path: &Path) {
+        let content = format!(
+            "pid={}\nstarted={}\nexited={}\nrestarts={}\n",
+            self.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            self.started.as_deref().unwrap_or_default(),
+            self.exited,
+            self.restarts
+        );
+        if let Err(err) = fs::write(path, content) {
+            warn!("Unable to write process marker {}: {}", path.display(), err);
+        }
+    }
+}
+
+impl Lifecycle {
+    /// Creates a new lifecycle tracker that keeps a record of runs in specified marker file.
+    pub fn new(marker: impl Into<PathBuf>) -> Self {
+        Self { marker: marker.into() }
+    }
+
+    /// Records a start of the process, submits a `ProcessStarted` event with a start time and an outcome
+    /// of the previous run and starts sampling uptime. The exit is marked as clean when the guard is
+    /// dropped.
+    pub fn start(self, client: &Arc<TelemetryClient>) -> LifecycleGuard {
+        let started = time::now();
+        let previous = fs::read_to_string(&self.marker)
+            .ok()
+            .map(|content| Run::parse(&content));
+        let (previous_exit, restarts) = match &previous {
+            None => (PreviousExit::Unknown, 0),
+            Some(run) if run.exited => (PreviousExit::Clean, 0),
+            Some(run) => (PreviousExit::Unclean, run.restarts + 1),
+        };
+
+        let run = Run {
+            pid: Some(process::id()),
+            started: Some(started.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            exited: false,
+            restarts,
+        };
+        run.write(&self.marker);
+
+        client.track(started_event(started, previous_exit, previous.as_ref(), restarts));
+
+        let uptime = Instant::now();
+        client.register_gauge("process_uptime_s", move || uptime.elapsed().as_secs_f64());
+
+        LifecycleGuard {
+            marker: self.marker,
+            run,
+        }
+    }
+}
+
+/// Builds a `ProcessStarted` event.
+fn started_event(
+    started: DateTime<Utc>,
+    previous_exit: PreviousExit,
+    previous: Option<&Run>,
+    restarts: u64,
+) -> EventTelemetry {
+    let mut event = EventTelemetry::new("ProcessStarted");
+    let properties = event.properties_mut();
+    properties.insert("pid".into(), process::id().to_string());
+    properties.insert(
+        "start_time".into(),
+        started.to_rfc3339_opts(SecondsFormat::Millis, true),
+    );
+    properties.insert("previous_exit".into(), previous_exit.as_str().into());
+    if let Some(pid) = previous.and_then(|run| run.pid) {
+        properties.insert("previous_pid".into(), pid.to_string());
+    }
+    if let Some(started) = previous.and_then(|run| run.started.clone()) {
+        properties.insert("previous_start_time".into(), started);
+    }
+    event.measurements_mut().insert("restart_count".into(), restarts as f64);
+    event
+}
+
+/// Marks a clean exit of the process in the marker file when dropped.
+#[derive(Debug)]
+pub struct LifecycleGuard {
+    marker: PathBuf,
+    run: Run,
+}
+
+impl Drop for LifecycleGuard {
+    fn drop(&mut self) {
+        self.run.exited = true;
+        self.run.restarts = 0;
+        self.run.write(&self.marker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_counts_restarts_after_unclean_exits() {
+        let marker = std::env::temp_dir().join(format!("appinsights-lifecycle-{}", uuid::Uuid::new_v4()));
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = Arc::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+
+        let first = Lifecycle::new(&marker).start(&client);
+        std::mem::forget(first);
+        let second = Lifecycle::new(&marker).start(&client);
+        drop(second);
+        let _third = Lifecycle::new(&marker).start(&client);
+        fs::remove_file(&marker).unwrap();
+
+        let starts: Vec<_> = std::iter::from_fn(|| events.pop()).map(started).collect();
+        assert_eq!(
+            starts,
+            vec![("unknown".into(), 0.0), ("unclean".into(), 1.0), ("clean".into(), 0.0)]
+        );
+    }
+
+    #[test]
+    fn it_parses_marker() {
+        let run = Run::parse("pid=42\nstarted=2019-01-02T03:04:05.000Z\nexited=false\nrestarts=3\n");
+
+        assert_eq!(
+            run,
+            Run {
+                pid: Some(42),
+                started: Some("2019-01-02T03:04:05.000Z".into()),
+                exited: false,
+                restarts: 3,
+            }
+        );
+    }
+
+    fn started(envelope: Envelope) -> (String, f64) {
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => (
+                data.properties.unwrap_or_default()["previous_exit"].clone(),
+                data.measurements.unwrap_or_default()["restart_count"],
+            ),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+}
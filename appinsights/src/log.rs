@@ -0,0 +1,274 @@
+//! Integration with [`log`](https://docs.rs/log) crate.
+//!
+//! [`init_with`](fn.init_with.html) installs a logger that works like
+//! [`env_logger`](https://docs.rs/env_logger): records are filtered with directives of `RUST_LOG`
+//! environment variable and printed to the standard error. Every printed record is also submitted as a
+//! [`TraceTelemetry`](../telemetry/struct.TraceTelemetry.html) item with the target, the module path
+//! and the location of the record as properties. Migrating from `env_logger` takes a single line.
+//!
+//! Records of the SDK itself and of HTTP crates it sends telemetry with, like `hyper` and `reqwest`, are
+//! printed but not submitted, because sending them would log and submit more records without end.
+//!
+//! ```rust, no_run
+//! use appinsights::TelemetryClient;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! // instead of env_logger::init()
+//! appinsights::log::init_with(client).expect("logger is not installed yet");
+//!
+//! log::info!("Connected to a gateway");
+//! # }
+//! ```
+use std::{
+    env,
+    io::{self, Write},
+    sync::Arc,
+};
+
+use ::log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{
+    telemetry::{SeverityLevel, Telemetry, TraceTelemetry},
+    TelemetryClient,
+};
+
+/// Name of an environment variable with filter directives.
+pub const FILTER_ENV: &str = "RUST_LOG";
+
+/// Targets whose records are never submitted.
+const INTERNAL_TARGETS: &[&str] = &[
+    "appinsights",
+    "hyper",
+    "reqwest",
+    "h2",
+    "rustls",
+    "tokio_util",
+    "want",
+    "mio",
+];
+
+/// Installs a logger that prints records to the standard error and submits them with specified client.
+/// Records are filtered with directives of `RUST_LOG` environment variable, and only errors are logged
+/// when it is not set. It fails if a logger is already installed.
+pub fn init_with(client: impl Into<Arc<TelemetryClient>>) -> Result<(), SetLoggerError> {
+    let filters = env::var(FILTER_ENV).unwrap_or_default();
+    TelemetryLogger::new(client).filters(&filters).install()
+}
+
+/// A logger that prints records to the standard error and submits them as trace telemetry.
+pub struct TelemetryLogger {
+    client: Arc<TelemetryClient>,
+    directives: Vec<Directive>,
+    console: bool,
+}
+
+/// Enables records of a target and its submodules up to a level, or of all targets without a target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directive {
+    target: Option<String>,
+    level: LevelFilter,
+}
+
+impl TelemetryLogger {
+    /// Creates a new logger that submits records with specified client. Only errors are logged until
+    /// filters are set.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            directives: parse(""),
+            console: true,
+        }
+    }
+
+    /// Sets filter directives in `RUST_LOG` format, e.g. `info,my_app::db=debug,hyper=off`. The most
+    /// specific directive for a target of a record wins.
+    pub fn filters(mut self, filters: &str) -> Self {
+        self.directives = parse(filters);
+        self
+    }
+
+    /// Enables or disables printing records to the standard error. Enabled by default.
+    pub fn console(mut self, console: bool) -> Self {
+        self.console = console;
+        self
+    }
+
+    /// Installs the logger. It fails if a logger is already installed.
+    pub fn install(self) -> Result<(), SetLoggerError> {
+        let max_level = self.directives.iter().map(|directive| directive.level).max();
+        ::log::set_boxed_logger(Box::new(self))?;
+        ::log::set_max_level(max_level.unwrap_or(LevelFilter::Off));
+        Ok(())
+    }
+
+    /// Returns a maximum level enabled for a target.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|directive| match &directive.target {
+                Some(prefix) => is_module(target, prefix),
+                None => true,
+            })
+            .max_by_key(|directive| directive.target.as_ref().map_or(0, |target| target.len() + 1))
+            .map_or(LevelFilter::Off, |directive| directive.level)
+    }
+}
+
+impl Log for TelemetryLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if self.console {
+            let _ = writeln!(
+                io::stderr(),
+                "[{} {:<5} {}] {}",
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+
+        if INTERNAL_TARGETS
+            .iter()
+            .any(|internal| is_module(record.target(), internal))
+        {
+            return;
+        }
+        let mut telemetry = TraceTelemetry::new(record.args().to_string(), severity(record.level()));
+        let properties = telemetry.properties_mut();
+        properties.insert("target".into(), record.target().into());
+        if let Some(module) = record.module_path() {
+            properties.insert("module".into(), module.into());
+        }
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            properties.insert("location".into(), format!("{}:{}", file, line));
+        }
+        self.client.track(telemetry);
+    }
+
+    fn flush(&self) {
+        self.client.flush_channel();
+    }
+}
+
+/// Maps a log level to severity level of a trace telemetry.
+fn severity(level: Level) -> SeverityLevel {
+    match level {
+        Level::Trace | Level::Debug => SeverityLevel::Verbose,
+        Level::Info => SeverityLevel::Information,
+        Level::Warn => SeverityLevel::Warning,
+        Level::Error => SeverityLevel::Error,
+    }
+}
+
+/// Returns `true` if a target is a module or a submodule of specified one.
+fn is_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Parses filter directives, ignoring malformed ones and regular expression filters after `/`.
+fn parse(filters: &str) -> Vec<Directive> {
+    let filters = filters.split('/').next().unwrap_or_default();
+    let mut directives: Vec<_> = filters
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .filter_map(|directive| match directive.split_once('=') {
+            Some((target, level)) => Some(Directive {
+                target: Some(target.trim().to_string()),
+                level: level.trim().parse().ok()?,
+            }),
+            None => match directive.parse() {
+                Ok(level) => Some(Directive { target: None, level }),
+                Err(_) => Some(Directive {
+                    target: Some(directive.to_string()),
+                    level: LevelFilter::Trace,
+                }),
+            },
+        })
+        .collect();
+    if directives.is_empty() {
+        directives.push(Directive {
+            target: None,
+            level: LevelFilter::Error,
+        });
+    }
+    directives
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data},
+        TelemetryConfig,
+    };
+
+    #[test_case("",                         "my_app",      LevelFilter::Error  ; "default")]
+    #[test_case("info",                     "my_app",      LevelFilter::Info   ; "global level")]
+    #[test_case("info,my_app::db=debug",    "my_app::db",  LevelFilter::Debug  ; "module level")]
+    #[test_case("info,my_app::db=debug",    "my_app::dbx", LevelFilter::Info   ; "not a submodule")]
+    #[test_case("my_app",                   "my_app",      LevelFilter::Trace  ; "target only")]
+    #[test_case("warn,my_app=off",          "my_app::db",  LevelFilter::Off    ; "disabled")]
+    #[test_case("debug/connected",          "my_app",      LevelFilter::Debug  ; "regex ignored")]
+    fn it_parses_filters(filters: &str, target: &str, expected: LevelFilter) {
+        let logger = TelemetryLogger::new(create_client(Arc::default())).filters(filters);
+
+        assert_eq!(logger.level(target), expected);
+    }
+
+    #[tokio::test]
+    async fn it_submits_enabled_records() {
+        let events = Arc::new(SegQueue::default());
+        let logger = TelemetryLogger::new(create_client(events.clone()))
+            .filters("info")
+            .console(false);
+
+        for (target, level) in [
+            ("my_app", Level::Warn),
+            ("my_app", Level::Debug),
+            ("hyper::proto", Level::Info),
+        ] {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("Connected"))
+                    .level(level)
+                    .target(target)
+                    .module_path(Some("my_app::gateway"))
+                    .build(),
+            );
+        }
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::MessageData(data)))
+                if data.message == "Connected"
+                    && data.severity_level == Some(crate::contracts::SeverityLevel::Warning)
+                    && data.properties.as_ref().unwrap()["target"] == "my_app"
+                    && data.properties.as_ref().unwrap()["module"] == "my_app::gateway"
+        );
+        assert!(events.is_empty());
+    }
+
+    fn create_client(events: Arc<SegQueue<crate::contracts::Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
@@ -0,0 +1,91 @@
+use std::{fmt::Display, future::Future};
+
+use http::{Method, Uri};
+
+use crate::{
+    correlation::{self, Extractor, TraceContext},
+    telemetry::{RemoteDependencyTelemetry, RequestTelemetry, Telemetry},
+    time::Stopwatch,
+    TelemetryClient,
+};
+
+/// Sends a message with trace context headers and submits it as a dependency call.
+pub(crate) async fn produce<F, Fut, T, E>(
+    client: &TelemetryClient,
+    dependency_type: &str,
+    name: String,
+    target: String,
+    parent: Option<&TraceContext>,
+    produce: F,
+) -> Result<T, E>
+where
+    F: FnOnce(Vec<(String, String)>) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let context = TraceContext::start(parent, &client.context().ids);
+    let mut headers = Vec::new();
+    correlation::inject(&context, &mut |key: &str, value: String| {
+        headers.push((key.to_string(), value))
+    });
+
+    let started = Stopwatch::start();
+    let result = produce(headers).await;
+
+    let mut telemetry =
+        RemoteDependencyTelemetry::new(name, dependency_type, started.elapsed(), target, result.is_ok());
+    telemetry.set_timestamp(started.started_at());
+    telemetry.set_id(context.span_id());
+    if let Err(err) = &result {
+        telemetry.properties_mut().insert("error".into(), err.to_string());
+    }
+    correlate(&mut telemetry, parent, &context);
+    client.track(telemetry);
+
+    result
+}
+
+/// Processes a received message in the trace context extracted from its headers and submits it as a request.
+pub(crate) async fn consume<F, Fut, T, E>(
+    client: &TelemetryClient,
+    name: String,
+    uri: String,
+    headers: &impl Extractor,
+    process: F,
+) -> Result<T, E>
+where
+    F: FnOnce(TraceContext) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let parent = correlation::extract(headers);
+    let context = TraceContext::start(parent.as_ref(), &client.context().ids);
+
+    let started = Stopwatch::start();
+    let result = process(context.clone()).await;
+
+    let uri = uri.parse().unwrap_or_else(|_| Uri::default());
+    let mut telemetry = RequestTelemetry::new(Method::GET, uri, started.elapsed(), "0");
+    telemetry.set_timestamp(started.started_at());
+    telemetry.set_name(name);
+    telemetry.set_id(context.span_id());
+    telemetry.set_success(result.is_ok());
+    if let Err(err) = &result {
+        telemetry.properties_mut().insert("error".into(), err.to_string());
+    }
+    correlate(&mut telemetry, parent.as_ref(), &context);
+    client.track(telemetry);
+
+    result
+}
+
+/// Makes a telemetry item a child of a parent operation or a root of a new trace if there is no parent.
+fn correlate<E: Telemetry>(telemetry: &mut E, parent: Option<&TraceContext>, context: &TraceContext) {
+    match parent {
+        Some(parent) => parent.correlate(telemetry),
+        None => telemetry
+            .tags_mut()
+            .operation_mut()
+            .set_id(context.trace_id().to_string()),
+    }
+}
@@ -0,0 +1,210 @@
+//! Dependency tracking for MongoDB commands.
+//!
+//! [`MongoCommandTracker`](struct.MongoCommandTracker.html) turns MongoDB command monitoring events into
+//! [`RemoteDependencyTelemetry`](../telemetry/struct.RemoteDependencyTelemetry.html) items. Each command
+//! is submitted with a name containing a collection and a command name, e.g. `users.find`, the server
+//! address as a target, duration and success status. Command documents are never submitted, as they may
+//! contain sensitive data.
+//!
+//! The tracker does not depend on a particular version of [`mongodb`](https://docs.rs/mongodb) crate.
+//! It is fed from a command event handler registered with a client.
+//!
+//! ```rust, ignore
+//! use std::sync::Arc;
+//!
+//! use appinsights::{mongodb::MongoCommandTracker, TelemetryClient};
+//! use mongodb::{
+//!     event::command::{CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent},
+//!     options::ClientOptions,
+//!     Client,
+//! };
+//!
+//! struct Handler(MongoCommandTracker);
+//!
+//! impl CommandEventHandler for Handler {
+//!     fn handle_command_started_event(&self, event: CommandStartedEvent) {
+//!         let collection = event.command.get_str(&event.command_name).ok();
+//!         let address = event.connection.address.to_string();
+//!         self.0.started(event.request_id, &event.command_name, collection, &address);
+//!     }
+//!
+//!     fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+//!         self.0.succeeded(event.request_id, event.duration);
+//!     }
+//!
+//!     fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+//!         self.0.failed(event.request_id, event.duration, event.failure);
+//!     }
+//! }
+//!
+//! # async fn run() -> mongodb::error::Result<()> {
+//! let telemetry = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let mut options = ClientOptions::parse("mongodb://db.local:27017").await?;
+//! options.command_event_handler = Some(Arc::new(Handler(MongoCommandTracker::new(telemetry))));
+//! let client = Client::with_options(options)?;
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration as StdDuration,
+};
+
+use crate::{
+    telemetry::{RemoteDependencyTelemetry, Telemetry},
+    TelemetryClient,
+};
+
+/// Dependency type of MongoDB commands.
+pub const DEPENDENCY_TYPE: &str = "mongodb";
+
+/// Maximum number of commands waiting for completion. Commands started above the limit are not tracked.
+const MAX_PENDING: usize = 1000;
+
+/// Tracks MongoDB commands as dependency calls to a MongoDB server.
+pub struct MongoCommandTracker {
+    client: Arc<TelemetryClient>,
+    pending: Mutex<HashMap<i32, Command>>,
+}
+
+struct Command {
+    name: String,
+    command: String,
+    target: String,
+}
+
+impl MongoCommandTracker {
+    /// Creates a new tracker that submits commands with specified client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Records a start of a command. A command is submitted when it succeeds or fails with the same
+    /// request id.
+    pub fn started(&self, request_id: i32, command: &str, collection: Option<&str>, address: &str) {
+        let name = match collection {
+            Some(collection) => format!("{}.{}", collection, command),
+            None => command.to_string(),
+        };
+
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending.len() < MAX_PENDING {
+            pending.insert(
+                request_id,
+                Command {
+                    name,
+                    command: command.to_string(),
+                    target: address.to_lowercase(),
+                },
+            );
+        }
+    }
+
+    /// Submits a successfully completed command.
+    pub fn succeeded(&self, request_id: i32, duration: StdDuration) {
+        self.complete(request_id, duration, None::<String>);
+    }
+
+    /// Submits a failed command with an error as an `error` property.
+    pub fn failed(&self, request_id: i32, duration: StdDuration, error: impl Display) {
+        self.complete(request_id, duration, Some(error));
+    }
+
+    fn complete<E: Display>(&self, request_id: i32, duration: StdDuration, error: Option<E>) {
+        let command = self
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&request_id);
+
+        if let Some(command) = command {
+            let mut telemetry = RemoteDependencyTelemetry::new(
+                command.name,
+                DEPENDENCY_TYPE,
+                duration,
+                command.target,
+                error.is_none(),
+            );
+            telemetry.set_data(command.command);
+            if let Some(error) = error {
+                telemetry.properties_mut().insert("error".into(), error.to_string());
+            }
+
+            self.client.track(telemetry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[test]
+    fn it_submits_succeeded_command() {
+        let events = Arc::new(SegQueue::default());
+        let tracker = MongoCommandTracker::new(create_client(events.clone()));
+
+        tracker.started(1, "find", Some("users"), "DB.local:27017");
+        tracker.succeeded(1, StdDuration::from_millis(15));
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RemoteDependencyData(data)))
+                if data.name == "users.find"
+                    && data.data == Some("find".into())
+                    && data.type_ == Some("mongodb".into())
+                    && data.target == Some("db.local:27017".into())
+                    && data.duration == "0.00:00:00.0150000"
+                    && data.success == Some(true)
+        );
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_submits_failed_command() {
+        let events = Arc::new(SegQueue::default());
+        let tracker = MongoCommandTracker::new(create_client(events.clone()));
+
+        tracker.started(1, "ping", None, "db.local:27017");
+        tracker.started(2, "insert", Some("users"), "db.local:27017");
+        tracker.failed(2, StdDuration::from_millis(15), "duplicate key");
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RemoteDependencyData(data)))
+                if data.name == "users.insert"
+                    && data.success == Some(false)
+                    && data.properties.as_ref().unwrap().get("error") == Some(&"duplicate key".to_string())
+        );
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_ignores_unknown_command() {
+        let events = Arc::new(SegQueue::default());
+        let tracker = MongoCommandTracker::new(create_client(events.clone()));
+
+        tracker.succeeded(1, StdDuration::from_millis(15));
+
+        assert!(events.is_empty());
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
@@ -0,0 +1,424 @@
+//! Conversion of telemetry items to OpenTelemetry protocol (OTLP) requests in JSON encoding.
+//!
+//! Requests and dependency calls become spans, metrics become gauges or summaries, and everything else
+//! becomes log records. Items are grouped by a resource identified by cloud role and role instance tags,
+//! which map to `service.name` and `service.instance.id` resource attributes.
+use std::collections::BTreeMap;
+
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+use crate::{
+    contracts::{Base, Data, DataPoint, DataPointType, Envelope, SeverityLevel},
+    time::Duration,
+};
+
+const SPAN_KIND_SERVER: u8 = 2;
+const SPAN_KIND_CLIENT: u8 = 3;
+
+const STATUS_CODE_UNSET: u8 = 0;
+const STATUS_CODE_ERROR: u8 = 2;
+
+/// A kind of OTLP signal a telemetry item is exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Signal {
+    Traces,
+    Logs,
+    Metrics,
+}
+
+impl Signal {
+    /// Returns a signal a telemetry item is exported as.
+    pub(crate) fn of(envelope: &Envelope) -> Self {
+        match &envelope.data {
+            Some(Base::Data(Data::RequestData(_))) | Some(Base::Data(Data::RemoteDependencyData(_))) => Signal::Traces,
+            Some(Base::Data(Data::MetricData(_))) => Signal::Metrics,
+            _ => Signal::Logs,
+        }
+    }
+
+    /// Returns a path of OTLP/HTTP receiver that accepts this signal.
+    pub(crate) fn path(self) -> &'static str {
+        match self {
+            Signal::Traces => "v1/traces",
+            Signal::Logs => "v1/logs",
+            Signal::Metrics => "v1/metrics",
+        }
+    }
+
+    /// Creates an export request body of this signal with telemetry items grouped by resource.
+    pub(crate) fn export(self, items: &[Envelope]) -> Value {
+        let mut resources: BTreeMap<_, Vec<Value>> = BTreeMap::new();
+        for envelope in items {
+            let tags = envelope.tags.clone().unwrap_or_default();
+            let resource = (tag(&tags, "ai.cloud.role"), tag(&tags, "ai.cloud.roleInstance"));
+            let records = resources.entry(resource).or_default();
+            match self {
+                Signal::Traces => records.extend(span(envelope, &tags)),
+                Signal::Logs => records.extend(log_record(envelope, &tags)),
+                Signal::Metrics => records.extend(metrics(envelope)),
+            }
+        }
+
+        let (resources_key, scopes_key, records_key) = match self {
+            Signal::Traces => ("resourceSpans", "scopeSpans", "spans"),
+            Signal::Logs => ("resourceLogs", "scopeLogs", "logRecords"),
+            Signal::Metrics => ("resourceMetrics", "scopeMetrics", "metrics"),
+        };
+
+        let resources: Vec<_> = resources
+            .into_iter()
+            .map(|((role, instance), records)| {
+                json!({
+                    "resource": {
+                        "attributes": attributes(vec![("service.name", role), ("service.instance.id", instance)]),
+                    },
+                    scopes_key: [{
+                        "scope": { "name": "appinsights", "version": env!("CARGO_PKG_VERSION") },
+                        records_key: records,
+                    }],
+                })
+            })
+            .collect();
+
+        json!({ resources_key: resources })
+    }
+}
+
+/// Converts a request or a dependency call to a span.
+fn span(envelope: &Envelope, tags: &BTreeMap<String, String>) -> Option<Value> {
+    let (kind, id, name, duration, success, mut values) = match envelope.data.as_ref()? {
+        Base::Data(Data::RequestData(data)) => (
+            SPAN_KIND_SERVER,
+            Some(data.id.as_str()),
+            data.name.clone().unwrap_or_default(),
+            &data.duration,
+            data.success,
+            vec![
+                ("url.full", data.url.clone()),
+                ("http.response.status_code", Some(data.response_code.clone())),
+                ("appinsights.request.source", data.source.clone()),
+            ],
+        ),
+        Base::Data(Data::RemoteDependencyData(data)) => (
+            SPAN_KIND_CLIENT,
+            data.id.as_deref(),
+            data.name.clone(),
+            &data.duration,
+            data.success.unwrap_or(true),
+            vec![
+                ("appinsights.dependency.type", data.type_.clone()),
+                ("server.address", data.target.clone()),
+                ("appinsights.dependency.data", data.data.clone()),
+                ("appinsights.dependency.result_code", data.result_code.clone()),
+            ],
+        ),
+        _ => return None,
+    };
+    values.extend(custom_properties(envelope));
+
+    let start = unix_nanos(&envelope.time);
+    let duration = duration
+        .parse::<Duration>()
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    let trace_id = hex_id(tags.get("ai.operation.id").map(String::as_str), 32)
+        .unwrap_or_else(|| crate::uuid::new().simple().to_string());
+    let span_id = hex_id(id, 16).unwrap_or_else(|| crate::uuid::new().simple().to_string()[..16].to_string());
+
+    let mut span = json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "name": name,
+        "kind": kind,
+        "startTimeUnixNano": start.to_string(),
+        "endTimeUnixNano": (start + duration).to_string(),
+        "attributes": attributes(values),
+        "status": { "code": if success { STATUS_CODE_UNSET } else { STATUS_CODE_ERROR } },
+    });
+    if let Some(parent_id) = hex_id(tags.get("ai.operation.parentId").map(String::as_str), 16) {
+        span["parentSpanId"] = Value::String(parent_id);
+    }
+
+    Some(span)
+}
+
+/// Converts a trace, an event, an exception, an availability result or a page view to a log record.
+fn log_record(envelope: &Envelope, tags: &BTreeMap<String, String>) -> Option<Value> {
+    let (body, severity, mut values) = match envelope.data.as_ref()? {
+        Base::Data(Data::MessageData(data)) => (data.message.clone(), data.severity_level.clone(), Vec::new()),
+        Base::Data(Data::EventData(data)) => (data.name.clone(), None, vec![("event.name", Some(data.name.clone()))]),
+        Base::Data(Data::ExceptionData(data)) => {
+            let exception = data.exceptions.first();
+            let values = vec![
+                ("exception.type", exception.map(|exception| exception.type_name.clone())),
+                (
+                    "exception.message",
+                    exception.map(|exception| exception.message.clone()),
+                ),
+                (
+                    "exception.stacktrace",
+                    exception.and_then(|exception| exception.stack.clone()),
+                ),
+            ];
+            let message = exception.map(|exception| exception.message.clone()).unwrap_or_default();
+            (
+                message,
+                data.severity_level.clone().or(Some(SeverityLevel::Error)),
+                values,
+            )
+        }
+        Base::Data(Data::AvailabilityData(data)) => (
+            data.name.clone(),
+            None,
+            vec![
+                ("event.name", Some("appinsights.availability".into())),
+                ("appinsights.availability.success", Some(data.success.to_string())),
+                ("appinsights.availability.duration", Some(data.duration.clone())),
+                ("appinsights.availability.run_location", data.run_location.clone()),
+                ("appinsights.availability.message", data.message.clone()),
+            ],
+        ),
+        Base::Data(Data::PageViewData(data)) => (
+            data.name.clone(),
+            None,
+            vec![
+                ("event.name", Some("appinsights.page_view".into())),
+                ("url.full", data.url.clone()),
+                ("appinsights.page_view.duration", data.duration.clone()),
+            ],
+        ),
+        _ => return None,
+    };
+    values.extend(custom_properties(envelope));
+
+    let mut record = json!({
+        "timeUnixNano": unix_nanos(&envelope.time).to_string(),
+        "body": { "stringValue": body },
+        "attributes": attributes(values),
+    });
+    if let Some(severity) = severity {
+        let (number, text) = match severity {
+            SeverityLevel::Verbose => (5, "DEBUG"),
+            SeverityLevel::Information => (9, "INFO"),
+            SeverityLevel::Warning => (13, "WARN"),
+            SeverityLevel::Error => (17, "ERROR"),
+            SeverityLevel::Critical => (21, "FATAL"),
+        };
+        record["severityNumber"] = json!(number);
+        record["severityText"] = json!(text);
+    }
+    if let Some(trace_id) = hex_id(tags.get("ai.operation.id").map(String::as_str), 32) {
+        record["traceId"] = Value::String(trace_id);
+    }
+    if let Some(span_id) = hex_id(tags.get("ai.operation.parentId").map(String::as_str), 16) {
+        record["spanId"] = Value::String(span_id);
+    }
+
+    Some(record)
+}
+
+/// Converts data points of a metric to gauges for measurements and summaries for aggregations.
+fn metrics(envelope: &Envelope) -> Vec<Value> {
+    let data = match &envelope.data {
+        Some(Base::Data(Data::MetricData(data))) => data,
+        _ => return Vec::new(),
+    };
+
+    let time = unix_nanos(&envelope.time).to_string();
+    let properties: Vec<_> = data
+        .properties
+        .iter()
+        .flatten()
+        .map(|(key, value)| (key.as_str(), Some(value.clone())))
+        .collect();
+    let dimensions = attributes(properties);
+
+    data.metrics
+        .iter()
+        .map(|point| metric(point, &time, &dimensions))
+        .collect()
+}
+
+fn metric(point: &DataPoint, time: &str, dimensions: &Value) -> Value {
+    match point.kind {
+        Some(DataPointType::Aggregation) => {
+            let count = point.count.unwrap_or(1).max(0);
+            let quantiles: Vec<_> = vec![(0.0, point.min), (1.0, point.max)]
+                .into_iter()
+                .filter_map(|(quantile, value)| value.map(|value| json!({ "quantile": quantile, "value": value })))
+                .collect();
+            json!({
+                "name": point.name,
+                "summary": {
+                    "dataPoints": [{
+                        "timeUnixNano": time,
+                        "count": count.to_string(),
+                        "sum": point.value,
+                        "quantileValues": quantiles,
+                        "attributes": dimensions,
+                    }],
+                },
+            })
+        }
+        _ => json!({
+            "name": point.name,
+            "gauge": {
+                "dataPoints": [{
+                    "timeUnixNano": time,
+                    "asDouble": point.value,
+                    "attributes": dimensions,
+                }],
+            },
+        }),
+    }
+}
+
+/// Returns custom properties of a telemetry item as attribute values.
+fn custom_properties(envelope: &Envelope) -> Vec<(&str, Option<String>)> {
+    let properties = match &envelope.data {
+        Some(Base::Data(Data::AvailabilityData(data))) => &data.properties,
+        Some(Base::Data(Data::EventData(data))) => &data.properties,
+        Some(Base::Data(Data::ExceptionData(data))) => &data.properties,
+        Some(Base::Data(Data::MessageData(data))) => &data.properties,
+        Some(Base::Data(Data::MetricData(data))) => &data.properties,
+        Some(Base::Data(Data::PageViewData(data))) => &data.properties,
+        Some(Base::Data(Data::PageViewPerfData(data))) => &data.properties,
+        Some(Base::Data(Data::RemoteDependencyData(data))) => &data.properties,
+        Some(Base::Data(Data::RequestData(data))) => &data.properties,
+        None => return Vec::new(),
+    };
+
+    properties
+        .iter()
+        .flatten()
+        .map(|(key, value)| (key.as_str(), Some(value.clone())))
+        .collect()
+}
+
+/// Converts key-value pairs to OTLP attributes skipping missing values.
+fn attributes(values: Vec<(&str, Option<String>)>) -> Value {
+    let attributes: Vec<_> = values
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| json!({ "key": key, "value": { "stringValue": value } })))
+        .collect();
+    Value::Array(attributes)
+}
+
+fn tag(tags: &BTreeMap<String, String>, key: &str) -> Option<String> {
+    tags.get(key).cloned()
+}
+
+/// Returns a number of nanoseconds since Unix epoch of a time formatted as RFC 3339.
+fn unix_nanos(time: &str) -> u128 {
+    DateTime::parse_from_rfc3339(time).map_or(0, |time| {
+        time.timestamp().max(0) as u128 * 1_000_000_000 + u128::from(time.timestamp_subsec_nanos())
+    })
+}
+
+/// Returns a lowercase hex id of specified length taken from an id as is or from the last segment of a
+/// hierarchical id like `|4bf92f3577b34da6a3ce929d0e0e4736.00f067aa0ba902b7.`.
+fn hex_id(id: Option<&str>, len: usize) -> Option<String> {
+    let id = id?.trim_matches(|c| c == '|' || c == '.');
+    let id = id.rsplit('.').next()?;
+    if id.len() == len && id.bytes().all(|b| b.is_ascii_hexdigit()) && id.bytes().any(|b| b != b'0') {
+        Some(id.to_lowercase())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use http::Method;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, MetricTelemetry, Properties, RequestTelemetry, Telemetry, TraceTelemetry},
+        TelemetryContext,
+    };
+
+    #[test_case(Some("4bf92f3577b34da6a3ce929d0e0e4736"), 32, Some("4bf92f3577b34da6a3ce929d0e0e4736") ; "trace id")]
+    #[test_case(Some("|4bf92f3577b34da6a3ce929d0e0e4736.00F067AA0BA902B7."), 16, Some("00f067aa0ba902b7") ; "hierarchical id")]
+    #[test_case(Some("0000000000000000"), 16, None ; "zero id")]
+    #[test_case(Some("request"), 16, None ; "not hex")]
+    #[test_case(None, 16, None ; "no id")]
+    fn it_extracts_hex_ids(id: Option<&str>, len: usize, expected: Option<&str>) {
+        assert_eq!(hex_id(id, len).as_deref(), expected);
+    }
+
+    #[test]
+    fn it_exports_requests_as_spans() {
+        let mut request = RequestTelemetry::new(
+            Method::GET,
+            "https://example.com/orders".parse().unwrap(),
+            StdDuration::from_millis(250),
+            "500",
+        );
+        request
+            .tags_mut()
+            .operation_mut()
+            .set_id("4bf92f3577b34da6a3ce929d0e0e4736".into());
+        request
+            .tags_mut()
+            .operation_mut()
+            .set_parent_id("00f067aa0ba902b7".into());
+        let mut envelope = Envelope::from((context(), request));
+        envelope.time = "2020-01-01T00:00:00Z".into();
+
+        assert_eq!(Signal::of(&envelope), Signal::Traces);
+
+        let export = Signal::Traces.export(&[envelope]);
+        let resource = &export["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"],
+            json!([{ "key": "service.name", "value": { "stringValue": "orders" } }])
+        );
+
+        let span = &resource["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(span["name"], "GET https://example.com/orders");
+        assert_eq!(span["kind"], SPAN_KIND_SERVER);
+        assert_eq!(span["startTimeUnixNano"], "1577836800000000000");
+        assert_eq!(span["endTimeUnixNano"], "1577836800250000000");
+        assert_eq!(span["status"]["code"], STATUS_CODE_ERROR);
+    }
+
+    #[test]
+    fn it_exports_traces_as_log_records() {
+        let envelope = Envelope::from((
+            context(),
+            TraceTelemetry::new("message", crate::telemetry::SeverityLevel::Warning),
+        ));
+
+        assert_eq!(Signal::of(&envelope), Signal::Logs);
+
+        let export = Signal::Logs.export(&[envelope]);
+        let record = &export["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["body"]["stringValue"], "message");
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(record["severityText"], "WARN");
+    }
+
+    #[test]
+    fn it_exports_metrics_as_gauges() {
+        let envelope = Envelope::from((context(), MetricTelemetry::new("latency", 42.0)));
+
+        assert_eq!(Signal::of(&envelope), Signal::Metrics);
+
+        let export = Signal::Metrics.export(&[envelope]);
+        let metric = &export["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0];
+        assert_eq!(metric["name"], "latency");
+        assert_eq!(metric["gauge"]["dataPoints"][0]["asDouble"], 42.0);
+    }
+
+    fn context() -> TelemetryContext {
+        let mut tags = ContextTags::default();
+        tags.cloud_mut().set_role("orders".into());
+        TelemetryContext::new("instrumentation".into(), tags, Properties::default())
+    }
+}
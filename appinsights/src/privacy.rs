@@ -0,0 +1,269 @@
+//! Controls over user data submitted with telemetry.
+//!
+//! Telemetry items may carry identifiers of an end user: user ids, a session id and an IP address of
+//! a client device. A [`UserDataPolicy`](struct.UserDataPolicy.html) configured with
+//! [`TelemetryConfig`](../struct.TelemetryConfig.html) decides whether each of them is submitted as is,
+//! replaced with a salted SHA-256 hash or dropped, and whether a client IP address is masked to its
+//! network part. The policy is applied to every telemetry item after
+//! all processors, so neither a client context nor a processor can bring user data back.
+//!
+//! ```rust
+//! use appinsights::{privacy::{UserDataAction, UserDataPolicy}, TelemetryConfig};
+//!
+//! // keep users distinguishable without revealing their ids
+//! let policy = UserDataPolicy::default()
+//!     .user_id(UserDataAction::Hash)
+//!     .auth_user_id(UserDataAction::Hash)
+//!     .client_ip(UserDataAction::Drop)
+//!     .salt("<secret salt>");
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .user_data(policy)
+//!     .build();
+//! ```
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ring::digest::{digest, SHA256};
+
+use crate::contracts::Envelope;
+
+const USER_ID: &str = "ai.user.id";
+const AUTH_USER_ID: &str = "ai.user.authUserId";
+const SESSION_ID: &str = "ai.session.id";
+const CLIENT_IP: &str = "ai.location.ip";
+
+/// Describes how a user data field is submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserDataAction {
+    /// Submits a value as is.
+    Keep,
+
+    /// Replaces a value with a hex-encoded SHA-256 hash of a value prefixed with a salt.
+    Hash,
+
+    /// Removes a value from telemetry.
+    Drop,
+
+    /// Zeroes the last octet of an IPv4 address or the last 80 bits of an IPv6 address, so an address
+    /// identifies a network rather than a device. It is meant for a client IP address, any value that is
+    /// not an IP address is removed.
+    Mask,
+}
+
+/// Describes how user ids, a session id and a client IP address are submitted with telemetry. By default
+/// all of them are submitted as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserDataPolicy {
+    user_id: UserDataAction,
+    auth_user_id: UserDataAction,
+    session_id: UserDataAction,
+    client_ip: UserDataAction,
+    salt: String,
+}
+
+impl UserDataPolicy {
+    /// Creates a policy that drops all user data from telemetry.
+    pub fn no_user_data() -> Self {
+        Self::default()
+            .user_id(UserDataAction::Drop)
+            .auth_user_id(UserDataAction::Drop)
+            .session_id(UserDataAction::Drop)
+            .client_ip(UserDataAction::Drop)
+    }
+
+    /// Sets how an anonymous user id (`ai.user.id`) is submitted.
+    pub fn user_id(mut self, action: UserDataAction) -> Self {
+        self.user_id = action;
+        self
+    }
+
+    /// Sets how an authenticated user id (`ai.user.authUserId`) is submitted.
+    pub fn auth_user_id(mut self, action: UserDataAction) -> Self {
+        self.auth_user_id = action;
+        self
+    }
+
+    /// Sets how a session id (`ai.session.id`) is submitted.
+    pub fn session_id(mut self, action: UserDataAction) -> Self {
+        self.session_id = action;
+        self
+    }
+
+    /// Sets how an IP address of a client device (`ai.location.ip`) is submitted.
+    pub fn client_ip(mut self, action: UserDataAction) -> Self {
+        self.client_ip = action;
+        self
+    }
+
+    /// Sets a salt values are prefixed with before they are hashed. Hashes stay stable for the same salt,
+    /// so telemetry of the same user can still be correlated, while a secret salt prevents finding
+    /// original values by hashing known ones.
+    pub fn salt(mut self, salt: impl Into<String>) -> Self {
+        self.salt = salt.into();
+        self
+    }
+
+    /// Determines whether all user data is submitted as is.
+    pub fn is_keep_all(&self) -> bool {
+        [self.user_id, self.auth_user_id, self.session_id, self.client_ip]
+            .iter()
+            .all(|action| *action == UserDataAction::Keep)
+    }
+
+    /// Applies the policy to user data tags of a telemetry item.
+    pub(crate) fn apply(&self, envelope: &mut Envelope) {
+        if self.is_keep_all() {
+            return;
+        }
+
+        if let Some(tags) = &mut envelope.tags {
+            for (key, action) in [
+                (USER_ID, self.user_id),
+                (AUTH_USER_ID, self.auth_user_id),
+                (SESSION_ID, self.session_id),
+                (CLIENT_IP, self.client_ip),
+            ] {
+                match action {
+                    UserDataAction::Keep => {}
+                    UserDataAction::Hash => {
+                        if let Some(value) = tags.get_mut(key) {
+                            *value = self.hash(value);
+                        }
+                    }
+                    UserDataAction::Drop => {
+                        tags.remove(key);
+                    }
+                    UserDataAction::Mask => {
+                        if let Some(value) = tags.remove(key) {
+                            if let Some(masked) = mask(&value) {
+                                tags.insert(key.into(), masked);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let data = [self.salt.as_bytes(), value.as_bytes()].concat();
+        digest(&SHA256, &data)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// Masks the host part of an IP address.
+fn mask(value: &str) -> Option<String> {
+    let masked = match value.trim().parse().ok()? {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, ..] = ip.segments();
+            IpAddr::V6(Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0))
+        }
+    };
+    Some(masked.to_string())
+}
+
+impl Default for UserDataPolicy {
+    fn default() -> Self {
+        Self {
+            user_id: UserDataAction::Keep,
+            auth_user_id: UserDataAction::Keep,
+            session_id: UserDataAction::Keep,
+            client_ip: UserDataAction::Keep,
+            salt: String::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_keeps_user_data_by_default() {
+        let mut envelope = envelope();
+
+        UserDataPolicy::default().apply(&mut envelope);
+
+        assert_eq!(envelope.tags, Some(tags()));
+    }
+
+    #[test]
+    fn it_drops_all_user_data() {
+        let mut envelope = envelope();
+
+        UserDataPolicy::no_user_data().apply(&mut envelope);
+
+        let expected: BTreeMap<_, _> = [("ai.operation.id".to_string(), "operation".to_string())].into();
+        assert_eq!(envelope.tags, Some(expected));
+    }
+
+    #[test_case("", "04f8996da763b7a969b1028ee3007569eaf3a635486ddab211d512c85b9df8fb" ; "without salt")]
+    #[test_case("secret", "199b02eb2cf264223ff766f473f2ad442e9b7f5534b500b69944273b5cfa86ca" ; "with salt")]
+    fn it_hashes_user_id(salt: &str, expected: &str) {
+        let mut envelope = envelope();
+
+        UserDataPolicy::default()
+            .user_id(UserDataAction::Hash)
+            .session_id(UserDataAction::Drop)
+            .salt(salt)
+            .apply(&mut envelope);
+
+        let tags = envelope.tags.unwrap();
+        assert_eq!(tags[USER_ID], expected);
+        assert_eq!(tags[AUTH_USER_ID], "user@example.com");
+        assert_eq!(tags.get(SESSION_ID), None);
+        assert_eq!(tags[CLIENT_IP], "10.0.0.1");
+    }
+
+    #[test_case("10.1.2.3",                  Some("10.1.2.0")          ; "ipv4")]
+    #[test_case("2001:db8:85a3:8d3:1319:8a2e:370:7348", Some("2001:db8:85a3::") ; "ipv6")]
+    #[test_case("::ffff:10.1.2.3",          Some("::")                ; "ipv4 mapped ipv6")]
+    #[test_case("unknown",                  None                      ; "not an address")]
+    fn it_masks_client_ip(ip: &str, expected: Option<&str>) {
+        let mut envelope = envelope();
+        envelope.tags.as_mut().unwrap().insert(CLIENT_IP.into(), ip.into());
+
+        UserDataPolicy::default()
+            .client_ip(UserDataAction::Mask)
+            .apply(&mut envelope);
+
+        assert_eq!(envelope.tags.unwrap().get(CLIENT_IP).map(String::as_str), expected);
+    }
+
+    fn envelope() -> Envelope {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let mut envelope = Envelope::from((context, EventTelemetry::new("event")));
+        envelope.tags = Some(tags());
+        envelope
+    }
+
+    fn tags() -> BTreeMap<String, String> {
+        [
+            (USER_ID, "user"),
+            (AUTH_USER_ID, "user@example.com"),
+            (SESSION_ID, "session"),
+            (CLIENT_IP, "10.0.0.1"),
+            ("ai.operation.id", "operation"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+    }
+}
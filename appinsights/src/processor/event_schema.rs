@@ -0,0 +1,382 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+use log::warn;
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    diagnostics::{Diagnostic, Diagnostics},
+    processor::TelemetryProcessor,
+};
+
+/// An expected shape of a custom event: custom properties and measurements it carries. Required keys
+/// have to be present on each event, optional ones may be omitted, and any other key is unexpected.
+///
+/// ```rust
+/// # use appinsights::processor::EventSchema;
+/// let schema = EventSchema::new("order placed")
+///     .property("tenant")
+///     .optional_property("coupon")
+///     .measurement("total");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSchema {
+    name: String,
+    properties: BTreeMap<String, bool>,
+    measurements: BTreeMap<String, bool>,
+}
+
+impl EventSchema {
+    /// Creates a schema of an event with specified name that carries no properties or measurements.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            properties: BTreeMap::new(),
+            measurements: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a custom property each event has to carry.
+    pub fn property(mut self, key: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), true);
+        self
+    }
+
+    /// Adds a custom property an event may carry.
+    pub fn optional_property(mut self, key: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), false);
+        self
+    }
+
+    /// Adds a custom measurement each event has to carry.
+    pub fn measurement(mut self, key: impl Into<String>) -> Self {
+        self.measurements.insert(key.into(), true);
+        self
+    }
+
+    /// Adds a custom measurement an event may carry.
+    pub fn optional_measurement(mut self, key: impl Into<String>) -> Self {
+        self.measurements.insert(key.into(), false);
+        self
+    }
+
+    /// Returns a name of the event.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Checks custom events against registered schemas, so analytics queries keep working while many teams
+/// emit events across a large codebase. Events that are not registered, lack required keys or carry
+/// unexpected ones are reported. Other telemetry items are not checked.
+///
+/// Mismatches are logged as warnings and reported to [`diagnostics`](../diagnostics/index.html) of a
+/// client as [`SchemaMismatch`](../diagnostics/enum.Diagnostic.html#variant.SchemaMismatch). A strict
+/// registry also drops mismatching events. Properties attached to every item, e.g. ones of the client context, can be
+/// allowed for all events at once.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::{EventRegistry, EventSchema}, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(
+///     EventRegistry::new()
+///         .common_property("tenant")
+///         .register(EventSchema::new("order placed").property("payment").measurement("total"))
+///         .register(EventSchema::new("order cancelled").optional_property("reason"))
+///         .strict(cfg!(debug_assertions)),
+/// );
+/// ```
+pub struct EventRegistry {
+    schemas: BTreeMap<String, EventSchema>,
+    common: BTreeSet<String>,
+    strict: bool,
+}
+
+impl EventRegistry {
+    /// Creates a new registry without schemas that logs mismatches and keeps mismatching events.
+    pub fn new() -> Self {
+        Self {
+            schemas: BTreeMap::new(),
+            common: BTreeSet::new(),
+            strict: false,
+        }
+    }
+
+    /// Registers a schema of an event. It replaces a schema registered with the same name before.
+    pub fn register(mut self, schema: EventSchema) -> Self {
+        self.schemas.insert(schema.name.clone(), schema);
+        self
+    }
+
+    /// Allows a custom property on all events without requiring it.
+    pub fn common_property(mut self, key: impl Into<String>) -> Self {
+        self.common.insert(key.into());
+        self
+    }
+
+    /// Sets whether mismatching events are dropped after mismatches are reported.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns all mismatches between an event and its registered schema.
+    fn check(
+        &self,
+        name: &str,
+        properties: Option<&BTreeMap<String, String>>,
+        measurements: Option<&BTreeMap<String, f64>>,
+    ) -> Vec<Mismatch> {
+        let schema = match self.schemas.get(name) {
+            Some(schema) => schema,
+            None => return vec![Mismatch::Unregistered { event: name.into() }],
+        };
+
+        let mut mismatches = Vec::new();
+        check_keys(
+            name,
+            &schema.properties,
+            properties
+                .into_iter()
+                .flat_map(BTreeMap::keys)
+                .filter(|key| !self.common.contains(*key)),
+            |key| properties.is_some_and(|properties| properties.contains_key(key)),
+            Kind::Property,
+            &mut mismatches,
+        );
+        check_keys(
+            name,
+            &schema.measurements,
+            measurements.into_iter().flat_map(BTreeMap::keys),
+            |key| measurements.is_some_and(|measurements| measurements.contains_key(key)),
+            Kind::Measurement,
+            &mut mismatches,
+        );
+        mismatches
+    }
+}
+
+impl Default for EventRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryProcessor for EventRegistry {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        self.process_with_diagnostics(envelope, &Diagnostics::default())
+    }
+
+    fn process_with_diagnostics(&self, envelope: &mut Envelope, diagnostics: &Diagnostics) -> bool {
+        let mismatches = match &envelope.data {
+            Some(Base::Data(Data::EventData(data))) => {
+                self.check(&data.name, data.properties.as_ref(), data.measurements.as_ref())
+            }
+            _ => return true,
+        };
+        if mismatches.is_empty() {
+            return true;
+        }
+
+        for mismatch in &mismatches {
+            warn!(
+                "Telemetry item {} does not match its schema: {}",
+                envelope.name, mismatch
+            );
+        }
+        diagnostics.report(Diagnostic::SchemaMismatch {
+            envelope,
+            mismatches: &mismatches,
+        });
+        !self.strict
+    }
+}
+
+/// A kind of a key of custom event.
+#[derive(Clone, Copy)]
+enum Kind {
+    Property,
+    Measurement,
+}
+
+/// Collects missing required keys and unexpected keys of an event.
+fn check_keys<'a>(
+    event: &str,
+    expected: &BTreeMap<String, bool>,
+    actual: impl Iterator<Item = &'a String>,
+    contains: impl Fn(&str) -> bool,
+    kind: Kind,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    for (key, _) in expected.iter().filter(|(key, required)| **required && !contains(key)) {
+        let (event, key) = (event.into(), key.clone());
+        mismatches.push(match kind {
+            Kind::Property => Mismatch::MissingProperty { event, key },
+            Kind::Measurement => Mismatch::MissingMeasurement { event, key },
+        });
+    }
+    for key in actual.filter(|key| !expected.contains_key(*key)) {
+        let (event, key) = (event.into(), key.clone());
+        mismatches.push(match kind {
+            Kind::Property => Mismatch::UnexpectedProperty { event, key },
+            Kind::Measurement => Mismatch::UnexpectedMeasurement { event, key },
+        });
+    }
+}
+
+/// A difference between a custom event and its registered schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// An event has no registered schema.
+    Unregistered {
+        /// A name of the event.
+        event: String,
+    },
+
+    /// An event lacks a required custom property.
+    MissingProperty {
+        /// A name of the event.
+        event: String,
+
+        /// A key of the property.
+        key: String,
+    },
+
+    /// An event carries a custom property its schema does not have.
+    UnexpectedProperty {
+        /// A name of the event.
+        event: String,
+
+        /// A key of the property.
+        key: String,
+    },
+
+    /// An event lacks a required custom measurement.
+    MissingMeasurement {
+        /// A name of the event.
+        event: String,
+
+        /// A key of the measurement.
+        key: String,
+    },
+
+    /// An event carries a custom measurement its schema does not have.
+    UnexpectedMeasurement {
+        /// A name of the event.
+        event: String,
+
+        /// A key of the measurement.
+        key: String,
+    },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Unregistered { event } => write!(f, "event {:?} is not registered", event),
+            Mismatch::MissingProperty { event, key } => write!(f, "event {:?} lacks property {:?}", event, key),
+            Mismatch::UnexpectedProperty { event, key } => {
+                write!(f, "event {:?} has unexpected property {:?}", event, key)
+            }
+            Mismatch::MissingMeasurement { event, key } => write!(f, "event {:?} lacks measurement {:?}", event, key),
+            Mismatch::UnexpectedMeasurement { event, key } => {
+                write!(f, "event {:?} has unexpected measurement {:?}", event, key)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, MetricTelemetry, Properties, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_accepts_events_that_match_schemas() {
+        let registry = registry().strict(true);
+        let mut event = EventTelemetry::new("order placed");
+        event.properties_mut().insert("payment".into(), "card".into());
+        event.properties_mut().insert("tenant".into(), "contoso".into());
+        event.measurements_mut().insert("total".into(), 42.0);
+
+        assert!(registry.process(&mut envelope(event)));
+        assert!(registry.process(&mut envelope(EventTelemetry::new("order cancelled"))));
+        assert!(registry.process(&mut Envelope::from((context(), MetricTelemetry::new("orders", 1.0)))));
+    }
+
+    #[test]
+    fn it_reports_mismatches_and_drops_events_in_strict_mode() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics = Diagnostics::new({
+            let reported = reported.clone();
+            move |diagnostic| {
+                if let Diagnostic::SchemaMismatch { mismatches, .. } = diagnostic {
+                    reported.lock().unwrap().extend_from_slice(mismatches)
+                }
+            }
+        });
+        let registry = registry().strict(true);
+
+        let mut event = EventTelemetry::new("order placed");
+        event.properties_mut().insert("paymnet".into(), "card".into());
+        event.measurements_mut().insert("total".into(), 42.0);
+        event.measurements_mut().insert("items".into(), 3.0);
+        assert!(!registry.process_with_diagnostics(&mut envelope(event), &diagnostics));
+        assert!(!registry.process_with_diagnostics(&mut envelope(EventTelemetry::new("order shipped")), &diagnostics));
+
+        let event = || "order placed".to_string();
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                Mismatch::MissingProperty {
+                    event: event(),
+                    key: "payment".into()
+                },
+                Mismatch::UnexpectedProperty {
+                    event: event(),
+                    key: "paymnet".into()
+                },
+                Mismatch::UnexpectedMeasurement {
+                    event: event(),
+                    key: "items".into()
+                },
+                Mismatch::Unregistered {
+                    event: "order shipped".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_keeps_mismatching_events_by_default() {
+        let registry = registry();
+
+        assert!(registry.process(&mut envelope(EventTelemetry::new("order shipped"))));
+    }
+
+    fn registry() -> EventRegistry {
+        EventRegistry::new()
+            .common_property("tenant")
+            .register(
+                EventSchema::new("order placed")
+                    .property("payment")
+                    .measurement("total"),
+            )
+            .register(EventSchema::new("order cancelled").optional_property("reason"))
+    }
+
+    fn envelope(event: EventTelemetry) -> Envelope {
+        Envelope::from((context(), event))
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default())
+    }
+}
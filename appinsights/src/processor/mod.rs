@@ -0,0 +1,97 @@
+//! Module for telemetry processors.
+//!
+//! A [`TelemetryProcessor`](trait.TelemetryProcessor.html) receives every telemetry item right before
+//! it is submitted to the channel. At this point telemetry item is converted to the
+//! [`Envelope`](../contracts/struct.Envelope.html) and client context is applied, so a processor can
+//! modify any field of an item or drop it altogether.
+//!
+//! ```rust, no_run
+//! use appinsights::{contracts::Envelope, processor::TelemetryProcessor, TelemetryClient};
+//!
+//! struct DropHealthChecks;
+//!
+//! impl TelemetryProcessor for DropHealthChecks {
+//!     fn process(&self, envelope: &mut Envelope) -> bool {
+//!         envelope.tags.as_ref().and_then(|tags| tags.get("ai.operation.name")).map(String::as_str) != Some("GET /health")
+//!     }
+//! }
+//!
+//! let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+//! client.add_processor(DropHealthChecks);
+//! ```
+mod event_schema;
+mod property_filter;
+mod quota;
+mod rate_limit;
+mod sampling;
+mod schema;
+mod size_guard;
+mod success;
+mod tenant_router;
+mod thread_metadata;
+
+pub use event_schema::{EventRegistry, EventSchema, Mismatch};
+pub use property_filter::PropertyFilter;
+pub use quota::{DailyQuota, Quota, QUOTA_EXCEEDED_EVENT};
+pub use rate_limit::TraceRateLimiter;
+pub use sampling::{Sampler, SamplingKey};
+pub(crate) use schema::validate;
+pub use schema::{SchemaValidator, Violation};
+pub use size_guard::{SizeGuard, Trim, MAX_ITEM_BYTES};
+pub use success::{CallKind, CallResult, SuccessClassifier};
+pub use tenant_router::TenantRouter;
+pub use thread_metadata::ThreadMetadata;
+
+use crate::{contracts::Envelope, diagnostics::Diagnostics, TelemetryConfig};
+
+/// Inspects, modifies or filters out telemetry items before they are submitted.
+pub trait TelemetryProcessor: Send + Sync {
+    /// Processes a telemetry item. Returns `false` when the item should be dropped.
+    fn process(&self, envelope: &mut Envelope) -> bool;
+
+    /// Processes a telemetry item and reports problems found in it to
+    /// [`diagnostics`](../diagnostics/index.html) of a client the processor is added to. A processor
+    /// that reports nothing only processes the item.
+    fn process_with_diagnostics(&self, envelope: &mut Envelope, _diagnostics: &Diagnostics) -> bool {
+        self.process(envelope)
+    }
+
+    /// Determines whether a processor keeps items sampled by specified key, e.g. an operation id. A
+    /// processor that does not sample keeps all of them.
+    fn is_sampled_in(&self, _key: &str) -> bool {
+        true
+    }
+}
+
+/// Runs all processors in order they were added until one of them drops a telemetry item.
+pub(crate) fn process(
+    processors: &[Box<dyn TelemetryProcessor>],
+    envelope: &mut Envelope,
+    diagnostics: &Diagnostics,
+) -> bool {
+    processors
+        .iter()
+        .all(|processor| processor.process_with_diagnostics(envelope, diagnostics))
+}
+
+/// Returns processors a client starts with according to the configuration.
+pub(crate) fn from_config(config: &TelemetryConfig) -> Vec<Box<dyn TelemetryProcessor>> {
+    let mut processors: Vec<Box<dyn TelemetryProcessor>> = Vec::new();
+    if config.thread_metadata() {
+        processors.push(Box::new(ThreadMetadata));
+    }
+    if let Some(max_item_bytes) = config.max_item_bytes() {
+        processors.push(Box::new(SizeGuard::new(max_item_bytes)));
+    }
+    processors
+}
+
+/// Returns quotas a client applies after all other processors according to the configuration, so items
+/// dropped by them don't use up a quota.
+pub(crate) fn quotas_from_config(config: &TelemetryConfig) -> Vec<Box<dyn TelemetryProcessor>> {
+    config
+        .daily_quota()
+        .map(|quota| Box::new(DailyQuota::new(quota)) as Box<dyn TelemetryProcessor>)
+        .into_iter()
+        .collect()
+}
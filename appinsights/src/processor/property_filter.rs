@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    processor::TelemetryProcessor,
+};
+
+/// Removes custom properties of telemetry items by their keys. A filter either allows only properties
+/// with keys that match one of configured patterns, or denies properties with keys that match any of them.
+/// It guarantees sensitive values never leave the process even if application code adds them to telemetry.
+///
+/// Patterns are matched against the whole key ignoring case. A pattern may contain `*` wildcard to match
+/// any sequence of characters and `?` wildcard to match any single character.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::PropertyFilter, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(PropertyFilter::deny(["password", "*token*", "ssn"]));
+/// ```
+pub struct PropertyFilter {
+    patterns: Vec<String>,
+    allow: bool,
+}
+
+impl PropertyFilter {
+    /// Creates a new processor that removes all properties with keys that don't match any of patterns.
+    pub fn allow<I>(patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self::new(patterns, true)
+    }
+
+    /// Creates a new processor that removes properties with keys that match any of patterns.
+    pub fn deny<I>(patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self::new(patterns, false)
+    }
+
+    fn new<I>(patterns: I, allow: bool) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|pattern| pattern.into().to_lowercase())
+                .collect(),
+            allow,
+        }
+    }
+
+    /// Determines whether a property with specified key is submitted.
+    fn is_allowed(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        let matched = self
+            .patterns
+            .iter()
+            .any(|pattern| matches(pattern.as_bytes(), key.as_bytes()));
+        matched == self.allow
+    }
+}
+
+impl TelemetryProcessor for PropertyFilter {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        if let Some(properties) = properties_mut(envelope) {
+            properties.retain(|key, _| self.is_allowed(key));
+        }
+
+        true
+    }
+}
+
+/// Returns custom properties of a telemetry item.
+pub(super) fn properties_mut(envelope: &mut Envelope) -> Option<&mut BTreeMap<String, String>> {
+    let properties = match envelope.data.as_mut()? {
+        Base::Data(Data::AvailabilityData(data)) => &mut data.properties,
+        Base::Data(Data::EventData(data)) => &mut data.properties,
+        Base::Data(Data::ExceptionData(data)) => &mut data.properties,
+        Base::Data(Data::MessageData(data)) => &mut data.properties,
+        Base::Data(Data::MetricData(data)) => &mut data.properties,
+        Base::Data(Data::PageViewData(data)) => &mut data.properties,
+        Base::Data(Data::PageViewPerfData(data)) => &mut data.properties,
+        Base::Data(Data::RemoteDependencyData(data)) => &mut data.properties,
+        Base::Data(Data::RequestData(data)) => &mut data.properties,
+    };
+    properties.as_mut()
+}
+
+/// Matches a text against a glob pattern with `*` and `?` wildcards.
+fn matches(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test_case("password",  "password",      true  ; "exact")]
+    #[test_case("password",  "PassWord",      false ; "exact case sensitive")]
+    #[test_case("*token*",   "access_token",  true  ; "suffix")]
+    #[test_case("*token*",   "tokens",        true  ; "prefix")]
+    #[test_case("*token*",   "tok",           false ; "partial")]
+    #[test_case("user.?d",   "user.id",       true  ; "single character")]
+    #[test_case("user.?d",   "user.d",        false ; "missing character")]
+    #[test_case("a*b*c",     "aXbYbZc",       true  ; "backtracking")]
+    #[test_case("*",         "",              true  ; "any")]
+    fn it_matches_patterns(pattern: &str, text: &str, expected: bool) {
+        assert_eq!(matches(pattern.as_bytes(), text.as_bytes()), expected);
+    }
+
+    #[test_case(PropertyFilter::deny(["password", "*Token*"]), &["id", "user"]                ; "deny")]
+    #[test_case(PropertyFilter::allow(["id", "user*"]),       &["id", "user"]                ; "allow")]
+    #[test_case(PropertyFilter::deny(Vec::<String>::new()),  &["AuthToken", "id", "password", "user"] ; "deny nothing")]
+    fn it_filters_properties(filter: PropertyFilter, expected: &[&str]) {
+        let mut event = EventTelemetry::new("event");
+        for key in ["id", "password", "AuthToken", "user"] {
+            event.properties_mut().insert(key.into(), "value".into());
+        }
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let mut envelope = Envelope::from((context, event));
+
+        assert!(filter.process(&mut envelope));
+
+        let keys: Vec<_> = properties_mut(&mut envelope).unwrap().keys().cloned().collect();
+        assert_eq!(keys, expected);
+    }
+}
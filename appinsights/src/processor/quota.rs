@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+};
+
+use chrono::NaiveDate;
+use log::warn;
+
+use crate::{
+    contracts::{Base, Data, Envelope, EventData, SeverityLevel},
+    processor::{size_guard::size_of, TelemetryProcessor},
+    time,
+};
+
+/// A name of an event a [`DailyQuota`](struct.DailyQuota.html) submits once a day when the quota is
+/// exceeded.
+pub const QUOTA_EXCEEDED_EVENT: &str = "Quota Exceeded";
+
+/// A limit of telemetry a client submits per day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quota {
+    /// Maximum number of telemetry items.
+    Items(u64),
+
+    /// Maximum estimated size of telemetry items in bytes of serialized JSON.
+    Bytes(u64),
+}
+
+impl Quota {
+    /// Returns how much of the quota an item uses.
+    fn cost(self, envelope: &Envelope) -> u64 {
+        match self {
+            Quota::Items(_) => 1,
+            Quota::Bytes(_) => size_of(envelope) as u64,
+        }
+    }
+
+    fn limit(self) -> u64 {
+        match self {
+            Quota::Items(limit) | Quota::Bytes(limit) => limit,
+        }
+    }
+}
+
+/// Caps telemetry a client submits per day by a number of items or their estimated size, a hard
+/// client-side guardrail against unexpected ingestion costs that complements the daily cap set in
+/// Azure Portal. Once the quota is exceeded, telemetry is dropped locally until the next day by UTC.
+/// Items needed to diagnose failures are still submitted: exceptions, failed requests and traces of
+/// `Error` severity level or higher.
+///
+/// The first item over the quota is replaced with a single `Quota Exceeded` event telling which
+/// quota was exceeded, so a gap in telemetry can be told apart from an outage.
+///
+/// It is added automatically when [`daily_quota`](../struct.TelemetryConfigBuilder.html#method.daily_quota)
+/// is set in the configuration.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::{DailyQuota, Quota}, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(DailyQuota::new(Quota::Items(100_000)));
+/// ```
+pub struct DailyQuota {
+    quota: Quota,
+    usage: Mutex<Usage>,
+}
+
+/// Telemetry submitted within a day.
+struct Usage {
+    day: NaiveDate,
+    used: u64,
+    exceeded: bool,
+}
+
+impl DailyQuota {
+    /// Creates a new processor that drops non-critical telemetry over specified quota per day.
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            usage: Mutex::new(Usage {
+                day: today(),
+                used: 0,
+                exceeded: false,
+            }),
+        }
+    }
+}
+
+impl TelemetryProcessor for DailyQuota {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        let cost = self.quota.cost(envelope);
+        let mut usage = self.usage.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let day = today();
+        if usage.day != day {
+            *usage = Usage {
+                day,
+                used: 0,
+                exceeded: false,
+            };
+        }
+
+        usage.used = usage.used.saturating_add(cost);
+        if usage.used <= self.quota.limit() || is_critical(envelope) {
+            return true;
+        }
+        if usage.exceeded {
+            return false;
+        }
+
+        usage.exceeded = true;
+        warn!(
+            "Daily quota of {:?} exceeded, telemetry is dropped until the next day",
+            self.quota
+        );
+        replace_with_event(envelope, self.quota);
+        true
+    }
+}
+
+/// Returns the current day by UTC.
+fn today() -> NaiveDate {
+    time::now().naive_utc().date()
+}
+
+/// Determines whether an item is needed to diagnose failures, so it is submitted over the quota.
+fn is_critical(envelope: &Envelope) -> bool {
+    match &envelope.data {
+        Some(Base::Data(Data::ExceptionData(_))) => true,
+        Some(Base::Data(Data::RequestData(data))) => !data.success,
+        Some(Base::Data(Data::MessageData(data))) => matches!(
+            data.severity_level,
+            Some(SeverityLevel::Error) | Some(SeverityLevel::Critical)
+        ),
+        _ => false,
+    }
+}
+
+/// Replaces an item with an event telling the quota is exceeded. It keeps the time and context tags of
+/// the item.
+fn replace_with_event(envelope: &mut Envelope, quota: Quota) {
+    let (kind, limit) = match quota {
+        Quota::Items(limit) => ("items", limit),
+        Quota::Bytes(limit) => ("bytes", limit),
+    };
+    envelope.name = "Microsoft.ApplicationInsights.Event".into();
+    envelope.sample_rate = None;
+    envelope.data = Some(Base::Data(Data::EventData(EventData {
+        name: QUOTA_EXCEEDED_EVENT.into(),
+        properties: Some(BTreeMap::from([
+            ("quota".to_string(), kind.to_string()),
+            ("limit".to_string(), limit.to_string()),
+        ])),
+        ..EventData::default()
+    })));
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{self, ContextTags, EventTelemetry, ExceptionTelemetry, Properties, TraceTelemetry},
+        TelemetryContext,
+    };
+
+    #[test_case(Quota::Items(2)                                  ; "items")]
+    #[test_case(Quota::Bytes(2 * size_of(&event("event")) as u64) ; "bytes")]
+    fn it_drops_telemetry_over_daily_quota(quota: Quota) {
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 0));
+        let processor = DailyQuota::new(quota);
+
+        assert!(processor.process(&mut event("event")));
+        assert!(processor.process(&mut event("event")));
+
+        let mut exceeded = event("event");
+        assert!(processor.process(&mut exceeded));
+        assert_eq!(event_name(&exceeded), Some(QUOTA_EXCEEDED_EVENT));
+
+        assert!(!processor.process(&mut event("event")));
+        assert!(!processor.process(&mut trace(telemetry::SeverityLevel::Warning)));
+        assert!(processor.process(&mut trace(telemetry::SeverityLevel::Error)));
+        assert!(processor.process(&mut Envelope::from((
+            context(),
+            ExceptionTelemetry::from_message("ParseError", "unexpected token")
+        ))));
+
+        time::set(Utc.ymd(2019, 1, 3).and_hms(0, 0, 1));
+        let mut next_day = event("event");
+        assert!(processor.process(&mut next_day));
+        assert_eq!(event_name(&next_day), Some("event"));
+        time::reset();
+    }
+
+    fn event_name(envelope: &Envelope) -> Option<&str> {
+        match &envelope.data {
+            Some(Base::Data(Data::EventData(data))) => Some(&data.name),
+            _ => None,
+        }
+    }
+
+    fn event(name: &str) -> Envelope {
+        Envelope::from((context(), EventTelemetry::new(name)))
+    }
+
+    fn trace(severity: telemetry::SeverityLevel) -> Envelope {
+        Envelope::from((context(), TraceTelemetry::new("trace", severity)))
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default())
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    contracts::{Base, Data, Envelope, SeverityLevel},
+    processor::TelemetryProcessor,
+    time,
+};
+
+/// Maximum number of distinct trace messages to keep track of.
+const MAX_ENTRIES: usize = 1000;
+
+/// Rate-limits identical trace messages, i.e. messages with the same text and severity level.
+/// Only the first message is submitted within a time window, all identical messages are dropped until
+/// the window expires. The next identical message after the window expires is submitted with a
+/// `suppressedCount` property containing the number of dropped messages. It protects against
+/// log storms caused by errors reported in a tight loop.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::TraceRateLimiter, TelemetryClient};
+/// use std::time::Duration;
+///
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(TraceRateLimiter::new(Duration::from_secs(60)));
+/// ```
+pub struct TraceRateLimiter {
+    window: Duration,
+    entries: Mutex<HashMap<(String, Option<SeverityLevel>), Entry>>,
+}
+
+struct Entry {
+    started: Instant,
+    suppressed: u64,
+}
+
+impl TraceRateLimiter {
+    /// Creates a new processor that submits at most one of identical trace messages per time window.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: Mutex::default(),
+        }
+    }
+}
+
+impl TelemetryProcessor for TraceRateLimiter {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        let data = match &mut envelope.data {
+            Some(Base::Data(Data::MessageData(data))) => data,
+            _ => return true,
+        };
+
+        let now = time::instant();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let key = (data.message.clone(), data.severity_level.clone());
+        if let Some(entry) = entries.get_mut(&key) {
+            if now.saturating_duration_since(entry.started) < self.window {
+                entry.suppressed += 1;
+                return false;
+            }
+
+            entry.started = now;
+            if entry.suppressed > 0 {
+                data.properties
+                    .get_or_insert_with(Default::default)
+                    .insert("suppressedCount".into(), entry.suppressed.to_string());
+                entry.suppressed = 0;
+            }
+            return true;
+        }
+
+        if entries.len() >= MAX_ENTRIES {
+            let window = self.window;
+            entries.retain(|_, entry| entry.suppressed > 0 || now.saturating_duration_since(entry.started) < window);
+        }
+        entries.insert(
+            key,
+            Entry {
+                started: now,
+                suppressed: 0,
+            },
+        );
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::{
+        telemetry::{self, ContextTags, EventTelemetry, Properties, TraceTelemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_suppresses_identical_messages_within_window() {
+        let limiter = TraceRateLimiter::new(Duration::from_secs(60));
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 0));
+        assert!(limiter.process(&mut trace("Unable to connect", telemetry::SeverityLevel::Error)));
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 30));
+        assert!(!limiter.process(&mut trace("Unable to connect", telemetry::SeverityLevel::Error)));
+        assert!(!limiter.process(&mut trace("Unable to connect", telemetry::SeverityLevel::Error)));
+        assert!(limiter.process(&mut trace("Unable to connect", telemetry::SeverityLevel::Warning)));
+        assert!(limiter.process(&mut trace("Connected", telemetry::SeverityLevel::Error)));
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 5, 0));
+        let mut envelope = trace("Unable to connect", telemetry::SeverityLevel::Error);
+        assert!(limiter.process(&mut envelope));
+        assert_eq!(suppressed_count(envelope), Some("2".into()));
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 6, 0));
+        let mut envelope = trace("Unable to connect", telemetry::SeverityLevel::Error);
+        assert!(limiter.process(&mut envelope));
+        assert_eq!(suppressed_count(envelope), None);
+    }
+
+    #[test]
+    fn it_does_not_limit_other_telemetry() {
+        let limiter = TraceRateLimiter::new(Duration::from_secs(60));
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        for _ in 0..3 {
+            let mut envelope = Envelope::from((context.clone(), EventTelemetry::new("started")));
+            assert!(limiter.process(&mut envelope));
+        }
+    }
+
+    fn trace(message: &str, severity: telemetry::SeverityLevel) -> Envelope {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        Envelope::from((context, TraceTelemetry::new(message, severity)))
+    }
+
+    fn suppressed_count(envelope: Envelope) -> Option<String> {
+        match envelope.data {
+            Some(Base::Data(Data::MessageData(data))) => data.properties.unwrap().get("suppressedCount").cloned(),
+            _ => None,
+        }
+    }
+}
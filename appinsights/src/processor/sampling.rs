@@ -0,0 +1,295 @@
+use crate::{
+    contracts::{Base, Data, Envelope, SeverityLevel as ContractsSeverityLevel},
+    processor::TelemetryProcessor,
+    telemetry::SeverityLevel,
+    uuid,
+};
+
+/// A context tag telemetry items are sampled together by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplingKey {
+    /// Keeps or drops all items of an operation together, so a sampled request comes with all its
+    /// dependencies, traces and exceptions.
+    #[default]
+    Operation,
+
+    /// Keeps or drops all items of a user together across operations. Items without a user id are
+    /// sampled by operation.
+    User,
+
+    /// Keeps or drops all items of a session together across operations. Items without a session id are
+    /// sampled by operation.
+    Session,
+}
+
+impl SamplingKey {
+    fn tag(self) -> &'static str {
+        match self {
+            SamplingKey::Operation => "ai.operation.id",
+            SamplingKey::User => "ai.user.id",
+            SamplingKey::Session => "ai.session.id",
+        }
+    }
+}
+
+/// Keeps a fixed percentage of telemetry items. Whether an item is kept depends on a hash of its
+/// operation id, so all items of an operation are kept or dropped together, and the hash is the same
+/// one other Application Insights SDKs use, so sampling decisions agree across components. Kept items
+/// carry a sample rate the portal uses to extrapolate counts. Metrics are never sampled.
+///
+/// Analytics of funnels need all telemetry of a user or a session rather than of a single operation,
+/// which a different [`SamplingKey`](enum.SamplingKey.html) provides.
+///
+/// Items needed to diagnose failures can be kept regardless of the percentage: exceptions, failed
+/// requests and traces of a minimum severity level. They are submitted as is, without a sample rate.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::{Sampler, SamplingKey}, telemetry::SeverityLevel, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(
+///     Sampler::new(10.0)
+///         .with_key(SamplingKey::User)
+///         .always_keep_exceptions()
+///         .always_keep_failed_requests()
+///         .always_keep_severity(SeverityLevel::Error),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    percentage: f64,
+    key: SamplingKey,
+    keep_exceptions: bool,
+    keep_failed_requests: bool,
+    keep_severity: Option<SeverityLevel>,
+}
+
+impl Sampler {
+    /// Creates a new processor that keeps specified percentage of items sampled by operation.
+    pub fn new(percentage: f64) -> Self {
+        Self {
+            percentage: percentage.clamp(0.0, 100.0),
+            key: SamplingKey::default(),
+            keep_exceptions: false,
+            keep_failed_requests: false,
+            keep_severity: None,
+        }
+    }
+
+    /// Sets a context tag items are sampled together by.
+    pub fn with_key(mut self, key: SamplingKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Keeps all exceptions regardless of the sampling percentage.
+    pub fn always_keep_exceptions(mut self) -> Self {
+        self.keep_exceptions = true;
+        self
+    }
+
+    /// Keeps all requests that failed regardless of the sampling percentage.
+    pub fn always_keep_failed_requests(mut self) -> Self {
+        self.keep_failed_requests = true;
+        self
+    }
+
+    /// Keeps all traces of specified severity level or higher regardless of the sampling percentage.
+    pub fn always_keep_severity(mut self, severity: SeverityLevel) -> Self {
+        self.keep_severity = Some(severity);
+        self
+    }
+
+    /// Determines whether an item is kept by one of the always-keep rules. Metrics are always kept.
+    fn always_keeps(&self, envelope: &Envelope) -> bool {
+        match &envelope.data {
+            Some(Base::Data(Data::MetricData(_))) => true,
+            Some(Base::Data(Data::ExceptionData(_))) => self.keep_exceptions,
+            Some(Base::Data(Data::RequestData(data))) => self.keep_failed_requests && !data.success,
+            Some(Base::Data(Data::MessageData(data))) => match (self.keep_severity, &data.severity_level) {
+                (Some(min), Some(severity)) => rank(severity) >= rank(&min.into()),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns a value of a context tag an item is sampled by.
+    fn key_of(&self, envelope: &Envelope) -> Option<String> {
+        let tags = envelope.tags.as_ref()?;
+        let tag = |key: SamplingKey| tags.get(key.tag()).filter(|value| !value.is_empty());
+        tag(self.key).or_else(|| tag(SamplingKey::Operation)).cloned()
+    }
+}
+
+impl TelemetryProcessor for Sampler {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        if self.percentage >= 100.0 || self.always_keeps(envelope) {
+            return true;
+        }
+
+        let key = self
+            .key_of(envelope)
+            .unwrap_or_else(|| uuid::new().simple().to_string());
+        if self.is_sampled_in(&key) {
+            envelope.sample_rate = Some(self.percentage);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Determines whether items with specified value of the sampling key are kept. Items kept by one of
+    /// the always-keep rules are kept regardless.
+    fn is_sampled_in(&self, key: &str) -> bool {
+        self.percentage >= 100.0 || score(key) < self.percentage
+    }
+}
+
+/// Returns an order of a severity level from the least to the most severe.
+fn rank(severity: &ContractsSeverityLevel) -> u8 {
+    match severity {
+        ContractsSeverityLevel::Verbose => 0,
+        ContractsSeverityLevel::Information => 1,
+        ContractsSeverityLevel::Warning => 2,
+        ContractsSeverityLevel::Error => 3,
+        ContractsSeverityLevel::Critical => 4,
+    }
+}
+
+/// Returns a sampling score of a key in `[0, 100]` computed the same way as in other Application
+/// Insights SDKs.
+fn score(key: &str) -> f64 {
+    let mut chars: Vec<u16> = key.encode_utf16().collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+    while chars.len() < 8 {
+        chars.extend_from_within(..);
+    }
+
+    let hash = chars.iter().fold(5381i32, |hash, c| {
+        (hash << 5).wrapping_add(hash).wrapping_add(i32::from(*c))
+    });
+    let hash = if hash == i32::MIN { i32::MAX } else { hash.abs() };
+    f64::from(hash) / f64::from(i32::MAX) * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration as StdDuration};
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{
+            ContextTags, EventTelemetry, ExceptionTelemetry, MetricTelemetry, Properties, RequestTelemetry, Telemetry,
+            TraceTelemetry,
+        },
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_scores_keys_in_range() {
+        for key in ["a", "operation", "00f067aa0ba902b7", "4bf92f3577b34da6a3ce929d0e0e4736"] {
+            assert!(
+                (0.0..=100.0).contains(&score(key)),
+                "unexpected score {} of {}",
+                score(key),
+                key
+            );
+        }
+        assert_eq!(score(""), 0.0);
+    }
+
+    #[test]
+    fn it_keeps_share_of_operations() {
+        let sampler = Sampler::new(25.0);
+
+        let kept = (0..10000)
+            .filter(|i| sampler.process(&mut envelope(&operation_id(*i), None)))
+            .count();
+
+        assert!((2000..3000).contains(&kept), "unexpected number of kept items {}", kept);
+    }
+
+    #[test_case(SamplingKey::Operation, false ; "different operations sampled separately")]
+    #[test_case(SamplingKey::User,      true  ; "same user sampled together")]
+    fn it_samples_items_together_by_key(key: SamplingKey, together: bool) {
+        let sampler = Sampler::new(50.0).with_key(key);
+
+        let decisions: Vec<_> = (0..100)
+            .map(|i| sampler.process(&mut envelope(&operation_id(i), Some("user"))))
+            .collect();
+
+        assert_eq!(decisions.iter().all(|kept| *kept == decisions[0]), together);
+    }
+
+    #[test_case(Some("user"), "user"      ; "user")]
+    #[test_case(Some(""),     "operation" ; "empty user")]
+    #[test_case(None,         "operation" ; "no user")]
+    fn it_falls_back_to_operation_without_user(user: Option<&str>, expected: &str) {
+        let sampler = Sampler::new(50.0).with_key(SamplingKey::User);
+
+        assert_eq!(sampler.key_of(&envelope("operation", user)), Some(expected.into()));
+    }
+
+    #[test]
+    fn it_sets_sample_rate_and_keeps_metrics() {
+        let operation = (0..).map(operation_id).find(|key| score(key) < 50.0).unwrap();
+        let mut kept = envelope(&operation, None);
+        assert!(Sampler::new(50.0).process(&mut kept));
+        assert_eq!(kept.sample_rate, Some(50.0));
+
+        let sampler = Sampler::new(0.0);
+        assert!(!sampler.process(&mut envelope(&operation, None)));
+        assert!(sampler.process(&mut Envelope::from((context(), MetricTelemetry::new("latency", 1.0)))));
+    }
+
+    #[test]
+    fn it_always_keeps_diagnostic_items() {
+        let sampler = Sampler::new(0.0)
+            .always_keep_exceptions()
+            .always_keep_failed_requests()
+            .always_keep_severity(SeverityLevel::Error);
+        let request = |code: &str| {
+            RequestTelemetry::new(
+                http::Method::GET,
+                "/".parse().unwrap(),
+                StdDuration::from_millis(10),
+                code,
+            )
+        };
+
+        let mut exception = Envelope::from((context(), ExceptionTelemetry::new(&io::Error::other("failed"))));
+        assert!(sampler.process(&mut exception));
+        assert_eq!(exception.sample_rate, Some(100.0));
+        assert!(sampler.process(&mut Envelope::from((context(), request("500")))));
+        assert!(!sampler.process(&mut Envelope::from((context(), request("200")))));
+        let trace = |severity| Envelope::from((context(), TraceTelemetry::new("trace", severity)));
+        assert!(sampler.process(&mut trace(SeverityLevel::Critical)));
+        assert!(sampler.process(&mut trace(SeverityLevel::Error)));
+        assert!(!sampler.process(&mut trace(SeverityLevel::Warning)));
+    }
+
+    /// Returns a scattered hex id like generated operation ids are.
+    fn operation_id(i: u128) -> String {
+        format!(
+            "{:032x}",
+            (i + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835)
+        )
+    }
+
+    fn envelope(operation: &str, user: Option<&str>) -> Envelope {
+        let mut event = EventTelemetry::new("event");
+        event.tags_mut().operation_mut().set_id(operation.into());
+        if let Some(user) = user {
+            event.tags_mut().user_mut().set_id(user.into());
+        }
+        Envelope::from((context(), event))
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default())
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use chrono::DateTime;
+use log::warn;
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    diagnostics::{Diagnostic, Diagnostics},
+    processor::TelemetryProcessor,
+    time::Duration,
+};
+
+/// Validates telemetry items against constraints of Application Insights item schema: required fields,
+/// maximum lengths of strings and formats of times and durations. The ingestion endpoint drops or
+/// truncates items that break them without telling an application, so a validator catches malformed
+/// telemetry in development.
+///
+/// Violations are logged as warnings and reported to [`diagnostics`](../diagnostics/index.html) of a
+/// client as [`SchemaViolation`](../diagnostics/enum.Diagnostic.html#variant.SchemaViolation). A strict
+/// validator also drops invalid items. Severity levels are always valid as they can only be one of
+/// [`SeverityLevel`](../telemetry/enum.SeverityLevel.html) variants.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::SchemaValidator, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(SchemaValidator::new().strict(cfg!(debug_assertions)));
+/// ```
+pub struct SchemaValidator {
+    strict: bool,
+}
+
+impl SchemaValidator {
+    /// Creates a new validator that logs violations and keeps invalid items.
+    pub fn new() -> Self {
+        Self { strict: false }
+    }
+
+    /// Sets whether invalid items are dropped after violations are reported.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl Default for SchemaValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryProcessor for SchemaValidator {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        self.process_with_diagnostics(envelope, &Diagnostics::default())
+    }
+
+    fn process_with_diagnostics(&self, envelope: &mut Envelope, diagnostics: &Diagnostics) -> bool {
+        let violations = validate(envelope);
+        if violations.is_empty() {
+            return true;
+        }
+
+        for violation in &violations {
+            warn!("Telemetry item {} is invalid: {}", envelope.name, violation);
+        }
+        diagnostics.report(Diagnostic::SchemaViolation {
+            envelope,
+            violations: &violations,
+        });
+        !self.strict
+    }
+}
+
+/// A constraint of the item schema a telemetry item breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A required field is missing or empty.
+    Missing {
+        /// A name of the field.
+        field: String,
+    },
+
+    /// A string field is longer than the schema allows.
+    TooLong {
+        /// A name of the field.
+        field: String,
+
+        /// A length of the value in characters.
+        length: usize,
+
+        /// A maximum length of the value in characters.
+        max: usize,
+    },
+
+    /// A field has a value of a wrong format.
+    Malformed {
+        /// A name of the field.
+        field: String,
+
+        /// A value of the field.
+        value: String,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Missing { field } => write!(f, "{} is required", field),
+            Violation::TooLong { field, length, max } => {
+                write!(f, "{} is {} characters long, at most {} allowed", field, length, max)
+            }
+            Violation::Malformed { field, value } => write!(f, "{} has malformed value {:?}", field, value),
+        }
+    }
+}
+
+/// Maximum lengths of context tag values.
+const TAG_LENGTHS: [(&str, usize); 14] = [
+    ("ai.application.ver", 1024),
+    ("ai.cloud.role", 256),
+    ("ai.cloud.roleInstance", 256),
+    ("ai.device.id", 1024),
+    ("ai.internal.sdkVersion", 64),
+    ("ai.location.ip", 46),
+    ("ai.operation.correlationVector", 64),
+    ("ai.operation.id", 128),
+    ("ai.operation.name", 1024),
+    ("ai.operation.parentId", 128),
+    ("ai.operation.syntheticSource", 1024),
+    ("ai.session.id", 64),
+    ("ai.user.authUserId", 1024),
+    ("ai.user.id", 128),
+];
+
+const MAX_PROPERTY_KEY: usize = 150;
+const MAX_PROPERTY_VALUE: usize = 8192;
+
+/// Returns all constraints of the item schema a telemetry item breaks.
+pub(crate) fn validate(envelope: &Envelope) -> Vec<Violation> {
+    let mut validator = Validator::default();
+    validator.required("name", &envelope.name);
+    validator.required("iKey", envelope.i_key.as_deref().unwrap_or_default());
+    if DateTime::parse_from_rfc3339(&envelope.time).is_err() {
+        validator.malformed("time", &envelope.time);
+    }
+    for (tag, max) in TAG_LENGTHS.iter() {
+        if let Some(value) = envelope.tags.as_ref().and_then(|tags| tags.get(*tag)) {
+            validator.max_length(&format!("tags.{}", tag), value, *max);
+        }
+    }
+
+    match &envelope.data {
+        Some(Base::Data(Data::AvailabilityData(data))) => {
+            validator.required("id", &data.id).max_length("id", &data.id, 64);
+            validator
+                .required("name", &data.name)
+                .max_length("name", &data.name, 1024);
+            validator.duration("duration", &data.duration);
+            validator.optional("runLocation", &data.run_location, 1024);
+            validator.optional("message", &data.message, 8192);
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::EventData(data))) => {
+            validator
+                .required("name", &data.name)
+                .max_length("name", &data.name, 512);
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::ExceptionData(data))) => {
+            if data.exceptions.is_empty() {
+                validator.missing("exceptions");
+            }
+            for exception in &data.exceptions {
+                validator
+                    .required("exceptions.typeName", &exception.type_name)
+                    .max_length("exceptions.typeName", &exception.type_name, 1024);
+                validator.max_length("exceptions.message", &exception.message, 32768);
+            }
+            validator.optional("problemId", &data.problem_id, 1024);
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::MessageData(data))) => {
+            validator
+                .required("message", &data.message)
+                .max_length("message", &data.message, 32768);
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::MetricData(data))) => {
+            if data.metrics.is_empty() {
+                validator.missing("metrics");
+            }
+            for metric in &data.metrics {
+                validator
+                    .required("metrics.name", &metric.name)
+                    .max_length("metrics.name", &metric.name, 1024);
+                validator.optional("metrics.ns", &metric.ns, 256);
+            }
+            validator.properties(&data.properties, &None);
+        }
+        Some(Base::Data(Data::PageViewData(data))) => {
+            validator
+                .required("name", &data.name)
+                .max_length("name", &data.name, 1024);
+            validator.optional("url", &data.url, 2048);
+            if let Some(duration) = &data.duration {
+                validator.duration("duration", duration);
+            }
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::PageViewPerfData(data))) => {
+            validator
+                .required("name", &data.name)
+                .max_length("name", &data.name, 1024);
+            validator.optional("url", &data.url, 2048);
+            let durations = [
+                ("duration", &data.duration),
+                ("perfTotal", &data.perf_total),
+                ("networkConnect", &data.network_connect),
+                ("sentRequest", &data.sent_request),
+                ("receivedResponse", &data.received_response),
+                ("domProcessing", &data.dom_processing),
+            ];
+            for (field, duration) in durations {
+                if let Some(duration) = duration {
+                    validator.duration(field, duration);
+                }
+            }
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::RemoteDependencyData(data))) => {
+            validator
+                .required("name", &data.name)
+                .max_length("name", &data.name, 1024);
+            validator.optional("id", &data.id, 128);
+            validator.optional("resultCode", &data.result_code, 1024);
+            validator.duration("duration", &data.duration);
+            validator.optional("data", &data.data, 8192);
+            validator.optional("target", &data.target, 1024);
+            validator.optional("type", &data.type_, 1024);
+            validator.properties(&data.properties, &data.measurements);
+        }
+        Some(Base::Data(Data::RequestData(data))) => {
+            validator.required("id", &data.id).max_length("id", &data.id, 128);
+            validator.optional("source", &data.source, 1024);
+            validator.optional("name", &data.name, 1024);
+            validator.duration("duration", &data.duration);
+            validator.required("responseCode", &data.response_code).max_length(
+                "responseCode",
+                &data.response_code,
+                1024,
+            );
+            validator.optional("url", &data.url, 2048);
+            validator.properties(&data.properties, &data.measurements);
+        }
+        None => validator.missing("data"),
+    }
+
+    validator.violations
+}
+
+/// Collects violations of schema constraints.
+#[derive(Default)]
+struct Validator {
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    fn missing(&mut self, field: &str) {
+        self.violations.push(Violation::Missing { field: field.into() });
+    }
+
+    fn malformed(&mut self, field: &str, value: &str) {
+        self.violations.push(Violation::Malformed {
+            field: field.into(),
+            value: value.into(),
+        });
+    }
+
+    fn required(&mut self, field: &str, value: &str) -> &mut Self {
+        if value.trim().is_empty() {
+            self.missing(field);
+        }
+        self
+    }
+
+    fn max_length(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        let length = value.chars().count();
+        if length > max {
+            self.violations.push(Violation::TooLong {
+                field: field.into(),
+                length,
+                max,
+            });
+        }
+        self
+    }
+
+    fn optional(&mut self, field: &str, value: &Option<String>, max: usize) {
+        if let Some(value) = value {
+            self.max_length(field, value, max);
+        }
+    }
+
+    fn duration(&mut self, field: &str, value: &str) {
+        if value.parse::<Duration>().is_err() {
+            self.malformed(field, value);
+        }
+    }
+
+    fn properties(
+        &mut self,
+        properties: &Option<BTreeMap<String, String>>,
+        measurements: &Option<BTreeMap<String, f64>>,
+    ) {
+        for (key, value) in properties.iter().flatten() {
+            self.max_length("properties key", key, MAX_PROPERTY_KEY);
+            self.max_length(&format!("properties.{}", key), value, MAX_PROPERTY_VALUE);
+        }
+        for key in measurements.iter().flatten().map(|(key, _)| key) {
+            self.max_length("measurements key", key, MAX_PROPERTY_KEY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        contracts::PageViewPerfData,
+        telemetry::{ContextTags, EventTelemetry, Properties, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_accepts_valid_items() {
+        let mut envelope = envelope(EventTelemetry::new("event"));
+
+        assert_eq!(validate(&envelope), Vec::new());
+        assert!(SchemaValidator::new().strict(true).process(&mut envelope));
+    }
+
+    #[test]
+    fn it_finds_all_violations() {
+        let mut event = EventTelemetry::new("");
+        event.properties_mut().insert("k".repeat(151), "value".into());
+        let mut envelope = envelope(event);
+        envelope.time = "yesterday".into();
+
+        assert_eq!(
+            validate(&envelope),
+            vec![
+                Violation::Malformed {
+                    field: "time".into(),
+                    value: "yesterday".into()
+                },
+                Violation::Missing { field: "name".into() },
+                Violation::TooLong {
+                    field: "properties key".into(),
+                    length: 151,
+                    max: 150
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_validates_page_view_performance() {
+        let mut envelope = envelope(EventTelemetry::new("page"));
+        envelope.data = Some(Base::Data(Data::PageViewPerfData(PageViewPerfData {
+            name: "page".into(),
+            perf_total: Some("soon".into()),
+            ..PageViewPerfData::default()
+        })));
+
+        assert_eq!(
+            validate(&envelope),
+            vec![Violation::Malformed {
+                field: "perfTotal".into(),
+                value: "soon".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn it_reports_violations_and_drops_invalid_items_in_strict_mode() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics = Diagnostics::new({
+            let reported = reported.clone();
+            move |diagnostic| {
+                if let Diagnostic::SchemaViolation { violations, .. } = diagnostic {
+                    reported.lock().unwrap().extend_from_slice(violations)
+                }
+            }
+        });
+        let validator = SchemaValidator::new().strict(true);
+
+        assert!(!validator.process_with_diagnostics(&mut envelope(EventTelemetry::new("e".repeat(513))), &diagnostics));
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![Violation::TooLong {
+                field: "name".into(),
+                length: 513,
+                max: 512
+            }]
+        );
+    }
+
+    fn envelope(event: EventTelemetry) -> Envelope {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        Envelope::from((context, event))
+    }
+}
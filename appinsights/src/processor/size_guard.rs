@@ -0,0 +1,297 @@
+use std::fmt::Write;
+
+use log::warn;
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    diagnostics::{Diagnostic, Diagnostics},
+    processor::{property_filter::properties_mut, TelemetryProcessor},
+};
+
+/// Maximum size of a single telemetry item the service accepts.
+pub const MAX_ITEM_BYTES: usize = 64 * 1024;
+
+/// A way to make a telemetry item smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trim {
+    /// Removes custom properties starting from the largest one.
+    LargestProperties,
+
+    /// Removes frames from the bottom of exception stacks and truncates raw stacks.
+    ExceptionStacks,
+}
+
+/// Trims telemetry items larger than a size limit in bytes of serialized JSON, so the service doesn't
+/// reject them. Trimming steps are applied in order until an item fits, by default dropping the largest
+/// properties first and then truncating exception stacks. Every trimmed item is logged with a warning and
+/// reported to [`diagnostics`](../diagnostics/index.html) of a client as
+/// [`Oversized`](../diagnostics/enum.Diagnostic.html#variant.Oversized) telling what was removed, and an
+/// item that is still too large is dropped.
+///
+/// It is added automatically when [`max_item_bytes`](../struct.TelemetryConfigBuilder.html#method.max_item_bytes)
+/// is set in the configuration.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::{SizeGuard, Trim}, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(SizeGuard::new(32 * 1024).with_steps([Trim::ExceptionStacks, Trim::LargestProperties]));
+/// ```
+pub struct SizeGuard {
+    max_bytes: usize,
+    steps: Vec<Trim>,
+}
+
+impl SizeGuard {
+    /// Creates a new processor that trims items larger than `max_bytes`.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            steps: vec![Trim::LargestProperties, Trim::ExceptionStacks],
+        }
+    }
+
+    /// Sets trimming steps in order they are applied.
+    pub fn with_steps(mut self, steps: impl IntoIterator<Item = Trim>) -> Self {
+        self.steps = steps.into_iter().collect();
+        self
+    }
+
+    /// Removes the largest custom properties until an item fits.
+    fn trim_properties(&self, envelope: &mut Envelope, size: &mut usize, trimmed: &mut Vec<String>) {
+        while *size > self.max_bytes {
+            let properties = match properties_mut(envelope) {
+                Some(properties) => properties,
+                None => return,
+            };
+            let largest = properties
+                .iter()
+                .max_by_key(|(key, value)| key.len() + value.len())
+                .map(|(key, _)| key.clone());
+            match largest {
+                Some(key) => {
+                    properties.remove(&key);
+                    trimmed.push(format!("property {}", key));
+                }
+                None => return,
+            }
+            *size = size_of(envelope);
+        }
+    }
+
+    /// Halves parsed stacks and truncates raw stacks of exceptions until an item fits.
+    fn trim_stacks(&self, envelope: &mut Envelope, size: &mut usize, trimmed: &mut Vec<String>) {
+        if !matches!(envelope.data, Some(Base::Data(Data::ExceptionData(_)))) {
+            return;
+        }
+
+        let mut truncated = false;
+        while *size > self.max_bytes {
+            let excess = *size - self.max_bytes;
+            let exceptions = match envelope.data.as_mut() {
+                Some(Base::Data(Data::ExceptionData(data))) => &mut data.exceptions,
+                _ => return,
+            };
+
+            let mut changed = false;
+            for exception in exceptions.iter_mut() {
+                if let Some(frames) = exception.parsed_stack.as_mut().filter(|frames| !frames.is_empty()) {
+                    frames.truncate(frames.len() / 2);
+                    changed = true;
+                } else if let Some(stack) = exception.stack.as_mut().filter(|stack| !stack.is_empty()) {
+                    let mut len = stack.len().saturating_sub(excess);
+                    while !stack.is_char_boundary(len) {
+                        len -= 1;
+                    }
+                    stack.truncate(len);
+                    changed = true;
+                }
+                if changed {
+                    exception.has_full_stack = Some(false);
+                    break;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+            truncated = true;
+            *size = size_of(envelope);
+        }
+
+        if truncated {
+            trimmed.push("exception stacks".into());
+        }
+    }
+}
+
+impl Default for SizeGuard {
+    fn default() -> Self {
+        Self::new(MAX_ITEM_BYTES)
+    }
+}
+
+impl TelemetryProcessor for SizeGuard {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        self.process_with_diagnostics(envelope, &Diagnostics::default())
+    }
+
+    fn process_with_diagnostics(&self, envelope: &mut Envelope, diagnostics: &Diagnostics) -> bool {
+        let original = size_of(envelope);
+        if original <= self.max_bytes {
+            return true;
+        }
+
+        let mut size = original;
+        let mut trimmed = Vec::new();
+        for step in &self.steps {
+            match step {
+                Trim::LargestProperties => self.trim_properties(envelope, &mut size, &mut trimmed),
+                Trim::ExceptionStacks => self.trim_stacks(envelope, &mut size, &mut trimmed),
+            }
+        }
+
+        let mut message = format!(
+            "Telemetry item {} of {} bytes exceeds size limit of {} bytes",
+            envelope.name, original, self.max_bytes
+        );
+        if !trimmed.is_empty() {
+            let _ = write!(message, ", trimmed {} to {} bytes", trimmed.join(", "), size);
+        }
+        let dropped = size > self.max_bytes;
+        if dropped {
+            warn!("{}, dropped", message);
+        } else {
+            warn!("{}", message);
+        }
+        diagnostics.report(Diagnostic::Oversized {
+            envelope,
+            size: original,
+            max_bytes: self.max_bytes,
+            trimmed: &trimmed,
+            dropped,
+        });
+        !dropped
+    }
+}
+
+/// Returns a size of serialized JSON of a telemetry item.
+pub(super) fn size_of(envelope: &Envelope) -> usize {
+    serde_json::to_vec(envelope).map_or(0, |json| json.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        contracts::{ExceptionData, ExceptionDetails, StackFrame},
+        telemetry::{ContextTags, EventTelemetry, Properties, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_keeps_items_within_limit_intact() {
+        let mut envelope = event(&[("small", 10)]);
+        let expected = envelope.clone();
+
+        assert!(SizeGuard::default().process(&mut envelope));
+        assert_eq!(envelope, expected);
+    }
+
+    #[test]
+    fn it_drops_largest_properties_first() {
+        let mut envelope = event(&[("large", 1000), ("medium", 500), ("small", 10)]);
+        let max_bytes = size_of(&envelope) - 800;
+
+        assert!(SizeGuard::new(max_bytes).process(&mut envelope));
+        assert_eq!(
+            properties_mut(&mut envelope).unwrap().keys().collect::<Vec<_>>(),
+            vec!["medium", "small"]
+        );
+    }
+
+    #[test]
+    fn it_truncates_exception_stacks() {
+        let mut envelope = exception(100);
+        let max_bytes = size_of(&envelope) / 2;
+
+        assert!(SizeGuard::new(max_bytes)
+            .with_steps(vec![Trim::ExceptionStacks])
+            .process(&mut envelope));
+        assert!(size_of(&envelope) <= max_bytes);
+        match envelope.data {
+            Some(Base::Data(Data::ExceptionData(data))) => {
+                let exception = &data.exceptions[0];
+                assert!(exception.parsed_stack.as_ref().unwrap().len() < 100);
+                assert_eq!(exception.has_full_stack, Some(false));
+            }
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_drops_items_that_cannot_be_trimmed() {
+        let mut envelope = event(&[("large", 1000)]);
+
+        assert!(!SizeGuard::new(100)
+            .with_steps(vec![Trim::ExceptionStacks])
+            .process(&mut envelope));
+    }
+
+    #[test_case(2000, vec!["property large".to_string()], false ; "trimmed")]
+    #[test_case(100,  vec!["property large".to_string(), "property small".to_string()], true  ; "dropped")]
+    fn it_reports_oversized_items(max_bytes: usize, expected: Vec<String>, expected_dropped: bool) {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics = Diagnostics::new({
+            let reported = reported.clone();
+            move |diagnostic| {
+                if let Diagnostic::Oversized { trimmed, dropped, .. } = diagnostic {
+                    reported.lock().unwrap().push((trimmed.to_vec(), *dropped));
+                }
+            }
+        });
+        let mut envelope = event(&[("large", 3000), ("small", 10)]);
+
+        SizeGuard::new(max_bytes)
+            .with_steps(vec![Trim::LargestProperties])
+            .process_with_diagnostics(&mut envelope, &diagnostics);
+
+        assert_eq!(*reported.lock().unwrap(), vec![(expected, expected_dropped)]);
+    }
+
+    fn event(properties: &[(&str, usize)]) -> Envelope {
+        let mut event = EventTelemetry::new("event");
+        for (key, len) in properties {
+            event.properties_mut().insert(key.to_string(), "x".repeat(*len));
+        }
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        Envelope::from((context, event))
+    }
+
+    fn exception(frames: i32) -> Envelope {
+        let parsed_stack = (0..frames)
+            .map(|level| StackFrame {
+                level,
+                method: format!("app::module::function_{}", level),
+                ..StackFrame::default()
+            })
+            .collect();
+        Envelope {
+            name: "Microsoft.ApplicationInsights.Exception".into(),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: vec![ExceptionDetails {
+                    type_name: "Error".into(),
+                    message: "failed".into(),
+                    has_full_stack: Some(true),
+                    parsed_stack: Some(parsed_stack),
+                    ..ExceptionDetails::default()
+                }],
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+}
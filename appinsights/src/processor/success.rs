@@ -0,0 +1,188 @@
+use crate::{
+    contracts::{Base, Data, Envelope},
+    processor::TelemetryProcessor,
+};
+
+type Classify = dyn Fn(&CallResult<'_>) -> Option<bool> + Send + Sync;
+
+/// Kind of a call a result code is classified for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    /// A request served by the application.
+    Request,
+
+    /// A dependency call made by the application.
+    Dependency,
+}
+
+/// A completed call to classify as successful or unsuccessful.
+#[derive(Debug, Clone, Copy)]
+pub struct CallResult<'a> {
+    kind: CallKind,
+    name: &'a str,
+    dependency_type: Option<&'a str>,
+    result_code: &'a str,
+}
+
+impl<'a> CallResult<'a> {
+    /// Returns a kind of the call.
+    pub fn kind(&self) -> CallKind {
+        self.kind
+    }
+
+    /// Returns a name of the call, e.g. `GET /orders/{id}`.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns a dependency type of a dependency call, e.g. `Http` or `SQL`.
+    pub fn dependency_type(&self) -> Option<&'a str> {
+        self.dependency_type
+    }
+
+    /// Returns a result code of the call, usually HTTP status code.
+    pub fn result_code(&self) -> &'a str {
+        self.result_code
+    }
+
+    /// Returns a result code as HTTP status code if it is one.
+    pub fn status(&self) -> Option<http::StatusCode> {
+        self.result_code.parse().ok()
+    }
+}
+
+/// Overrides how requests and dependency calls are classified as successful or unsuccessful. By default
+/// a request is successful when it is responded with a status code below 400 or with 401, and an integration
+/// considers a dependency call successful when it is responded with a status code below 400. A classifier
+/// decides it based on a result code instead. It returns `None` to keep a default decision.
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// use appinsights::processor::{CallKind, SuccessClassifier};
+/// use http::StatusCode;
+///
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(SuccessClassifier::new(|call| match (call.kind(), call.status()?) {
+///     // missing items are expected on lookup API
+///     (CallKind::Request, StatusCode::NOT_FOUND) if call.name().starts_with("GET /lookup") => Some(true),
+///     // throttled calls are failures even though they will be retried
+///     (_, StatusCode::TOO_MANY_REQUESTS) => Some(false),
+///     _ => None,
+/// }));
+/// ```
+pub struct SuccessClassifier {
+    classify: Box<Classify>,
+}
+
+impl SuccessClassifier {
+    /// Creates a new processor that classifies calls with specified function.
+    pub fn new<F>(classify: F) -> Self
+    where
+        F: Fn(&CallResult<'_>) -> Option<bool> + Send + Sync + 'static,
+    {
+        Self {
+            classify: Box::new(classify),
+        }
+    }
+}
+
+impl TelemetryProcessor for SuccessClassifier {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        match &mut envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                let call = CallResult {
+                    kind: CallKind::Request,
+                    name: data.name.as_deref().unwrap_or_default(),
+                    dependency_type: None,
+                    result_code: &data.response_code,
+                };
+                if let Some(success) = (self.classify)(&call) {
+                    data.success = success;
+                }
+            }
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                if let Some(result_code) = &data.result_code {
+                    let call = CallResult {
+                        kind: CallKind::Dependency,
+                        name: &data.name,
+                        dependency_type: data.type_.as_deref(),
+                        result_code,
+                    };
+                    if let Some(success) = (self.classify)(&call) {
+                        data.success = Some(success);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::{Method, StatusCode};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, Properties, RemoteDependencyTelemetry, RequestTelemetry},
+        TelemetryContext,
+    };
+
+    #[test_case("GET /lookup/{id}", "404", true  ; "expected not found")]
+    #[test_case("GET /orders/{id}", "404", false ; "default not found")]
+    #[test_case("GET /orders/{id}", "429", false ; "throttled")]
+    #[test_case("GET /orders/{id}", "200", true  ; "default ok")]
+    fn it_classifies_requests(name: &str, response_code: &str, expected: bool) {
+        let mut request = RequestTelemetry::new(
+            Method::GET,
+            "https://example.com/orders/42".parse().unwrap(),
+            Duration::from_millis(42),
+            response_code,
+        );
+        request.set_name(name);
+        let mut envelope = Envelope::from((context(), request));
+
+        assert!(classifier().process(&mut envelope));
+
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => assert_eq!(data.success, expected),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test_case(Some("429"), true, Some(false) ; "throttled")]
+    #[test_case(Some("200"), true, Some(true)  ; "default ok")]
+    #[test_case(None,        true, Some(true)  ; "without result code")]
+    fn it_classifies_dependencies(result_code: Option<&str>, success: bool, expected: Option<bool>) {
+        let mut dependency =
+            RemoteDependencyTelemetry::new("GET /orders", "Http", Duration::from_millis(42), "example.com", success);
+        if let Some(result_code) = result_code {
+            dependency.set_result_code(result_code);
+        }
+        let mut envelope = Envelope::from((context(), dependency));
+
+        assert!(classifier().process(&mut envelope));
+
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => assert_eq!(data.success, expected),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    fn classifier() -> SuccessClassifier {
+        SuccessClassifier::new(|call| match (call.kind(), call.status()?) {
+            (CallKind::Request, StatusCode::NOT_FOUND) if call.name().starts_with("GET /lookup") => Some(true),
+            (_, StatusCode::TOO_MANY_REQUESTS) => Some(false),
+            _ => None,
+        })
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default())
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::{
+    context::TENANT_PROPERTY,
+    contracts::Envelope,
+    processor::{property_filter::properties_mut, TelemetryProcessor},
+};
+
+/// Directs telemetry items of specific tenants to their own Application Insights resources. An item
+/// with a [`TENANT_PROPERTY`](../constant.TENANT_PROPERTY.html) property, usually set with
+/// [`TelemetryContext::set_tenant`](../struct.TelemetryContext.html#method.set_tenant), is submitted with
+/// an instrumentation key routed for its tenant. Items of other tenants keep the key of the client, or
+/// are dropped if the router is exclusive.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::TenantRouter, TelemetryClient};
+/// let mut client = TelemetryClient::new("<shared instrumentation key>".to_string());
+/// client.add_processor(
+///     TenantRouter::new()
+///         .route("contoso", "<contoso instrumentation key>")
+///         .route("fabrikam", "<fabrikam instrumentation key>"),
+/// );
+///
+/// let mut context = client.context().clone();
+/// context.set_tenant("contoso");
+/// client.with_context(context).track_event("order placed");
+/// ```
+#[derive(Debug, Default)]
+pub struct TenantRouter {
+    routes: HashMap<String, String>,
+    exclusive: bool,
+}
+
+impl TenantRouter {
+    /// Creates a new router without routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a route of items of a tenant to an instrumentation key.
+    pub fn route(mut self, tenant: impl Into<String>, i_key: impl Into<String>) -> Self {
+        self.routes.insert(tenant.into(), i_key.into());
+        self
+    }
+
+    /// Sets whether items without a route are dropped instead of submitted with the key of the client.
+    pub fn exclusive(mut self, exclusive: bool) -> Self {
+        self.exclusive = exclusive;
+        self
+    }
+}
+
+impl TelemetryProcessor for TenantRouter {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        let i_key = properties_mut(envelope)
+            .and_then(|properties| properties.get(TENANT_PROPERTY))
+            .and_then(|tenant| self.routes.get(tenant));
+        match i_key {
+            Some(i_key) => {
+                envelope.i_key = Some(i_key.clone());
+                true
+            }
+            None => !self.exclusive,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties},
+        TelemetryContext,
+    };
+
+    #[test_case(Some("contoso"),  false, Some("contoso-key") ; "routed tenant")]
+    #[test_case(Some("fabrikam"), false, Some("default-key") ; "unknown tenant")]
+    #[test_case(None,             false, Some("default-key") ; "no tenant")]
+    #[test_case(Some("fabrikam"), true,  None                ; "unknown tenant dropped")]
+    fn it_routes_items_by_tenant(tenant: Option<&str>, exclusive: bool, expected: Option<&str>) {
+        let mut context = TelemetryContext::new("default-key".into(), ContextTags::default(), Properties::default());
+        if let Some(tenant) = tenant {
+            context.set_tenant(tenant);
+        }
+        let mut envelope = Envelope::from((context, EventTelemetry::new("event")));
+        let router = TenantRouter::new().route("contoso", "contoso-key").exclusive(exclusive);
+
+        let kept = router.process(&mut envelope);
+
+        assert_eq!(kept.then_some(envelope.i_key).flatten().as_deref(), expected);
+    }
+}
@@ -0,0 +1,112 @@
+use std::thread;
+
+use crate::{
+    contracts::{Base, Data, Envelope},
+    processor::TelemetryProcessor,
+};
+
+/// Stamps traces and exceptions with a name and an id of a thread and an id of a Tokio task they were
+/// tracked from, as `thread.name`, `thread.id` and `task.id` properties. It helps to tell apart
+/// interleaved telemetry from many concurrent tasks. Processors run at the time an item is tracked, so
+/// the values belong to the code that tracked it.
+///
+/// It is added automatically when [`thread_metadata`](../struct.TelemetryConfigBuilder.html#method.thread_metadata)
+/// is enabled in the configuration.
+///
+/// ```rust, no_run
+/// # use appinsights::{processor::ThreadMetadata, TelemetryClient};
+/// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.add_processor(ThreadMetadata);
+/// ```
+pub struct ThreadMetadata;
+
+impl TelemetryProcessor for ThreadMetadata {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        let properties = match envelope.data.as_mut() {
+            Some(Base::Data(Data::MessageData(data))) => &mut data.properties,
+            Some(Base::Data(Data::ExceptionData(data))) => &mut data.properties,
+            _ => return true,
+        };
+        let properties = properties.get_or_insert_with(Default::default);
+
+        let current = thread::current();
+        if let Some(name) = current.name() {
+            properties.insert("thread.name".into(), name.into());
+        }
+        properties.insert("thread.id".into(), thread_id(current.id()));
+        if let Some(id) = tokio::task::try_id() {
+            properties.insert("task.id".into(), id.to_string());
+        }
+
+        true
+    }
+}
+
+/// Returns a number of a thread id. Its `Debug` representation is the only way to get it on stable Rust.
+fn thread_id(id: thread::ThreadId) -> String {
+    format!("{:?}", id).chars().filter(char::is_ascii_digit).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties, SeverityLevel, TraceTelemetry},
+        TelemetryContext,
+    };
+
+    #[tokio::test]
+    async fn it_stamps_traces_with_thread_and_task() {
+        let mut envelope = Envelope::from((context(), TraceTelemetry::new("trace", SeverityLevel::Information)));
+
+        let properties = tokio::spawn(async move {
+            ThreadMetadata.process(&mut envelope);
+            match envelope.data {
+                Some(Base::Data(Data::MessageData(data))) => data.properties.unwrap(),
+                data => panic!("unexpected data {:?}", data),
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(properties["thread.id"].parse::<u64>().is_ok());
+        assert!(properties.contains_key("task.id"));
+    }
+
+    #[test]
+    fn it_skips_other_items() {
+        let mut envelope = Envelope::from((context(), EventTelemetry::new("event")));
+
+        assert!(ThreadMetadata.process(&mut envelope));
+
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => assert!(!data.properties.unwrap().contains_key("thread.id")),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_stamps_thread_name_outside_of_tasks() {
+        let properties = thread::Builder::new()
+            .name("worker".into())
+            .spawn(|| {
+                let mut envelope =
+                    Envelope::from((context(), TraceTelemetry::new("trace", SeverityLevel::Information)));
+                ThreadMetadata.process(&mut envelope);
+                match envelope.data {
+                    Some(Base::Data(Data::MessageData(data))) => data.properties.unwrap(),
+                    data => panic!("unexpected data {:?}", data),
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(properties["thread.name"], "worker");
+        assert!(!properties.contains_key("task.id"));
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default())
+    }
+}
@@ -0,0 +1,192 @@
+//! Dependency tracking for Redis commands.
+//!
+//! [`RedisInstrumentation`](struct.RedisInstrumentation.html) measures Redis commands and submits each
+//! of them as a [`RemoteDependencyTelemetry`](../telemetry/struct.RemoteDependencyTelemetry.html) item
+//! with the command name, the server address as a target, duration and success status. Command arguments
+//! are never submitted, as they may contain keys and values with sensitive data.
+//!
+//! The instrumentation does not depend on a particular version of
+//! [`redis`](https://docs.rs/redis) crate. It wraps any future or function that executes a command.
+//!
+//! ```rust, ignore
+//! use appinsights::{redis::RedisInstrumentation, TelemetryClient};
+//!
+//! # async fn run() -> redis::RedisResult<()> {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! let redis = RedisInstrumentation::new(client, "redis://cache.local:6379/0");
+//!
+//! let mut connection = redis::Client::open("redis://cache.local:6379/0")?.get_async_connection().await?;
+//!
+//! // submitted as a dependency named "GET" with "cache.local:6379" target
+//! let value: Option<String> = redis
+//!     .track("GET", redis::cmd("GET").arg("user:42").query_async(&mut connection))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::{fmt::Display, future::Future, sync::Arc};
+
+use http::Uri;
+
+use crate::{
+    telemetry::{RemoteDependencyTelemetry, Telemetry},
+    time::Stopwatch,
+    TelemetryClient,
+};
+
+/// Dependency type of Redis commands.
+pub const DEPENDENCY_TYPE: &str = "Redis";
+
+/// Tracks Redis commands as dependency calls to a Redis server.
+pub struct RedisInstrumentation {
+    client: Arc<TelemetryClient>,
+    target: String,
+}
+
+impl RedisInstrumentation {
+    /// Creates a new instrumentation that submits commands sent to a Redis server with specified address.
+    /// An address can be a connection URL, like `redis://:password@host:6379/0`, or `host:port` pair.
+    /// Credentials and a database number are not submitted.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>, address: &str) -> Self {
+        Self {
+            client: client.into(),
+            target: target(address),
+        }
+    }
+
+    /// Returns a target all commands are submitted with.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Awaits a future that executes a Redis command and submits it as a dependency call.
+    pub async fn track<F, T, E>(&self, command: &str, future: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let started = Stopwatch::start();
+        let result = future.await;
+        self.submit(command, started, result.as_ref().err());
+        result
+    }
+
+    /// Runs a function that executes a Redis command synchronously and submits it as a dependency call.
+    pub fn track_blocking<F, T, E>(&self, command: &str, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: Display,
+    {
+        let started = Stopwatch::start();
+        let result = f();
+        self.submit(command, started, result.as_ref().err());
+        result
+    }
+
+    fn submit<E: Display>(&self, command: &str, started: Stopwatch, error: Option<&E>) {
+        let name = command_name(command);
+
+        let mut telemetry = RemoteDependencyTelemetry::new(
+            name.clone(),
+            DEPENDENCY_TYPE,
+            started.elapsed(),
+            self.target.clone(),
+            error.is_none(),
+        );
+        telemetry.set_timestamp(started.started_at());
+        telemetry.set_data(name);
+        if let Some(error) = error {
+            telemetry.properties_mut().insert("error".into(), error.to_string());
+        }
+
+        self.client.track(telemetry);
+    }
+}
+
+/// Returns a command name without arguments, e.g. `SET` for `set user:42 value`.
+pub fn command_name(command: &str) -> String {
+    command.split_whitespace().next().unwrap_or_default().to_uppercase()
+}
+
+/// Normalizes a server address to `host:port` form.
+fn target(address: &str) -> String {
+    match address.parse::<Uri>() {
+        Ok(uri) if uri.scheme().is_some() => {
+            let host = uri.host().unwrap_or_default().to_lowercase();
+            match uri.port_u16() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            }
+        }
+        _ => address.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[test_case("redis://cache.local:6379/0",           "cache.local:6379" ; "url")]
+    #[test_case("rediss://:secret@Cache.Local:6380/1",  "cache.local:6380" ; "url with credentials")]
+    #[test_case("redis://cache.local",                  "cache.local"      ; "url without port")]
+    #[test_case("cache.local:6379",                     "cache.local:6379" ; "host and port")]
+    fn it_normalizes_target(address: &str, expected: &str) {
+        assert_eq!(target(address), expected);
+    }
+
+    #[test_case("get user:42",          "GET"   ; "lowercase")]
+    #[test_case("SET user:42 secret",   "SET"   ; "with arguments")]
+    #[test_case("  HGETALL  ",          "HGETALL" ; "with spaces")]
+    fn it_strips_command_arguments(command: &str, expected: &str) {
+        assert_eq!(command_name(command), expected);
+    }
+
+    #[tokio::test]
+    async fn it_submits_successful_command() {
+        let events = Arc::new(SegQueue::default());
+        let redis = RedisInstrumentation::new(create_client(events.clone()), "redis://cache.local:6379/0");
+
+        let result = redis.track("SET user:42 secret", async { Ok::<_, String>("OK") }).await;
+
+        assert_eq!(result, Ok("OK"));
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RemoteDependencyData(data)))
+                if data.name == "SET"
+                    && data.data == Some("SET".into())
+                    && data.type_ == Some("Redis".into())
+                    && data.target == Some("cache.local:6379".into())
+                    && data.success == Some(true)
+        );
+    }
+
+    #[test]
+    fn it_submits_failed_command() {
+        let events = Arc::new(SegQueue::default());
+        let redis = RedisInstrumentation::new(create_client(events.clone()), "cache.local:6379");
+
+        let result = redis.track_blocking("GET user:42", || Err::<(), _>("connection refused"));
+
+        assert!(result.is_err());
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RemoteDependencyData(data)))
+                if data.success == Some(false)
+                    && data.properties.as_ref().unwrap().get("error") == Some(&"connection refused".to_string())
+        );
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
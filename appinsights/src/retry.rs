@@ -0,0 +1,228 @@
+//! Retry policies of telemetry transmission.
+//!
+//! When a batch of telemetry fails to be sent, the channel asks a [`RetryPolicy`](trait.RetryPolicy.html)
+//! how long to wait before sending items that can be sent again. A policy gives up by returning `None`,
+//! and the remaining items are dropped. [`ExponentialBackoff`](struct.ExponentialBackoff.html) is used
+//! by default. A custom policy configured with [`TelemetryConfig`](../struct.TelemetryConfig.html) suits
+//! unusual network environments, e.g. a proxy that needs more attempts or longer delays.
+//!
+//! ```rust, no_run
+//! use appinsights::{retry::{ExponentialBackoff, Failure, RetryPolicy}, TelemetryConfig};
+//! use std::time::Duration;
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .retry_policy(|attempt: usize, failure: &Failure| match failure {
+//!         Failure::Error(_) if attempt < 10 => Some(Duration::from_secs(30)),
+//!         _ => ExponentialBackoff::default().next_delay(attempt, failure),
+//!     })
+//!     .build();
+//! ```
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// A reason the last attempt to send a batch of telemetry failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The server responded with a status code telling some or all items can be sent again.
+    Status(StatusCode),
+
+    /// The server asked to wait until specified time before sending again.
+    Throttled(DateTime<Utc>),
+
+    /// The request failed without a response, e.g. because of a connection error.
+    Error(String),
+}
+
+/// Decides whether and when a batch of telemetry is sent again after a failed attempt.
+pub trait RetryPolicy: Send + Sync {
+    /// Returns a delay before the next attempt to send a batch that failed `attempt + 1` times, or `None`
+    /// to give up and drop the remaining items.
+    fn next_delay(&self, attempt: usize, failure: &Failure) -> Option<Duration>;
+}
+
+impl<F> RetryPolicy for F
+where
+    F: Fn(usize, &Failure) -> Option<Duration> + Send + Sync,
+{
+    fn next_delay(&self, attempt: usize, failure: &Failure) -> Option<Duration> {
+        self(attempt, failure)
+    }
+}
+
+/// Delays before retries the channel has always made, which the default policy keeps.
+const DEFAULT_DELAYS: [Duration; 3] = [Duration::from_secs(2), Duration::from_secs(4), Duration::from_secs(16)];
+
+/// Retries a batch a limited number of times with exponentially growing delays, i.e. 2, 4, 16 seconds by
+/// default, and doubling the last delay for every further attempt allowed. Every delay is randomly
+/// shortened or extended by a jitter, so many instances of an application recovering from the same
+/// outage don't retry at once. A time the server asked to wait for is respected when it is later than a
+/// computed delay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExponentialBackoff {
+    initial_delays: &'static [Duration],
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_attempts: usize,
+    jitter: f64,
+}
+
+impl ExponentialBackoff {
+    /// Creates a new policy that waits an initial delay before the first retry and doubles it for every
+    /// next one.
+    pub fn new(initial_delay: Duration) -> Self {
+        Self {
+            initial_delays: &[],
+            initial_delay,
+            ..Self::default()
+        }
+    }
+
+    /// Sets a maximum delay between attempts. Defaults to 1 minute.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets a maximum number of retries of a batch. Defaults to 3.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets a fraction of a delay it is randomly changed by, e.g. `0.2` for ±20%. Defaults to `0.2`.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Returns a delay before a retry without a jitter: one of initial delays, or the last of them, or an
+    /// initial delay if there are none, doubled for every attempt after them.
+    fn delay(&self, attempt: usize) -> Duration {
+        if let Some(delay) = self.initial_delays.get(attempt) {
+            return (*delay).min(self.max_delay);
+        }
+
+        let (base, doublings) = match self.initial_delays.last() {
+            Some(last) => (*last, attempt + 1 - self.initial_delays.len()),
+            None => (self.initial_delay, attempt),
+        };
+        let factor = 2u32.saturating_pow(doublings.min(31) as u32);
+        base.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            initial_delays: &DEFAULT_DELAYS,
+            initial_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            max_attempts: 3,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn next_delay(&self, attempt: usize, failure: &Failure) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        let delay = self.delay(attempt).mul_f64(1.0 + self.jitter * (2.0 * random() - 1.0));
+        match failure {
+            Failure::Throttled(retry_after) => {
+                let throttled = (*retry_after - Utc::now()).to_std().unwrap_or_default();
+                Some(delay.max(throttled))
+            }
+            _ => Some(delay),
+        }
+    }
+}
+
+/// Returns a random number in `[0, 1)`.
+fn random() -> f64 {
+    let mut bytes = [0; 4];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return 0.5;
+    }
+    f64::from(u32::from_le_bytes(bytes)) / (f64::from(u32::MAX) + 1.0)
+}
+
+/// A retry policy configured with [`TelemetryConfig`](../struct.TelemetryConfig.html).
+#[derive(Clone)]
+pub(crate) struct SharedRetryPolicy(pub(crate) Arc<dyn RetryPolicy>);
+
+impl Debug for SharedRetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedRetryPolicy").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for SharedRetryPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0, Some(2)  ; "first retry")]
+    #[test_case(1, Some(4)  ; "second retry")]
+    #[test_case(2, Some(16) ; "last retry")]
+    #[test_case(3, None     ; "exhausted")]
+    fn it_keeps_default_delays_until_attempts_exhausted(attempt: usize, expected: Option<u64>) {
+        let policy = ExponentialBackoff::default().with_jitter(0.0);
+
+        let delay = policy.next_delay(attempt, &Failure::Status(StatusCode::SERVICE_UNAVAILABLE));
+
+        assert_eq!(delay, expected.map(Duration::from_secs));
+    }
+
+    #[test_case(ExponentialBackoff::default(),                       3, 32 ; "after default delays")]
+    #[test_case(ExponentialBackoff::new(Duration::from_secs(1)),   0, 1  ; "initial delay")]
+    #[test_case(ExponentialBackoff::new(Duration::from_secs(1)),   3, 8  ; "after initial delay")]
+    fn it_doubles_delays_of_further_attempts(policy: ExponentialBackoff, attempt: usize, expected: u64) {
+        let policy = policy.with_jitter(0.0).with_max_attempts(10);
+
+        let delay = policy.next_delay(attempt, &Failure::Error("connection reset".into()));
+
+        assert_eq!(delay, Some(Duration::from_secs(expected)));
+    }
+
+    #[test]
+    fn it_limits_delays_and_applies_jitter() {
+        let policy = ExponentialBackoff::new(Duration::from_secs(10))
+            .with_max_delay(Duration::from_secs(15))
+            .with_max_attempts(10);
+
+        for _ in 0..100 {
+            let delay = policy
+                .next_delay(5, &Failure::Error("connection reset".into()))
+                .unwrap();
+            assert!(delay >= Duration::from_secs(12) && delay <= Duration::from_secs(18));
+        }
+    }
+
+    #[test]
+    fn it_waits_until_throttling_ends() {
+        let policy = ExponentialBackoff::default().with_jitter(0.0);
+        let retry_after = Utc::now() + chrono::Duration::seconds(30);
+
+        let delay = policy.next_delay(0, &Failure::Throttled(retry_after)).unwrap();
+
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,140 @@
+//! Request tracking for [`rocket`](https://docs.rs/rocket) web servers.
+//!
+//! A Rocket fairing starts a [`RequestScope`](struct.RequestScope.html) for every incoming request with
+//! [`on_request`](fn.on_request.html), caches it in request-local state, and submits it with
+//! [`on_response`](fn.on_response.html), naming an operation after the route template, e.g.
+//! `GET /orders/<id>`. [`response_headers`](struct.RequestScope.html#method.response_headers) of the scope
+//! are added to the response, so callers can correlate to the request. A request guard gives handlers
+//! access to the scope, so they can add custom properties or correlate their own telemetry to the request.
+//!
+//! The functions do not depend on a particular version of Rocket. They receive parts of a request that
+//! are converted from Rocket types in a few lines of glue code:
+//!
+//! ```rust, ignore
+//! use std::sync::Arc;
+//!
+//! use appinsights::{rocket::{self as telemetry, RequestScope}, TelemetryClient};
+//! use rocket::{
+//!     fairing::{Fairing, Info, Kind},
+//!     request::{FromRequest, Outcome},
+//!     Data, Request, Response,
+//! };
+//!
+//! struct Telemetry(Arc<TelemetryClient>);
+//!
+//! #[rocket::async_trait]
+//! impl Fairing for Telemetry {
+//!     fn info(&self) -> Info {
+//!         Info { name: "Application Insights", kind: Kind::Request | Kind::Response }
+//!     }
+//!
+//!     async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+//!         let method = request.method().as_str().parse().unwrap();
+//!         let uri = request.uri().to_string().parse().unwrap();
+//!         let mut headers = http::HeaderMap::new();
+//!         for header in request.headers().iter() {
+//!             if let (Ok(key), Ok(value)) = (header.name().as_str().parse::<http::header::HeaderName>(), header.value().parse()) {
+//!                 headers.insert(key, value);
+//!             }
+//!         }
+//!         let scope = telemetry::on_request(self.0.clone(), &method, &uri, &headers);
+//!         request.local_cache(|| Some(scope));
+//!     }
+//!
+//!     async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+//!         if let Some(scope) = request.local_cache(|| None::<RequestScope>) {
+//!             let route = request.route().map(|route| route.uri.to_string());
+//!             let status = http::StatusCode::from_u16(response.status().code).unwrap_or_default();
+//!             telemetry::on_response(scope, request.method().as_str(), route.as_deref(), status);
+//!             for (name, value) in scope.response_headers().iter() {
+//!                 if let Ok(value) = value.to_str() {
+//!                     response.set_raw_header(name.to_string(), value.to_string());
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! struct Scope(RequestScope);
+//!
+//! #[rocket::async_trait]
+//! impl<'r> FromRequest<'r> for Scope {
+//!     type Error = ();
+//!
+//!     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+//!         match request.local_cache(|| None::<RequestScope>) {
+//!             Some(scope) => Outcome::Success(Scope(scope.clone())),
+//!             None => Outcome::Forward(()),
+//!         }
+//!     }
+//! }
+//!
+//! #[rocket::get("/orders/<id>")]
+//! fn order(id: u32, scope: Scope) -> String {
+//!     scope.0.insert_property("order", id.to_string());
+//!     format!("order {}", id)
+//! }
+//! ```
+use std::sync::Arc;
+
+use http::{HeaderMap, Method, StatusCode, Uri};
+
+pub use crate::server::RequestScope;
+use crate::TelemetryClient;
+
+/// Starts tracking a request when Rocket receives it.
+pub fn on_request(
+    client: impl Into<Arc<TelemetryClient>>,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> RequestScope {
+    RequestScope::start(client, method, uri, headers)
+}
+
+/// Submits a request when Rocket responds to it. A request matched to a route is named after the method
+/// and the route template, otherwise it keeps a name derived from its path.
+pub fn on_response(scope: &RequestScope, method: &str, route: Option<&str>, status: StatusCode) {
+    if let Some(route) = route {
+        scope.set_name(format!("{} {}", method.to_uppercase(), route));
+    }
+    scope.finish(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[test_case(Some("/orders/<id>"), "GET /orders/<id>"                 ; "matched route")]
+    #[test_case(None,                 "GET http://localhost/orders/42"   ; "unmatched route")]
+    fn it_names_request_after_route(route: Option<&str>, expected: &str) {
+        let events = Arc::new(SegQueue::default());
+
+        let scope = on_request(
+            create_client(events.clone()),
+            &Method::GET,
+            &"http://localhost/orders/42".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+        on_response(&scope, "get", route, StatusCode::OK);
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RequestData(data))) if data.name == Some(expected.into())
+        );
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
@@ -0,0 +1,545 @@
+//! Module for tracking requests served by the application.
+//!
+//! A [`RequestScope`](struct.RequestScope.html) measures a single incoming HTTP request. It continues
+//! a distributed trace of the caller when request headers contain a trace context, and it is submitted
+//! as a [`RequestTelemetry`](../telemetry/struct.RequestTelemetry.html) item when the response is ready.
+//! Web framework integrations start a scope for every request and make it available to handlers, so
+//! they can name an operation after a route, add custom properties or correlate their own telemetry
+//! to the request.
+//!
+//! ```rust, no_run
+//! use appinsights::{server::RequestScope, telemetry::{SeverityLevel, TraceTelemetry}, TelemetryClient};
+//! use http::{HeaderMap, Method, StatusCode};
+//! use std::sync::Arc;
+//!
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//!
+//! let scope = RequestScope::start(client, &Method::GET, &"/orders/42".parse().unwrap(), &HeaderMap::new());
+//! scope.set_name("GET /orders/{id}");
+//! scope.insert_property("tenant", "contoso");
+//! scope.track(TraceTelemetry::new("Loading order", SeverityLevel::Information));
+//!
+//! scope.finish(StatusCode::OK);
+//! ```
+//!
+//! A scope is cheap to clone, so middlewares hand it over to handlers by request extensions. Handlers
+//! behind [`TelemetryService`](struct.TelemetryService.html) get it with
+//! [`from_extensions`](struct.RequestScope.html#method.from_extensions) or a framework extractor, e.g.
+//! `axum::Extension`:
+//!
+//! ```rust, ignore
+//! use appinsights::server::RequestScope;
+//! use axum::{extract::Path, Extension};
+//!
+//! async fn order(Path(id): Path<u32>, Extension(scope): Extension<RequestScope>) -> String {
+//!     scope.set_name("GET /orders/{id}");
+//!     scope.insert_measurement("items", 3.0);
+//!     format!("order {}", id)
+//! }
+//! ```
+//!
+//! [`TelemetryService`](struct.TelemetryService.html) also adds
+//! [`response_headers`](struct.RequestScope.html#method.response_headers) to every response, so a browser
+//! instrumented with Application Insights JavaScript SDK correlates its calls to requests of the server.
+//! A cross-origin caller needs `Access-Control-Expose-Headers: Request-Id, Request-Context` to read them.
+//!
+//! Frameworks with their own extensions, like actix-web, start a scope in a middleware and insert it
+//! there, so handlers can get it with `actix_web::web::ReqData`:
+//!
+//! ```rust, ignore
+//! use actix_web::{dev::Service, web::ReqData, App, HttpMessage};
+//! use appinsights::server::RequestScope;
+//!
+//! let app = App::new()
+//!     .wrap_fn(move |request, service| {
+//!         let scope = RequestScope::start(client.clone(), request.method(), request.uri(), request.headers());
+//!         request.extensions_mut().insert(scope.clone());
+//!         let response = service.call(request);
+//!         async move {
+//!             let response = response.await?;
+//!             scope.finish(response.status());
+//!             Ok(response)
+//!         }
+//!     })
+//!     .route("/orders/{id}", actix_web::web::get().to(|scope: ReqData<RequestScope>| async move {
+//!         scope.insert_property("tenant", "contoso");
+//!         "order"
+//!     }));
+//! ```
+#[cfg(any(feature = "hyper", feature = "warp"))]
+mod service;
+
+#[cfg(any(feature = "hyper", feature = "warp"))]
+pub use service::TelemetryService;
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use http::{Extensions, HeaderMap, Method, StatusCode, Uri};
+
+use crate::{
+    contracts::Envelope,
+    correlation::{self, Baggage, Injector, TraceContext, REQUEST_CONTEXT_HEADER, REQUEST_ID_HEADER},
+    telemetry::{ExceptionTelemetry, Measurements, Properties, RequestPhase, RequestTelemetry, Telemetry},
+    time::Stopwatch,
+    TelemetryClient, TelemetryContext,
+};
+
+/// Measures a single incoming request and submits it when it is finished. Clones refer to the same
+/// request, so a scope can be shared between a middleware and request handlers.
+#[derive(Clone)]
+pub struct RequestScope {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    client: Arc<TelemetryClient>,
+    context: TraceContext,
+    parent: Option<TraceContext>,
+    baggage: Baggage,
+    source: Option<String>,
+    method: Method,
+    uri: Uri,
+    started: Stopwatch,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    name: Option<String>,
+    properties: Properties,
+    measurements: Measurements,
+    exception_tracked: bool,
+    finished: bool,
+}
+
+impl RequestScope {
+    /// Starts measuring a request. A trace context, baggage and a caller's application id are extracted
+    /// from request headers.
+    pub fn start(client: impl Into<Arc<TelemetryClient>>, method: &Method, uri: &Uri, headers: &HeaderMap) -> Self {
+        let client = client.into();
+        let parent = correlation::extract(headers);
+        let context = TraceContext::start(parent.as_ref(), &client.context().ids);
+        let source = headers
+            .get(REQUEST_CONTEXT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(correlation::app_id_from_request_context)
+            .map(String::from);
+
+        Self {
+            inner: Arc::new(Inner {
+                client,
+                context,
+                parent,
+                baggage: correlation::extract_baggage(headers),
+                source,
+                method: method.clone(),
+                uri: uri.clone(),
+                started: Stopwatch::start(),
+                state: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Returns a trace context of the request. Use it as a parent of outgoing dependency calls.
+    pub fn context(&self) -> &TraceContext {
+        &self.inner.context
+    }
+
+    /// Returns values a caller propagated with the operation. They are attached as properties to the
+    /// request and all correlated telemetry. Pass them on to outgoing dependency calls with
+    /// [`correlation::inject_baggage`](../correlation/fn.inject_baggage.html).
+    pub fn baggage(&self) -> &Baggage {
+        &self.inner.baggage
+    }
+
+    /// Sets a request name, which is also an operation name all correlated telemetry is grouped by.
+    /// Usually a route template, like `GET /orders/{id}`, so requests with different parameters are
+    /// grouped together. A method and a path of the request are used by default.
+    pub fn set_name(&self, name: impl Into<String>) {
+        self.state().name = Some(name.into());
+    }
+
+    /// Determines whether telemetry of the request will be submitted, so handlers can skip expensive
+    /// enrichment when it would be discarded anyway. See
+    /// [`TelemetryClient::is_sampled_in`](../struct.TelemetryClient.html#method.is_sampled_in) for details.
+    pub fn is_sampled_in(&self) -> bool {
+        self.inner.client.is_sampled_in(self.inner.context.trace_id())
+    }
+
+    /// Adds a custom property to submit with the request telemetry.
+    pub fn insert_property(&self, key: impl Into<String>, value: impl Into<String>) {
+        self.state().properties.insert(key.into(), value.into());
+    }
+
+    /// Adds a custom measurement to submit with the request telemetry.
+    pub fn insert_measurement(&self, key: impl Into<String>, value: f64) {
+        self.state().measurements.insert(key.into(), value);
+    }
+
+    /// Adds a duration spent in a phase of serving the request. Durations of the same phase sum up, e.g.
+    /// all database calls of the request. See [`RequestPhase`](../telemetry/enum.RequestPhase.html) for
+    /// names of measurements phases are submitted as.
+    pub fn add_phase(&self, phase: &RequestPhase, duration: Duration) {
+        self.state()
+            .measurements
+            .add_duration(phase.measurement_name(), duration);
+    }
+
+    /// Starts timing a phase of serving the request. The phase ends when the returned timer is dropped.
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{server::RequestScope, telemetry::RequestPhase};
+    /// # fn run(scope: RequestScope) {
+    /// let orders = {
+    ///     let _timer = scope.start_phase(RequestPhase::Database);
+    ///     vec!["order 42"]
+    /// };
+    /// # }
+    /// ```
+    pub fn start_phase(&self, phase: RequestPhase) -> PhaseTimer {
+        PhaseTimer {
+            scope: self.clone(),
+            phase,
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns headers a response to the request should carry, so a caller, e.g. Application Insights
+    /// JavaScript SDK in a browser, can correlate its dependency call to the request: `Request-Id` with an id
+    /// of the request and `Request-Context` with an application id of the component. The application id
+    /// is looked up in the background and is added once it is known.
+    pub fn response_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.set(REQUEST_ID_HEADER, self.inner.context.request_id());
+        if let Some(app_id) = self.inner.client.known_app_id() {
+            headers.set(REQUEST_CONTEXT_HEADER, correlation::request_context(&app_id));
+        }
+        headers
+    }
+
+    /// Returns a scope of a request served by [`TelemetryService`](struct.TelemetryService.html) or other
+    /// middleware that adds the scope to request extensions.
+    pub fn from_extensions(extensions: &Extensions) -> Option<Self> {
+        extensions.get::<Self>().cloned()
+    }
+
+    /// Makes a telemetry item a child of the request and attaches baggage of the operation to it.
+    pub fn correlate<E: Telemetry>(&self, telemetry: &mut E) {
+        self.inner.baggage.stamp(telemetry.properties_mut());
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.inner.context.trace_id().to_string());
+        operation.set_parent_id(self.inner.context.span_id().to_string());
+        if let Some(name) = &self.state().name {
+            operation.set_name(name.clone());
+        }
+    }
+
+    /// Correlates a telemetry item to the request and submits it with the telemetry client.
+    pub fn track<E>(&self, mut telemetry: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.correlate(&mut telemetry);
+        self.inner.client.track(telemetry);
+    }
+
+    /// Correlates an exception to the request and submits it with the telemetry client. A request that
+    /// has an exception will not be submitted with a generic one when it fails.
+    pub fn track_exception(&self, exception: ExceptionTelemetry) {
+        self.state().exception_tracked = true;
+        self.track(exception);
+    }
+
+    /// Submits the request with a status code of the response. Only the first call submits the request,
+    /// consequent calls have no effect. A request that failed with a server error status code and did not
+    /// track any exception is submitted together with a generic exception, so the failure shows up among
+    /// exceptions of the operation.
+    pub fn finish(&self, status: StatusCode) {
+        let state = {
+            let mut state = self.state();
+            if state.finished {
+                return;
+            }
+            std::mem::replace(
+                &mut *state,
+                State {
+                    finished: true,
+                    ..State::default()
+                },
+            )
+        };
+
+        let inner = &self.inner;
+        if status.is_server_error() && !state.exception_tracked {
+            let mut exception =
+                ExceptionTelemetry::from_message("HttpError", format!("Request failed with status {}", status));
+            self.correlate(&mut exception);
+            if let Some(name) = &state.name {
+                exception.tags_mut().operation_mut().set_name(name.clone());
+            }
+            inner.client.track(exception);
+        }
+
+        let mut telemetry = RequestTelemetry::new(
+            inner.method.clone(),
+            inner.uri.clone(),
+            inner.started.elapsed(),
+            status.as_str(),
+        );
+        telemetry.set_timestamp(inner.started.started_at());
+        if let Some(name) = state.name {
+            telemetry.set_name(name);
+        }
+        telemetry.set_id(inner.context.span_id());
+        if let Some(source) = &inner.source {
+            telemetry.set_source(source.clone());
+        }
+        telemetry.properties_mut().extend(BTreeMap::from(state.properties));
+        inner.baggage.stamp(telemetry.properties_mut());
+        telemetry.measurements_mut().extend(BTreeMap::from(state.measurements));
+
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(inner.context.trace_id().to_string());
+        if let Some(parent) = &inner.parent {
+            operation.set_parent_id(parent.span_id().to_string());
+        }
+
+        inner.client.track(telemetry);
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Times a phase of serving a request and adds its duration to the request when dropped.
+pub struct PhaseTimer {
+    scope: RequestScope,
+    phase: RequestPhase,
+    started: Instant,
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        self.scope.add_phase(&self.phase, self.started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::{create_client, take_requests},
+        contracts::{Base, Data},
+        telemetry::{SeverityLevel, TraceTelemetry},
+    };
+
+    #[test]
+    fn it_submits_request_as_child_of_caller() {
+        let events = Arc::new(SegQueue::default());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("request-context", "appId=cid-v1:1234".parse().unwrap());
+
+        let scope = RequestScope::start(
+            create_client(events.clone()),
+            &Method::GET,
+            &"http://localhost/orders/42?expand=true".parse().unwrap(),
+            &headers,
+        );
+        scope.set_name("GET /orders/{id}");
+        scope.insert_property("tenant", "contoso");
+        scope.insert_measurement("items", 3.0);
+        scope.track(TraceTelemetry::new("Loading order", SeverityLevel::Information));
+        scope.finish(StatusCode::NOT_FOUND);
+        scope.finish(StatusCode::OK);
+
+        let trace = events.pop().unwrap();
+        let tags = trace.tags.unwrap();
+        assert_eq!(tags["ai.operation.id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(tags["ai.operation.parentId"], scope.context().span_id());
+        assert_eq!(tags["ai.operation.name"], "GET /orders/{id}");
+
+        let requests = take_requests(&events);
+        assert_eq!(requests.len(), 1);
+        let (tags, request) = &requests[0];
+        assert_eq!(tags["ai.operation.id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(tags["ai.operation.parentId"], "b7ad6b7169203331");
+        assert_eq!(tags["ai.operation.name"], "GET /orders/{id}");
+        assert_eq!(request.id, scope.context().span_id());
+        assert_eq!(request.name.as_deref(), Some("GET /orders/{id}"));
+        assert_eq!(request.source.as_deref(), Some("cid-v1:1234"));
+        assert_eq!(request.response_code, "404");
+        assert!(!request.success);
+        assert_eq!(request.url.as_deref(), Some("http://localhost/orders/42"));
+        assert_eq!(request.properties.as_ref().unwrap()["tenant"], "contoso");
+        assert_eq!(request.measurements.as_ref().unwrap()["items"], 3.0);
+    }
+
+    #[test]
+    fn it_submits_generic_exception_for_server_error() {
+        let events = Arc::new(SegQueue::default());
+        let scope = RequestScope::start(
+            create_client(events.clone()),
+            &Method::GET,
+            &"http://localhost/orders".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+        scope.set_name("GET /orders");
+        scope.finish(StatusCode::SERVICE_UNAVAILABLE);
+
+        let exception = events.pop().unwrap();
+        let tags = exception.tags.unwrap();
+        assert_eq!(tags["ai.operation.id"], scope.context().trace_id());
+        assert_eq!(tags["ai.operation.parentId"], scope.context().span_id());
+        assert_eq!(tags["ai.operation.name"], "GET /orders");
+        assert_matches!(
+            exception.data,
+            Some(Base::Data(Data::ExceptionData(data)))
+                if data.exceptions[0].type_name == "HttpError"
+                    && data.exceptions[0].message == "Request failed with status 503 Service Unavailable"
+        );
+        assert_matches!(events.pop().unwrap().data, Some(Base::Data(Data::RequestData(_))));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_does_not_submit_generic_exception_when_exception_tracked() {
+        let events = Arc::new(SegQueue::default());
+        let scope = RequestScope::start(
+            create_client(events.clone()),
+            &Method::GET,
+            &"http://localhost/orders".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+        scope.track_exception(ExceptionTelemetry::from_message("DbError", "connection refused"));
+        scope.finish(StatusCode::INTERNAL_SERVER_ERROR);
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::ExceptionData(data))) if data.exceptions[0].type_name == "DbError"
+        );
+        assert_matches!(events.pop().unwrap().data, Some(Base::Data(Data::RequestData(_))));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_attaches_caller_baggage() {
+        let events = Arc::new(SegQueue::default());
+        let mut headers = HeaderMap::new();
+        headers.insert("baggage", "tenant=contoso,experiment=beta".parse().unwrap());
+
+        let scope = RequestScope::start(
+            create_client(events.clone()),
+            &Method::GET,
+            &"http://localhost/orders".parse().unwrap(),
+            &headers,
+        );
+        scope.insert_property("tenant", "fabrikam");
+        scope.track(TraceTelemetry::new("Loading orders", SeverityLevel::Information));
+        scope.finish(StatusCode::OK);
+
+        let properties = |envelope: Envelope| match envelope.data {
+            Some(Base::Data(Data::MessageData(data))) => data.properties.unwrap(),
+            Some(Base::Data(Data::RequestData(data))) => data.properties.unwrap(),
+            data => panic!("unexpected data {:?}", data),
+        };
+        let trace = properties(events.pop().unwrap());
+        assert_eq!(trace["tenant"], "contoso");
+        assert_eq!(trace["experiment"], "beta");
+        let request = properties(events.pop().unwrap());
+        assert_eq!(request["tenant"], "fabrikam");
+        assert_eq!(request["experiment"], "beta");
+    }
+
+    #[test]
+    fn it_finds_scope_in_extensions() {
+        let events = Arc::new(SegQueue::default());
+        let scope = RequestScope::start(
+            create_client(events),
+            &Method::GET,
+            &"http://localhost/orders".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+
+        let mut extensions = Extensions::new();
+        assert!(RequestScope::from_extensions(&extensions).is_none());
+
+        extensions.insert(scope.clone());
+        let found = RequestScope::from_extensions(&extensions).unwrap();
+        assert_eq!(found.context(), scope.context());
+    }
+
+    #[test]
+    fn it_returns_request_id_in_response_headers() {
+        let events = Arc::new(SegQueue::default());
+        let scope = RequestScope::start(
+            create_client(events),
+            &Method::GET,
+            &"http://localhost/orders".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+
+        let headers = scope.response_headers();
+
+        assert_eq!(
+            headers["request-id"],
+            format!("|{}.{}.", scope.context().trace_id(), scope.context().span_id())
+        );
+        assert_eq!(headers.get("request-context"), None);
+    }
+
+    #[test]
+    fn it_starts_new_trace_without_caller_context() {
+        let events = Arc::new(SegQueue::default());
+
+        let scope = RequestScope::start(
+            create_client(events.clone()),
+            &Method::POST,
+            &"http://localhost/orders".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+        scope.finish(StatusCode::CREATED);
+
+        let (tags, request) = take_requests(&events).remove(0);
+        assert_eq!(tags["ai.operation.id"], scope.context().trace_id());
+        assert_eq!(tags.get("ai.operation.parentId"), None);
+        assert_eq!(request.name.as_deref(), Some("POST http://localhost/orders"));
+        assert!(request.success);
+    }
+
+    #[test]
+    fn it_submits_phase_durations() {
+        let events = Arc::new(SegQueue::default());
+
+        let scope = RequestScope::start(
+            create_client(events.clone()),
+            &Method::GET,
+            &"http://localhost/orders".parse().unwrap(),
+            &HeaderMap::new(),
+        );
+        scope.add_phase(&RequestPhase::Routing, Duration::from_micros(500));
+        scope.add_phase(&RequestPhase::Database, Duration::from_millis(3));
+        scope.add_phase(&RequestPhase::Database, Duration::from_millis(4));
+        drop(scope.start_phase(RequestPhase::Custom("auth".into())));
+        scope.finish(StatusCode::OK);
+
+        let measurements = take_requests(&events).remove(0).1.measurements.unwrap();
+        assert_eq!(measurements["phase_routing_ms"], 0.5);
+        assert_eq!(measurements["phase_db_ms"], 7.0);
+        assert!(measurements.contains_key("phase_auth_ms"));
+    }
+}
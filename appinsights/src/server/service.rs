@@ -0,0 +1,311 @@
+use std::{
+    future::Future,
+    panic,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{
+    header::{CONTENT_TYPE, UPGRADE},
+    HeaderMap, Request, Response, StatusCode,
+};
+use tower_service::Service;
+
+use crate::{server::RequestScope, task::CatchUnwind, telemetry::ExceptionTelemetry, TelemetryClient};
+
+/// Wraps a service that handles HTTP requests, e.g. `warp::service(routes)`, and submits every request
+/// it serves. A [`RequestScope`](struct.RequestScope.html) of a request is added to request extensions
+/// before the request reaches the inner service, so handlers can access it, and its
+/// [`response_headers`](struct.RequestScope.html#method.response_headers) are added to the response. A
+/// request the inner service fails to respond to or panics on is submitted with `500 Internal Server Error`
+/// status together with an exception correlated to the request.
+///
+/// A request is submitted as soon as response headers are ready, so its duration is time to the first
+/// byte of a response. Connections that live on after that, i.e. websocket upgrades, server-sent
+/// events and gRPC or gRPC-web streams, are marked with a `stream` property naming their kind, and time
+/// they took to start is recorded as a `time_to_upgrade_ms` or `ttfb_ms` measurement.
+#[derive(Clone)]
+pub struct TelemetryService<S> {
+    client: Arc<TelemetryClient>,
+    inner: S,
+}
+
+impl<S> TelemetryService<S> {
+    /// Creates a new service that submits requests served by an inner service with specified client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>, inner: S) -> Self {
+        Self {
+            client: client.into(),
+            inner,
+        }
+    }
+}
+
+impl<S, B, RB> Service<Request<B>> for TelemetryService<S>
+where
+    S: Service<Request<B>, Response = Response<RB>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<RB>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let scope = RequestScope::start(self.client.clone(), request.method(), request.uri(), request.headers());
+        request.extensions_mut().insert(scope.clone());
+        let upgrade = request
+            .headers()
+            .get(UPGRADE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_lowercase);
+
+        let future = CatchUnwind(Box::pin(self.inner.call(request)));
+        Box::pin(async move {
+            match future.await {
+                Ok(Ok(mut response)) => {
+                    mark_stream(&scope, upgrade.as_deref(), response.status(), response.headers());
+                    scope.finish(response.status());
+                    response.headers_mut().extend(scope.response_headers());
+                    Ok(response)
+                }
+                Ok(Err(err)) => {
+                    scope.finish(StatusCode::INTERNAL_SERVER_ERROR);
+                    Err(err)
+                }
+                Err(payload) => {
+                    scope.track_exception(ExceptionTelemetry::from_panic(payload.as_ref()));
+                    scope.finish(StatusCode::INTERNAL_SERVER_ERROR);
+                    panic::resume_unwind(payload)
+                }
+            }
+        })
+    }
+}
+
+/// Marks a request whose connection outlives the response headers with a kind of the stream and time it
+/// took to start.
+fn mark_stream(scope: &RequestScope, upgrade: Option<&str>, status: StatusCode, headers: &HeaderMap) {
+    let elapsed = scope.inner.started.elapsed().as_secs_f64() * 1000.0;
+    if status == StatusCode::SWITCHING_PROTOCOLS {
+        scope.insert_property("stream", upgrade.unwrap_or("upgrade"));
+        scope.insert_measurement("time_to_upgrade_ms", elapsed);
+        return;
+    }
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let kind = if content_type.starts_with("text/event-stream") {
+        "sse"
+    } else if content_type.starts_with("application/grpc-web") {
+        "grpc-web"
+    } else if content_type.starts_with("application/grpc") {
+        "grpc"
+    } else {
+        return;
+    };
+    scope.insert_property("stream", kind);
+    scope.insert_measurement("ttfb_ms", elapsed);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use crossbeam_queue::SegQueue;
+    use futures_util::future;
+    use hyper::Body;
+    use matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        client::tests::{create_client, take_requests, TestChannel},
+        contracts::{Base, Data},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_submits_served_requests() {
+        let events = Arc::new(SegQueue::default());
+        let inner = hyper::service::service_fn(|request: Request<Body>| {
+            let scope = request.extensions().get::<RequestScope>().cloned().unwrap();
+            scope.set_name("GET /orders/{id}");
+            future::ok::<_, Infallible>(
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        });
+        let mut service = TelemetryService::new(create_client(events.clone()), inner);
+
+        let request = Request::get("http://localhost/orders/42").body(Body::empty()).unwrap();
+        let response = service.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let (_, request) = take_requests(&events).remove(0);
+        assert_eq!(request.name.as_deref(), Some("GET /orders/{id}"));
+        assert_eq!(request.response_code, "404");
+        assert!(!request.success);
+    }
+
+    #[test_case("websocket", StatusCode::SWITCHING_PROTOCOLS, None,                      Some(("websocket", "time_to_upgrade_ms")) ; "websocket upgrade")]
+    #[test_case("",          StatusCode::OK,                  Some("text/event-stream"),     Some(("sse", "ttfb_ms"))                  ; "server-sent events")]
+    #[test_case("",          StatusCode::OK,                  Some("application/grpc-web"),  Some(("grpc-web", "ttfb_ms"))             ; "grpc-web stream")]
+    #[test_case("",          StatusCode::OK,                  Some("application/json"),      None                                      ; "regular response")]
+    #[tokio::test]
+    async fn it_marks_long_lived_responses(
+        upgrade: &'static str,
+        status: StatusCode,
+        content_type: Option<&'static str>,
+        expected: Option<(&str, &str)>,
+    ) {
+        let events = Arc::new(SegQueue::default());
+        let inner = hyper::service::service_fn(move |_: Request<Body>| {
+            let mut response = Response::builder().status(status);
+            if let Some(content_type) = content_type {
+                response = response.header(CONTENT_TYPE, content_type);
+            }
+            future::ok::<_, Infallible>(response.body(Body::empty()).unwrap())
+        });
+        let mut service = TelemetryService::new(create_client(events.clone()), inner);
+
+        let mut request = Request::get("http://localhost/events");
+        if !upgrade.is_empty() {
+            request = request.header(UPGRADE, upgrade);
+        }
+        service.call(request.body(Body::empty()).unwrap()).await.unwrap();
+
+        let (_, request) = take_requests(&events).remove(0);
+        let stream = request.properties.unwrap_or_default().get("stream").cloned();
+        let measurements = request.measurements.unwrap_or_default();
+        assert_eq!(stream.as_deref(), expected.map(|(kind, _)| kind));
+        if let Some((_, measurement)) = expected {
+            assert!(measurements.contains_key(measurement));
+        }
+        assert!(request.success);
+    }
+
+    #[tokio::test]
+    async fn it_submits_failed_requests() {
+        let events = Arc::new(SegQueue::default());
+        let inner = hyper::service::service_fn(|_: Request<Body>| future::err::<Response<Body>, _>("unavailable"));
+        let mut service = TelemetryService::new(create_client(events.clone()), inner);
+
+        let request = Request::get("http://localhost/orders/42").body(Body::empty()).unwrap();
+        assert!(service.call(request).await.is_err());
+
+        assert_matches!(events.pop().unwrap().data, Some(Base::Data(Data::ExceptionData(_))));
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RequestData(data))) if data.response_code == "500" && !data.success
+        );
+    }
+
+    #[tokio::test]
+    async fn it_submits_panicked_requests() {
+        let events = Arc::new(SegQueue::default());
+        let inner = hyper::service::service_fn(|_: Request<Body>| async {
+            if true {
+                panic!("index out of bounds");
+            }
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        });
+        let mut service = TelemetryService::new(create_client(events.clone()), inner);
+
+        let request = Request::get("http://localhost/orders/42").body(Body::empty()).unwrap();
+        let future = service.call(request);
+        assert!(tokio::spawn(future).await.unwrap_err().is_panic());
+
+        let exception = events.pop().unwrap();
+        let request = events.pop().unwrap();
+        assert_eq!(
+            exception.tags.unwrap()["ai.operation.parentId"],
+            match request.data {
+                Some(Base::Data(Data::RequestData(data))) => {
+                    assert_eq!(data.response_code, "500");
+                    data.id
+                }
+                data => panic!("unexpected data {:?}", data),
+            }
+        );
+        assert_matches!(
+            exception.data,
+            Some(Base::Data(Data::ExceptionData(data)))
+                if data.exceptions[0].type_name == "panic" && data.exceptions[0].message == "index out of bounds"
+        );
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_serves_requests_with_hyper() {
+        let events = Arc::new(SegQueue::default());
+        let service = TelemetryService::new(
+            create_client(events.clone()),
+            hyper::service::service_fn(|_: Request<Body>| future::ok::<_, Infallible>(Response::new(Body::empty()))),
+        );
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let service = service.clone();
+            future::ok::<_, Infallible>(service)
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/orders", server.local_addr());
+        tokio::spawn(server);
+
+        let response = reqwest::Client::new()
+            .get(url)
+            .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let (tags, request) = take_requests(&events).remove(0);
+        assert_eq!(tags["ai.operation.id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(tags["ai.operation.parentId"], "b7ad6b7169203331");
+        assert_eq!(request.response_code, "200");
+        assert!(request.success);
+    }
+
+    #[tokio::test]
+    async fn it_echoes_app_id_in_response_headers() {
+        let profiles = hyper::service::make_service_fn(|_| {
+            future::ok::<_, Infallible>(hyper::service::service_fn(|_: Request<Body>| {
+                future::ok::<_, Infallible>(Response::new(Body::from("1234")))
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(profiles);
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(format!("http://{}/v2/track", server.local_addr()))
+            .build();
+        tokio::spawn(server);
+
+        let client = TelemetryClient::create(&config, TestChannel::new(Arc::new(SegQueue::default())));
+        let inner =
+            hyper::service::service_fn(|_: Request<Body>| future::ok::<_, Infallible>(Response::new(Body::empty())));
+        let mut service = TelemetryService::new(client, inner);
+
+        let request = Request::get("http://localhost/orders").body(Body::empty()).unwrap();
+        let response = service.call(request).await.unwrap();
+        assert!(response.headers()["request-id"].to_str().unwrap().starts_with('|'));
+
+        let mut app_id = None;
+        for _ in 0..100 {
+            let request = Request::get("http://localhost/orders").body(Body::empty()).unwrap();
+            let response = service.call(request).await.unwrap();
+            app_id = response.headers().get("request-context").cloned();
+            if app_id.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(app_id.unwrap(), "appId=cid-v1:1234");
+    }
+}
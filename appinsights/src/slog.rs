@@ -0,0 +1,219 @@
+//! Integration with [`slog`](https://docs.rs/slog) structured logging.
+//!
+//! [`TelemetryDrain`](struct.TelemetryDrain.html) submits slog records as
+//! [`TraceTelemetry`](../telemetry/struct.TraceTelemetry.html) items. Key-value pairs of a record and
+//! of its loggers become properties of the item, next to the module and the location of the record, so
+//! they can be queried in Application Insights like any other custom dimension.
+//!
+//! The drain does not depend on a particular version of [`slog`](https://docs.rs/slog) crate. A small
+//! adapter implements `slog::Drain` with it.
+//!
+//! ```rust, ignore
+//! use appinsights::{slog::{Level, TelemetryDrain}, TelemetryClient};
+//! use slog::{o, Drain, Key, OwnedKVList, Record, Serializer, KV};
+//! use std::fmt::Arguments;
+//!
+//! struct Telemetry(TelemetryDrain);
+//!
+//! #[derive(Default)]
+//! struct KeyValues(Vec<(String, String)>);
+//!
+//! impl Serializer for KeyValues {
+//!     fn emit_arguments(&mut self, key: Key, value: &Arguments) -> slog::Result {
+//!         self.0.push((key.to_string(), value.to_string()));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! impl Drain for Telemetry {
+//!     type Ok = ();
+//!     type Err = slog::Never;
+//!
+//!     fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), slog::Never> {
+//!         let mut pairs = KeyValues::default();
+//!         let _ = values.serialize(record, &mut pairs);
+//!         let _ = record.kv().serialize(record, &mut pairs);
+//!         let level = Level::from_str(record.level().as_str()).unwrap_or(Level::Info);
+//!         self.0.log(level, record.msg(), record.module(), record.file(), record.line(), pairs.0);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! let logger = slog::Logger::root(Telemetry(TelemetryDrain::new(client)).fuse(), o!("component" => "gateway"));
+//!
+//! // submitted as a trace telemetry with "component" and "attempt" properties
+//! slog::info!(logger, "Connected to a gateway"; "attempt" => 3);
+//! # }
+//! ```
+use std::{fmt::Display, sync::Arc};
+
+use crate::{
+    telemetry::{SeverityLevel, Telemetry, TraceTelemetry},
+    TelemetryClient,
+};
+
+/// A level of a slog record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// A critical error.
+    Critical,
+
+    /// An error.
+    Error,
+
+    /// A warning.
+    Warning,
+
+    /// An informational message.
+    Info,
+
+    /// A debugging message.
+    Debug,
+
+    /// A tracing message.
+    Trace,
+}
+
+impl Level {
+    /// Parses a name of a level as slog formats it, either a full one like `WARN` or a short one like
+    /// `WRN`, ignoring case.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(level: &str) -> Option<Self> {
+        match level.to_ascii_uppercase().as_str() {
+            "CRITICAL" | "CRIT" => Some(Level::Critical),
+            "ERROR" | "ERRO" => Some(Level::Error),
+            "WARNING" | "WARN" => Some(Level::Warning),
+            "INFO" => Some(Level::Info),
+            "DEBUG" | "DEBG" => Some(Level::Debug),
+            "TRACE" | "TRCE" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    /// Returns severity level of a trace telemetry.
+    fn severity(self) -> SeverityLevel {
+        match self {
+            Level::Critical => SeverityLevel::Critical,
+            Level::Error => SeverityLevel::Error,
+            Level::Warning => SeverityLevel::Warning,
+            Level::Info => SeverityLevel::Information,
+            Level::Debug | Level::Trace => SeverityLevel::Verbose,
+        }
+    }
+}
+
+/// Submits slog records as trace telemetry.
+pub struct TelemetryDrain {
+    client: Arc<TelemetryClient>,
+    level: Level,
+}
+
+impl TelemetryDrain {
+    /// Creates a new drain that submits records of `Info` level and above with specified client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            level: Level::Info,
+        }
+    }
+
+    /// Sets the least severe level of submitted records.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Returns `true` if records of specified level are submitted.
+    pub fn is_enabled(&self, level: Level) -> bool {
+        level <= self.level
+    }
+
+    /// Submits a record with its key-value pairs as properties. Pairs of a record come after pairs of
+    /// its loggers, so they override values of the same keys.
+    pub fn log<K, V>(
+        &self,
+        level: Level,
+        message: impl Display,
+        module: &str,
+        file: &str,
+        line: u32,
+        pairs: impl IntoIterator<Item = (K, V)>,
+    ) where
+        K: Into<String>,
+        V: Display,
+    {
+        if !self.is_enabled(level) {
+            return;
+        }
+
+        let mut telemetry = TraceTelemetry::new(message.to_string(), level.severity());
+        let properties = telemetry.properties_mut();
+        properties.insert("module".into(), module.into());
+        properties.insert("location".into(), format!("{}:{}", file, line));
+        for (key, value) in pairs {
+            properties.insert(key.into(), value.to_string());
+        }
+        self.client.track(telemetry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{self, Base, Data},
+        TelemetryConfig,
+    };
+
+    #[test_case("CRIT",    Some(Level::Critical) ; "short critical")]
+    #[test_case("warning", Some(Level::Warning)  ; "full lowercase")]
+    #[test_case("DEBG",    Some(Level::Debug)    ; "short debug")]
+    #[test_case("verbose", None                  ; "unknown")]
+    fn it_parses_levels(level: &str, expected: Option<Level>) {
+        assert_eq!(Level::from_str(level), expected);
+    }
+
+    #[tokio::test]
+    async fn it_submits_records_with_key_values() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+        let drain = TelemetryDrain::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+
+        let pairs = vec![("component", "gateway".to_string()), ("attempt", 3.to_string())];
+        drain.log(
+            Level::Warning,
+            "Reconnecting",
+            "my_app::gateway",
+            "src/gateway.rs",
+            42,
+            pairs,
+        );
+        drain.log(
+            Level::Debug,
+            "Polling",
+            "my_app::gateway",
+            "src/gateway.rs",
+            50,
+            Vec::<(String, String)>::new(),
+        );
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::MessageData(data)))
+                if data.message == "Reconnecting"
+                    && data.severity_level == Some(contracts::SeverityLevel::Warning)
+                    && data.properties.as_ref().unwrap()["attempt"] == "3"
+                    && data.properties.as_ref().unwrap()["component"] == "gateway"
+                    && data.properties.as_ref().unwrap()["location"] == "src/gateway.rs:42"
+        );
+        assert!(events.is_empty());
+    }
+}
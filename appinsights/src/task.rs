@@ -0,0 +1,123 @@
+//! Exception telemetry for panics of spawned Tokio tasks.
+//!
+//! A panic inside a spawned task is caught by Tokio and surfaces only as a `JoinError`, which is often
+//! dropped. A global panic hook still sees it, but runs on whatever thread polled the task and knows
+//! nothing about an operation the task worked on. [`spawn_instrumented`](fn.spawn_instrumented.html)
+//! spawns a task that submits its panic as an unhandled exception under a context of the operation
+//! the task belongs to, before the panic is resumed and reaches the `JoinHandle` as usual.
+//!
+//! ```rust, no_run
+//! use appinsights::{task, TelemetryClient};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//!
+//! // a panic is submitted as an exception correlated to the operation of the context
+//! let context = client.context().child();
+//! let handle = task::spawn_instrumented(&client, context, "reindex", async {
+//!     let orders: Vec<u32> = Vec::new();
+//!     orders[0]
+//! });
+//! assert!(handle.await.unwrap_err().is_panic());
+//! # }
+//! ```
+use std::{
+    any::Any,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tokio::task::JoinHandle;
+
+use crate::{
+    telemetry::{ExceptionTelemetry, Telemetry},
+    TelemetryClient, TelemetryContext,
+};
+
+/// Name of a property a name of a panicked task is submitted with.
+pub const TASK_PROPERTY: &str = "task.name";
+
+/// Spawns a new Tokio task that submits a panic as an exception tracked under specified context. The
+/// exception carries the name of the task in a [`TASK_PROPERTY`](constant.TASK_PROPERTY.html) property.
+/// It must be called within Tokio runtime.
+pub fn spawn_instrumented<F>(
+    client: &Arc<TelemetryClient>,
+    context: TelemetryContext,
+    name: impl Into<String>,
+    future: F,
+) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let client = client.clone();
+    let name = name.into();
+    tokio::spawn(async move {
+        match CatchUnwind(Box::pin(future)).await {
+            Ok(output) => output,
+            Err(payload) => {
+                let exception = ExceptionTelemetry::from_panic(payload.as_ref()).with_property(TASK_PROPERTY, name);
+                client.track_in(&context, exception);
+                panic::resume_unwind(payload)
+            }
+        }
+    })
+}
+
+/// Catches a panic of an inner future, so it can be submitted before the panic is resumed.
+pub(crate) struct CatchUnwind<F>(pub(crate) Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_submits_task_panic_under_context() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = Arc::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+        let mut context = client.context().child();
+        context.tags_mut().operation_mut().set_id("operation".into());
+
+        let handle = spawn_instrumented(&client, context, "reindex", async { panic!("index out of bounds") });
+        let ok = spawn_instrumented(&client, client.context().clone(), "ok", async { 42 });
+
+        assert!(handle.await.unwrap_err().is_panic());
+        assert_eq!(ok.await.unwrap(), 42);
+
+        let exception = events.pop().unwrap();
+        assert_eq!(exception.tags.unwrap()["ai.operation.id"], "operation");
+        assert_matches!(
+            exception.data,
+            Some(Base::Data(Data::ExceptionData(data)))
+                if data.exceptions[0].message == "index out of bounds"
+                    && data.properties.as_ref().unwrap()[TASK_PROPERTY] == "reindex"
+        );
+        assert!(events.is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+//! Audit copies of transmitted telemetry.
+//!
+//! A [`Tee`](struct.Tee.html) configured with [`TelemetryConfig`](../struct.TelemetryConfig.html) receives
+//! every batch of telemetry items right before the batch is transmitted to the server. It either appends
+//! items to a local file as newline-delimited JSON or hands them over to a custom sink, so an application
+//! can retain a copy of everything sent to Azure Monitor. Items submitted again after a failed attempt are
+//! copied again with every attempt.
+//!
+//! ```rust, no_run
+//! use appinsights::{tee::Tee, TelemetryConfig};
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .tee(Tee::ndjson("/var/log/telemetry-audit.ndjson")?)
+//!     .build();
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use std::{
+    fmt::{self, Debug, Formatter},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use log::warn;
+
+use crate::contracts::Envelope;
+
+/// Receives copies of telemetry batches before they are transmitted.
+pub trait TeeSink: Send + Sync {
+    /// Receives a batch of telemetry items that is about to be transmitted.
+    fn write(&self, batch: &[Envelope]);
+}
+
+impl<F> TeeSink for F
+where
+    F: Fn(&[Envelope]) + Send + Sync,
+{
+    fn write(&self, batch: &[Envelope]) {
+        self(batch)
+    }
+}
+
+/// Copies every transmitted batch of telemetry items to a sink.
+#[derive(Clone)]
+pub struct Tee {
+    sink: Arc<dyn TeeSink>,
+}
+
+impl Tee {
+    /// Creates a new tee that hands over every transmitted batch to a custom sink, e.g. a closure.
+    pub fn new(sink: impl TeeSink + 'static) -> Self {
+        Self { sink: Arc::new(sink) }
+    }
+
+    /// Creates a new tee that appends every transmitted item to a file at specified path as a single line
+    /// of JSON. The file is created if it doesn't exist.
+    pub fn ndjson(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(NdjsonFile { file: Mutex::new(file) }))
+    }
+
+    /// Copies a batch of telemetry items to the sink.
+    pub(crate) fn write(&self, batch: &[Envelope]) {
+        self.sink.write(batch)
+    }
+}
+
+impl Debug for Tee {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tee").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Tee {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.sink, &other.sink)
+    }
+}
+
+/// Appends telemetry items to a file as newline-delimited JSON.
+struct NdjsonFile {
+    file: Mutex<File>,
+}
+
+impl TeeSink for NdjsonFile {
+    fn write(&self, batch: &[Envelope]) {
+        let mut lines = Vec::new();
+        for envelope in batch {
+            if let Err(err) = serde_json::to_writer(&mut lines, envelope) {
+                warn!("Unable to serialize telemetry item for audit copy: {}", err);
+                return;
+            }
+            lines.push(b'\n');
+        }
+
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(err) = file.write_all(&lines) {
+            warn!("Unable to write audit copy of {} telemetry items: {}", batch.len(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    #[test]
+    fn it_hands_batches_over_to_custom_sink() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let tee = Tee::new({
+            let batches = batches.clone();
+            move |batch: &[Envelope]| batches.lock().unwrap().push(batch.len())
+        });
+
+        tee.write(&items(2));
+        tee.write(&items(3));
+
+        assert_eq!(*batches.lock().unwrap(), vec![2, 3]);
+    }
+
+    #[test]
+    fn it_appends_items_to_ndjson_file() {
+        let path = std::env::temp_dir().join(format!("appinsights-tee-{}.ndjson", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let tee = Tee::ndjson(&path).unwrap();
+        tee.write(&items(2));
+        tee.write(&items(1));
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let names: Vec<_> = content
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["event 0", "event 1", "event 0"]);
+    }
+
+    fn items(count: usize) -> Vec<Envelope> {
+        (0..count)
+            .map(|i| Envelope {
+                name: format!("event {}", i),
+                ..Envelope::default()
+            })
+            .collect()
+    }
+}
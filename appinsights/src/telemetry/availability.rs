@@ -1,5 +1,3 @@
-use std::time::Duration as StdDuration;
-
 use chrono::{DateTime, SecondsFormat, Utc};
 
 use crate::{
@@ -7,7 +5,6 @@ use crate::{
     contracts::{AvailabilityData, Base, Data, Envelope},
     telemetry::{ContextTags, Measurements, Properties, Telemetry},
     time::{self, Duration},
-    uuid::Uuid,
 };
 
 /// Represents the result of executing an availability test.
@@ -38,7 +35,7 @@ use crate::{
 pub struct AvailabilityTelemetry {
     /// Identifier of a test run.
     /// It is used to correlate steps of test run and telemetry generated by the service.
-    id: Option<Uuid>,
+    id: Option<String>,
 
     /// Name of the test that this result represents.
     name: String,
@@ -70,7 +67,7 @@ pub struct AvailabilityTelemetry {
 
 impl AvailabilityTelemetry {
     /// Creates a new availability telemetry item with the specified test name, duration and success code.
-    pub fn new(name: impl Into<String>, duration: StdDuration, success: bool) -> Self {
+    pub fn new(name: impl Into<String>, duration: impl Into<Duration>, success: bool) -> Self {
         Self {
             id: Option::default(),
             name: name.into(),
@@ -94,6 +91,62 @@ impl AvailabilityTelemetry {
     pub fn measurements_mut(&mut self) -> &mut Measurements {
         &mut self.measurements
     }
+
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
+
+    /// Returns a duration of the test run.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Sets a duration of the test run, e.g. `std::time::Duration` or `chrono::Duration`.
+    pub fn set_duration(&mut self, duration: impl Into<Duration>) {
+        self.duration = duration.into();
+    }
+
+    /// Works like [`set_duration`](#method.set_duration), but consumes and returns the item to construct it inline.
+    pub fn with_duration(mut self, duration: impl Into<Duration>) -> Self {
+        self.set_duration(duration);
+        self
+    }
+
+    /// Sets the identifier of a test run. Use this to correlate steps of a test run by setting their
+    /// operation parent id to this id.
+    pub fn set_id(&mut self, id: impl Into<String>) {
+        self.id = Some(id.into());
+    }
+
+    /// Works like [`set_id`](#method.set_id), but consumes and returns the item to construct it inline.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.set_id(id);
+        self
+    }
+
+    /// Sets the name of the location where the test was run.
+    pub fn set_run_location(&mut self, run_location: impl Into<String>) {
+        self.run_location = Some(run_location.into());
+    }
+
+    /// Works like [`set_run_location`](#method.set_run_location), but consumes and returns the item to construct it inline.
+    pub fn with_run_location(mut self, run_location: impl Into<String>) -> Self {
+        self.set_run_location(run_location);
+        self
+    }
+
+    /// Sets the diagnostic message for the result, e.g. a reason the test failed.
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = Some(message.into());
+    }
+
+    /// Works like [`set_message`](#method.set_message), but consumes and returns the item to construct it inline.
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.set_message(message);
+        self
+    }
 }
 
 impl Telemetry for AvailabilityTelemetry {
@@ -102,6 +155,11 @@ impl Telemetry for AvailabilityTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -131,10 +189,7 @@ impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
             i_key: Some(context.i_key),
             tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
             data: Some(Base::Data(Data::AvailabilityData(AvailabilityData {
-                id: telemetry
-                    .id
-                    .map(|id| id.as_hyphenated().to_string())
-                    .unwrap_or_default(),
+                id: telemetry.id.unwrap_or_default(),
                 name: telemetry.name,
                 duration: telemetry.duration.to_string(),
                 success: telemetry.success,
@@ -151,7 +206,7 @@ impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::{collections::BTreeMap, time::Duration as StdDuration};
 
     use chrono::TimeZone;
 
@@ -65,6 +65,12 @@ impl EventTelemetry {
     pub fn measurements_mut(&mut self) -> &mut Measurements {
         &mut self.measurements
     }
+
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
 }
 
 impl Telemetry for EventTelemetry {
@@ -73,6 +79,11 @@ impl Telemetry for EventTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -162,6 +173,18 @@ mod tests {
         assert_eq!(envelop, expected)
     }
 
+    #[test]
+    fn it_submits_specified_timestamp() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600));
+
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let telemetry = EventTelemetry::new("test").with_timestamp(Utc.ymd(2018, 6, 7).and_hms_milli(8, 9, 10, 110));
+
+        let envelop = Envelope::from((context, telemetry));
+
+        assert_eq!(envelop.time, "2018-06-07T08:09:10.110Z");
+    }
+
     #[test]
     fn it_overrides_tags_from_context() {
         time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 700));
@@ -1 +1,512 @@
-// TODO implement exception collection telemetry item
+use std::{any::Any, backtrace::Backtrace, backtrace::BacktraceStatus, error::Error};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{
+    context::TelemetryContext,
+    contracts::{Base, Data, Envelope, ExceptionData, ExceptionDetails, StackFrame},
+    telemetry::{ContextTags, Measurements, Properties, SeverityLevel, Telemetry},
+    time,
+};
+
+/// Represents handled or unhandled exceptions that occurred during execution of the monitored application.
+/// An exception telemetry item contains the whole chain of errors starting from the outermost one,
+/// so the [`source`](https://doc.rust-lang.org/std/error/trait.Error.html#method.source) of every
+/// error is reported as well.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{Telemetry, ExceptionTelemetry};
+/// use std::backtrace::Backtrace;
+///
+/// let error = "not a number".parse::<i32>().unwrap_err();
+///
+/// // create a telemetry item
+/// let mut telemetry = ExceptionTelemetry::new(&error);
+/// telemetry.set_backtrace(&Backtrace::capture());
+///
+/// // attach custom properties, measurements and context tags
+/// telemetry.properties_mut().insert("component".to_string(), "data_processor".to_string());
+/// telemetry.tags_mut().insert("os_version".to_string(), "linux x86_64".to_string());
+/// telemetry.measurements_mut().insert("records_count".to_string(), 115.0);
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct ExceptionTelemetry {
+    /// Exception chain. The first item represents the outermost error.
+    exceptions: Vec<ExceptionDetails>,
+
+    /// Severity level of the exception.
+    severity_level: Option<SeverityLevel>,
+
+    /// Whether the exception was handled by application code.
+    handled: bool,
+
+    /// A key exceptions are grouped by instead of their type and stack.
+    problem_id: Option<String>,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: DateTime<Utc>,
+
+    /// Custom properties.
+    properties: Properties,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+
+    /// Custom measurements.
+    measurements: Measurements,
+}
+
+impl ExceptionTelemetry {
+    /// Creates a new exception telemetry item from the error and the chain of its sources.
+    pub fn new(error: &(dyn Error + 'static)) -> Self {
+        let mut exceptions = Vec::new();
+
+        let mut source = Some(error);
+        while let Some(error) = source {
+            let id = exceptions.len() as i32;
+            exceptions.push(ExceptionDetails {
+                id: Some(id),
+                outer_id: if id > 0 { Some(id - 1) } else { None },
+                type_name: type_name(error),
+                message: error.to_string(),
+                ..ExceptionDetails::default()
+            });
+            source = error.source();
+        }
+
+        Self {
+            exceptions,
+            severity_level: None,
+            handled: true,
+            problem_id: None,
+            timestamp: time::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+            measurements: Measurements::default(),
+        }
+    }
+
+    /// Creates a new exception telemetry item with specified type name and message, e.g. for failures
+    /// that are not represented by an error value.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::ExceptionTelemetry;
+    /// let telemetry = ExceptionTelemetry::from_message("HttpError", "Request failed with status 503");
+    /// ```
+    pub fn from_message(type_name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            exceptions: vec![ExceptionDetails {
+                id: Some(0),
+                type_name: type_name.into(),
+                message: message.into(),
+                ..ExceptionDetails::default()
+            }],
+            severity_level: None,
+            handled: true,
+            problem_id: None,
+            timestamp: time::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+            measurements: Measurements::default(),
+        }
+    }
+
+    /// Creates a new exception telemetry item from a payload of a caught panic. A panic message is
+    /// reported when a panic was raised with a string payload, like `panic!` macro does. The item is
+    /// marked as [`unhandled`](#method.set_handled) with [`Critical`](enum.SeverityLevel.html) severity.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::ExceptionTelemetry;
+    /// let payload = std::panic::catch_unwind(|| panic!("index out of bounds")).unwrap_err();
+    /// let telemetry = ExceptionTelemetry::from_panic(payload.as_ref());
+    /// ```
+    pub fn from_panic(payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".into());
+
+        Self::from_message("panic", message)
+            .with_severity_level(SeverityLevel::Critical)
+            .with_handled(false)
+    }
+
+    /// Returns a type name of the outermost error.
+    pub fn type_name(&self) -> &str {
+        self.exceptions
+            .first()
+            .map_or("", |exception| exception.type_name.as_str())
+    }
+
+    /// Returns a message of the outermost error.
+    pub fn message(&self) -> &str {
+        self.exceptions
+            .first()
+            .map_or("", |exception| exception.message.as_str())
+    }
+
+    /// Returns a key the exception is grouped by, if set.
+    pub fn problem_id(&self) -> Option<&str> {
+        self.problem_id.as_deref()
+    }
+
+    /// Sets a key the exception is grouped by in Failures instead of its type and stack, e.g. an error
+    /// code. Exceptions with the same key form a single problem.
+    pub fn set_problem_id(&mut self, problem_id: impl Into<String>) {
+        self.problem_id = Some(problem_id.into());
+    }
+
+    /// Works like [`set_problem_id`](#method.set_problem_id), but consumes and returns the item to construct it inline.
+    pub fn with_problem_id(mut self, problem_id: impl Into<String>) -> Self {
+        self.set_problem_id(problem_id);
+        self
+    }
+
+    /// Returns a severity level of the exception.
+    pub fn severity_level(&self) -> Option<SeverityLevel> {
+        self.severity_level
+    }
+
+    /// Sets a severity level of the exception. It is not submitted by default.
+    pub fn set_severity_level(&mut self, severity_level: SeverityLevel) {
+        self.severity_level = Some(severity_level);
+    }
+
+    /// Works like [`set_severity_level`](#method.set_severity_level), but consumes and returns the item to construct it inline.
+    pub fn with_severity_level(mut self, severity_level: SeverityLevel) -> Self {
+        self.set_severity_level(severity_level);
+        self
+    }
+
+    /// Returns `true` if the exception was handled by application code.
+    pub fn is_handled(&self) -> bool {
+        self.handled
+    }
+
+    /// Sets whether the exception was logged and recovered from by application code, or it crashed an
+    /// operation. It is submitted as `handledAt` property with either `UserCode` or `Unhandled` value,
+    /// so alerts can tell them apart. An exception is handled by default.
+    pub fn set_handled(&mut self, handled: bool) {
+        self.handled = handled;
+    }
+
+    /// Works like [`set_handled`](#method.set_handled), but consumes and returns the item to construct it inline.
+    pub fn with_handled(mut self, handled: bool) -> Self {
+        self.set_handled(handled);
+        self
+    }
+
+    /// Returns custom measurements to submit with the telemetry item.
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    /// Returns mutable reference to custom measurements.
+    pub fn measurements_mut(&mut self) -> &mut Measurements {
+        &mut self.measurements
+    }
+
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
+
+    /// Attaches a stack of the outermost error. It does nothing when backtrace was not captured.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::ExceptionTelemetry;
+    /// use std::backtrace::Backtrace;
+    ///
+    /// let error = "not a number".parse::<i32>().unwrap_err();
+    ///
+    /// let mut telemetry = ExceptionTelemetry::new(&error);
+    /// telemetry.set_backtrace(&Backtrace::force_capture());
+    /// ```
+    pub fn set_backtrace(&mut self, backtrace: &Backtrace) {
+        if backtrace.status() != BacktraceStatus::Captured {
+            return;
+        }
+
+        if let Some(exception) = self.exceptions.first_mut() {
+            let stack = backtrace.to_string();
+            exception.parsed_stack = Some(parse_stack(&stack));
+            exception.stack = Some(stack);
+        }
+    }
+
+    /// Works like [`set_backtrace`](#method.set_backtrace), but consumes and returns the item to construct it inline.
+    pub fn with_backtrace(mut self, backtrace: &Backtrace) -> Self {
+        self.set_backtrace(backtrace);
+        self
+    }
+}
+
+impl Telemetry for ExceptionTelemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+
+    /// Returns this item.
+    fn as_exception_mut(&mut self) -> Option<&mut ExceptionTelemetry> {
+        Some(self)
+    }
+}
+
+impl From<(TelemetryContext, ExceptionTelemetry)> for Envelope {
+    fn from((context, mut telemetry): (TelemetryContext, ExceptionTelemetry)) -> Self {
+        let handled_at = if telemetry.handled { "UserCode" } else { "Unhandled" };
+        telemetry.properties.insert("handledAt".into(), handled_at.into());
+
+        Self {
+            name: "Microsoft.ApplicationInsights.Exception".into(),
+            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            i_key: Some(context.i_key),
+            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: telemetry.exceptions,
+                severity_level: telemetry.severity_level.map(Into::into),
+                problem_id: telemetry.problem_id,
+                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                measurements: Some(telemetry.measurements.into()),
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+}
+
+/// Returns a type name of the error. Rust does not provide type information for trait objects, so the
+/// name is taken from the `Debug` representation, which is the type name for derived implementations.
+fn type_name(error: &dyn Error) -> String {
+    let debug = format!("{:?}", error);
+    let name: String = debug
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == ':')
+        .collect();
+
+    if name.is_empty() {
+        "Error".into()
+    } else {
+        name
+    }
+}
+
+/// Parses frames from a captured backtrace in the format std library renders them:
+/// ```text
+///    0: app::module::function
+///              at ./src/module.rs:10:5
+/// ```
+fn parse_stack(stack: &str) -> Vec<StackFrame> {
+    let mut frames: Vec<StackFrame> = Vec::new();
+
+    for line in stack.lines().map(str::trim) {
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                let mut parts = location.rsplitn(3, ':');
+                let _column = parts.next();
+                let line = parts.next().and_then(|line| line.parse().ok());
+                match (parts.next(), line) {
+                    (Some(file_name), Some(line)) => {
+                        frame.file_name = Some(file_name.into());
+                        frame.line = Some(line);
+                    }
+                    _ => frame.file_name = Some(location.into()),
+                }
+            }
+        } else if let Some((level, method)) = line.split_once(": ") {
+            if let Ok(level) = level.parse() {
+                frames.push(StackFrame {
+                    level,
+                    method: method.into(),
+                    ..StackFrame::default()
+                });
+            }
+        }
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fmt};
+
+    use chrono::TimeZone;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::contracts::SeverityLevel as ContractsSeverityLevel;
+
+    #[derive(Debug)]
+    struct ConnectionError {
+        source: std::io::Error,
+    }
+
+    impl fmt::Display for ConnectionError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "unable to connect")
+        }
+    }
+
+    impl Error for ConnectionError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.source)
+        }
+    }
+
+    #[test]
+    fn it_collects_chain_of_errors() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let error = ConnectionError {
+            source: std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"),
+        };
+        let telemetry = ExceptionTelemetry::new(&error);
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Exception".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(BTreeMap::default()),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: vec![
+                    ExceptionDetails {
+                        id: Some(0),
+                        type_name: "ConnectionError".into(),
+                        message: "unable to connect".into(),
+                        ..ExceptionDetails::default()
+                    },
+                    ExceptionDetails {
+                        id: Some(1),
+                        outer_id: Some(0),
+                        type_name: "Custom".into(),
+                        message: "timed out".into(),
+                        ..ExceptionDetails::default()
+                    },
+                ],
+                properties: Some(BTreeMap::from([("handledAt".into(), "UserCode".into())])),
+                measurements: Some(BTreeMap::default()),
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+
+    #[test_case(Box::new("index out of bounds"),     "index out of bounds" ; "str")]
+    #[test_case(Box::new(format!("value {}", 42)), "value 42"            ; "string")]
+    #[test_case(Box::new(42),                      "Box<dyn Any>"        ; "other")]
+    fn it_reports_panic_message(payload: Box<dyn Any + Send>, expected: &str) {
+        let telemetry = ExceptionTelemetry::from_panic(payload.as_ref());
+
+        assert_eq!(telemetry.exceptions.len(), 1);
+        assert_eq!(telemetry.exceptions[0].type_name, "panic");
+        assert_eq!(telemetry.exceptions[0].message, expected);
+    }
+
+    #[test_case(ExceptionTelemetry::from_message("Error", "failed"), None, "UserCode" ; "handled")]
+    #[test_case(
+        ExceptionTelemetry::from_message("Error", "failed").with_severity_level(SeverityLevel::Warning),
+        Some(ContractsSeverityLevel::Warning),
+        "UserCode" ;
+        "handled with severity"
+    )]
+    #[test_case(
+        ExceptionTelemetry::from_panic(&"crashed"),
+        Some(ContractsSeverityLevel::Critical),
+        "Unhandled" ;
+        "panic"
+    )]
+    fn it_submits_severity_level_and_handled_at(
+        telemetry: ExceptionTelemetry,
+        severity_level: Option<ContractsSeverityLevel>,
+        handled_at: &str,
+    ) {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        match Envelope::from((context, telemetry)).data {
+            Some(Base::Data(Data::ExceptionData(data))) => {
+                assert_eq!(data.severity_level, severity_level);
+                assert_eq!(data.properties.unwrap()["handledAt"], handled_at);
+            }
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_attaches_backtrace_to_outermost_error() {
+        let error = ConnectionError {
+            source: std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"),
+        };
+        let mut telemetry = ExceptionTelemetry::new(&error);
+
+        telemetry.set_backtrace(&Backtrace::force_capture());
+
+        assert!(telemetry.exceptions[0].stack.is_some());
+        assert!(!telemetry.exceptions[0].parsed_stack.as_ref().unwrap().is_empty());
+        assert!(telemetry.exceptions[1].stack.is_none());
+    }
+
+    #[test]
+    fn it_parses_stack_frames() {
+        let stack = "   0: app::module::function\n             at ./src/module.rs:10:5\n   1: main\n";
+
+        let frames = parse_stack(stack);
+
+        assert_eq!(
+            frames,
+            vec![
+                StackFrame {
+                    level: 0,
+                    method: "app::module::function".into(),
+                    file_name: Some("./src/module.rs".into()),
+                    line: Some(10),
+                    ..StackFrame::default()
+                },
+                StackFrame {
+                    level: 1,
+                    method: "main".into(),
+                    ..StackFrame::default()
+                }
+            ]
+        )
+    }
+}
@@ -0,0 +1,132 @@
+use crate::telemetry::{EventTelemetry, Telemetry};
+
+/// Name of an event every feature usage is submitted with.
+pub const FEATURE_USAGE_EVENT: &str = "FeatureUsage";
+
+/// Describes a use of a product feature submitted as a custom event with a stable schema: the event is
+/// always named `FeatureUsage` and has `feature`, `result` and, optionally, `variant` properties. Product
+/// analytics queries can rely on these names regardless of where an application tracks usage from.
+///
+/// ```text
+/// customEvents
+/// | where name == "FeatureUsage"
+/// | summarize trials = countif(customDimensions.result == "trial"),
+///             conversions = countif(customDimensions.result == "converted")
+///     by feature = tostring(customDimensions.feature), variant = tostring(customDimensions.variant)
+/// ```
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{EventTelemetry, FeatureResult, FeatureUsage};
+///
+/// let usage = FeatureUsage::new("export").with_variant("csv").with_result(FeatureResult::Failure);
+/// client.track(EventTelemetry::from(usage));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureUsage {
+    feature: String,
+    variant: Option<String>,
+    result: FeatureResult,
+}
+
+impl FeatureUsage {
+    /// Creates a new usage of a feature with [`Used`](enum.FeatureResult.html#variant.Used) result.
+    pub fn new(feature: impl Into<String>) -> Self {
+        Self {
+            feature: feature.into(),
+            variant: None,
+            result: FeatureResult::Used,
+        }
+    }
+
+    /// Returns a name of a feature.
+    pub fn feature(&self) -> &str {
+        &self.feature
+    }
+
+    /// Returns a variant of a feature, e.g. an experiment arm, a user was presented with.
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// Returns an outcome of a feature usage.
+    pub fn result(&self) -> FeatureResult {
+        self.result
+    }
+
+    /// Sets a variant of a feature and returns the usage, so it can be constructed inline.
+    pub fn with_variant(mut self, variant: impl Into<String>) -> Self {
+        self.variant = Some(variant.into());
+        self
+    }
+
+    /// Sets an outcome of a feature usage and returns the usage, so it can be constructed inline.
+    pub fn with_result(mut self, result: FeatureResult) -> Self {
+        self.result = result;
+        self
+    }
+}
+
+impl From<FeatureUsage> for EventTelemetry {
+    fn from(usage: FeatureUsage) -> Self {
+        let mut event = EventTelemetry::new(FEATURE_USAGE_EVENT)
+            .with_property("feature", usage.feature)
+            .with_property("result", usage.result.as_str());
+        if let Some(variant) = usage.variant {
+            event.properties_mut().insert("variant".into(), variant);
+        }
+        event
+    }
+}
+
+/// An outcome of a feature usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureResult {
+    /// A feature was used.
+    Used,
+
+    /// A user started a trial of a feature.
+    Trial,
+
+    /// A user converted after a trial of a feature.
+    Converted,
+
+    /// A feature did what a user expected.
+    Success,
+
+    /// A feature failed to do what a user expected.
+    Failure,
+}
+
+impl FeatureResult {
+    /// Returns a value of `result` property.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeatureResult::Used => "used",
+            FeatureResult::Trial => "trial",
+            FeatureResult::Converted => "converted",
+            FeatureResult::Success => "success",
+            FeatureResult::Failure => "failure",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(FeatureUsage::new("export"),                                               &[("feature", "export"), ("result", "used")]                      ; "usage")]
+    #[test_case(FeatureUsage::new("export").with_variant("b").with_result(FeatureResult::Trial), &[("feature", "export"), ("result", "trial"), ("variant", "b")] ; "trial")]
+    fn it_converts_usage_to_event(usage: FeatureUsage, expected: &[(&str, &str)]) {
+        let event = EventTelemetry::from(usage);
+
+        let expected: BTreeMap<_, _> = expected.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        assert_eq!(**event.properties(), expected);
+    }
+}
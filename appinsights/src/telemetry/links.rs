@@ -0,0 +1,85 @@
+use serde::Serialize;
+
+use crate::telemetry::Properties;
+
+/// A name of the property links of a request or a dependency call are submitted in.
+pub const LINKS_PROPERTY: &str = "_MS.links";
+
+/// A link to an operation related to a request or a dependency call, e.g. one of many messages a batch
+/// consumer processes in a single request. Application Insights shows linked items as related ones.
+///
+/// ```rust
+/// # use appinsights::telemetry::{RequestTelemetry, SpanLink};
+/// # use http::Method;
+/// # use std::time::Duration;
+/// let request = RequestTelemetry::new(
+///     Method::POST,
+///     "https://api.example.com/batches".parse().unwrap(),
+///     Duration::from_millis(42),
+///     "200",
+/// )
+/// .with_link(SpanLink::new("0123456789abcdef0123456789abcdef", "0123456789abcdef"))
+/// .with_link(SpanLink::new("fedcba9876543210fedcba9876543210", "fedcba9876543210"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpanLink {
+    #[serde(rename = "operation_Id")]
+    operation_id: String,
+    id: String,
+}
+
+impl SpanLink {
+    /// Creates a new link to a span with specified id of an operation with specified id.
+    pub fn new(operation_id: impl Into<String>, id: impl Into<String>) -> Self {
+        Self {
+            operation_id: operation_id.into(),
+            id: id.into(),
+        }
+    }
+
+    /// Returns an id of a linked operation.
+    pub fn operation_id(&self) -> &str {
+        &self.operation_id
+    }
+
+    /// Returns an id of a linked span.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Adds links to properties of a telemetry item in the format of Application Insights.
+pub(crate) fn insert(properties: &mut Properties, links: &[SpanLink]) {
+    if links.is_empty() {
+        return;
+    }
+    if let Ok(links) = serde_json::to_string(links) {
+        properties.insert(LINKS_PROPERTY.into(), links);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_inserts_links_property() {
+        let mut properties = Properties::default();
+
+        insert(&mut properties, &[SpanLink::new("operation", "span")]);
+
+        assert_eq!(
+            properties[LINKS_PROPERTY],
+            r#"[{"operation_Id":"operation","id":"span"}]"#
+        );
+    }
+
+    #[test]
+    fn it_skips_empty_links() {
+        let mut properties = Properties::default();
+
+        insert(&mut properties, &[]);
+
+        assert!(properties.is_empty());
+    }
+}
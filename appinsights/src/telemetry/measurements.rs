@@ -1,12 +1,182 @@
 use std::{
     collections::BTreeMap,
+    iter::FromIterator,
     ops::{Deref, DerefMut},
 };
 
+use crate::time::Duration;
+
 /// Contains all measurements for telemetry to submit.
+///
+/// Measurements can be collected from an iterator of key-value pairs or created with
+/// [`measurements!`](../macro.measurements.html) macro.
+///
+/// Application Insights stores measurements as plain numbers, so a unit of a measurement is encoded
+/// into its name with a suffix, e.g. `db_time_ms`. Helper methods insert measurements with a unit
+/// and convert values consistently.
+///
+/// ```rust
+/// # use appinsights::telemetry::{EventTelemetry, Measurement, Unit};
+/// use std::time::Duration;
+///
+/// let mut event = EventTelemetry::new("report generated");
+/// event.measurements_mut().insert_duration("db_time", Duration::from_micros(1500));
+/// event.measurements_mut().insert_bytes("report_size", 4096);
+/// event.measurements_mut().insert_measurement(Measurement::new("pages", 12.0, Unit::Count));
+///
+/// assert_eq!(event.measurements()["db_time_ms"], 1.5);
+/// assert_eq!(event.measurements()["report_size_bytes"], 4096.0);
+/// assert_eq!(event.measurements()["pages_count"], 12.0);
+/// ```
 #[derive(Debug, Clone, Default)]
 pub struct Measurements(BTreeMap<String, f64>);
 
+impl Measurements {
+    /// Inserts a measurement with a name suffixed with its unit.
+    pub fn insert_measurement(&mut self, measurement: Measurement) {
+        self.0.insert(measurement.key(), measurement.value);
+    }
+
+    /// Inserts a duration in milliseconds, e.g. `db_time_ms`.
+    pub fn insert_duration(&mut self, name: impl Into<String>, duration: impl Into<Duration>) {
+        self.insert_measurement(Measurement::duration(name, duration));
+    }
+
+    /// Adds a duration in milliseconds to a measurement, so durations of repeated steps sum up, e.g. a
+    /// total `db_time_ms` of all queries. A missing measurement starts at zero.
+    pub fn add_duration(&mut self, name: impl Into<String>, duration: impl Into<Duration>) {
+        let measurement = Measurement::duration(name, duration);
+        *self.0.entry(measurement.key()).or_default() += measurement.value;
+    }
+
+    /// Inserts a size in bytes, e.g. `payload_bytes`.
+    pub fn insert_bytes(&mut self, name: impl Into<String>, bytes: u64) {
+        self.insert_measurement(Measurement::new(name, bytes as f64, Unit::Bytes));
+    }
+
+    /// Inserts a number of items, e.g. `rows_count`.
+    pub fn insert_count(&mut self, name: impl Into<String>, count: u64) {
+        self.insert_measurement(Measurement::new(name, count as f64, Unit::Count));
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Measurements
+where
+    K: Into<String>,
+    V: Into<f64>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> Extend<(K, V)> for Measurements
+where
+    K: Into<String>,
+    V: Into<f64>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0
+            .extend(iter.into_iter().map(|(key, value)| (key.into(), value.into())));
+    }
+}
+
+/// Creates [`Measurements`](telemetry/struct.Measurements.html) from key-value pairs. Values are
+/// converted to `f64` with `as` operator, so any numeric value is accepted.
+///
+/// ```rust
+/// # use appinsights::measurements;
+/// let measurements = measurements!["rows" => 42, "ratio" => 0.5];
+///
+/// assert_eq!(measurements["rows"], 42.0);
+/// ```
+#[macro_export]
+macro_rules! measurements {
+    ( $( $key:expr => $value:expr ),* $(,)? ) => {
+        <$crate::telemetry::Measurements as ::std::iter::FromIterator<(String, f64)>>::from_iter([
+            $( (::std::string::String::from($key), $value as f64) ),*
+        ])
+    };
+}
+
+/// A unit of a measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Duration in milliseconds.
+    Milliseconds,
+
+    /// Size in bytes.
+    Bytes,
+
+    /// Number of items.
+    Count,
+
+    /// Ratio in percents.
+    Percent,
+}
+
+impl Unit {
+    /// Returns a suffix a measurement name ends with.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Unit::Milliseconds => "ms",
+            Unit::Bytes => "bytes",
+            Unit::Count => "count",
+            Unit::Percent => "percent",
+        }
+    }
+}
+
+/// A measurement value with a unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    name: String,
+    value: f64,
+    unit: Unit,
+}
+
+impl Measurement {
+    /// Creates a new measurement with specified name, value and unit.
+    pub fn new(name: impl Into<String>, value: f64, unit: Unit) -> Self {
+        Self {
+            name: name.into(),
+            value,
+            unit,
+        }
+    }
+
+    /// Creates a new measurement of a duration in milliseconds with fractional part.
+    pub fn duration(name: impl Into<String>, duration: impl Into<Duration>) -> Self {
+        let duration = duration.into();
+        Self::new(name, duration.as_secs_f64() * 1000.0, Unit::Milliseconds)
+    }
+
+    /// Returns a measurement value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns a measurement unit.
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    /// Returns a measurement name suffixed with its unit. A name that already ends with the suffix is kept
+    /// as is.
+    pub fn key(&self) -> String {
+        let suffix = format!("_{}", self.unit.suffix());
+        if self.name.ends_with(&suffix) {
+            self.name.clone()
+        } else {
+            format!("{}{}", self.name, suffix)
+        }
+    }
+}
+
 impl From<Measurements> for BTreeMap<String, f64> {
     fn from(measurements: Measurements) -> Self {
         measurements.0
@@ -26,3 +196,23 @@ impl DerefMut for Measurements {
         &mut self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Measurement::duration("db_time", StdDuration::from_micros(1500)), "db_time_ms", 1.5 ; "duration")]
+    #[test_case(Measurement::duration("db_time_ms", chrono::Duration::seconds(2)), "db_time_ms", 2000.0 ; "suffixed name")]
+    #[test_case(Measurement::new("cpu", 42.0, Unit::Percent), "cpu_percent", 42.0 ; "percent")]
+    fn it_encodes_unit_into_name(measurement: Measurement, key: &str, value: f64) {
+        let mut measurements = Measurements::default();
+
+        measurements.insert_measurement(measurement);
+
+        assert_eq!(measurements.get(key), Some(&value));
+    }
+}
@@ -0,0 +1,100 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{aggregator::Histogram, telemetry::Stats};
+
+/// An approximate distribution of durations of requests or dependency calls a client aggregated locally
+/// for a standard metric, e.g. `Server response time`, and a combination of its dimensions, e.g. a result
+/// code. It covers all items tracked since the client was created, so a service can compute latency
+/// percentiles or SLO burn rates from differences between snapshots without querying Log Analytics.
+/// Percentiles and counts are estimated within 10% of exact values.
+///
+/// ```rust, no_run
+/// # use appinsights::{TelemetryClient, TelemetryConfig};
+/// use std::time::Duration;
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .standard_metrics(true)
+///     .build();
+/// let client = TelemetryClient::from_config(config);
+///
+/// // share of requests slower than the objective of 300 ms
+/// let (slow, total) = client
+///     .duration_distributions()
+///     .iter()
+///     .filter(|distribution| distribution.name() == "Server response time")
+///     .fold((0, 0), |(slow, total), distribution| {
+///         (slow + distribution.count_over(Duration::from_millis(300)), total + distribution.count())
+///     });
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationDistribution {
+    name: String,
+    dimensions: BTreeMap<String, String>,
+    stats: Stats,
+    histogram: Histogram,
+}
+
+impl DurationDistribution {
+    /// Creates a new distribution of durations in milliseconds.
+    pub(crate) fn new(name: String, dimensions: BTreeMap<String, String>, stats: Stats, histogram: Histogram) -> Self {
+        Self {
+            name,
+            dimensions,
+            stats,
+            histogram,
+        }
+    }
+
+    /// Returns a name of the standard metric, i.e. `Server response time` or `Dependency duration`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns dimensions of the series, e.g. `request/resultCode` or `dependency/target`.
+    pub fn dimensions(&self) -> &BTreeMap<String, String> {
+        &self.dimensions
+    }
+
+    /// Returns a number of durations in the distribution.
+    pub fn count(&self) -> u64 {
+        self.stats.count.max(0) as u64
+    }
+
+    /// Returns the shortest duration.
+    pub fn min(&self) -> Duration {
+        millis(self.stats.min)
+    }
+
+    /// Returns the longest duration.
+    pub fn max(&self) -> Duration {
+        millis(self.stats.max)
+    }
+
+    /// Returns an average duration.
+    pub fn mean(&self) -> Duration {
+        if self.stats.count > 0 {
+            millis(self.stats.value / f64::from(self.stats.count))
+        } else {
+            Duration::default()
+        }
+    }
+
+    /// Returns an estimate of a duration specified percent of requests or calls complete within, e.g. 99
+    /// for the 99th percentile. Returns `None` for an empty distribution.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        self.histogram
+            .percentile(percent)
+            .map(|value| millis(value.clamp(self.stats.min, self.stats.max)))
+    }
+
+    /// Returns an estimate of a number of requests or calls that took longer than a threshold, e.g. a
+    /// latency objective.
+    pub fn count_over(&self, threshold: Duration) -> u64 {
+        self.histogram.count_above(threshold.as_secs_f64() * 1000.0)
+    }
+}
+
+fn millis(value: f64) -> Duration {
+    Duration::from_secs_f64(value.max(0.0) / 1000.0)
+}
@@ -7,7 +7,8 @@ use crate::{
     time,
 };
 
-/// Metric telemetry item that represents a single data point.
+/// Metric telemetry item that represents a single data point or an aggregate computed by the
+/// application, when created with a [`builder`](#method.builder).
 ///
 /// # Examples
 /// ```rust, no_run
@@ -30,9 +31,24 @@ pub struct MetricTelemetry {
     /// Metric name.
     name: String,
 
-    /// Sampled value.
+    /// Sampled value, or a sum of values for pre-aggregated metric.
     value: f64,
 
+    /// Metric namespace.
+    namespace: Option<String>,
+
+    /// Count of measurements in the sample.
+    count: Option<i32>,
+
+    /// Minimum value of the aggregated metric.
+    min: Option<f64>,
+
+    /// Maximum value of the aggregated metric.
+    max: Option<f64>,
+
+    /// Standard deviation of the aggregated metric.
+    std_dev: Option<f64>,
+
     /// The time stamp when this telemetry was measured.
     timestamp: DateTime<Utc>,
 
@@ -49,11 +65,90 @@ impl MetricTelemetry {
         Self {
             name: name.into(),
             value,
+            namespace: None,
+            count: None,
+            min: None,
+            max: None,
+            std_dev: None,
             timestamp: time::now(),
             properties: Properties::default(),
             tags: ContextTags::default(),
         }
     }
+
+    /// Creates a new builder of a metric telemetry item with specified name and value. The builder
+    /// makes possible to submit metric aggregated by the application with count, min, max and standard
+    /// deviation, so the value should be a sum of all aggregated values.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::MetricTelemetry;
+    /// let telemetry = MetricTelemetry::builder("request_latency_ms", 1250.0)
+    ///     .namespace("gateway")
+    ///     .count(10)
+    ///     .min(42.0)
+    ///     .max(310.0)
+    ///     .std_dev(76.4)
+    ///     .build();
+    /// ```
+    pub fn builder(name: impl Into<String>, value: f64) -> MetricTelemetryBuilder {
+        MetricTelemetryBuilder {
+            telemetry: Self::new(name, value),
+        }
+    }
+
+    /// Determines whether this metric item represents an aggregate of several values.
+    fn is_aggregation(&self) -> bool {
+        self.count.is_some() || self.min.is_some() || self.max.is_some() || self.std_dev.is_some()
+    }
+}
+
+/// Constructs a new instance of a [`MetricTelemetry`](struct.MetricTelemetry.html) with custom
+/// aggregated values and namespace.
+pub struct MetricTelemetryBuilder {
+    telemetry: MetricTelemetry,
+}
+
+impl MetricTelemetryBuilder {
+    /// Initializes a builder with a metric namespace.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.telemetry.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Initializes a builder with a count of aggregated measurements.
+    pub fn count(mut self, count: i32) -> Self {
+        self.telemetry.count = Some(count);
+        self
+    }
+
+    /// Initializes a builder with a minimum value of aggregated measurements.
+    pub fn min(mut self, min: f64) -> Self {
+        self.telemetry.min = Some(min);
+        self
+    }
+
+    /// Initializes a builder with a maximum value of aggregated measurements.
+    pub fn max(mut self, max: f64) -> Self {
+        self.telemetry.max = Some(max);
+        self
+    }
+
+    /// Initializes a builder with a standard deviation of aggregated measurements.
+    pub fn std_dev(mut self, std_dev: f64) -> Self {
+        self.telemetry.std_dev = Some(std_dev);
+        self
+    }
+
+    /// Initializes a builder with a metric dimension. Dimensions are submitted as custom properties.
+    pub fn dimension(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.telemetry.properties.insert(name.into(), value.into());
+        self
+    }
+
+    /// Constructs a new instance of a [`MetricTelemetry`](struct.MetricTelemetry.html) with custom settings.
+    pub fn build(self) -> MetricTelemetry {
+        self.telemetry
+    }
 }
 
 impl Telemetry for MetricTelemetry {
@@ -62,6 +157,11 @@ impl Telemetry for MetricTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -85,6 +185,12 @@ impl Telemetry for MetricTelemetry {
 
 impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, MetricTelemetry)) -> Self {
+        let kind = if telemetry.is_aggregation() {
+            DataPointType::Aggregation
+        } else {
+            DataPointType::Measurement
+        };
+
         Self {
             name: "Microsoft.ApplicationInsights.Metric".into(),
             time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
@@ -92,11 +198,14 @@ impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
             tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
+                    ns: telemetry.namespace,
                     name: telemetry.name,
-                    kind: Some(DataPointType::Measurement),
+                    kind: Some(kind),
                     value: telemetry.value,
-                    count: Some(1),
-                    ..DataPoint::default()
+                    count: Some(telemetry.count.unwrap_or(1)),
+                    min: telemetry.min,
+                    max: telemetry.max,
+                    std_dev: telemetry.std_dev,
                 }],
                 properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
                 ..MetricData::default()
@@ -196,4 +305,49 @@ mod tests {
 
         assert_eq!(envelop, expected)
     }
+
+    #[test]
+    fn it_submits_pre_aggregated_metric() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 102));
+
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let telemetry = MetricTelemetry::builder("test", 250.0)
+            .namespace("gateway")
+            .count(3)
+            .min(50.0)
+            .max(120.0)
+            .std_dev(29.4)
+            .dimension("endpoint", "/orders")
+            .build();
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Metric".into(),
+            time: "2019-01-02T03:04:05.102Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(BTreeMap::default()),
+            data: Some(Base::Data(Data::MetricData(MetricData {
+                metrics: vec![DataPoint {
+                    ns: Some("gateway".into()),
+                    name: "test".into(),
+                    kind: Some(DataPointType::Aggregation),
+                    value: 250.0,
+                    count: Some(3),
+                    min: Some(50.0),
+                    max: Some(120.0),
+                    std_dev: Some(29.4),
+                }],
+                properties: Some({
+                    let mut properties = BTreeMap::default();
+                    properties.insert("endpoint".into(), "/orders".into());
+                    properties
+                }),
+                ..MetricData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
 }
@@ -1,7 +1,9 @@
 mod aggregation;
+mod distribution;
 mod measurement;
 mod stats;
 
 pub use aggregation::*;
+pub use distribution::*;
 pub use measurement::*;
 pub use stats::*;
@@ -1,5 +1,5 @@
 /// Stores statistics for aggregated metric.
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Stats {
     /// Sampled value.
     pub(crate) value: f64,
@@ -65,8 +65,8 @@ impl Stats {
                 mean = self.value / self.count as f64;
             }
 
-            self.min = values.iter().fold(std::f64::NAN, |x, min| min.min(x));
-            self.max = values.iter().fold(std::f64::NAN, |x, max| max.max(x));
+            self.min = values.iter().fold(self.min, |x, min| min.min(x));
+            self.max = values.iter().fold(self.max, |x, max| max.max(x));
 
             // Welford's algorithm to compute variance. The divide occurs in the caller.
             let mut value = self.value;
@@ -114,6 +114,25 @@ mod tests {
         )
     }
 
+    #[test]
+    fn it_calculates_stats_incrementally() {
+        let mut stats = Stats::default();
+        for value in [9.0, 10.0, 11.0, 7.0, 13.0] {
+            stats.add_data(&[value]);
+        }
+
+        assert_eq!(
+            stats,
+            Stats {
+                value: 50.0,
+                min: 7.0,
+                max: 13.0,
+                count: 5,
+                std_dev: 2.0,
+            }
+        )
+    }
+
     #[test_case(&[],                           0.0,    0.0,    0.0     ; "for empty collection")]
     #[test_case(&[0.0],                        0.0,    0.0,    0.0     ; "for single zero value")]
     #[test_case(&[50.0],                       0.0,    50.0,   50.0    ; "for single non-zero value")]
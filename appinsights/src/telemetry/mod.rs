@@ -1,7 +1,10 @@
 //! Module for Application Insights telemetry items.
 mod availability;
+mod dependency_target;
 mod event;
 mod exception;
+mod feature_usage;
+mod links;
 mod measurements;
 mod metric;
 mod page_view;
@@ -12,13 +15,18 @@ mod tags;
 mod trace;
 
 pub use availability::AvailabilityTelemetry;
+pub use dependency_target::DependencyTarget;
 pub use event::EventTelemetry;
-pub use measurements::Measurements;
-pub use metric::{AggregateMetricTelemetry, MetricTelemetry, Stats};
+pub use exception::ExceptionTelemetry;
+pub use feature_usage::{FeatureResult, FeatureUsage, FEATURE_USAGE_EVENT};
+pub use links::{SpanLink, LINKS_PROPERTY};
+pub use measurements::{Measurement, Measurements, Unit};
+pub use metric::{AggregateMetricTelemetry, DurationDistribution, MetricTelemetry, Stats};
 pub use page_view::PageViewTelemetry;
 pub use properties::Properties;
-pub use remote_dependency::RemoteDependencyTelemetry;
-pub use request::RequestTelemetry;
+pub use remote_dependency::{ConnectionTimings, RemoteDependencyTelemetry};
+pub(crate) use request::path_of;
+pub use request::{RequestPhase, RequestTelemetry};
 pub use tags::{
     ApplicationTags, CloudTags, ContextTags, DeviceTags, InternalTags, LocationTags, OperationTags, SessionTags,
     UserTags,
@@ -32,6 +40,10 @@ pub trait Telemetry {
     /// Returns the time when this telemetry was measured.
     fn timestamp(&self) -> DateTime<Utc>;
 
+    /// Sets the time when this telemetry was measured, e.g. to submit historical data. It is the time the
+    /// item was created by default.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>);
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties;
 
@@ -43,4 +55,50 @@ pub trait Telemetry {
 
     /// Returns mutable reference to custom tags.
     fn tags_mut(&mut self) -> &mut ContextTags;
+
+    /// Returns the item as an exception if it is one, so an
+    /// [`initializer`](../initializer/index.html) can refine exceptions it receives as `dyn Telemetry`.
+    fn as_exception_mut(&mut self) -> Option<&mut ExceptionTelemetry> {
+        None
+    }
+
+    /// Adds a custom property and returns the item, so it can be constructed inline.
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{EventTelemetry, Telemetry};
+    ///
+    /// client.track(
+    ///     EventTelemetry::new("order placed")
+    ///         .with_property("order", "42")
+    ///         .with_tag("ai.user.authUserId", "user@example.com")
+    ///         .with_measurement("total", 99.5),
+    /// );
+    /// ```
+    fn with_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self
+    where
+        Self: Sized,
+    {
+        self.properties_mut().insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the time when this telemetry was measured and returns the item, so it can be constructed inline.
+    fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self
+    where
+        Self: Sized,
+    {
+        self.set_timestamp(timestamp);
+        self
+    }
+
+    /// Adds a context tag, e.g. `ai.user.id`, and returns the item, so it can be constructed inline.
+    fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self
+    where
+        Self: Sized,
+    {
+        self.tags_mut().insert(key.into(), value.into());
+        self
+    }
 }
@@ -85,6 +85,34 @@ impl PageViewTelemetry {
     pub fn measurements_mut(&mut self) -> &mut Measurements {
         &mut self.measurements
     }
+
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
+
+    /// Sets an identifier of the page view that telemetry of nested operations refers to as a parent.
+    pub(crate) fn with_id(mut self, id: Uuid) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Returns a duration of loading the page, if known.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Sets a duration of loading the page, e.g. `std::time::Duration` or `chrono::Duration`.
+    pub fn set_duration(&mut self, duration: impl Into<Duration>) {
+        self.duration = Some(duration.into());
+    }
+
+    /// Works like [`set_duration`](#method.set_duration), but consumes and returns the item to construct it inline.
+    pub fn with_duration(mut self, duration: impl Into<Duration>) -> Self {
+        self.set_duration(duration);
+        self
+    }
 }
 
 impl Telemetry for PageViewTelemetry {
@@ -93,6 +121,11 @@ impl Telemetry for PageViewTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -116,6 +149,7 @@ impl Telemetry for PageViewTelemetry {
 
 impl From<(TelemetryContext, PageViewTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, PageViewTelemetry)) -> Self {
+        let ids = context.ids;
         Self {
             name: "Microsoft.ApplicationInsights.PageView".into(),
             time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
@@ -128,6 +162,7 @@ impl From<(TelemetryContext, PageViewTelemetry)> for Envelope {
                 referrer_uri: None,
                 id: telemetry
                     .id
+                    .or_else(|| ids.new_time_ordered())
                     .map(|id| id.as_hyphenated().to_string())
                     .unwrap_or_default(),
                 properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
@@ -1,9 +1,21 @@
 use std::{
     collections::BTreeMap,
+    iter::FromIterator,
     ops::{Deref, DerefMut},
 };
 
 /// Contains all properties for telemetry to submit.
+///
+/// Properties can be collected from an iterator of key-value pairs or created with
+/// [`properties!`](../macro.properties.html) macro.
+///
+/// ```rust
+/// # use appinsights::telemetry::Properties;
+/// let mut properties: Properties = vec![("region", "westeurope")].into_iter().collect();
+/// properties.extend([("tier", "premium")]);
+///
+/// assert_eq!(properties["tier"], "premium");
+/// ```
 #[derive(Debug, Clone, Default)]
 pub struct Properties(BTreeMap<String, String>);
 
@@ -35,3 +47,100 @@ impl DerefMut for Properties {
         &mut self.0
     }
 }
+
+impl<K, V> FromIterator<(K, V)> for Properties
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> Extend<(K, V)> for Properties
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0
+            .extend(iter.into_iter().map(|(key, value)| (key.into(), value.into())));
+    }
+}
+
+/// Creates [`Properties`](telemetry/struct.Properties.html) from key-value pairs. Values are converted
+/// with their `Display` implementation.
+///
+/// ```rust
+/// # use appinsights::properties;
+/// let properties = properties!["region" => "westeurope", "attempt" => 3];
+///
+/// assert_eq!(properties["attempt"], "3");
+/// ```
+#[macro_export]
+macro_rules! properties {
+    ( $( $key:expr => $value:expr ),* $(,)? ) => {
+        <$crate::telemetry::Properties as ::std::iter::FromIterator<(String, String)>>::from_iter([
+            $( (::std::string::String::from($key), ::std::string::ToString::to_string(&$value)) ),*
+        ])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{ContextTags, Measurements};
+
+    #[test]
+    fn it_creates_properties_with_macro() {
+        let properties = properties!["name" => "value", "number" => 1, "flag" => true,];
+
+        assert_eq!(
+            BTreeMap::from(properties),
+            BTreeMap::from([
+                ("flag".to_string(), "true".to_string()),
+                ("name".into(), "value".into()),
+                ("number".into(), "1".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_creates_empty_collections_with_macros() {
+        assert!(properties![].is_empty());
+        assert!(crate::context_tags![].is_empty());
+        assert!(crate::measurements![].is_empty());
+    }
+
+    #[test]
+    fn it_extends_collections() {
+        let mut properties: Properties = vec![("a", "1")].into_iter().collect();
+        properties.extend([("b".to_string(), "2".to_string())]);
+
+        let mut tags: ContextTags = vec![("ai.user.id", "user")].into_iter().collect();
+        tags.extend([("ai.session.id", "session")]);
+
+        let mut measurements: Measurements = vec![("size", 1.5)].into_iter().collect();
+        measurements.extend([("count".to_string(), 2.0)]);
+
+        assert_eq!(properties.len(), 2);
+        assert_eq!(tags.user().id(), Some("user"));
+        assert_eq!(tags.session().id(), Some("session"));
+        assert_eq!(measurements["count"], 2.0);
+    }
+
+    #[test]
+    fn it_creates_context_tags_and_measurements_with_macros() {
+        let tags = crate::context_tags!["ai.cloud.role" => "orders"];
+        let measurements = crate::measurements!["size" => 4096, "ratio" => 0.5];
+
+        assert_eq!(tags.cloud().role(), Some("orders"));
+        assert_eq!(measurements["size"], 4096.0);
+        assert_eq!(measurements["ratio"], 0.5);
+    }
+}
@@ -5,7 +5,7 @@ use chrono::{DateTime, SecondsFormat, Utc};
 use crate::{
     context::TelemetryContext,
     contracts::{Base, Data, Envelope, RemoteDependencyData},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
+    telemetry::{links, ContextTags, Measurements, Properties, SpanLink, Telemetry},
     time::{self, Duration},
 };
 
@@ -78,6 +78,26 @@ pub struct RemoteDependencyTelemetry {
 
     /// Custom measurements.
     measurements: Measurements,
+
+    /// Links to related operations.
+    links: Vec<SpanLink>,
+}
+
+/// Phases of establishing a connection and waiting for a response of an HTTP dependency call. An HTTP
+/// client that exposes connection events, e.g. a custom `hyper` connector, measures them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionTimings {
+    /// Time spent resolving a host name.
+    pub dns: Option<StdDuration>,
+
+    /// Time spent establishing a TCP connection.
+    pub connect: Option<StdDuration>,
+
+    /// Time spent on a TLS handshake.
+    pub tls: Option<StdDuration>,
+
+    /// Time from sending a request until the first byte of a response arrived.
+    pub ttfb: Option<StdDuration>,
 }
 
 impl RemoteDependencyTelemetry {
@@ -85,7 +105,7 @@ impl RemoteDependencyTelemetry {
     pub fn new(
         name: impl Into<String>,
         dependency_type: impl Into<String>,
-        duration: StdDuration,
+        duration: impl Into<Duration>,
         target: impl Into<String>,
         success: bool,
     ) -> Self {
@@ -102,6 +122,7 @@ impl RemoteDependencyTelemetry {
             properties: Properties::default(),
             tags: ContextTags::default(),
             measurements: Measurements::default(),
+            links: Vec::new(),
         }
     }
 
@@ -115,6 +136,67 @@ impl RemoteDependencyTelemetry {
         &mut self.measurements
     }
 
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
+
+    /// Returns a duration of the dependency call.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Sets a duration of the dependency call, e.g. `std::time::Duration` or `chrono::Duration`.
+    pub fn set_duration(&mut self, duration: impl Into<Duration>) {
+        self.duration = duration.into();
+    }
+
+    /// Works like [`set_duration`](#method.set_duration), but consumes and returns the item to construct it inline.
+    pub fn with_duration(mut self, duration: impl Into<Duration>) -> Self {
+        self.set_duration(duration);
+        self
+    }
+
+    /// Adds a breakdown of time an HTTP call spent before the response started as measurements `dns_ms`,
+    /// `connect_ms`, `tls_ms` and `ttfb_ms`, so slow name resolution or handshakes can be told apart from a
+    /// slow upstream. Phases that were not measured, e.g. DNS and TLS of a reused connection, are skipped.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::{ConnectionTimings, RemoteDependencyTelemetry};
+    /// use std::time::Duration;
+    ///
+    /// let mut dependency = RemoteDependencyTelemetry::new("GET /orders", "Http", Duration::from_millis(120), "example.com", true);
+    /// dependency.set_connection_timings(ConnectionTimings {
+    ///     dns: Some(Duration::from_millis(12)),
+    ///     ttfb: Some(Duration::from_millis(95)),
+    ///     ..ConnectionTimings::default()
+    /// });
+    ///
+    /// assert_eq!(dependency.measurements()["dns_ms"], 12.0);
+    /// assert_eq!(dependency.measurements().get("tls_ms"), None);
+    /// ```
+    pub fn set_connection_timings(&mut self, timings: ConnectionTimings) {
+        let phases = vec![
+            ("dns", timings.dns),
+            ("connect", timings.connect),
+            ("tls", timings.tls),
+            ("ttfb", timings.ttfb),
+        ];
+        for (name, duration) in phases {
+            if let Some(duration) = duration {
+                self.measurements.insert_duration(name, duration);
+            }
+        }
+    }
+
+    /// Works like [`set_connection_timings`](#method.set_connection_timings), but consumes and returns the item
+    /// to construct it inline.
+    pub fn with_connection_timings(mut self, timings: ConnectionTimings) -> Self {
+        self.set_connection_timings(timings);
+        self
+    }
+
     /// Sets the dependency id. Use this to link other telemetry to this dependency by setting their operation
     /// parent id to this id.
     ///
@@ -146,6 +228,79 @@ impl RemoteDependencyTelemetry {
     pub fn set_id(&mut self, id: impl Into<String>) {
         self.id = Some(id.into());
     }
+
+    /// Works like [`set_id`](#method.set_id), but consumes and returns the item to construct it inline.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.set_id(id);
+        self
+    }
+
+    /// Sets the command initiated by this dependency call, e.g. SQL statement or HTTP URL with all the
+    /// query parameters.
+    pub fn set_data(&mut self, data: impl Into<String>) {
+        self.data = Some(data.into());
+    }
+
+    /// Works like [`set_data`](#method.set_data), but consumes and returns the item to construct it inline.
+    pub fn with_data(mut self, data: impl Into<String>) -> Self {
+        self.set_data(data);
+        self
+    }
+
+    /// Sets the result code of a dependency call, e.g. HTTP status code or SQL error code.
+    pub fn set_result_code(&mut self, result_code: impl Into<String>) {
+        self.result_code = Some(result_code.into());
+    }
+
+    /// Works like [`set_result_code`](#method.set_result_code), but consumes and returns the item to construct it inline.
+    pub fn with_result_code(mut self, result_code: impl Into<String>) -> Self {
+        self.set_result_code(result_code);
+        self
+    }
+
+    /// Appends an application id of the called component to the target site, so Application Map can
+    /// draw an edge to that component. An application id is usually returned by the called component in
+    /// the `Request-Context` response header. See [`correlation`](../correlation/index.html) for details.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::RemoteDependencyTelemetry;
+    /// # use std::time::Duration;
+    /// let mut dependency = RemoteDependencyTelemetry::new(
+    ///     "GET /orders",
+    ///     "Http",
+    ///     Duration::from_millis(42),
+    ///     "api.example.com",
+    ///     true,
+    /// );
+    /// dependency.set_target_app_id("cid-v1:1234");
+    /// ```
+    pub fn set_target_app_id(&mut self, app_id: impl AsRef<str>) {
+        let target = self.target.split(" | ").next().unwrap_or_default();
+        self.target = format!("{} | {}", target, app_id.as_ref());
+    }
+
+    /// Works like [`set_target_app_id`](#method.set_target_app_id), but consumes and returns the item to construct it inline.
+    pub fn with_target_app_id(mut self, app_id: impl AsRef<str>) -> Self {
+        self.set_target_app_id(app_id);
+        self
+    }
+
+    /// Returns links to related operations.
+    pub fn links(&self) -> &[SpanLink] {
+        &self.links
+    }
+
+    /// Adds a link to a related operation, e.g. to an operation that produced one of many messages
+    /// processed together. Links are submitted in `_MS.links` property.
+    pub fn add_link(&mut self, link: SpanLink) {
+        self.links.push(link);
+    }
+
+    /// Works like [`add_link`](#method.add_link), but consumes and returns the item to construct it inline.
+    pub fn with_link(mut self, link: SpanLink) -> Self {
+        self.add_link(link);
+        self
+    }
 }
 
 impl Telemetry for RemoteDependencyTelemetry {
@@ -154,6 +309,11 @@ impl Telemetry for RemoteDependencyTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -177,6 +337,8 @@ impl Telemetry for RemoteDependencyTelemetry {
 
 impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, RemoteDependencyTelemetry)) -> Self {
+        let mut properties = Properties::combine(context.properties, telemetry.properties);
+        links::insert(&mut properties, &telemetry.links);
         Self {
             name: "Microsoft.ApplicationInsights.RemoteDependency".into(),
             time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
@@ -191,7 +353,7 @@ impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
                 data: telemetry.data,
                 target: Some(telemetry.target),
                 type_: Some(telemetry.dependency_type),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(properties.into()),
                 measurements: Some(telemetry.measurements.into()),
                 ..RemoteDependencyData::default()
             }))),
@@ -202,7 +364,7 @@ impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::{collections::BTreeMap, time::Duration as StdDuration};
 
     use chrono::TimeZone;
 
@@ -246,6 +408,42 @@ mod tests {
         assert_eq!(envelop, expected)
     }
 
+    #[test]
+    fn it_adds_measured_connection_timings() {
+        let telemetry =
+            RemoteDependencyTelemetry::new("GET /orders", "HTTP", StdDuration::from_secs(2), "example.com", true)
+                .with_connection_timings(ConnectionTimings {
+                    connect: Some(StdDuration::from_micros(2500)),
+                    tls: Some(StdDuration::from_millis(30)),
+                    ttfb: Some(StdDuration::from_millis(1900)),
+                    ..ConnectionTimings::default()
+                });
+
+        let measurements: BTreeMap<_, _> = telemetry.measurements.into();
+        assert_eq!(
+            measurements,
+            vec![
+                ("connect_ms".to_string(), 2.5),
+                ("tls_ms".to_string(), 30.0),
+                ("ttfb_ms".to_string(), 1900.0)
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn it_appends_target_app_id() {
+        let mut telemetry =
+            RemoteDependencyTelemetry::new("GET /orders", "HTTP", StdDuration::from_secs(2), "example.com", true);
+
+        telemetry.set_target_app_id("cid-v1:1234");
+        assert_eq!(telemetry.target, "example.com | cid-v1:1234");
+
+        telemetry.set_target_app_id("cid-v1:5678");
+        assert_eq!(telemetry.target, "example.com | cid-v1:5678");
+    }
+
     #[test]
     fn it_overrides_properties_from_context() {
         time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
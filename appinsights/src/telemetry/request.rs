@@ -1,4 +1,4 @@
-use std::{str::FromStr, time::Duration as StdDuration};
+use std::{fmt::Write, str::FromStr};
 
 use chrono::{DateTime, SecondsFormat, Utc};
 use http::{Method, StatusCode, Uri};
@@ -6,9 +6,8 @@ use http::{Method, StatusCode, Uri};
 use crate::{
     context::TelemetryContext,
     contracts::{Base, Data, Envelope, RequestData},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
+    telemetry::{links, ContextTags, Measurements, Properties, SpanLink, Telemetry},
     time::{self, Duration},
-    uuid,
 };
 
 /// Represents completion of an external request to the application and contains a summary of that
@@ -41,9 +40,14 @@ use crate::{
 #[derive(Debug)]
 pub struct RequestTelemetry {
     /// Identifier of a request call instance.
-    /// It is used for correlation between request and other telemetry items.
+    /// It is used for correlation between request and other telemetry items. A generated one has the
+    /// format of a W3C span id, e.g. `aff6326632aac566`.
     id: Option<String>,
 
+    /// Source of the request. For requests made by other instrumented components it is an
+    /// application id of the caller.
+    source: Option<String>,
+
     /// Request name. For HTTP requests it represents the HTTP method and URL path template.
     name: String,
 
@@ -56,6 +60,9 @@ pub struct RequestTelemetry {
     /// Results of a request execution. HTTP status code for HTTP requests.
     response_code: String,
 
+    /// Indication of successful or unsuccessful call that overrides the one derived from response code.
+    success: Option<bool>,
+
     /// The time stamp when this telemetry was measured.
     timestamp: DateTime<Utc>,
 
@@ -67,11 +74,14 @@ pub struct RequestTelemetry {
 
     /// Custom measurements.
     measurements: Measurements,
+
+    /// Links to related operations.
+    links: Vec<SpanLink>,
 }
 
 impl RequestTelemetry {
     /// Creates a new telemetry item for HTTP request.
-    pub fn new(method: Method, uri: Uri, duration: StdDuration, response_code: impl Into<String>) -> Self {
+    pub fn new(method: Method, uri: Uri, duration: impl Into<Duration>, response_code: impl Into<String>) -> Self {
         let mut authority = String::new();
         if let Some(host) = &uri.host() {
             authority.push_str(host);
@@ -94,14 +104,17 @@ impl RequestTelemetry {
 
         Self {
             id: Option::default(),
+            source: Option::default(),
             name,
             uri,
             duration: duration.into(),
             response_code: response_code.into(),
+            success: Option::default(),
             timestamp: time::now(),
             properties: Properties::default(),
             tags,
             measurements: Measurements::default(),
+            links: Vec::new(),
         }
     }
 
@@ -115,9 +128,39 @@ impl RequestTelemetry {
         &mut self.measurements
     }
 
+    /// Adds a duration spent in a phase of serving the request as a `phase_<name>_ms` measurement.
+    /// Durations of the same phase sum up, e.g. all database calls of the request.
+    pub fn add_phase(&mut self, phase: &RequestPhase, duration: impl Into<Duration>) {
+        self.measurements.add_duration(phase.measurement_name(), duration);
+    }
+
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
+
+    /// Returns a duration of serving the request.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Sets a duration of serving the request, e.g. `std::time::Duration` or `chrono::Duration`.
+    pub fn set_duration(&mut self, duration: impl Into<Duration>) {
+        self.duration = duration.into();
+    }
+
+    /// Works like [`set_duration`](#method.set_duration), but consumes and returns the item to construct it inline.
+    pub fn with_duration(mut self, duration: impl Into<Duration>) -> Self {
+        self.set_duration(duration);
+        self
+    }
+
     /// Returns an indication of successful or unsuccessful call.
     pub fn is_success(&self) -> bool {
-        if let Ok(response_code) = StatusCode::from_str(&self.response_code) {
+        if let Some(success) = self.success {
+            success
+        } else if let Ok(response_code) = StatusCode::from_str(&self.response_code) {
             response_code < StatusCode::BAD_REQUEST || response_code == StatusCode::UNAUTHORIZED
         } else {
             true
@@ -157,6 +200,142 @@ impl RequestTelemetry {
     pub fn set_id(&mut self, id: impl Into<String>) {
         self.id = Some(id.into());
     }
+
+    /// Works like [`set_id`](#method.set_id), but consumes and returns the item to construct it inline.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.set_id(id);
+        self
+    }
+
+    /// Sets the source of the request, so Application Map can draw an edge from a calling component.
+    /// For requests made by other instrumented components it is an application id of the caller, which
+    /// is usually sent in the `Request-Context` request header. See [`correlation`](../correlation/index.html)
+    /// for details.
+    ///
+    /// ```rust
+    /// # use appinsights::{correlation, telemetry::RequestTelemetry};
+    /// # use http::Method;
+    /// # use std::time::Duration;
+    /// let mut request = RequestTelemetry::new(
+    ///     Method::GET,
+    ///     "https://api.example.com/orders".parse().unwrap(),
+    ///     Duration::from_millis(42),
+    ///     "200",
+    /// );
+    /// if let Some(app_id) = correlation::app_id_from_request_context("appId=cid-v1:1234") {
+    ///     request.set_source(app_id);
+    /// }
+    /// ```
+    pub fn set_source(&mut self, source: impl Into<String>) {
+        self.source = Some(source.into());
+    }
+
+    /// Works like [`set_source`](#method.set_source), but consumes and returns the item to construct it inline.
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.set_source(source);
+        self
+    }
+
+    /// Sets the request name and the operation name, e.g. for requests that are not HTTP requests,
+    /// like processing of a queue message.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+        self.tags.operation_mut().set_name(self.name.clone());
+    }
+
+    /// Works like [`set_name`](#method.set_name), but consumes and returns the item to construct it inline.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    /// Sets an indication of successful or unsuccessful call instead of deriving it from the response code.
+    pub fn set_success(&mut self, success: bool) {
+        self.success = Some(success);
+    }
+
+    /// Works like [`set_success`](#method.set_success), but consumes and returns the item to construct it inline.
+    pub fn with_success(mut self, success: bool) -> Self {
+        self.set_success(success);
+        self
+    }
+
+    /// Returns links to related operations.
+    pub fn links(&self) -> &[SpanLink] {
+        &self.links
+    }
+
+    /// Adds a link to a related operation, e.g. to an operation that produced one of many messages
+    /// processed together. Links are submitted in `_MS.links` property.
+    pub fn add_link(&mut self, link: SpanLink) {
+        self.links.push(link);
+    }
+
+    /// Works like [`add_link`](#method.add_link), but consumes and returns the item to construct it inline.
+    pub fn with_link(mut self, link: SpanLink) -> Self {
+        self.add_link(link);
+        self
+    }
+}
+
+/// Returns a relative URL of a request named after an operation, e.g. a command or a function, with every
+/// character other than unreserved ones percent-encoded, so a name with spaces or slashes keeps its own URL.
+/// A long name is truncated, so the path fits the maximum length of a request URL.
+pub(crate) fn path_of(name: &str) -> Uri {
+    let mut path = String::from("/");
+    for byte in name.bytes() {
+        let unreserved = matches!(byte, b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~');
+        if path.len() + if unreserved { 1 } else { 3 } > MAX_PATH_LENGTH {
+            break;
+        }
+        if unreserved {
+            path.push(byte as char);
+        } else {
+            let _ = write!(path, "%{:02X}", byte);
+        }
+    }
+    path.parse().expect("percent-encoded path is a valid URI")
+}
+
+/// The maximum length of a request URL the ingestion endpoint accepts.
+const MAX_PATH_LENGTH: usize = 2048;
+
+/// A phase of serving a request timed separately, so Workbooks can break request durations down by
+/// phases. Each phase is submitted as a measurement with a standard name, e.g. `phase_routing_ms`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestPhase {
+    /// Matching a request to a handler, `phase_routing_ms`.
+    Routing,
+
+    /// Running a handler, `phase_handler_ms`.
+    Handler,
+
+    /// Serializing a response body, `phase_serialization_ms`.
+    Serialization,
+
+    /// All database calls of the request, `phase_db_ms`.
+    Database,
+
+    /// A phase specific to the application, `phase_<name>_ms`.
+    Custom(String),
+}
+
+impl RequestPhase {
+    /// Returns a name of the phase.
+    pub fn name(&self) -> &str {
+        match self {
+            RequestPhase::Routing => "routing",
+            RequestPhase::Handler => "handler",
+            RequestPhase::Serialization => "serialization",
+            RequestPhase::Database => "db",
+            RequestPhase::Custom(name) => name,
+        }
+    }
+
+    /// Returns a name of a measurement of the phase without a unit suffix, e.g. `phase_routing`.
+    pub(crate) fn measurement_name(&self) -> String {
+        format!("phase_{}", self.name())
+    }
 }
 
 impl Telemetry for RequestTelemetry {
@@ -165,6 +344,11 @@ impl Telemetry for RequestTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -189,19 +373,23 @@ impl Telemetry for RequestTelemetry {
 impl From<(TelemetryContext, RequestTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, RequestTelemetry)) -> Self {
         let success = telemetry.is_success();
+        let ids = context.ids;
+        let mut properties = Properties::combine(context.properties, telemetry.properties);
+        links::insert(&mut properties, &telemetry.links);
         Self {
             name: "Microsoft.ApplicationInsights.Request".into(),
             time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
             i_key: Some(context.i_key),
             tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
             data: Some(Base::Data(Data::RequestData(RequestData {
-                id: telemetry.id.unwrap_or_else(|| uuid::new().as_hyphenated().to_string()),
+                id: telemetry.id.unwrap_or_else(|| ids.new_request_id()),
+                source: telemetry.source,
                 name: Some(telemetry.name),
                 duration: telemetry.duration.to_string(),
                 response_code: telemetry.response_code,
                 success,
                 url: Some(telemetry.uri.to_string()),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(properties.into()),
                 measurements: Some(telemetry.measurements.into()),
                 ..RequestData::default()
             }))),
@@ -212,10 +400,10 @@ impl From<(TelemetryContext, RequestTelemetry)> for Envelope {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
-    use std::str::FromStr;
+    use std::{collections::BTreeMap, str::FromStr, time::Duration as StdDuration};
 
     use chrono::TimeZone;
+    use test_case::test_case;
 
     use super::*;
     use crate::uuid::{self, Uuid};
@@ -263,6 +451,81 @@ mod tests {
         assert_eq!(envelop, expected)
     }
 
+    #[test]
+    fn it_submits_source() {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let mut telemetry = RequestTelemetry::new(
+            Method::GET,
+            "https://example.com/main.html".parse().unwrap(),
+            StdDuration::from_secs(2),
+            "200",
+        );
+        telemetry.set_source("cid-v1:1234");
+
+        let envelop = Envelope::from((context, telemetry));
+
+        match envelop.data {
+            Some(Base::Data(Data::RequestData(data))) => assert_eq!(data.source, Some("cid-v1:1234".into())),
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_submits_specified_name_and_success() {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let mut telemetry = RequestTelemetry::new(
+            Method::GET,
+            "kafka://broker:9092/orders".parse().unwrap(),
+            StdDuration::from_secs(2),
+            "0",
+        );
+        telemetry.set_name("Process orders");
+        telemetry.set_success(false);
+
+        let envelop = Envelope::from((context, telemetry));
+
+        assert_eq!(
+            envelop.tags.unwrap().get("ai.operation.name"),
+            Some(&"Process orders".to_string())
+        );
+        match envelop.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("Process orders".into()));
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_constructs_telemetry_inline() {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let telemetry = RequestTelemetry::new(
+            Method::GET,
+            "https://example.com/orders/42".parse().unwrap(),
+            StdDuration::from_secs(2),
+            "200",
+        )
+        .with_name("GET /orders/{id}")
+        .with_duration(chrono::Duration::milliseconds(1500))
+        .with_property("order", "42")
+        .with_measurement("items", 3.0)
+        .with_tag("ai.user.id", "user");
+
+        let envelop = Envelope::from((context, telemetry));
+
+        assert_eq!(envelop.tags.unwrap().get("ai.user.id"), Some(&"user".to_string()));
+        match envelop.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /orders/{id}".into()));
+                assert_eq!(data.duration, "0.00:00:01.5000000");
+                assert_eq!(data.properties.unwrap()["order"], "42");
+                assert_eq!(data.measurements.unwrap()["items"], 3.0);
+            }
+            data => panic!("unexpected data {:?}", data),
+        }
+    }
+
     #[test]
     fn it_overrides_properties_from_context() {
         time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
@@ -294,7 +557,7 @@ mod tests {
                 tags
             }),
             data: Some(Base::Data(Data::RequestData(RequestData {
-                id: "910b414a-f368-4b3a-aff6-326632aac566".into(),
+                id: "aff6326632aac566".into(),
                 name: Some("GET https://example.com/main.html".into()),
                 duration: "0.00:00:02.0000000".into(),
                 response_code: "200".into(),
@@ -350,7 +613,7 @@ mod tests {
                 tags
             }),
             data: Some(Base::Data(Data::RequestData(RequestData {
-                id: "910b414a-f368-4b3a-aff6-326632aac566".into(),
+                id: "aff6326632aac566".into(),
                 name: Some("GET https://example.com/main.html".into()),
                 duration: "0.00:00:02.0000000".into(),
                 response_code: "200".into(),
@@ -365,4 +628,22 @@ mod tests {
 
         assert_eq!(envelop, expected)
     }
+
+    #[test_case("deploy",       "/deploy"          ; "plain")]
+    #[test_case("db migrate",   "/db%20migrate"    ; "space")]
+    #[test_case("jobs/cleanup", "/jobs%2Fcleanup"  ; "slash")]
+    #[test_case("résumé",       "/r%C3%A9sum%C3%A9" ; "non-ascii")]
+    #[test_case("",             "/"                ; "empty")]
+    fn it_encodes_operation_name_into_path(name: &str, expected: &str) {
+        assert_eq!(path_of(name), expected);
+    }
+
+    #[test_case("a" ; "unreserved")]
+    #[test_case("/" ; "encoded")]
+    fn it_truncates_long_operation_name(character: &str) {
+        let path = path_of(&character.repeat(70_000));
+
+        assert!(path.to_string().len() <= 2048);
+        assert!(path.to_string().len() > 2045);
+    }
 }
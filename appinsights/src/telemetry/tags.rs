@@ -1,9 +1,13 @@
 use std::{
     collections::BTreeMap,
+    iter::FromIterator,
     ops::{Deref, DerefMut},
 };
 
 /// Contains all tags for telemetry to submit.
+///
+/// Tags can be collected from an iterator of key-value pairs or created with
+/// [`context_tags!`](../macro.context_tags.html) macro.
 #[derive(Debug, Clone, Default)]
 pub struct ContextTags(BTreeMap<String, String>);
 
@@ -36,6 +40,49 @@ impl DerefMut for ContextTags {
     }
 }
 
+impl<K, V> FromIterator<(K, V)> for ContextTags
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> Extend<(K, V)> for ContextTags
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0
+            .extend(iter.into_iter().map(|(key, value)| (key.into(), value.into())));
+    }
+}
+
+/// Creates [`ContextTags`](telemetry/struct.ContextTags.html) from key-value pairs. Values are converted
+/// with their `Display` implementation.
+///
+/// ```rust
+/// # use appinsights::context_tags;
+/// let tags = context_tags!["ai.cloud.role" => "orders", "ai.user.id" => 42];
+///
+/// assert_eq!(tags.cloud().role(), Some("orders"));
+/// ```
+#[macro_export]
+macro_rules! context_tags {
+    ( $( $key:expr => $value:expr ),* $(,)? ) => {
+        <$crate::telemetry::ContextTags as ::std::iter::FromIterator<(String, String)>>::from_iter([
+            $( (::std::string::String::from($key), ::std::string::ToString::to_string(&$value)) ),*
+        ])
+    };
+}
+
 /// Macros to generate well-known context tags.
 #[macro_export]
 macro_rules! tags {
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use chrono::{DateTime, SecondsFormat, Utc};
 
 use crate::{
@@ -61,6 +63,36 @@ impl TraceTelemetry {
         }
     }
 
+    /// Creates a trace telemetry item from a message template with named placeholders, like
+    /// `"user {user_id} failed"`, and values of named arguments. Placeholders are replaced with argument
+    /// values to produce a message and every argument becomes a custom property, so traces can be
+    /// filtered by structured fields instead of parsing messages. The original template is kept in the
+    /// `{OriginalFormat}` property. Use `{{` and `}}` to put literal braces in a message.
+    ///
+    /// The [`trace!`](../macro.trace.html) macro provides more convenient syntax to create structured trace.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::{SeverityLevel, Telemetry, TraceTelemetry};
+    /// let telemetry = TraceTelemetry::from_template(
+    ///     "user {user_id} failed to login",
+    ///     SeverityLevel::Warning,
+    ///     &[("user_id", &42)],
+    /// );
+    ///
+    /// assert_eq!(telemetry.properties().get("user_id"), Some(&"42".to_string()));
+    /// ```
+    pub fn from_template(template: impl Into<String>, severity: SeverityLevel, args: &[(&str, &dyn Display)]) -> Self {
+        let template = template.into();
+
+        let mut telemetry = Self::new(render(&template, args), severity);
+        for (name, value) in args {
+            telemetry.properties.insert((*name).into(), value.to_string());
+        }
+        telemetry.properties.insert(ORIGINAL_FORMAT.into(), template);
+
+        telemetry
+    }
+
     /// Returns custom measurements to submit with the telemetry item.
     pub fn measurements(&self) -> &Measurements {
         &self.measurements
@@ -70,6 +102,71 @@ impl TraceTelemetry {
     pub fn measurements_mut(&mut self) -> &mut Measurements {
         &mut self.measurements
     }
+
+    /// Adds a custom measurement and returns the item, so it can be constructed inline.
+    pub fn with_measurement(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.measurements.insert(key.into(), value);
+        self
+    }
+}
+
+/// Name of a property that contains a message template of a structured trace.
+const ORIGINAL_FORMAT: &str = "{OriginalFormat}";
+
+/// Replaces named placeholders of a template with argument values. Unknown placeholders are left as is.
+fn render(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::with_capacity(template.len());
+
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        message.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            message.push_str(&rest[..1]);
+            rest = &rest[2..];
+        } else if let Some(end) = rest.find('}').filter(|_| rest.starts_with('{')) {
+            let name = &rest[1..end];
+            match args.iter().find(|(arg, _)| *arg == name) {
+                Some((_, value)) => message.push_str(&value.to_string()),
+                None => message.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        } else {
+            message.push_str(&rest[..1]);
+            rest = &rest[1..];
+        }
+    }
+    message.push_str(rest);
+
+    message
+}
+
+/// Creates a structured [`TraceTelemetry`](telemetry/struct.TraceTelemetry.html) from a severity level,
+/// a message template and named arguments. Every argument replaces a placeholder with the same name in
+/// the template and becomes a custom property of the telemetry item.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::SeverityLevel;
+///
+/// let user_id = 42;
+/// let telemetry = appinsights::trace!(SeverityLevel::Warning, "user {user_id} failed to login", user_id = user_id);
+///
+/// client.track(telemetry);
+/// ```
+#[macro_export]
+macro_rules! trace {
+    ($severity:expr, $template:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::telemetry::TraceTelemetry::from_template(
+            $template,
+            $severity,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
 }
 
 impl Telemetry for TraceTelemetry {
@@ -78,6 +175,11 @@ impl Telemetry for TraceTelemetry {
         self.timestamp
     }
 
+    /// Sets the time when this telemetry was measured.
+    fn set_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+    }
+
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties {
         &self.properties
@@ -119,7 +221,7 @@ impl From<(TelemetryContext, TraceTelemetry)> for Envelope {
 }
 
 /// Defines the level of severity for the event.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SeverityLevel {
     /// Verbose severity level.
     Verbose,
@@ -154,14 +256,37 @@ mod tests {
     use std::collections::BTreeMap;
 
     use chrono::{TimeZone, Utc};
+    use test_case::test_case;
 
-    use super::{SeverityLevel, TraceTelemetry};
+    use super::{render, SeverityLevel, TraceTelemetry};
     use crate::{
         contracts::{Base, Data, Envelope, MessageData},
         telemetry::{ContextTags, Properties, Telemetry},
         time, TelemetryContext,
     };
 
+    #[test_case("user {user_id} failed",          "user 42 failed"         ; "placeholder")]
+    #[test_case("{user_id}{status}",              "42denied"               ; "adjacent placeholders")]
+    #[test_case("user {unknown} failed",          "user {unknown} failed"  ; "unknown placeholder")]
+    #[test_case("{{user_id}} is {user_id}",       "{user_id} is 42"        ; "escaped braces")]
+    #[test_case("unbalanced { and } braces {",    "unbalanced { and } braces {" ; "unbalanced braces")]
+    fn it_renders_message_template(template: &str, expected: &str) {
+        assert_eq!(render(template, &[("user_id", &42), ("status", &"denied")]), expected);
+    }
+
+    #[test]
+    fn it_creates_structured_trace_with_macro() {
+        let user_id = 42;
+        let telemetry = crate::trace!(SeverityLevel::Warning, "user {user_id} failed", user_id = user_id);
+
+        assert_eq!(telemetry.message, "user 42 failed");
+        assert_eq!(telemetry.properties().get("user_id"), Some(&"42".to_string()));
+        assert_eq!(
+            telemetry.properties().get("{OriginalFormat}"),
+            Some(&"user {user_id} failed".to_string())
+        );
+    }
+
     #[test]
     fn it_overrides_properties_from_context() {
         time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
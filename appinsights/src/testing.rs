@@ -0,0 +1,163 @@
+//! Hooks that make produced telemetry deterministic in tests of an application.
+//!
+//! A [`Clock`](trait.Clock.html) and an [`IdGenerator`](trait.IdGenerator.html) configured with
+//! [`TelemetryConfig`](../struct.TelemetryConfig.html) replace the system clock and random ids of a
+//! client created with it, so tests can assert on whole envelopes. The clock stamps every item the client
+//! tracks, replacing a timestamp the item was created with, while the generator makes ids of operations,
+//! requests, spans and page views the client and contexts derived from its context generate. Hooks
+//! belong to the client, so tests with different hooks can run concurrently.
+//!
+//! ```rust, no_run
+//! # use appinsights::{testing::SequentialIds, TelemetryClient, TelemetryConfig};
+//! use chrono::{TimeZone, Utc};
+//!
+//! let now = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .clock(move || now)
+//!     .id_generator(SequentialIds::default())
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//! ```
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::telemetry::Telemetry;
+
+/// A source of current time of telemetry items.
+pub trait Clock: Send + Sync {
+    /// Returns current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> DateTime<Utc> + Send + Sync,
+{
+    fn now(&self) -> DateTime<Utc> {
+        self()
+    }
+}
+
+/// A source of ids of operations, requests, spans and other telemetry items.
+pub trait IdGenerator: Send + Sync {
+    /// Returns a new id.
+    fn generate(&self) -> Uuid;
+}
+
+impl<F> IdGenerator for F
+where
+    F: Fn() -> Uuid + Send + Sync,
+{
+    fn generate(&self) -> Uuid {
+        self()
+    }
+}
+
+/// Generates ids from a counter, i.e. `00000000-0000-0000-0000-000000000001`, then `...0002` and so on.
+#[derive(Debug, Default)]
+pub struct SequentialIds {
+    last: AtomicU64,
+}
+
+impl IdGenerator for SequentialIds {
+    fn generate(&self) -> Uuid {
+        Uuid::from_u128(u128::from(self.last.fetch_add(1, Ordering::Relaxed) + 1))
+    }
+}
+
+/// A clock and an id generator configured to replace the system ones.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    clock: Option<Arc<dyn Clock>>,
+    ids: Option<Arc<dyn IdGenerator>>,
+}
+
+impl Hooks {
+    pub(crate) fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Some(Arc::new(clock));
+    }
+
+    pub(crate) fn set_ids(&mut self, ids: impl IdGenerator + 'static) {
+        self.ids = Some(Arc::new(ids));
+    }
+
+    /// Returns a configured id generator.
+    pub(crate) fn ids(&self) -> Option<Arc<dyn IdGenerator>> {
+        self.ids.clone()
+    }
+
+    /// Stamps a telemetry item with current time of a configured clock.
+    pub(crate) fn stamp(&self, telemetry: &mut dyn Telemetry) {
+        if let Some(clock) = &self.clock {
+            telemetry.set_timestamp(clock.now());
+        }
+    }
+}
+
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("clock", &self.clock.is_some())
+            .field("ids", &self.ids.is_some())
+            .finish()
+    }
+}
+
+impl PartialEq for Hooks {
+    fn eq(&self, other: &Self) -> bool {
+        fn same<T: ?Sized>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        same(&self.clock, &other.clock) && same(&self.ids, &other.ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::telemetry::EventTelemetry;
+
+    #[test]
+    fn it_generates_sequential_ids() {
+        let ids = SequentialIds::default();
+
+        assert_eq!(ids.generate().to_string(), "00000000-0000-0000-0000-000000000001");
+        assert_eq!(ids.generate().to_string(), "00000000-0000-0000-0000-000000000002");
+    }
+
+    #[test]
+    fn it_stamps_telemetry_with_configured_clock() {
+        let now = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let mut hooks = Hooks::default();
+        hooks.set_clock(move || now);
+
+        let mut telemetry = EventTelemetry::new("event");
+        hooks.stamp(&mut telemetry);
+
+        assert_eq!(telemetry.timestamp(), now);
+    }
+
+    #[test]
+    fn it_compares_hooks_by_identity() {
+        let mut hooks = Hooks::default();
+        hooks.set_ids(SequentialIds::default());
+
+        assert_eq!(hooks, hooks.clone());
+        assert_ne!(hooks, Hooks::default());
+    }
+}
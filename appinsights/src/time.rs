@@ -1,47 +1,127 @@
-pub use imp::*;
+//! Durations formatted the way Application Insights expects them and a stopwatch to measure them with.
+pub(crate) use imp::*;
 
 use std::{
+    error::Error,
     fmt::{Display, Formatter},
-    ops::Deref,
-    time::Duration as StdDuration,
+    iter::Sum,
+    ops::{Add, AddAssign, Deref, Div, Mul, Sub, SubAssign},
+    str::FromStr,
+    time::{Duration as StdDuration, Instant},
 };
 
+use chrono::{DateTime, Utc};
+
 #[cfg(not(test))]
 mod imp {
+    use std::time::Instant;
+
     use chrono::{DateTime, Utc};
 
     /// Returns a DateTime which corresponds to a current date.
-    pub fn now() -> DateTime<Utc> {
+    pub(crate) fn now() -> DateTime<Utc> {
         Utc::now()
     }
+
+    /// Returns a current instant of a monotonic clock durations and intervals are measured with.
+    pub(crate) fn instant() -> Instant {
+        Instant::now()
+    }
 }
 
 #[cfg(test)]
 mod imp {
-    use std::cell::RefCell;
+    use std::{cell::RefCell, time::Duration, time::Instant};
 
     use chrono::{DateTime, Utc};
 
-    thread_local!(static NOW: RefCell<Option<DateTime<Utc>>> = RefCell::new(None));
+    thread_local!(static NOW: RefCell<Option<DateTime<Utc>>> = const { RefCell::new(None) });
+    thread_local!(static ORIGIN: Instant = Instant::now());
 
     /// Returns a DateTime which corresponds to a current date or the value user set in advance.
-    pub fn now() -> DateTime<Utc> {
+    pub(crate) fn now() -> DateTime<Utc> {
         NOW.with(|ts| if let Some(now) = *ts.borrow() { now } else { Utc::now() })
     }
 
+    /// Returns a current instant of a monotonic clock, or an instant that advances along with the value
+    /// user set in advance, so intervals can be asserted against it.
+    pub(crate) fn instant() -> Instant {
+        match NOW.with(|ts| *ts.borrow()) {
+            Some(now) => ORIGIN.with(|origin| *origin + Duration::from_millis(now.timestamp_millis().max(0) as u64)),
+            None => Instant::now(),
+        }
+    }
+
     /// Sets known DateTime value as now to assert test against it.
-    pub fn set(now: DateTime<Utc>) {
+    pub(crate) fn set(now: DateTime<Utc>) {
         NOW.with(|ts| *ts.borrow_mut() = Some(now))
     }
 
     /// Resets pre-defined DateTime value to use Utc::now() instead.
-    pub fn reset() {
+    #[allow(dead_code)]
+    pub(crate) fn reset() {
         NOW.with(|ts| *ts.borrow_mut() = None)
     }
 }
 
-/// Provides dotnet duration aware formatting rules.
-#[derive(Debug)]
+/// Measures how long an operation takes with a monotonic clock and remembers when it started by the wall
+/// clock. Telemetry items are stamped with the wall-clock time, while their durations come from the
+/// monotonic clock, so adjustments of the system time, e.g. by NTP, can't make durations negative or
+/// absurdly long. Built-in middleware, dependency helpers and availability tests measure this way.
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// use appinsights::{
+///     telemetry::{RemoteDependencyTelemetry, Telemetry},
+///     time::Stopwatch,
+/// };
+///
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// let stopwatch = Stopwatch::start();
+/// // call a dependency
+/// client.track(
+///     RemoteDependencyTelemetry::new("GET /items", "HTTP", stopwatch.elapsed(), "api.example.com", true)
+///         .with_timestamp(stopwatch.started_at()),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch {
+    started_at: DateTime<Utc>,
+    started: Instant,
+}
+
+impl Stopwatch {
+    /// Starts measuring an operation at the current time.
+    pub fn start() -> Self {
+        Self {
+            started_at: now(),
+            started: instant(),
+        }
+    }
+
+    /// Returns the wall-clock time the operation started at, a timestamp of its telemetry.
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// Returns time elapsed since the operation started by the monotonic clock.
+    pub fn elapsed(&self) -> StdDuration {
+        instant().saturating_duration_since(self.started)
+    }
+}
+
+/// A non-negative span of time with formatting rules of .NET `TimeSpan`, i.e. `d.hh:mm:ss.fffffff`, which
+/// Application Insights expects durations in. It converts from and to `std::time::Duration` and
+/// `chrono::Duration`, supports arithmetic, and parses back from its string representation.
+///
+/// ```rust
+/// use appinsights::time::Duration;
+///
+/// let duration = Duration::from(std::time::Duration::from_millis(1500)) + chrono::Duration::minutes(1).into();
+/// assert_eq!(duration.to_string(), "0.00:01:01.5000000");
+/// assert_eq!("0.00:01:01.5000000".parse::<Duration>(), Ok(duration));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Duration(StdDuration);
 
 impl From<StdDuration> for Duration {
@@ -50,6 +130,76 @@ impl From<StdDuration> for Duration {
     }
 }
 
+impl From<Duration> for StdDuration {
+    fn from(duration: Duration) -> Self {
+        duration.0
+    }
+}
+
+/// Converts a `chrono::Duration` to a duration. A negative duration is converted to zero.
+impl From<chrono::Duration> for Duration {
+    fn from(duration: chrono::Duration) -> Self {
+        Duration(duration.to_std().unwrap_or_default())
+    }
+}
+
+impl From<Duration> for chrono::Duration {
+    fn from(duration: Duration) -> Self {
+        chrono::Duration::from_std(duration.0).unwrap_or_else(|_| chrono::Duration::max_value())
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Duration(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Duration) {
+        self.0 += rhs.0;
+    }
+}
+
+/// Subtracts a duration. The result is zero when a subtracted duration is longer.
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Duration(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<u32> for Duration {
+    type Output = Duration;
+
+    fn mul(self, rhs: u32) -> Self::Output {
+        Duration(self.0 * rhs)
+    }
+}
+
+impl Div<u32> for Duration {
+    type Output = Duration;
+
+    fn div(self, rhs: u32) -> Self::Output {
+        Duration(self.0 / rhs)
+    }
+}
+
+impl Sum for Duration {
+    fn sum<I: Iterator<Item = Duration>>(iter: I) -> Self {
+        iter.fold(Duration::default(), Add::add)
+    }
+}
+
 impl Display for Duration {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let nanoseconds = self.0.as_nanos();
@@ -68,6 +218,66 @@ impl Display for Duration {
     }
 }
 
+/// Parses a duration formatted as `[d.]hh:mm:ss[.fffffff]`.
+impl FromStr for Duration {
+    type Err = ParseDurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (days, time) = match s.split_once(':') {
+            Some((head, _)) if head.contains('.') => s.split_once('.').ok_or(ParseDurationError)?,
+            _ => ("0", s),
+        };
+        let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+
+        let mut parts = time.split(':');
+        let (hours, minutes, seconds) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(hours), Some(minutes), Some(seconds), None) => (hours, minutes, seconds),
+            _ => return Err(ParseDurationError),
+        };
+
+        let days = parse_number(days, u64::MAX)?;
+        let hours = parse_number(hours, 23)?;
+        let minutes = parse_number(minutes, 59)?;
+        let seconds = parse_number(seconds, 59)?;
+        if fraction.len() > 7 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseDurationError);
+        }
+        let ticks = format!("{:0<7}", fraction)
+            .parse::<u32>()
+            .map_err(|_| ParseDurationError)?;
+
+        let total_seconds = days
+            .checked_mul(86400)
+            .and_then(|seconds| seconds.checked_add(hours * 3600 + minutes * 60))
+            .and_then(|total| total.checked_add(seconds))
+            .ok_or(ParseDurationError)?;
+        Ok(Duration(StdDuration::new(total_seconds, ticks * 100)))
+    }
+}
+
+fn parse_number(value: &str, max: u64) -> Result<u64, ParseDurationError> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseDurationError);
+    }
+    value
+        .parse()
+        .ok()
+        .filter(|number| *number <= max)
+        .ok_or(ParseDurationError)
+}
+
+/// An error returned when a string is not a valid duration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDurationError;
+
+impl Display for ParseDurationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid duration, expected d.hh:mm:ss.fffffff")
+    }
+}
+
+impl Error for ParseDurationError {}
+
 impl Deref for Duration {
     type Target = StdDuration;
 
@@ -92,4 +302,65 @@ mod tests {
     fn it_converts_duration_to_string(duration: Duration, expected: &'static str) {
         assert_eq!(duration.to_string(), expected.to_string());
     }
+
+    #[test_case("0.01:00:00.0000000", StdDuration::from_secs(3600)        ; "hour")]
+    #[test_case("2.01:02:03.0010000", StdDuration::new(176_523, 1_000_000) ; "days")]
+    #[test_case("00:00:01.5",         StdDuration::from_millis(1500)      ; "without days")]
+    #[test_case("0.00:00:01",         StdDuration::from_secs(1)           ; "without fraction")]
+    fn it_parses_duration(value: &str, expected: StdDuration) {
+        assert_eq!(value.parse::<Duration>(), Ok(expected.into()));
+    }
+
+    #[test_case(""                     ; "empty")]
+    #[test_case("1:00"                 ; "without seconds")]
+    #[test_case("0.24:00:00"           ; "hours overflow")]
+    #[test_case("0.00:00:00.12345678"  ; "fraction too long")]
+    #[test_case("-1.00:00:00"          ; "negative")]
+    fn it_rejects_invalid_duration(value: &str) {
+        assert_eq!(value.parse::<Duration>(), Err(ParseDurationError));
+    }
+
+    #[test_case(StdDuration::from_nanos(100)        ; "tick")]
+    #[test_case(StdDuration::new(90061, 1_234_500) ; "all components")]
+    fn it_round_trips_duration_string(duration: StdDuration) {
+        let duration = Duration::from(duration);
+        assert_eq!(duration.to_string().parse::<Duration>(), Ok(duration));
+    }
+
+    #[test]
+    fn it_converts_chrono_duration() {
+        assert_eq!(
+            Duration::from(chrono::Duration::seconds(2)),
+            StdDuration::from_secs(2).into()
+        );
+        assert_eq!(Duration::from(chrono::Duration::seconds(-2)), Duration::default());
+        assert_eq!(
+            chrono::Duration::from(Duration::from(StdDuration::from_secs(2))),
+            chrono::Duration::seconds(2)
+        );
+    }
+
+    #[test]
+    fn it_computes_durations() {
+        let second = Duration::from(StdDuration::from_secs(1));
+
+        assert_eq!(second + second, second * 2);
+        assert_eq!(second - second * 2, Duration::default());
+        assert_eq!((second * 3) / 2, StdDuration::from_millis(1500).into());
+        assert_eq!([second, second].iter().copied().sum::<Duration>(), second * 2);
+    }
+
+    #[test]
+    fn it_measures_elapsed_time_with_stopwatch() {
+        set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 0));
+        let stopwatch = Stopwatch::start();
+
+        set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 30));
+        assert_eq!(stopwatch.elapsed(), StdDuration::from_secs(30));
+        assert_eq!(stopwatch.started_at(), Utc.ymd(2019, 1, 2).and_hms(3, 4, 0));
+
+        set(Utc.ymd(2019, 1, 2).and_hms(3, 3, 0));
+        assert_eq!(stopwatch.elapsed(), StdDuration::ZERO);
+        reset();
+    }
 }
@@ -0,0 +1,293 @@
+//! Integration with [`tracing`](https://docs.rs/tracing) ecosystem.
+//!
+//! [`TelemetryLayer`](struct.TelemetryLayer.html) converts every event recorded via
+//! [`tracing`](https://docs.rs/tracing) macros into a telemetry item and submits it with the
+//! telemetry client. Events are reported as [`TraceTelemetry`](../telemetry/struct.TraceTelemetry.html)
+//! items unless they carry an `error` field with a value recorded as `&dyn Error`. Such events
+//! are reported as [`ExceptionTelemetry`](../telemetry/struct.ExceptionTelemetry.html) with the
+//! whole chain of errors and a backtrace, so they show up under Failures in Azure Portal.
+//!
+//! Failures ignore traces, so error-level events of chosen targets can be
+//! [promoted](struct.TelemetryLayer.html#method.promote_errors) to exceptions even without an error
+//! value. The exception type is synthesized from the target, so Failures group such errors per module.
+//!
+//! ```rust, no_run
+//! use appinsights::{tracing::TelemetryLayer, TelemetryClient};
+//! use tracing_subscriber::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! tracing_subscriber::registry().with(TelemetryLayer::new(client)).init();
+//!
+//! // submitted as a trace telemetry
+//! tracing::info!(component = "gateway", "Connected to a gateway");
+//!
+//! // submitted as an exception telemetry
+//! let error = "not a number".parse::<i32>().unwrap_err();
+//! tracing::error!(error = &error as &dyn std::error::Error, "Failed to parse response");
+//! # }
+//! ```
+use std::{backtrace::Backtrace, error::Error, fmt::Debug, sync::Arc};
+
+use ::tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::{
+    telemetry::{ExceptionTelemetry, Properties, SeverityLevel, Telemetry, TraceTelemetry},
+    TelemetryClient,
+};
+
+/// A [`Layer`](https://docs.rs/tracing-subscriber/latest/tracing_subscriber/layer/trait.Layer.html)
+/// that submits events as telemetry items with the telemetry client.
+pub struct TelemetryLayer {
+    client: Arc<TelemetryClient>,
+    promoted_targets: Vec<String>,
+    group_by_message: bool,
+}
+
+impl TelemetryLayer {
+    /// Creates a new layer that submits telemetry with a specified telemetry client.
+    pub fn new(client: impl Into<Arc<TelemetryClient>>) -> Self {
+        Self {
+            client: client.into(),
+            promoted_targets: Vec::new(),
+            group_by_message: false,
+        }
+    }
+
+    /// Submits error-level events of a target and its child modules as exceptions instead of traces,
+    /// e.g. `my_app::db` for `my_app::db::pool` as well. An empty target promotes errors of all targets.
+    /// Events that carry an `error` field are always submitted as exceptions.
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{tracing::TelemetryLayer, TelemetryClient};
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let layer = TelemetryLayer::new(client).promote_errors("my_app::db").promote_errors("my_app::payments");
+    /// ```
+    pub fn promote_errors(mut self, target: impl Into<String>) -> Self {
+        self.promoted_targets.push(target.into());
+        self
+    }
+
+    /// Sets whether promoted errors of a target are grouped by their message as well. Exception type of
+    /// a promoted error is a target it was recorded in, or a target and a message when enabled, so
+    /// Failures tell apart different errors of the same module. It is disabled by default.
+    pub fn group_errors_by_message(mut self, group_by_message: bool) -> Self {
+        self.group_by_message = group_by_message;
+        self
+    }
+
+    /// Determines whether an event is promoted to an exception.
+    fn is_promoted(&self, level: &Level, target: &str) -> bool {
+        *level == Level::ERROR
+            && self.promoted_targets.iter().any(|promoted| {
+                promoted.is_empty()
+                    || target
+                        .strip_prefix(promoted.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for TelemetryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+
+        let mut properties = visitor.properties;
+        properties.insert("target".into(), metadata.target().into());
+
+        if let Some(mut telemetry) = visitor.exception {
+            telemetry.set_backtrace(&Backtrace::capture());
+            if let Some(message) = visitor.message {
+                properties.insert("message".into(), message);
+            }
+            *telemetry.properties_mut() = properties;
+            self.client.track(telemetry);
+        } else if self.is_promoted(metadata.level(), metadata.target()) {
+            let message = visitor.message.unwrap_or_default();
+            let type_name = if self.group_by_message {
+                format!("{}: {}", metadata.target(), message)
+            } else {
+                metadata.target().to_string()
+            };
+            let mut telemetry = ExceptionTelemetry::from_message(type_name, message)
+                .with_severity_level(SeverityLevel::Error)
+                .with_backtrace(&Backtrace::capture());
+            *telemetry.properties_mut() = properties;
+            self.client.track(telemetry);
+        } else {
+            let message = visitor.message.unwrap_or_default();
+            let mut telemetry = TraceTelemetry::new(message, severity(metadata.level()));
+            *telemetry.properties_mut() = properties;
+            self.client.track(telemetry);
+        }
+    }
+}
+
+/// Maps a tracing level to severity level of a trace telemetry.
+fn severity(level: &Level) -> SeverityLevel {
+    match *level {
+        Level::TRACE | Level::DEBUG => SeverityLevel::Verbose,
+        Level::INFO => SeverityLevel::Information,
+        Level::WARN => SeverityLevel::Warning,
+        Level::ERROR => SeverityLevel::Error,
+    }
+}
+
+/// Collects event fields. A `message` field becomes a message of a telemetry item, an `error` field
+/// recorded as `&dyn Error` becomes an exception and all other fields become custom properties.
+#[derive(Default)]
+struct EventVisitor {
+    message: Option<String>,
+    exception: Option<ExceptionTelemetry>,
+    properties: Properties,
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.into());
+        } else {
+            self.properties.insert(field.name().into(), value.into());
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        if field.name() == "error" {
+            self.exception = Some(ExceptionTelemetry::new(value));
+        } else {
+            self.properties.insert(field.name().into(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            self.properties.insert(field.name().into(), format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+    use matches::assert_matches;
+    use test_case::test_case;
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[test]
+    fn it_submits_event_as_trace() {
+        let events = Arc::new(SegQueue::default());
+        let subscriber = tracing_subscriber::registry().with(TelemetryLayer::new(create_client(events.clone())));
+
+        ::tracing::subscriber::with_default(subscriber, || {
+            ::tracing::warn!(component = "gateway", attempt = 3, "Unable to connect");
+        });
+
+        let envelope = events.pop().unwrap();
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::MessageData(data)))
+                if data.message == "Unable to connect"
+                    && data.severity_level == Some(crate::contracts::SeverityLevel::Warning)
+                    && data.properties.as_ref().unwrap().get("component") == Some(&"gateway".to_string())
+                    && data.properties.as_ref().unwrap().get("attempt") == Some(&"3".to_string())
+        );
+    }
+
+    #[test]
+    fn it_submits_event_with_error_as_exception() {
+        let events = Arc::new(SegQueue::default());
+        let subscriber = tracing_subscriber::registry().with(TelemetryLayer::new(create_client(events.clone())));
+
+        let error = "not a number".parse::<i32>().unwrap_err();
+        ::tracing::subscriber::with_default(subscriber, || {
+            ::tracing::error!(error = &error as &dyn Error, "Failed to parse response");
+        });
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.name, "Microsoft.ApplicationInsights.Exception");
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::ExceptionData(data)))
+                if data.exceptions[0].type_name == "ParseIntError"
+                    && data.exceptions[0].message == "invalid digit found in string"
+                    && data.properties.as_ref().unwrap().get("message") == Some(&"Failed to parse response".to_string())
+        );
+    }
+
+    #[test]
+    fn it_submits_error_in_other_field_as_property() {
+        let events = Arc::new(SegQueue::default());
+        let subscriber = tracing_subscriber::registry().with(TelemetryLayer::new(create_client(events.clone())));
+
+        let error = "not a number".parse::<i32>().unwrap_err();
+        ::tracing::subscriber::with_default(subscriber, || {
+            ::tracing::error!(cause = &error as &dyn Error, "Failed to parse response");
+        });
+
+        let envelope: Envelope = events.pop().unwrap();
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::MessageData(data)))
+                if data.properties.as_ref().unwrap().get("cause") == Some(&"invalid digit found in string".to_string())
+        );
+    }
+
+    #[test_case("my_app::db",  "my_app::db::pool", false, Some("my_app::db::pool")                 ; "child module promoted")]
+    #[test_case("my_app::db",  "my_app::db",       true,  Some("my_app::db: Connection refused") ; "grouped by message")]
+    #[test_case("",            "other",            false, Some("other")                            ; "all targets promoted")]
+    #[test_case("my_app::db",  "my_app::dbx",      false, None                                     ; "sibling module not promoted")]
+    fn it_promotes_errors_of_targets_to_exceptions(
+        promoted: &str,
+        target: &'static str,
+        group_by_message: bool,
+        expected: Option<&str>,
+    ) {
+        let events = Arc::new(SegQueue::default());
+        let layer = TelemetryLayer::new(create_client(events.clone()))
+            .promote_errors(promoted)
+            .group_errors_by_message(group_by_message);
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        ::tracing::subscriber::with_default(subscriber, || match target {
+            "my_app::db::pool" => ::tracing::error!(target: "my_app::db::pool", "Connection refused"),
+            "my_app::db" => ::tracing::error!(target: "my_app::db", "Connection refused"),
+            "my_app::dbx" => ::tracing::error!(target: "my_app::dbx", "Connection refused"),
+            _ => ::tracing::error!(target: "other", "Connection refused"),
+        });
+
+        let envelope = events.pop().unwrap();
+        match (envelope.data, expected) {
+            (Some(Base::Data(Data::ExceptionData(data))), Some(expected)) => {
+                assert_eq!(data.exceptions[0].type_name, expected);
+                assert_eq!(data.exceptions[0].message, "Connection refused");
+            }
+            (Some(Base::Data(Data::MessageData(_))), None) => {}
+            (data, _) => panic!("unexpected data {:?}", data),
+        }
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
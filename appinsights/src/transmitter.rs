@@ -1,49 +1,221 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
 use chrono::{DateTime, Utc};
-use http::{header::RETRY_AFTER, StatusCode};
-use log::debug;
-use reqwest::Client;
+use futures_util::{stream, StreamExt};
+use http::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE, RETRY_AFTER},
+    StatusCode,
+};
+use log::{debug, warn};
+use tokio::time::{self, Instant};
 
 use crate::{
     contracts::{Envelope, Transmission, TransmissionItem},
+    diagnostics::{Diagnostic, Diagnostics},
+    otlp::Signal,
+    retry::Failure,
+    tee::Tee,
+    transport::{self, IngestionResponse, IngestionTransport, SharedTransport},
     Result,
 };
 
 #[derive(Debug, PartialEq)]
 pub enum Response {
     Success,
-    Retry(Vec<Envelope>),
+    Retry(Failure, Vec<Envelope>),
     Throttled(DateTime<Utc>, Vec<Envelope>),
     NoRetry,
 }
 
+/// Maximum size of a request body the track endpoint accepts.
+pub const MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
 /// Sends telemetry items to the server.
 pub struct Transmitter {
     url: String,
-    client: Client,
+    transport: Arc<dyn IngestionTransport>,
+    tee: Option<Tee>,
+    otlp_endpoint: Option<String>,
+    max_payload_bytes: usize,
+    max_concurrent_requests: usize,
+    bandwidth: Option<Bandwidth>,
+    diagnostics: Diagnostics,
+    responses: Mutex<Vec<IngestionResponse>>,
 }
 
 impl Transmitter {
     /// Creates a new instance of telemetry items sender.
     pub fn new(url: &str) -> Self {
-        let client = Client::new();
         Self {
             url: url.into(),
-            client,
+            transport: match transport::of_endpoint(url) {
+                Some(transport) => transport.0,
+                None => transport::built_in(),
+            },
+            tee: None,
+            otlp_endpoint: None,
+            max_payload_bytes: MAX_PAYLOAD_BYTES,
+            max_concurrent_requests: 1,
+            bandwidth: None,
+            diagnostics: Diagnostics::default(),
+            responses: Mutex::default(),
+        }
+    }
+
+    /// Splits telemetry items into several requests to the track endpoint, so none of them is larger than
+    /// specified size of serialized JSON.
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = max_payload_bytes;
+        self
+    }
+
+    /// Sends a batch in up to specified number of requests at once. A batch is split into at least that
+    /// many requests, so items of a batch may be accepted by the server in any order.
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    /// Paces requests to the track endpoint so that all of them together send at most specified number
+    /// of bytes per second on average.
+    pub fn with_max_bytes_per_second(mut self, max_bytes_per_second: Option<usize>) -> Self {
+        self.bandwidth = max_bytes_per_second.map(Bandwidth::new);
+        self
+    }
+
+    /// Reports responses of the ingestion endpoint and problems of sending telemetry, e.g. items that fail
+    /// to serialize or exceed the payload size limit, to diagnostics of a client. Such items are dropped, so the rest of a batch is sent
+    /// regardless.
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Sends requests through a custom transport instead of the built-in HTTP client if specified.
+    pub fn with_transport(mut self, transport: Option<SharedTransport>) -> Self {
+        if let Some(transport) = transport {
+            self.transport = transport.0;
         }
+        self
+    }
+
+    /// Takes responses of requests sent since responses were taken last time.
+    pub fn take_responses(&self) -> Vec<IngestionResponse> {
+        mem::take(&mut *self.responses.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Copies every batch of telemetry items to a tee before it is sent.
+    pub fn with_tee(mut self, tee: Option<Tee>) -> Self {
+        self.tee = tee;
+        self
+    }
+
+    /// Exports telemetry items to an OTLP/HTTP receiver at a base URL instead of the track endpoint.
+    pub fn with_otlp_endpoint(mut self, otlp_endpoint: Option<&str>) -> Self {
+        self.otlp_endpoint = otlp_endpoint.map(|endpoint| endpoint.trim_end_matches('/').into());
+        self
     }
 
     /// Sends a telemetry items to the server.
-    pub async fn send(&self, mut items: Vec<Envelope>) -> Result<Response> {
-        let payload = serde_json::to_string(&items)?;
+    pub async fn send(&self, items: Vec<Envelope>) -> Result<Response> {
+        if let Some(tee) = &self.tee {
+            tee.write(&items);
+        }
+
+        match &self.otlp_endpoint {
+            Some(endpoint) => self.export(endpoint, items).await,
+            None => self.track(items).await,
+        }
+    }
+
+    /// Sends telemetry items to the track endpoint in as few requests as the payload size limit and the
+    /// number of concurrent requests allow.
+    async fn track(&self, items: Vec<Envelope>) -> Result<Response> {
+        let mut payloads = Vec::new();
+        let chunk = items.len().div_ceil(self.max_concurrent_requests);
+        let mut items = items.into_iter().peekable();
+        while items.peek().is_some() {
+            payloads.extend(split(
+                items.by_ref().take(chunk.max(1)).collect(),
+                self.max_payload_bytes,
+                &self.diagnostics,
+                |item| self.serialize(item),
+            ));
+        }
+        if payloads.len() == 1 {
+            let (payload, items) = payloads.remove(0);
+            return self.track_payload(payload, items).await;
+        }
+
+        let responses: Vec<_> = stream::iter(payloads)
+            .map(|(payload, items)| async move {
+                // an error is converted before it is held across an await, so sending stays `Send`
+                let response = self.track_payload(payload, items.clone()).await;
+                (response.map_err(|err| err.to_string()), items)
+            })
+            .buffer_unordered(self.max_concurrent_requests)
+            .collect()
+            .await;
+
+        let mut retry_items = Vec::new();
+        let mut throttled_until = None;
+        let mut failure = None;
+        let mut rejected = false;
+        for (response, items) in responses {
+            match response {
+                Ok(Response::Success) => {}
+                Ok(Response::Retry(reason, items)) => {
+                    failure = Some(reason);
+                    retry_items.extend(items);
+                }
+                Ok(Response::Throttled(retry_after, items)) => {
+                    failure = Some(Failure::Throttled(retry_after));
+                    throttled_until = throttled_until.max(Some(retry_after));
+                    retry_items.extend(items);
+                }
+                Ok(Response::NoRetry) => rejected = true,
+                Err(err) => {
+                    debug!("Unable to send {} items: {}. Retry sending", items.len(), err);
+                    failure = Some(Failure::Error(err));
+                    retry_items.extend(items);
+                }
+            }
+        }
+
+        Ok(match (failure, throttled_until) {
+            (None, _) if rejected => Response::NoRetry,
+            (None, _) => Response::Success,
+            (Some(_), Some(retry_after)) => Response::Throttled(retry_after, retry_items),
+            (Some(failure), None) => Response::Retry(failure, retry_items),
+        })
+    }
 
-        let response = self.client.post(&self.url).body(payload).send().await?;
-        let response = match response.status() {
+    /// Sends a single payload of serialized telemetry items to the track endpoint.
+    async fn track_payload(&self, payload: String, mut items: Vec<Envelope>) -> Result<Response> {
+        if let Some(bandwidth) = &self.bandwidth {
+            time::sleep_until(bandwidth.reserve(payload.len())).await;
+        }
+
+        let response = self
+            .transport
+            .send(&self.url, payload.into(), HeaderMap::new())
+            .await
+            .map_err(|err| err.to_string())?;
+        let status = response.status();
+        self.record(IngestionResponse::new(status, &items, response.body()));
+        let response = match status {
             StatusCode::OK => {
                 debug!("Successfully sent {} items", items.len());
                 Response::Success
             }
             StatusCode::PARTIAL_CONTENT => {
-                let content: Transmission = response.json().await?;
+                let content: Transmission = serde_json::from_slice(response.body())?;
                 let log_prefix = format!(
                     "Successfully sent {}/{} telemetry items",
                     content.items_accepted, content.items_received
@@ -58,20 +230,22 @@ impl Transmitter {
                         Response::NoRetry
                     } else {
                         debug!("{}. Retry sending {} items", log_prefix, items.len());
-                        Response::Retry(items)
+                        Response::Retry(Failure::Status(status), items)
                     }
                 }
             }
             StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT => {
-                let retry_after = response.headers().get(RETRY_AFTER).cloned();
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after);
 
-                if let Ok(content) = response.json::<Transmission>().await {
+                if let Ok(content) = serde_json::from_slice::<Transmission>(response.body()) {
                     retain_retry_items(&mut items, content);
                 }
 
                 if let Some(retry_after) = retry_after {
-                    let retry_after = retry_after.to_str()?;
-                    let retry_after = DateTime::parse_from_rfc2822(retry_after)?.with_timezone(&Utc);
                     debug!(
                         "Some items were discarded. Retry sending {} items after {}",
                         items.len(),
@@ -80,33 +254,33 @@ impl Transmitter {
                     Response::Throttled(retry_after, items)
                 } else {
                     debug!("Some items were discarded. Retry sending {} items", items.len());
-                    Response::Retry(items)
+                    Response::Retry(Failure::Status(status), items)
                 }
             }
             StatusCode::SERVICE_UNAVAILABLE => {
                 debug!("Service unavailable. Retry sending {} items", items.len());
-                Response::Retry(items.to_vec())
+                Response::Retry(Failure::Status(status), items)
             }
             StatusCode::INTERNAL_SERVER_ERROR => {
-                if let Ok(content) = response.json::<Transmission>().await {
+                if let Ok(content) = serde_json::from_slice::<Transmission>(response.body()) {
                     retain_retry_items(&mut items, content);
                     if items.is_empty() {
                         debug!("Service error. Nothing to re-send");
                         Response::NoRetry
                     } else {
                         debug!("Service error. Retry sending {} items", items.len());
-                        Response::Retry(items)
+                        Response::Retry(Failure::Status(status), items)
                     }
                 } else {
                     debug!("Service error. Retry sending {} items", items.len());
-                    Response::Retry(items.to_vec())
+                    Response::Retry(Failure::Status(status), items)
                 }
             }
             _ => {
                 debug!(
                     "Unknown status: {}. {}. Nothing to re-send",
-                    response.status(),
-                    response.text().await.unwrap_or_default()
+                    status,
+                    String::from_utf8_lossy(response.body())
                 );
                 Response::NoRetry
             }
@@ -114,6 +288,233 @@ impl Transmitter {
 
         Ok(response)
     }
+
+    /// Keeps a response of the ingestion endpoint until it is taken and reports it to diagnostics.
+    fn record(&self, response: IngestionResponse) {
+        self.diagnostics.report(Diagnostic::Response(&response));
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(response);
+    }
+
+    /// Serializes a telemetry item, or reports and drops it if it fails to serialize.
+    fn serialize(&self, item: &Envelope) -> Option<String> {
+        self.serialized(item, serde_json::to_string(item))
+    }
+
+    /// Reports an item if its serialization failed.
+    fn serialized<T>(&self, item: &Envelope, result: serde_json::Result<T>) -> Option<T> {
+        match result {
+            Ok(json) => Some(json),
+            Err(err) => {
+                warn!("Unable to serialize telemetry item {}. Dropping it: {}", item.name, err);
+                self.diagnostics.report(Diagnostic::SerializationFailed {
+                    envelope: item,
+                    error: &err,
+                });
+                None
+            }
+        }
+    }
+
+    /// Serializes telemetry items of a signal into a single export request. When the request fails to
+    /// serialize, items that fail on their own are dropped and the rest are exported without them.
+    fn export_payload(&self, signal: Signal, items: Vec<Envelope>) -> Option<(String, Vec<Envelope>)> {
+        if let Ok(payload) = serde_json::to_string(&signal.export(&items)) {
+            return Some((payload, items));
+        }
+
+        let items: Vec<_> = items
+            .into_iter()
+            .filter(|item| {
+                let result = serde_json::to_string(&signal.export(std::slice::from_ref(item)));
+                self.serialized(item, result).is_some()
+            })
+            .collect();
+        match serde_json::to_string(&signal.export(&items)) {
+            Ok(payload) if !items.is_empty() => Some((payload, items)),
+            Ok(_) => None,
+            Err(err) => {
+                warn!(
+                    "Unable to serialize {} telemetry items. Dropping them: {}",
+                    items.len(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Sends telemetry items converted to OTLP signals to the receiver, one request per signal.
+    async fn export(&self, endpoint: &str, items: Vec<Envelope>) -> Result<Response> {
+        let mut signals: BTreeMap<Signal, Vec<Envelope>> = BTreeMap::new();
+        for envelope in items {
+            signals.entry(Signal::of(&envelope)).or_default().push(envelope);
+        }
+
+        let mut retry_items = Vec::new();
+        let mut throttled_until = None;
+        let mut failure = None;
+        let mut rejected = false;
+        for (signal, items) in signals {
+            let url = format!("{}/{}", endpoint, signal.path());
+            let (payload, items) = match self.export_payload(signal, items) {
+                Some(exported) => exported,
+                None => continue,
+            };
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            let response = self.transport.send(&url, payload.into(), headers).await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    debug!(
+                        "Unable to export {} items to {}: {}. Retry sending",
+                        items.len(),
+                        url,
+                        err
+                    );
+                    failure = failure.or_else(|| Some(Failure::Error(err.to_string())));
+                    retry_items.extend(items);
+                    continue;
+                }
+            };
+
+            self.record(IngestionResponse::new(response.status(), &items, response.body()));
+            match response.status() {
+                StatusCode::OK => debug!("Successfully exported {} items to {}", items.len(), url),
+                StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT => {
+                    debug!(
+                        "Export to {} failed with {}. Retry sending {} items",
+                        url,
+                        response.status(),
+                        items.len()
+                    );
+                    let retry_after = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok());
+                    if let Some(retry_after) = retry_after.and_then(parse_retry_after) {
+                        throttled_until = throttled_until.max(Some(retry_after));
+                    }
+                    failure = Some(Failure::Status(response.status()));
+                    retry_items.extend(items);
+                }
+                status => {
+                    debug!(
+                        "Export to {} failed with {}. {}. Nothing to re-send",
+                        url,
+                        status,
+                        String::from_utf8_lossy(response.body())
+                    );
+                    rejected = true;
+                }
+            }
+        }
+
+        Ok(match (failure, throttled_until) {
+            (None, _) if rejected => Response::NoRetry,
+            (None, _) => Response::Success,
+            (Some(_), Some(retry_after)) => Response::Throttled(retry_after, retry_items),
+            (Some(failure), None) => Response::Retry(failure, retry_items),
+        })
+    }
+}
+
+/// Limits a rate requests send bytes at by scheduling every next payload after the previous ones would
+/// have been sent at a maximum rate.
+struct Bandwidth {
+    bytes_per_second: usize,
+    next: Mutex<Option<Instant>>,
+}
+
+impl Bandwidth {
+    fn new(bytes_per_second: usize) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Returns time a payload of specified size can be sent at.
+    fn reserve(&self, bytes: usize) -> Instant {
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let start = next.map_or(now, |next| next.max(now));
+        *next = Some(start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64));
+        start
+    }
+}
+
+/// Serializes telemetry items into JSON arrays of at most `max_bytes` each, keeping items of every array
+/// along with it. An item that doesn't fit into a payload on its own is dropped and reported to diagnostics,
+/// since the service would reject the whole payload with it, and so is an item `serialize` fails to serialize.
+fn split<F>(
+    items: Vec<Envelope>,
+    max_bytes: usize,
+    diagnostics: &Diagnostics,
+    serialize: F,
+) -> Vec<(String, Vec<Envelope>)>
+where
+    F: Fn(&Envelope) -> Option<String>,
+{
+    let mut payloads = Vec::new();
+    let mut payload = String::from("[");
+    let mut batch = Vec::new();
+    for item in items {
+        let json = match serialize(&item) {
+            Some(json) => json,
+            None => continue,
+        };
+        if json.len() + 2 > max_bytes {
+            warn!(
+                "Telemetry item {} of {} bytes exceeds payload size limit of {} bytes and is dropped",
+                item.name,
+                json.len(),
+                max_bytes
+            );
+            diagnostics.report(Diagnostic::Oversized {
+                envelope: &item,
+                size: json.len(),
+                max_bytes,
+                trimmed: &[],
+                dropped: true,
+            });
+            continue;
+        }
+
+        if !batch.is_empty() && payload.len() + json.len() + 2 > max_bytes {
+            payload.push(']');
+            payloads.push((mem::replace(&mut payload, String::from("[")), mem::take(&mut batch)));
+        }
+        if !batch.is_empty() {
+            payload.push(',');
+        }
+        payload.push_str(&json);
+        batch.push(item);
+    }
+
+    if !batch.is_empty() {
+        payload.push(']');
+        payloads.push((payload, batch));
+    }
+    payloads
+}
+
+/// Parses `Retry-After` header value given either in seconds or as a date. Returns `None` if the value is
+/// malformed, so sending is retried after a delay of a retry policy instead.
+fn parse_retry_after(value: &str) -> Option<DateTime<Utc>> {
+    match value.trim().parse::<i64>() {
+        Ok(seconds) => Some(Utc::now() + chrono::Duration::seconds(seconds)),
+        Err(_) => DateTime::parse_from_rfc2822(value)
+            .ok()
+            .map(|at| at.with_timezone(&Utc)),
+    }
 }
 
 /// Filters out those telemetry items that cannot be re-sent.
@@ -123,6 +524,10 @@ fn retain_retry_items(items: &mut Vec<Envelope>, content: Transmission) {
         retry_items.push(items.remove(error.index - retry_items.len()));
     }
 
+    for error in content.errors.iter().filter(|error| !can_retry_item(error)) {
+        debug!("Item {} rejected: {} {}", error.index, error.status_code, error.message);
+    }
+
     *items = retry_items;
 }
 
@@ -138,6 +543,9 @@ fn can_retry_item(item: &TransmissionItem) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
     use chrono::TimeZone;
     use http::{Request, StatusCode};
     use hyper::{
@@ -148,22 +556,24 @@ mod tests {
     use test_case::test_case;
 
     use super::*;
+    use crate::transport::{TransportError, TransportResponse};
 
     #[test_case(items(), StatusCode::OK, None, Some(all_accepted()), Response::Success; "success")]
-    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(partial_some_retries()), Response::Retry(retry_items()); "partial. resend some items")]
+    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(partial_some_retries()), Response::Retry(Failure::Status(StatusCode::PARTIAL_CONTENT), retry_items()); "partial. resend some items")]
     #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(partial_no_retries()), Response::NoRetry; "partial. nothing to resend")]
-    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(none_accepted()), Response::Retry(items()); "partial. resend everything")]
+    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(none_accepted()), Response::Retry(Failure::Status(StatusCode::PARTIAL_CONTENT), items()); "partial. resend everything")]
     #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(all_accepted()), Response::Success; "partial. everything accepted")]
     #[test_case(items(), StatusCode::BAD_REQUEST, None, None, Response::NoRetry; "bad request. no retry")]
-    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, None, None, Response::Retry(items()); "timeout. resend everything")]
+    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, None, None, Response::Retry(Failure::Status(StatusCode::REQUEST_TIMEOUT), items()); "timeout. resend everything")]
     #[test_case(items(), StatusCode::REQUEST_TIMEOUT, Some(retry_after_str()), None, Response::Throttled(retry_after(), items()); "timeout. throttled")]
-    #[test_case(items(), StatusCode::TOO_MANY_REQUESTS, None, None,Response::Retry(items()); "too many requests. no retry-after. resend everything")]
+    #[test_case(items(), StatusCode::TOO_MANY_REQUESTS, None, None,Response::Retry(Failure::Status(StatusCode::TOO_MANY_REQUESTS), items()); "too many requests. no retry-after. resend everything")]
     #[test_case(items(), StatusCode::TOO_MANY_REQUESTS, Some(retry_after_str()), None, Response::Throttled(retry_after(), items()); "too many requests. retry-after. throttled")]
-    #[test_case(items(), StatusCode::INTERNAL_SERVER_ERROR, None, None, Response::Retry(items()); "server error. resend everything")]
-    #[test_case(items(), StatusCode::SERVICE_UNAVAILABLE, None, None, Response::Retry(items()); "service unavailable. resend everything")]
+    #[test_case(items(), StatusCode::TOO_MANY_REQUESTS, Some("soon"), None, Response::Retry(Failure::Status(StatusCode::TOO_MANY_REQUESTS), items()); "too many requests. malformed retry-after. resend everything")]
+    #[test_case(items(), StatusCode::INTERNAL_SERVER_ERROR, None, None, Response::Retry(Failure::Status(StatusCode::INTERNAL_SERVER_ERROR), items()); "server error. resend everything")]
+    #[test_case(items(), StatusCode::SERVICE_UNAVAILABLE, None, None, Response::Retry(Failure::Status(StatusCode::SERVICE_UNAVAILABLE), items()); "service unavailable. resend everything")]
     #[test_case(items(), StatusCode::UNAUTHORIZED, None, None, Response::NoRetry; "unauthorized. no retry")]
-    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, None, Some(partial_some_retries()), Response::Retry(retry_items()); "timeout. resend some items")]
-    #[test_case(items(), StatusCode::INTERNAL_SERVER_ERROR, None, Some(partial_some_retries()), Response::Retry(retry_items()); "server error. resend some items")]
+    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, None, Some(partial_some_retries()), Response::Retry(Failure::Status(StatusCode::REQUEST_TIMEOUT), retry_items()); "timeout. resend some items")]
+    #[test_case(items(), StatusCode::INTERNAL_SERVER_ERROR, None, Some(partial_some_retries()), Response::Retry(Failure::Status(StatusCode::INTERNAL_SERVER_ERROR), retry_items()); "server error. resend some items")]
     fn it_sends_telemetry_and_handles_server_response(
         items: Vec<Envelope>,
         status_code: StatusCode,
@@ -183,6 +593,211 @@ mod tests {
         });
     }
 
+    #[tokio::test]
+    async fn it_copies_sent_items_to_tee() {
+        let url = create_server(StatusCode::OK, None, Some(all_accepted()));
+        let copied = Arc::new(Mutex::new(Vec::new()));
+        let tee = Tee::new({
+            let copied = copied.clone();
+            move |batch: &[Envelope]| copied.lock().unwrap().extend_from_slice(batch)
+        });
+
+        let transmitter = Transmitter::new(&format!("{}/track", url)).with_tee(Some(tee));
+        let response = transmitter.send(items()).await.unwrap();
+
+        assert_eq!(response, Response::Success);
+        assert_eq!(*copied.lock().unwrap(), items());
+    }
+
+    #[test_case(3, vec![3, 2]          ; "split by size")]
+    #[test_case(5, vec![5]             ; "single payload")]
+    #[test_case(1, vec![1, 1, 1, 1, 1] ; "one item per payload")]
+    fn it_splits_items_into_payloads_by_size(capacity: usize, expected: Vec<usize>) {
+        let size = serde_json::to_string(&items()[0]).unwrap().len();
+        let max_bytes = 2 + capacity * size + capacity - 1;
+
+        let payloads = split(items(), max_bytes, &Diagnostics::default(), json);
+
+        for (payload, items) in &payloads {
+            assert!(payload.len() <= max_bytes);
+            assert_eq!(serde_json::from_str::<Vec<Value>>(payload).unwrap().len(), items.len());
+        }
+        assert_eq!(
+            payloads.iter().map(|(_, items)| items.len()).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_drops_items_larger_than_payload() {
+        let size = serde_json::to_string(&items()[0]).unwrap().len();
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics = Diagnostics::new({
+            let dropped = dropped.clone();
+            move |diagnostic| {
+                if let Diagnostic::Oversized {
+                    envelope,
+                    max_bytes,
+                    dropped: true,
+                    ..
+                } = diagnostic
+                {
+                    dropped.lock().unwrap().push((envelope.name.clone(), *max_bytes))
+                }
+            }
+        });
+
+        assert!(split(items(), size, &diagnostics, json).is_empty());
+        assert_eq!(dropped.lock().unwrap().len(), 5);
+        assert_eq!(dropped.lock().unwrap()[0], ("event 0".to_string(), size));
+    }
+
+    #[tokio::test]
+    async fn it_sends_telemetry_through_custom_transport() {
+        struct Recording(Mutex<Vec<(String, Bytes)>>);
+
+        #[async_trait::async_trait]
+        impl IngestionTransport for Recording {
+            async fn send(
+                &self,
+                url: &str,
+                body: Bytes,
+                _: HeaderMap,
+            ) -> std::result::Result<TransportResponse, TransportError> {
+                self.0.lock().unwrap().push((url.into(), body));
+                Ok(TransportResponse::new(StatusCode::PARTIAL_CONTENT).with_body(partial_some_retries().to_string()))
+            }
+        }
+
+        let transport = Arc::new(Recording(Mutex::new(Vec::new())));
+        let transmitter =
+            Transmitter::new("http://sidecar/track").with_transport(Some(SharedTransport(transport.clone())));
+        let response = transmitter.send(items()).await.unwrap();
+
+        assert_eq!(
+            response,
+            Response::Retry(Failure::Status(StatusCode::PARTIAL_CONTENT), retry_items())
+        );
+        let requests = transport.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].0, "http://sidecar/track");
+        assert_eq!(serde_json::from_slice::<Vec<Value>>(&requests[0].1).unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn it_reports_ingestion_responses() {
+        let url = create_server(StatusCode::PARTIAL_CONTENT, None, Some(partial_some_retries()));
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let transmitter = Transmitter::new(&url).with_diagnostics(Diagnostics::new({
+            let reported = reported.clone();
+            move |diagnostic| {
+                if let Diagnostic::Response(response) = diagnostic {
+                    reported.lock().unwrap().push((*response).clone())
+                }
+            }
+        }));
+
+        transmitter.send(items()).await.unwrap();
+
+        let responses = transmitter.take_responses();
+        assert_eq!(*reported.lock().unwrap(), responses);
+        assert_eq!(responses.len(), 1);
+        assert_eq!((responses[0].items_received(), responses[0].items_accepted()), (5, 2));
+        let errors: Vec<_> = responses[0]
+            .errors()
+            .iter()
+            .map(|error| (error.name(), error.status_code()))
+            .collect();
+        assert_eq!(errors, vec![("event 2", 400), ("event 4", 408)]);
+        assert!(transmitter.take_responses().is_empty());
+    }
+
+    #[test]
+    fn it_skips_items_failing_to_serialize() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let transmitter = Transmitter::new("http://localhost/track").with_diagnostics(Diagnostics::new({
+            let dropped = dropped.clone();
+            move |diagnostic| {
+                if let Diagnostic::SerializationFailed { envelope, .. } = diagnostic {
+                    dropped.lock().unwrap().push(envelope.name.clone())
+                }
+            }
+        }));
+
+        let payloads = split(items(), MAX_PAYLOAD_BYTES, &Diagnostics::default(), |item| {
+            let result = match item.name.as_str() {
+                "event 2" => Err(serde::ser::Error::custom("unsupported value")),
+                _ => serde_json::to_string(item),
+            };
+            transmitter.serialized(item, result)
+        });
+
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(serde_json::from_str::<Vec<Value>>(&payloads[0].0).unwrap().len(), 4);
+        assert_eq!(*dropped.lock().unwrap(), vec!["event 2"]);
+    }
+
+    #[tokio::test]
+    async fn it_sends_items_in_several_requests_when_payload_is_too_large() {
+        let url = create_server(StatusCode::OK, None, Some(all_accepted()));
+        let size = serde_json::to_string(&items()[0]).unwrap().len();
+
+        let transmitter = Transmitter::new(&format!("{}/track", url)).with_max_payload_bytes(2 + 2 * size + 1);
+        let response = transmitter.send(items()).await.unwrap();
+
+        assert_eq!(response, Response::Success);
+    }
+
+    #[tokio::test]
+    async fn it_sends_items_in_concurrent_requests() {
+        let url = create_server(StatusCode::OK, None, Some(all_accepted()));
+        let copied = Arc::new(Mutex::new(Vec::new()));
+        let tee = Tee::new({
+            let copied = copied.clone();
+            move |batch: &[Envelope]| copied.lock().unwrap().extend_from_slice(batch)
+        });
+
+        let transmitter = Transmitter::new(&format!("{}/track", url))
+            .with_tee(Some(tee))
+            .with_max_concurrent_requests(2)
+            .with_max_bytes_per_second(Some(1024 * 1024));
+        let response = transmitter.send(items()).await.unwrap();
+
+        assert_eq!(response, Response::Success);
+        assert_eq!(copied.lock().unwrap().len(), items().len());
+    }
+
+    #[test]
+    fn it_paces_payloads_to_bandwidth() {
+        let bandwidth = Bandwidth::new(1000);
+
+        let first = bandwidth.reserve(500);
+        let second = bandwidth.reserve(1000);
+        let third = bandwidth.reserve(10);
+
+        assert!(first <= Instant::now());
+        assert_eq!(second - first, Duration::from_millis(500));
+        assert_eq!(third - second, Duration::from_secs(1));
+    }
+
+    #[test_case(StatusCode::OK, None, Response::Success; "otlp success")]
+    #[test_case(StatusCode::SERVICE_UNAVAILABLE, None, Response::Retry(Failure::Status(StatusCode::SERVICE_UNAVAILABLE), items()); "otlp unavailable. resend everything")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS, Some(retry_after_str()), Response::Throttled(retry_after(), items()); "otlp throttled")]
+    #[test_case(StatusCode::BAD_REQUEST, None, Response::NoRetry; "otlp bad request. no retry")]
+    #[tokio::test]
+    async fn it_exports_telemetry_to_otlp_receiver(
+        status_code: StatusCode,
+        retry_after: Option<&'static str>,
+        expected: Response,
+    ) {
+        let url = create_server(status_code, retry_after, Some(json!({})));
+
+        let transmitter = Transmitter::new("http://localhost/track").with_otlp_endpoint(Some(&format!("{}/", url)));
+        let response = transmitter.send(items()).await.unwrap();
+
+        assert_eq!(response, expected);
+    }
+
     fn create_server(status_code: StatusCode, retry_after: Option<&'static str>, body: Option<Value>) -> String {
         let make_service = make_service_fn(move |_| {
             let retry_after = retry_after.map(ToString::to_string);
@@ -311,6 +926,10 @@ mod tests {
             .collect()
     }
 
+    fn json(item: &Envelope) -> Option<String> {
+        serde_json::to_string(item).ok()
+    }
+
     fn retry_items() -> Vec<Envelope> {
         vec![Envelope {
             name: "event 4".into(),
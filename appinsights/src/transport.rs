@@ -0,0 +1,562 @@
+//! Pluggable HTTP transport of telemetry requests.
+//!
+//! A channel serializes batches of telemetry into request bodies and hands them over to an
+//! [`IngestionTransport`](trait.IngestionTransport.html) that delivers them to the endpoint and returns a
+//! response. Telemetry is sent with a built-in HTTP client by default, while a transport configured with
+//! [`TelemetryConfig`](../struct.TelemetryConfig.html) lets an application route requests through its own
+//! HTTP stack, e.g. one that applies corporate proxy settings, a sidecar listening on a unix socket, or a
+//! test double that records requests. Batching, retries, throttling and payload limits keep working
+//! regardless of a transport in use.
+//!
+//! The built-in HTTP client and [`UnixSocketTransport`](struct.UnixSocketTransport.html) come with `net`
+//! feature, which is enabled by default. A crate built without it, e.g. for a target without sockets,
+//! must configure a transport of its own, otherwise every request fails.
+//!
+//! Architectures where a sidecar owns all egress and credentials don't need a custom transport. An
+//! endpoint with `http` scheme, e.g. `http://localhost:8080/v2/track`, sends batches to a local sidecar
+//! over plain HTTP without TLS, and on Unix an endpoint with `unix` scheme, e.g.
+//! `unix:///var/run/appinsights.sock`, sends them over a unix domain socket at that path with
+//! [`UnixSocketTransport`](struct.UnixSocketTransport.html).
+//!
+//! ```rust, no_run
+//! use appinsights::{
+//!     transport::{IngestionTransport, TransportError, TransportResponse},
+//!     TelemetryConfig,
+//! };
+//! use async_trait::async_trait;
+//! use bytes::Bytes;
+//! use http::{HeaderMap, StatusCode};
+//!
+//! struct Discard;
+//!
+//! #[async_trait]
+//! impl IngestionTransport for Discard {
+//!     async fn send(&self, _url: &str, _body: Bytes, _headers: HeaderMap) -> Result<TransportResponse, TransportError> {
+//!         Ok(TransportResponse::new(StatusCode::OK))
+//!     }
+//! }
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .transport(Discard)
+//!     .build();
+//! ```
+use std::{
+    error::Error,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+#[cfg(all(unix, feature = "net"))]
+use std::{io, path::PathBuf};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+#[cfg(all(unix, feature = "net"))]
+use http::header::{HeaderName, HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{HeaderMap, StatusCode};
+#[cfg(feature = "net")]
+use reqwest::Client;
+#[cfg(all(unix, feature = "net"))]
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use crate::{
+    contracts::{Envelope, Transmission},
+    retry::Failure,
+};
+
+/// An error of a request that failed without a response, e.g. because of a connection error. A batch of
+/// telemetry is sent again according to a retry policy.
+pub type TransportError = Box<dyn Error + Send + Sync>;
+
+/// Delivers serialized telemetry to an ingestion endpoint.
+#[async_trait]
+pub trait IngestionTransport: Send + Sync {
+    /// Sends a `POST` request to `url` with a JSON body and headers and returns a response of the server.
+    async fn send(&self, url: &str, body: Bytes, headers: HeaderMap) -> Result<TransportResponse, TransportError>;
+}
+
+/// A response of an ingestion endpoint to a request with telemetry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TransportResponse {
+    /// Creates a new response with a status code, no headers and an empty body.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    /// Sets response headers, e.g. `Retry-After` of a throttled request.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Sets a response body, e.g. JSON that lists items the server accepted.
+    pub fn with_body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Returns a status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns a body of the response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+}
+
+/// An outcome of a single request with telemetry as the ingestion endpoint reported it. Besides a status
+/// code, the track endpoint tells how many items it received and accepted and why it rejected others,
+/// e.g. because of an invalid instrumentation key or a field that is too long.
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # async fn run(client: TelemetryClient) {
+/// for response in client.flush_channel_and_wait().await {
+///     for error in response.errors() {
+///         eprintln!("{} rejected with {}: {}", error.name(), error.status_code(), error.message());
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngestionResponse {
+    status: StatusCode,
+    items_received: usize,
+    items_accepted: usize,
+    errors: Vec<ItemError>,
+    message: Option<String>,
+}
+
+impl IngestionResponse {
+    /// Creates a new response of a request with `items` from its status code and body.
+    pub(crate) fn new(status: StatusCode, items: &[Envelope], body: &[u8]) -> Self {
+        match serde_json::from_slice::<Transmission>(body) {
+            Ok(content) => Self {
+                status,
+                items_received: content.items_received,
+                items_accepted: content.items_accepted,
+                errors: content
+                    .errors
+                    .into_iter()
+                    .map(|error| ItemError {
+                        name: items.get(error.index).map(|item| item.name.clone()).unwrap_or_default(),
+                        index: error.index,
+                        status_code: error.status_code,
+                        message: error.message,
+                    })
+                    .collect(),
+                message: None,
+            },
+            Err(_) => Self {
+                status,
+                items_received: items.len(),
+                items_accepted: if status.is_success() { items.len() } else { 0 },
+                errors: Vec::new(),
+                message: Some(String::from_utf8_lossy(body).trim().to_string())
+                    .filter(|message| !status.is_success() && !message.is_empty()),
+            },
+        }
+    }
+
+    /// Returns a status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns a number of items the endpoint received.
+    pub fn items_received(&self) -> usize {
+        self.items_received
+    }
+
+    /// Returns a number of items the endpoint accepted.
+    pub fn items_accepted(&self) -> usize {
+        self.items_accepted
+    }
+
+    /// Returns reasons the endpoint rejected items for.
+    pub fn errors(&self) -> &[ItemError] {
+        &self.errors
+    }
+
+    /// Returns a body of the response if it doesn't list rejected items, e.g. an error page of a proxy.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+/// A reason the ingestion endpoint rejected a telemetry item for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemError {
+    name: String,
+    index: usize,
+    status_code: u16,
+    message: String,
+}
+
+impl ItemError {
+    /// Returns a name of the rejected item, e.g. `Microsoft.ApplicationInsights.Exception`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a position of the rejected item in its request.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns a status code of the item, e.g. 400 for an item that is invalid or 429 for an item throttled.
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    /// Returns an explanation of the endpoint, e.g. `Field 'name' on type 'EventData' is too long`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// A change of connectivity to the ingestion endpoint a channel observed. The channel reports when the
+/// first submission fails after successful ones and when a submission succeeds again to
+/// [`diagnostics`](../diagnostics/index.html) of a client, so operators can
+/// reconstruct windows of outages that delayed or lost telemetry.
+///
+/// ```rust, no_run
+/// # use appinsights::{diagnostics::Diagnostic, transport::ConnectivityEvent, TelemetryConfig};
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .on_diagnostic(|diagnostic| match diagnostic {
+///         Diagnostic::Connectivity(ConnectivityEvent::Lost { timestamp, failure, queue_depth }) => {
+///             eprintln!("{}: telemetry is offline with {} items queued: {:?}", timestamp, queue_depth, failure)
+///         }
+///         Diagnostic::Connectivity(ConnectivityEvent::Restored { timestamp, lost_at, queue_depth }) => {
+///             eprintln!("{}: telemetry is back online since {} with {} items queued", timestamp, lost_at, queue_depth)
+///         }
+///         _ => {}
+///     })
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectivityEvent {
+    /// A submission failed after successful ones, or the first submission of a channel failed.
+    Lost {
+        /// Time the submission failed at.
+        timestamp: DateTime<Utc>,
+
+        /// A reason the submission failed.
+        failure: Failure,
+
+        /// A number of items waiting to be sent, including the ones of the failed submission.
+        queue_depth: usize,
+    },
+
+    /// A submission succeeded after failed ones.
+    Restored {
+        /// Time the submission succeeded at.
+        timestamp: DateTime<Utc>,
+
+        /// Time the first of failed submissions failed at.
+        lost_at: DateTime<Utc>,
+
+        /// A number of items waiting to be sent.
+        queue_depth: usize,
+    },
+}
+
+/// Returns a transport telemetry is sent with unless another one is configured.
+pub(crate) fn built_in() -> Arc<dyn IngestionTransport> {
+    #[cfg(feature = "net")]
+    return Arc::new(ReqwestTransport::new());
+    #[cfg(not(feature = "net"))]
+    return Arc::new(MissingTransport);
+}
+
+/// Sends requests with a built-in HTTP client.
+#[cfg(feature = "net")]
+struct ReqwestTransport {
+    client: Client,
+}
+
+#[cfg(feature = "net")]
+impl ReqwestTransport {
+    /// Creates a new transport with a default HTTP client.
+    fn new() -> Self {
+        Self { client: Client::new() }
+    }
+}
+
+#[cfg(feature = "net")]
+#[async_trait]
+impl IngestionTransport for ReqwestTransport {
+    async fn send(&self, url: &str, body: Bytes, headers: HeaderMap) -> Result<TransportResponse, TransportError> {
+        let response = self.client.post(url).headers(headers).body(body).send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        Ok(TransportResponse { status, headers, body })
+    }
+}
+
+/// Fails every request, since the crate is built without the built-in HTTP client.
+#[cfg(not(feature = "net"))]
+struct MissingTransport;
+
+#[cfg(not(feature = "net"))]
+#[async_trait]
+impl IngestionTransport for MissingTransport {
+    async fn send(&self, _url: &str, _body: Bytes, _headers: HeaderMap) -> Result<TransportResponse, TransportError> {
+        Err("built-in HTTP client requires `net` feature, configure a transport instead".into())
+    }
+}
+
+/// Sends requests as HTTP/1.1 over a unix domain socket, e.g. to a sidecar that forwards telemetry to
+/// Azure Monitor. It opens a new connection per request. An endpoint with `unix` scheme selects it
+/// automatically, and requests of such an endpoint go to `/v2/track`.
+///
+/// ```rust, no_run
+/// use appinsights::{transport::UnixSocketTransport, TelemetryConfig};
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .endpoint("http://sidecar/v2/track")
+///     .transport(UnixSocketTransport::new("/var/run/appinsights.sock"))
+///     .build();
+/// ```
+#[cfg(all(unix, feature = "net"))]
+#[derive(Debug, Clone)]
+pub struct UnixSocketTransport {
+    path: PathBuf,
+}
+
+#[cfg(all(unix, feature = "net"))]
+impl UnixSocketTransport {
+    /// Creates a new transport that connects to a socket at specified path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Creates a new transport for an endpoint with `unix` scheme, e.g. `unix:///var/run/appinsights.sock`.
+    pub(crate) fn from_endpoint(endpoint: &str) -> Option<Self> {
+        endpoint
+            .strip_prefix("unix://")
+            .filter(|path| path.starts_with('/'))
+            .map(Self::new)
+    }
+}
+
+#[cfg(all(unix, feature = "net"))]
+#[async_trait]
+impl IngestionTransport for UnixSocketTransport {
+    async fn send(&self, url: &str, body: Bytes, headers: HeaderMap) -> Result<TransportResponse, TransportError> {
+        let path = match url.parse::<http::Uri>() {
+            Ok(uri) if !url.starts_with("unix:") => uri.path_and_query().map_or("/", |path| path.as_str()).to_string(),
+            _ => "/v2/track".to_string(),
+        };
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+            path,
+            body.len()
+        )
+        .into_bytes();
+        for (name, value) in &headers {
+            request.extend_from_slice(name.as_str().as_bytes());
+            request.extend_from_slice(b": ");
+            request.extend_from_slice(value.as_bytes());
+            request.extend_from_slice(b"\r\n");
+        }
+        request.extend_from_slice(b"\r\n");
+        request.extend_from_slice(&body);
+
+        let mut stream = UnixStream::connect(&self.path).await?;
+        stream.write_all(&request).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        Ok(parse_response(&response)?)
+    }
+}
+
+/// Parses an HTTP/1.1 response read until a server closed a connection.
+#[cfg(all(unix, feature = "net"))]
+fn parse_response(response: &[u8]) -> io::Result<TransportResponse> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid response: {}", reason));
+
+    let end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| invalid("no end of headers"))?;
+    let head = std::str::from_utf8(&response[..end]).map_err(|_| invalid("headers are not UTF-8"))?;
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|code| code.parse::<StatusCode>().ok())
+        .ok_or_else(|| invalid("no status code"))?;
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let (name, value) = line.split_once(':').ok_or_else(|| invalid("malformed header"))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid("malformed header name"))?;
+        let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid("malformed header value"))?;
+        headers.append(name, value);
+    }
+
+    let mut body = &response[end + 4..];
+    if headers
+        .get(TRANSFER_ENCODING)
+        .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"chunked"))
+    {
+        let mut decoded = Vec::new();
+        loop {
+            let line = body
+                .windows(2)
+                .position(|window| window == b"\r\n")
+                .ok_or_else(|| invalid("truncated chunk"))?;
+            let size = std::str::from_utf8(&body[..line])
+                .ok()
+                .and_then(|size| usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16).ok())
+                .ok_or_else(|| invalid("malformed chunk size"))?;
+            body = &body[line + 2..];
+            if size == 0 {
+                break;
+            }
+            let chunk = body.get(..size).ok_or_else(|| invalid("truncated chunk"))?;
+            decoded.extend_from_slice(chunk);
+            body = body.get(size + 2..).unwrap_or_default();
+        }
+        return Ok(TransportResponse::new(status).with_headers(headers).with_body(decoded));
+    }
+
+    if let Some(length) = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+    {
+        body = body.get(..length).ok_or_else(|| invalid("truncated body"))?;
+    }
+    Ok(TransportResponse::new(status)
+        .with_headers(headers)
+        .with_body(body.to_vec()))
+}
+
+/// Returns a transport an endpoint selects by its scheme if it is not the built-in HTTP client.
+pub(crate) fn of_endpoint(endpoint: &str) -> Option<SharedTransport> {
+    #[cfg(all(unix, feature = "net"))]
+    if let Some(transport) = UnixSocketTransport::from_endpoint(endpoint) {
+        return Some(SharedTransport(Arc::new(transport)));
+    }
+    let _ = endpoint;
+    None
+}
+
+/// A transport configured with [`TelemetryConfig`](../struct.TelemetryConfig.html).
+#[derive(Clone)]
+pub(crate) struct SharedTransport(pub(crate) Arc<dyn IngestionTransport>);
+
+impl Debug for SharedTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedTransport").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for SharedTransport {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(all(test, unix, feature = "net"))]
+mod tests {
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Read, Write},
+        os::unix::net::UnixListener,
+        process, thread,
+    };
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_sends_request_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("appinsights-transport-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line.trim_end().to_string());
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+                .unwrap();
+            (head, body)
+        });
+
+        let transport = UnixSocketTransport::from_endpoint(&format!("unix://{}", path.display())).unwrap();
+        let response = transport
+            .send("unix:///ignored", Bytes::from_static(b"[]"), HeaderMap::new())
+            .await
+            .unwrap();
+        let (head, body) = server.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), &Bytes::from_static(b"{}"));
+        assert_eq!(head[0], "POST /v2/track HTTP/1.1");
+        assert_eq!(body, b"[]");
+    }
+
+    #[test_case(b"HTTP/1.1 206 Partial Content\r\nContent-Length: 4\r\n\r\nbodyextra",                  206, "body" ; "content length")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nbo\r\n2;ext\r\ndy\r\n0\r\n\r\n", 200, "body" ; "chunked")]
+    #[test_case(b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 5\r\n\r\n",                           503, ""     ; "no body")]
+    fn it_parses_response(response: &[u8], status: u16, body: &str) {
+        let response = parse_response(response).unwrap();
+
+        assert_eq!(response.status().as_u16(), status);
+        assert_eq!(response.body(), body.as_bytes());
+    }
+
+    #[test]
+    fn it_selects_transport_by_endpoint_scheme() {
+        assert!(of_endpoint("unix:///var/run/appinsights.sock").is_some());
+        assert!(of_endpoint("http://localhost:8080/v2/track").is_none());
+        assert!(of_endpoint("unix://relative.sock").is_none());
+    }
+}
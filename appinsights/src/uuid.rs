@@ -1,5 +1,109 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::{Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 pub use imp::*;
 pub use uuid::Uuid;
+use uuid::{timestamp::context::ContextV7, Timestamp};
+
+#[cfg(feature = "test-util")]
+use crate::testing::IdGenerator;
+use crate::TelemetryConfig;
+
+/// A strategy a client generates ids of operations, requests and page views with. Ids are time-ordered
+/// if the client is configured with [`time_ordered_ids`](../struct.TelemetryConfigBuilder.html#method.time_ordered_ids)
+/// and random otherwise, unless an [`id generator`](../testing/trait.IdGenerator.html) for tests is
+/// configured. A telemetry context of the client and contexts derived from it carry the strategy, so
+/// clients configured differently in one process don't affect each other.
+#[derive(Clone, Default)]
+pub(crate) struct IdStrategy {
+    time_ordered: bool,
+    #[cfg(feature = "test-util")]
+    generator: Option<std::sync::Arc<dyn IdGenerator>>,
+}
+
+impl IdStrategy {
+    /// Creates a strategy of a client with specified configuration.
+    pub(crate) fn new(config: &TelemetryConfig) -> Self {
+        Self {
+            time_ordered: config.time_ordered_ids(),
+            #[cfg(feature = "test-util")]
+            generator: config.testing().ids(),
+        }
+    }
+
+    /// Generates a new id of an operation or a request.
+    pub(crate) fn new_id(&self) -> Uuid {
+        match self.new_time_ordered() {
+            Some(id) => id,
+            None => self.new_random(),
+        }
+    }
+
+    /// Generates a new random id, e.g. of a span or an availability test run.
+    pub(crate) fn new_random(&self) -> Uuid {
+        match self.generated() {
+            Some(id) => id,
+            None => new(),
+        }
+    }
+
+    /// Generates a new span id in format of `traceparent` header.
+    pub(crate) fn new_span_id(&self) -> String {
+        span_id(self.new_random())
+    }
+
+    /// Generates a new id of a request in W3C compatible format Application Insights uses for
+    /// correlation, i.e. 16 lowercase hex characters like span ids of `traceparent` header.
+    pub(crate) fn new_request_id(&self) -> String {
+        span_id(self.new_id())
+    }
+
+    /// Generates a new time-ordered id if the strategy is time-ordered.
+    pub(crate) fn new_time_ordered(&self) -> Option<Uuid> {
+        self.time_ordered.then(|| self.generated().unwrap_or_else(new_v7))
+    }
+
+    /// Generates a new id with a generator for tests if there is one.
+    fn generated(&self) -> Option<Uuid> {
+        #[cfg(feature = "test-util")]
+        if let Some(generator) = &self.generator {
+            return Some(generator.generate());
+        }
+
+        None
+    }
+}
+
+impl Debug for IdStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("IdStrategy");
+        debug.field("time_ordered", &self.time_ordered);
+        #[cfg(feature = "test-util")]
+        debug.field("generator", &self.generator.is_some());
+        debug.finish()
+    }
+}
+
+/// Formats low 64 bits of an id as a span id. High bits of a time-ordered id are its timestamp, so ids
+/// generated within a millisecond share them, while low bits are random. Low bits that are all zeros,
+/// which W3C Trace Context treats as invalid, are replaced with random ones.
+pub fn span_id(id: Uuid) -> String {
+    let (_, low) = id.as_u64_pair();
+    let low = if low == 0 { Uuid::new_v4().as_u64_pair().1 } else { low };
+    format!("{:016x}", low)
+}
+
+/// Generates a UUIDv7 that is greater than any generated before by the process.
+fn now_v7() -> Uuid {
+    static CONTEXT: Mutex<ContextV7> = Mutex::new(ContextV7::new());
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let context = CONTEXT.lock().unwrap_or_else(PoisonError::into_inner);
+    Uuid::new_v7(Timestamp::from_unix(&*context, now.as_secs(), now.subsec_nanos()))
+}
 
 #[cfg(not(test))]
 mod imp {
@@ -9,6 +113,11 @@ mod imp {
     pub fn new() -> Uuid {
         Uuid::new_v4()
     }
+
+    /// Generates a new instance of unique identifier ordered by time of generation.
+    pub fn new_v7() -> Uuid {
+        super::now_v7()
+    }
 }
 
 #[cfg(test)]
@@ -17,7 +126,7 @@ mod imp {
 
     use uuid::Uuid;
 
-    thread_local!(static ID: RefCell<Option<Uuid>> = RefCell::new(None));
+    thread_local!(static ID: RefCell<Option<Uuid>> = const { RefCell::new(None) });
 
     /// Generates a new instance of unique identifier or predefined value to test against it.
     pub fn new() -> Uuid {
@@ -30,13 +139,74 @@ mod imp {
         })
     }
 
+    /// Generates a new instance of time-ordered unique identifier or predefined value to test against it.
+    pub fn new_v7() -> Uuid {
+        ID.with(|is| is.borrow().unwrap_or_else(super::now_v7))
+    }
+
     /// Sets known Uuid value as now to assert test against it.
     pub fn set(uuid: Uuid) {
         ID.with(|is| *is.borrow_mut() = Some(uuid))
     }
 
     /// Resets pre-defined Uuid value to use Uuid::new_v4() instead.
+    #[allow(dead_code)]
     pub fn reset() {
         ID.with(|is| *is.borrow_mut() = None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn it_generates_time_ordered_ids() {
+        reset();
+        let ids: Vec<_> = (0..10).map(|_| new_v7()).collect();
+
+        assert!(ids.iter().all(|id| id.get_version_num() == 7));
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn it_generates_ids_with_strategy_of_config() {
+        reset();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .time_ordered_ids(true)
+            .build();
+
+        assert_eq!(IdStrategy::new(&config).new_id().get_version_num(), 7);
+        assert_eq!(IdStrategy::default().new_id().get_version_num(), 4);
+        assert_eq!(IdStrategy::default().new_time_ordered(), None);
+    }
+
+    #[test]
+    fn it_generates_unique_span_ids_of_time_ordered_ids_within_millisecond() {
+        reset();
+        let ids: HashSet<_> = (0..1000).map(|_| span_id(new_v7())).collect();
+
+        assert_eq!(ids.len(), 1000);
+        assert!(ids.iter().all(|id| id.len() == 16));
+    }
+
+    #[test]
+    fn it_takes_span_id_from_low_bits() {
+        assert_eq!(span_id(Uuid::from_u128(1)), "0000000000000001");
+        assert_eq!(
+            span_id(Uuid::parse_str("910b414a-f368-4b3a-aff6-326632aac566").unwrap()),
+            "aff6326632aac566"
+        );
+    }
+
+    #[test]
+    fn it_never_generates_span_id_of_zeros() {
+        let id = span_id(Uuid::nil());
+
+        assert_eq!(id.len(), 16);
+        assert_ne!(id, "0000000000000000");
+    }
+}
@@ -0,0 +1,362 @@
+//! Local viewer of telemetry for development.
+//!
+//! A [`Viewer`](struct.Viewer.html) keeps the last telemetry items a client queued and the last ones it
+//! sent, and serves them along with counters on an embedded HTTP endpoint, so developers can see what
+//! the application submits without going to Azure Portal. Its [`processor`](struct.Viewer.html#method.processor)
+//! records items queued to the channel and its [`tee`](struct.Viewer.html#method.tee) records items as
+//! they are transmitted. The endpoint serves an HTML page at `/appinsights` and the same data as JSON at
+//! `/appinsights.json`.
+//!
+//! ```rust, no_run
+//! use appinsights::{viewer::Viewer, TelemetryClient, TelemetryConfig};
+//!
+//! let viewer = Viewer::new(100);
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .tee(viewer.tee())
+//!     .build();
+//!
+//! let mut client = TelemetryClient::from_config(config);
+//! client.add_processor(viewer.processor());
+//!
+//! // browse http://127.0.0.1:8642/appinsights
+//! let _server = viewer.serve(([127, 0, 0, 1], 8642).into())?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! The endpoint has no authentication and is meant to be bound to a loopback address only.
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::warn;
+use serde::Serialize;
+
+use crate::{contracts::Envelope, processor::TelemetryProcessor, tee::Tee};
+
+/// Interval the server checks whether it was stopped while no connection arrives.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Keeps the last telemetry items a client queued and sent, and serves them on a local HTTP endpoint.
+#[derive(Clone)]
+pub struct Viewer {
+    recent: Arc<Mutex<Recent>>,
+}
+
+impl Viewer {
+    /// Creates a new viewer that keeps at most specified number of queued and sent items each.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            recent: Arc::new(Mutex::new(Recent {
+                capacity,
+                started: Utc::now(),
+                queued: VecDeque::new(),
+                sent: VecDeque::new(),
+                stats: ViewerStats::default(),
+            })),
+        }
+    }
+
+    /// Returns a processor that records every item queued to the channel. Add it after all other
+    /// processors, so it sees items that are actually submitted.
+    pub fn processor(&self) -> impl TelemetryProcessor {
+        RecordQueued(self.clone())
+    }
+
+    /// Returns a tee that records every item as it is transmitted. Items sent again after a failed
+    /// attempt are recorded with every attempt.
+    pub fn tee(&self) -> Tee {
+        let viewer = self.clone();
+        Tee::new(move |batch: &[Envelope]| {
+            let mut recent = viewer.lock();
+            recent.stats.batches_sent += 1;
+            for envelope in batch {
+                recent.stats.items_sent += 1;
+                recent.push_sent(envelope.clone());
+            }
+        })
+    }
+
+    /// Returns counters of items recorded so far.
+    pub fn stats(&self) -> ViewerStats {
+        self.lock().stats
+    }
+
+    /// Starts serving recorded items on a background thread at specified address. The server stops when
+    /// the returned handle is dropped.
+    pub fn serve(&self, addr: SocketAddr) -> io::Result<ViewerServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        thread::Builder::new().name("appinsights-viewer".into()).spawn({
+            let viewer = self.clone();
+            let stopped = stopped.clone();
+            move || {
+                while !stopped.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) = viewer.respond(stream) {
+                                warn!("Unable to serve telemetry viewer request: {}", err);
+                            }
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                        Err(err) => warn!("Unable to accept telemetry viewer connection: {}", err),
+                    }
+                }
+            }
+        })?;
+
+        Ok(ViewerServer { local_addr, stopped })
+    }
+
+    /// Reads a request line of a connection and writes a response to it.
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+        let path = path.split('?').next().unwrap_or_default();
+
+        let (status, content_type, body) = match (method, path) {
+            ("GET", "/appinsights.json") => match serde_json::to_string(&self.snapshot()) {
+                Ok(body) => ("200 OK", "application/json", body),
+                Err(err) => ("500 Internal Server Error", "text/plain", err.to_string()),
+            },
+            ("GET", "/appinsights") | ("GET", "/appinsights/") => ("200 OK", "text/html; charset=utf-8", self.html()),
+            ("GET", _) => ("404 Not Found", "text/plain", "not found".into()),
+            _ => ("405 Method Not Allowed", "text/plain", "method not allowed".into()),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    /// Returns a copy of recorded items and counters.
+    fn snapshot(&self) -> Snapshot {
+        let recent = self.lock();
+        Snapshot {
+            started: recent.started.to_rfc3339_opts(SecondsFormat::Secs, true),
+            stats: recent.stats,
+            queued: recent.queued.iter().rev().cloned().collect(),
+            sent: recent.sent.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// Renders recorded items and counters as an HTML page, newest items first.
+    fn html(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut html = String::from(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Application Insights</title>\
+             <style>body{font-family:sans-serif}td,th{padding:2px 8px;text-align:left;vertical-align:top}\
+             pre{margin:0;white-space:pre-wrap}</style></head><body>",
+        );
+        html.push_str(&format!(
+            "<h1>Application Insights</h1><p>Since {}: {} items queued, {} items sent in {} batches. \
+             <a href=\"/appinsights.json\">JSON</a></p>",
+            snapshot.started, snapshot.stats.items_queued, snapshot.stats.items_sent, snapshot.stats.batches_sent
+        ));
+        for (title, items) in [("Queued", &snapshot.queued), ("Sent", &snapshot.sent)] {
+            html.push_str(&format!(
+                "<h2>{}</h2><table><tr><th>Time</th><th>Name</th><th>Item</th></tr>",
+                title
+            ));
+            for envelope in items {
+                let json = serde_json::to_string_pretty(envelope).unwrap_or_default();
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td><details><summary>JSON</summary><pre>{}</pre></details></td></tr>",
+                    escape(&envelope.time),
+                    escape(&envelope.name),
+                    escape(&json)
+                ));
+            }
+            html.push_str("</table>");
+        }
+        html.push_str("</body></html>");
+        html
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recent> {
+        self.recent.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Counters of telemetry items a viewer recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewerStats {
+    /// A number of items queued to the channel.
+    pub items_queued: u64,
+
+    /// A number of items transmitted, including attempts to send them again.
+    pub items_sent: u64,
+
+    /// A number of transmitted batches.
+    pub batches_sent: u64,
+}
+
+/// A handle of a running viewer endpoint. The endpoint stops when the handle is dropped.
+pub struct ViewerServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ViewerServer {
+    /// Returns an address the endpoint listens on, e.g. to find out a port chosen by the system.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for ViewerServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The last items recorded by a viewer.
+struct Recent {
+    capacity: usize,
+    started: DateTime<Utc>,
+    queued: VecDeque<Envelope>,
+    sent: VecDeque<Envelope>,
+    stats: ViewerStats,
+}
+
+impl Recent {
+    fn push_queued(&mut self, envelope: Envelope) {
+        push(&mut self.queued, envelope, self.capacity);
+    }
+
+    fn push_sent(&mut self, envelope: Envelope) {
+        push(&mut self.sent, envelope, self.capacity);
+    }
+}
+
+/// Appends an item and drops the oldest ones over capacity.
+fn push(items: &mut VecDeque<Envelope>, envelope: Envelope, capacity: usize) {
+    items.push_back(envelope);
+    while items.len() > capacity {
+        items.pop_front();
+    }
+}
+
+/// Records items queued to the channel.
+struct RecordQueued(Viewer);
+
+impl TelemetryProcessor for RecordQueued {
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        let mut recent = self.0.lock();
+        recent.stats.items_queued += 1;
+        recent.push_queued(envelope.clone());
+        true
+    }
+}
+
+/// Recorded items and counters served as JSON.
+#[derive(Serialize)]
+struct Snapshot {
+    started: String,
+    stats: ViewerStats,
+    queued: Vec<Envelope>,
+    sent: Vec<Envelope>,
+}
+
+/// Escapes a text to be included in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        telemetry::{ContextTags, EventTelemetry, Properties},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_keeps_last_queued_and_sent_items() {
+        let viewer = Viewer::new(2);
+        let processor = viewer.processor();
+        for name in ["first", "second", "third"] {
+            processor.process(&mut envelope(name));
+        }
+        viewer.tee().write(&[envelope("third")]);
+
+        let snapshot = viewer.snapshot();
+        let names = |items: &[Envelope]| items.iter().map(|item| item.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&snapshot.queued), vec!["third", "second"]);
+        assert_eq!(names(&snapshot.sent), vec!["third"]);
+        assert_eq!(
+            viewer.stats(),
+            ViewerStats {
+                items_queued: 3,
+                items_sent: 1,
+                batches_sent: 1
+            }
+        );
+    }
+
+    #[test_case("GET /appinsights.json HTTP/1.1", "200 OK",           "\"itemsQueued\":1" ; "json")]
+    #[test_case("GET /appinsights HTTP/1.1",      "200 OK",           "&lt;script&gt;"    ; "html")]
+    #[test_case("GET /other HTTP/1.1",            "404 Not Found",    "not found"         ; "unknown path")]
+    fn it_serves_recorded_items(request_line: &str, status: &str, body: &str) {
+        let viewer = Viewer::new(10);
+        viewer.processor().process(&mut envelope("<script>"));
+        let server = viewer.serve(([127, 0, 0, 1], 0).into()).unwrap();
+
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "{}\r\nHost: localhost\r\n\r\n", request_line).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(
+            response.starts_with(&format!("HTTP/1.1 {}\r\n", status)),
+            "{}",
+            response
+        );
+        assert!(response.contains(body), "{}", response);
+    }
+
+    fn envelope(name: &str) -> Envelope {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let mut envelope = Envelope::from((context, EventTelemetry::new(name)));
+        envelope.name = name.into();
+        envelope
+    }
+}
@@ -0,0 +1,33 @@
+//! Request tracking for [`warp`](https://docs.rs/warp) web servers.
+//!
+//! Routes converted to a service with `warp::service` are wrapped with
+//! [`TelemetryService`](struct.TelemetryService.html), which submits every request with its path, status
+//! code, duration and a trace context of the caller. The [`RequestScope`](struct.RequestScope.html) of a
+//! request is available to handlers with `warp::ext::get` filter, so they can name an operation after
+//! a route, add custom properties or correlate their own telemetry to the request.
+//!
+//! ```rust, ignore
+//! use std::convert::Infallible;
+//!
+//! use appinsights::{warp::{RequestScope, TelemetryService}, TelemetryClient};
+//! use hyper::{service::make_service_fn, Server};
+//! use warp::Filter;
+//!
+//! let client = std::sync::Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//!
+//! let orders = warp::path!("orders" / u32)
+//!     .and(warp::ext::get::<RequestScope>())
+//!     .map(|id, scope: RequestScope| {
+//!         scope.set_name("GET /orders/{id}");
+//!         scope.insert_property("order", id.to_string());
+//!         format!("order {}", id)
+//!     });
+//!
+//! let service = TelemetryService::new(client, warp::service(orders));
+//! let make_service = make_service_fn(move |_| {
+//!     let service = service.clone();
+//!     async move { Ok::<_, Infallible>(service) }
+//! });
+//! Server::bind(&([127, 0, 0, 1], 3030).into()).serve(make_service).await?;
+//! ```
+pub use crate::server::{RequestScope, TelemetryService};